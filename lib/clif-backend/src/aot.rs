@@ -0,0 +1,604 @@
+//! Compiles modules ahead of time to shared objects, and loads
+//! them back without generating any code at runtime.
+//!
+//! The code is compiled as position-independent, so it only refers
+//! to itself with relative offsets. Calls to libcalls and vmcalls go
+//! through stubs at the end of the code, which jump through a GOT
+//! that's filled in once the object is loaded. The functions of the
+//! module and their trap tables are described by the metadata
+//! written next to the code.
+
+use crate::{
+    call::{Caller, HandlerData},
+    elf,
+    relocation::{HeapSrcLocs, Reloc, RelocationType, TrapData, TrapSink, VmCall, VmCallKind},
+    resolver::{compile_functions, external_address, CompiledFunctions},
+    trampoline::{compile_trampolines, round_up, Trampolines},
+};
+use cranelift_codegen::ir::{self, LibCall, SourceLoc, TrapCode};
+use cranelift_codegen::isa;
+use hashbrown::HashMap;
+use nix::libc::{c_void, dlclose, dlerror, dlopen, dlsym, RTLD_LOCAL, RTLD_NOW};
+use std::{
+    ffi::{CStr, CString},
+    mem,
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    ptr::NonNull,
+    slice,
+    sync::Arc,
+};
+use wasmer_runtime_core::{
+    backend::FuncResolver,
+    error::{CompileError, CompileResult},
+    module::ModuleInner,
+    perf_map::{self, FuncNames},
+    structures::{Map, TypedIndex},
+    types::{FuncIndex, LocalFuncIndex, MemoryIndex},
+    vm, Features,
+};
+
+const MAGIC: &[u8; 8] = b"WASMRAOT";
+const FORMAT_VERSION: u32 = 4;
+
+/// Compiles the functions of `module`, and its trampolines,
+/// into a shared object. `isa` must generate PIC.
+pub fn write_object(
+    isa: &isa::TargetIsa,
+    module: &ModuleInner,
+    function_bodies: Map<LocalFuncIndex, ir::Function>,
+    heap_srclocs: Map<LocalFuncIndex, HeapSrcLocs>,
+    wasm: &[u8],
+    features: &Features,
+) -> CompileResult<Vec<u8>> {
+    let CompiledFunctions {
+        code: compiled_functions,
+        offsets,
+        relocations,
+        trap_sink,
+        total_size,
+        ..
+    } = compile_functions(isa, function_bodies, heap_srclocs, false)?;
+    let import_len = module.imported_functions.len();
+
+    let mut code = vec![0xcc; total_size];
+    for (compiled, (_, &offset)) in compiled_functions.iter().zip(offsets.iter()) {
+        code[offset..offset + compiled.len()].copy_from_slice(compiled);
+    }
+
+    let mut trampolines = Vec::new();
+    for (func_index, compiled) in compile_trampolines(isa, module) {
+        trampolines.push((func_index, code.len()));
+        code.extend_from_slice(&compiled);
+        let len = round_up(code.len(), mem::size_of::<usize>());
+        code.resize(len, 0xcc);
+    }
+
+    // Everything the code calls outside of itself gets a GOT slot,
+    // and a stub that jumps through it.
+    let mut got: Vec<RelocationType> = Vec::new();
+    let mut got_slots: HashMap<RelocationType, usize> = HashMap::new();
+    for (_, relocs) in relocations.iter() {
+        for reloc in relocs {
+            let is_external = match reloc.target {
+                RelocationType::Normal(_) => reloc.reloc == Reloc::X86GOTPCRel4,
+                _ => true,
+            };
+            if is_external && !got_slots.contains_key(&reloc.target) {
+                got_slots.insert(reloc.target.clone(), got.len());
+                got.push(reloc.target.clone());
+            }
+        }
+    }
+    let mut stubs = Vec::with_capacity(got.len());
+    for _ in 0..got.len() {
+        stubs.push(code.len());
+        // jmp [rip + rel32], patched below.
+        code.extend_from_slice(&[0xff, 0x25, 0, 0, 0, 0, 0xcc, 0xcc]);
+    }
+    if code.is_empty() {
+        code.push(0xcc);
+    }
+
+    let got_offset = elf::got_offset(code.len());
+    for (slot, &stub) in stubs.iter().enumerate() {
+        let target = got_offset + slot * mem::size_of::<usize>();
+        write_rel32(&mut code, stub + 2, target, -4)?;
+    }
+
+    for (index, relocs) in relocations.iter() {
+        let func_offset = offsets[index];
+        for reloc in relocs {
+            let slot = got_slots.get(&reloc.target).cloned();
+            let target = match (reloc.reloc, &reloc.target, slot) {
+                (Reloc::X86GOTPCRel4, _, Some(slot)) => got_offset + slot * mem::size_of::<usize>(),
+                (_, RelocationType::Normal(func_index), _) => {
+                    // Adjust from wasm-wide function index to index of locally-defined functions only.
+                    offsets[LocalFuncIndex::new(func_index.index() - import_len)]
+                }
+                (_, _, Some(slot)) => stubs[slot],
+                (_, target, None) => Err(CompileError::InternalError {
+                    msg: format!("no GOT slot for {:?}", target),
+                })?,
+            };
+
+            match reloc.reloc {
+                Reloc::X86PCRel4
+                | Reloc::X86CallPCRel4
+                | Reloc::X86CallPLTRel4
+                | Reloc::X86GOTPCRel4 => {
+                    let at = func_offset + reloc.offset as usize;
+                    write_rel32(&mut code, at, target, reloc.addend)?;
+                }
+                _ => Err(CompileError::InternalError {
+                    msg: format!(
+                        "unsupported reloc kind for ahead-of-time compilation: {}",
+                        reloc.reloc
+                    ),
+                })?,
+            }
+        }
+    }
+
+    let metadata = Metadata {
+        module_hash: hash(wasm, features),
+        code_size: code.len(),
+        func_offsets: offsets.iter().map(|(_, &offset)| offset).collect(),
+        trampolines,
+        got,
+        traps: trap_sink
+            .iter()
+            .map(|(offset, trap_data)| (offset, *trap_data))
+            .collect(),
+    };
+
+    Ok(elf::write(&elf::SharedObject {
+        code: &code,
+        got_slots: metadata.got.len(),
+        metadata: &metadata.serialize(),
+    }))
+}
+
+/// Loads the code of `module`, compiled from `wasm`,
+/// from the shared object at `path`. The object must have
+/// been compiled with the same `features`, which decide what
+/// the module can use and how its code is generated.
+pub fn load_object(
+    module: &mut ModuleInner,
+    wasm: &[u8],
+    features: &Features,
+    path: &Path,
+) -> CompileResult<()> {
+    let library = Arc::new(Library::open(path)?);
+    let code = library.symbol(elf::CODE_SYMBOL)? as *const u8;
+    let got = library.symbol(elf::GOT_SYMBOL)? as *mut usize;
+    let metadata = unsafe { Metadata::deserialize(library.symbol(elf::METADATA_SYMBOL)?)? };
+
+    let num_local_funcs = module.func_assoc.len() - module.imported_functions.len();
+    if metadata.module_hash != hash(wasm, features)
+        || metadata.func_offsets.len() != num_local_funcs
+    {
+        return Err(CompileError::InternalError {
+            msg: format!(
+                "{} wasn't compiled from this module with these features",
+                path.display()
+            ),
+        }
+        .into());
+    }
+
+    let func_offsets: Map<LocalFuncIndex, usize> = metadata.func_offsets.iter().cloned().collect();
+    let import_len = module.imported_functions.len();
+    for (slot, target) in metadata.got.iter().enumerate() {
+        let address = match *target {
+            RelocationType::Normal(func_index) => {
+                let local_func_index = LocalFuncIndex::new(func_index.index() - import_len);
+                unsafe { code.add(func_offsets[local_func_index]) as usize }
+            }
+            ref target => external_address(target)? as usize,
+        };
+        // The GOT is the only writable part of the object.
+        unsafe {
+            got.add(slot).write(address);
+        }
+    }
+
+    let mut trap_sink = TrapSink::new();
+    for &(offset, trap_data) in metadata.traps.iter() {
+        trap_sink.insert(offset, trap_data);
+    }
+    let handler_data = Arc::new(HandlerData::new(
+        trap_sink,
+        code as *const c_void,
+        metadata.code_size,
+    ));
+
+    let trampolines = metadata
+        .trampolines
+        .iter()
+        .map(|&(func_index, offset)| (module.func_assoc[func_index], offset))
+        .collect();
+    let trampolines = unsafe { Trampolines::from_code(code, trampolines) };
+
+    if perf_map::is_enabled() {
+        let names = FuncNames::new(module, wasm);
+        let mut symbols: Vec<(usize, String)> = func_offsets
+            .iter()
+            .map(|(index, &offset)| (offset, names.get_local(module, index).to_string()))
+            .collect();
+        for &(func_index, offset) in metadata.trampolines.iter() {
+            symbols.push((offset, format!("trampoline for {}", names.get(func_index))));
+        }
+        perf_map::record(code, metadata.code_size, symbols);
+    }
+
+    module.func_resolver = Box::new(ObjectFuncResolver {
+        library,
+        code,
+        func_offsets,
+    });
+    module.protected_caller = Box::new(Caller::new(module, handler_data, trampolines));
+
+    Ok(())
+}
+
+/// Writes the relative offset `target - at + addend` at `at`.
+fn write_rel32(code: &mut [u8], at: usize, target: usize, addend: i64) -> CompileResult<()> {
+    let delta = target as i64 - at as i64 + addend;
+    if delta < i64::from(i32::min_value()) || delta > i64::from(i32::max_value()) {
+        return Err(CompileError::InternalError {
+            msg: "relocation out of range".to_string(),
+        }
+        .into());
+    }
+    code[at..at + 4].copy_from_slice(&(delta as i32).to_le_bytes());
+    Ok(())
+}
+
+/// The FNV-1a hash of a wasm binary and the features it's compiled
+/// with, to tell which module a shared object was compiled from, and how.
+fn hash(wasm: &[u8], features: &Features) -> u64 {
+    // Destructured so that a new feature can't be left out.
+    let Features {
+        sign_extension,
+        sat_float_to_int,
+        mutable_global,
+        threads,
+        reference_types,
+        multi_value,
+        deterministic,
+        ref allowed_imports,
+        bounds_checks,
+    } = *features;
+
+    let mut out = Writer(Vec::new());
+    for &flag in &[
+        sign_extension,
+        sat_float_to_int,
+        mutable_global,
+        threads,
+        reference_types,
+        multi_value,
+        deterministic,
+        bounds_checks,
+    ] {
+        out.u8(flag as u8);
+    }
+    out.u32(allowed_imports.len() as u32);
+    for (namespace, name) in allowed_imports.iter() {
+        out.str(namespace);
+        out.str(name);
+    }
+
+    wasm.iter()
+        .chain(out.0.iter())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+/// Resolves functions to their code in a shared object.
+struct ObjectFuncResolver {
+    /// Keeps the code mapped.
+    #[allow(dead_code)]
+    library: Arc<Library>,
+    code: *const u8,
+    func_offsets: Map<LocalFuncIndex, usize>,
+}
+
+unsafe impl Send for ObjectFuncResolver {}
+unsafe impl Sync for ObjectFuncResolver {}
+
+impl FuncResolver for ObjectFuncResolver {
+    fn get(
+        &self,
+        _module: &ModuleInner,
+        local_func_index: LocalFuncIndex,
+    ) -> Option<NonNull<vm::Func>> {
+        let offset = *self.func_offsets.get(local_func_index)?;
+        NonNull::new(unsafe { self.code.add(offset) } as *mut vm::Func)
+    }
+}
+
+/// A shared object opened with `dlopen`.
+struct Library {
+    handle: *mut c_void,
+}
+
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    fn open(path: &Path) -> CompileResult<Self> {
+        let path_str =
+            CString::new(path.as_os_str().as_bytes()).map_err(|_| CompileError::InternalError {
+                msg: format!("invalid path: {}", path.display()),
+            })?;
+        let handle = unsafe { dlopen(path_str.as_ptr(), RTLD_NOW | RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(CompileError::InternalError {
+                msg: format!("can't load {}: {}", path.display(), last_dl_error()),
+            }
+            .into());
+        }
+        Ok(Self { handle })
+    }
+
+    fn symbol(&self, name: &str) -> CompileResult<*mut u8> {
+        let name_str = CString::new(name).unwrap();
+        let ptr = unsafe { dlsym(self.handle, name_str.as_ptr()) };
+        if ptr.is_null() {
+            return Err(CompileError::InternalError {
+                msg: format!("missing symbol {}: {}", name, last_dl_error()),
+            }
+            .into());
+        }
+        Ok(ptr as *mut u8)
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe {
+            dlclose(self.handle);
+        }
+    }
+}
+
+fn last_dl_error() -> String {
+    let error = unsafe { dlerror() };
+    if error.is_null() {
+        "unknown error".to_string()
+    } else {
+        unsafe { CStr::from_ptr(error) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// What the loader needs to know about the code in a shared object.
+struct Metadata {
+    /// The hash of the wasm binary and the features
+    /// that the code was compiled from.
+    module_hash: u64,
+    code_size: usize,
+    /// The offset of each local function in the code.
+    func_offsets: Vec<usize>,
+    /// The offset of the trampoline of each function
+    /// that the runtime can call.
+    trampolines: Vec<(FuncIndex, usize)>,
+    /// What each GOT slot points to.
+    got: Vec<RelocationType>,
+    traps: Vec<(usize, TrapData)>,
+}
+
+impl Metadata {
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = Writer(Vec::new());
+        out.0.extend_from_slice(MAGIC);
+        // The total size, filled in at the end.
+        out.u64(0);
+        out.u32(FORMAT_VERSION);
+        out.u64(self.module_hash);
+        out.u64(self.code_size as u64);
+
+        out.u32(self.func_offsets.len() as u32);
+        for &offset in self.func_offsets.iter() {
+            out.u64(offset as u64);
+        }
+
+        out.u32(self.trampolines.len() as u32);
+        for &(func_index, offset) in self.trampolines.iter() {
+            out.u32(func_index.index() as u32);
+            out.u64(offset as u64);
+        }
+
+        out.u32(self.got.len() as u32);
+        for target in self.got.iter() {
+            match *target {
+                RelocationType::Normal(func_index) => {
+                    out.u8(0);
+                    out.u32(func_index.index() as u32);
+                }
+                RelocationType::LibCall(libcall) => {
+                    out.u8(1);
+                    out.str(&libcall.to_string());
+                }
+                RelocationType::VmCall(VmCall::Local(kind)) => {
+                    out.u8(2);
+                    out.u8(kind as u8);
+                }
+                RelocationType::VmCall(VmCall::Import(kind)) => {
+                    out.u8(3);
+                    out.u8(kind as u8);
+                }
+                RelocationType::Intrinsic(ref name) => {
+                    out.u8(4);
+                    out.str(name);
+                }
+            }
+        }
+
+        out.u32(self.traps.len() as u32);
+        for &(offset, ref trap_data) in self.traps.iter() {
+            out.u64(offset as u64);
+            out.str(&trap_data.trapcode.to_string());
+            out.u32(trap_data.srcloc.bits());
+            out.u32(
+                trap_data
+                    .memory
+                    .map_or(u32::max_value(), |memory| memory.index() as u32),
+            );
+        }
+
+        let len = out.0.len() as u64;
+        out.0[MAGIC.len()..MAGIC.len() + 8].copy_from_slice(&len.to_le_bytes());
+        out.0
+    }
+
+    /// Reads the metadata written at `ptr`.
+    unsafe fn deserialize(ptr: *const u8) -> CompileResult<Self> {
+        let header = slice::from_raw_parts(ptr, MAGIC.len() + 8);
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(malformed());
+        }
+        let mut len_bytes = [0; 8];
+        len_bytes.copy_from_slice(&header[MAGIC.len()..]);
+        let len = u64::from_le_bytes(len_bytes) as usize;
+
+        let mut reader = Reader {
+            bytes: slice::from_raw_parts(ptr, len),
+            pos: header.len(),
+        };
+        if reader.u32()? != FORMAT_VERSION {
+            return Err(CompileError::InternalError {
+                msg: "shared object was written by another version of wasmer".to_string(),
+            }
+            .into());
+        }
+        let module_hash = reader.u64()?;
+        let code_size = reader.u64()? as usize;
+
+        let mut func_offsets = Vec::new();
+        for _ in 0..reader.u32()? {
+            func_offsets.push(reader.u64()? as usize);
+        }
+
+        let mut trampolines = Vec::new();
+        for _ in 0..reader.u32()? {
+            let func_index = FuncIndex::new(reader.u32()? as usize);
+            trampolines.push((func_index, reader.u64()? as usize));
+        }
+
+        let mut got = Vec::new();
+        for _ in 0..reader.u32()? {
+            got.push(match reader.u8()? {
+                0 => RelocationType::Normal(LocalFuncIndex::new(reader.u32()? as usize)),
+                1 => RelocationType::LibCall(
+                    reader.str()?.parse::<LibCall>().map_err(|_| malformed())?,
+                ),
+                2 => RelocationType::VmCall(VmCall::Local(reader.vmcall_kind()?)),
+                3 => RelocationType::VmCall(VmCall::Import(reader.vmcall_kind()?)),
+                4 => RelocationType::Intrinsic(reader.str()?),
+                _ => Err(malformed())?,
+            });
+        }
+
+        let mut traps = Vec::new();
+        for _ in 0..reader.u32()? {
+            let offset = reader.u64()? as usize;
+            let trapcode = reader.str()?.parse::<TrapCode>().map_err(|_| malformed())?;
+            let srcloc = SourceLoc::new(reader.u32()?);
+            let memory = match reader.u32()? {
+                index if index == u32::max_value() => None,
+                index => Some(MemoryIndex::new(index as usize)),
+            };
+            traps.push((
+                offset,
+                TrapData {
+                    trapcode,
+                    srcloc,
+                    memory,
+                },
+            ));
+        }
+
+        Ok(Self {
+            module_hash,
+            code_size,
+            func_offsets,
+            trampolines,
+            got,
+            traps,
+        })
+    }
+}
+
+fn malformed() -> Box<CompileError> {
+    Box::new(CompileError::InternalError {
+        msg: "malformed shared object metadata".to_string(),
+    })
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> CompileResult<&'a [u8]> {
+        if self.pos + len > self.bytes.len() {
+            return Err(malformed());
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> CompileResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> CompileResult<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> CompileResult<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn str(&mut self) -> CompileResult<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| malformed())
+    }
+
+    fn vmcall_kind(&mut self) -> CompileResult<VmCallKind> {
+        let index = self.u8()? as usize;
+        VmCallKind::ALL.get(index).cloned().ok_or_else(malformed)
+    }
+}
@@ -0,0 +1,93 @@
+//! Makes the bounds checks of dynamic heaps cover the whole of each
+//! access. Cranelift only checks that an access starts in bounds, and
+//! relies on the guard page after a dynamic heap to catch the bytes
+//! that go past its end, which takes a signal handler. Memories that
+//! are bounds-checked explicitly must not fault at all.
+
+use cranelift_codegen::ir::{self, immediates::Uimm32, InstructionData, Opcode, ValueDef};
+
+/// Raises the size checked by every `heap_addr` of a dynamic heap
+/// in `func` to the end of the furthest access that uses it.
+pub fn check_whole_accesses(func: &mut ir::Function) {
+    let mut ends = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            let (addr, offset, bytes) = match func.dfg[inst] {
+                InstructionData::Load {
+                    opcode,
+                    arg,
+                    offset,
+                    ..
+                } => {
+                    let bytes = match opcode {
+                        Opcode::Uload8 | Opcode::Sload8 => 1,
+                        Opcode::Uload16 | Opcode::Sload16 => 2,
+                        Opcode::Uload32 | Opcode::Sload32 => 4,
+                        _ => func.dfg.value_type(func.dfg.first_result(inst)).bytes(),
+                    };
+                    (arg, offset, bytes)
+                }
+                InstructionData::Store {
+                    opcode,
+                    args,
+                    offset,
+                    ..
+                } => {
+                    let bytes = match opcode {
+                        Opcode::Istore8 => 1,
+                        Opcode::Istore16 => 2,
+                        Opcode::Istore32 => 4,
+                        _ => func.dfg.value_type(args[0]).bytes(),
+                    };
+                    (args[1], offset, bytes)
+                }
+                _ => continue,
+            };
+
+            if let Some((heap_addr, adjustment)) = dynamic_heap_addr(func, addr) {
+                let end = adjustment + i64::from(offset) + i64::from(bytes);
+                ends.push((heap_addr, end));
+            }
+        }
+    }
+
+    for (heap_addr, end) in ends {
+        if let InstructionData::HeapAddr { ref mut imm, .. } = func.dfg[heap_addr] {
+            let end = end.min(i64::from(u32::max_value())) as u32;
+            if end > u32::from(*imm) {
+                *imm = Uimm32::from(end);
+            }
+        }
+    }
+}
+
+/// The `heap_addr` of a dynamic heap that `addr` was computed
+/// from, and how far past its result `addr` is. Offsets that
+/// don't fit in a load or store are added to the result first.
+fn dynamic_heap_addr(func: &ir::Function, addr: ir::Value) -> Option<(ir::Inst, i64)> {
+    let mut value = addr;
+    let mut adjustment = 0;
+    loop {
+        let inst = match func.dfg.value_def(func.dfg.resolve_aliases(value)) {
+            ValueDef::Result(inst, _) => inst,
+            ValueDef::Param(..) => return None,
+        };
+        match func.dfg[inst] {
+            InstructionData::BinaryImm {
+                opcode: Opcode::IaddImm,
+                arg,
+                imm,
+            } => {
+                adjustment += i64::from(imm);
+                value = arg;
+            }
+            InstructionData::HeapAddr { heap, .. } => {
+                return match func.heaps[heap].style {
+                    ir::HeapStyle::Dynamic { .. } => Some((inst, adjustment)),
+                    ir::HeapStyle::Static { .. } => None,
+                };
+            }
+            _ => return None,
+        }
+    }
+}
@@ -12,18 +12,22 @@ use wasmer_runtime_core::{
     error::RuntimeResult,
     export::Context,
     module::{ExportIndex, ModuleInner},
-    types::{FuncIndex, FuncSig, LocalOrImport, SigIndex, Type, Value},
+    types::{FuncIndex, FuncSig, LocalOrImport, SigIndex, Value},
     vm::{self, ImportBacking},
 };
 
 pub struct Caller {
     func_export_set: HashSet<FuncIndex>,
-    handler_data: HandlerData,
+    handler_data: Arc<HandlerData>,
     trampolines: Trampolines,
 }
 
 impl Caller {
-    pub fn new(module: &ModuleInner, handler_data: HandlerData, trampolines: Trampolines) -> Self {
+    pub fn new(
+        module: &ModuleInner,
+        handler_data: Arc<HandlerData>,
+        trampolines: Trampolines,
+    ) -> Self {
         let mut func_export_set = HashSet::new();
         for export_index in module.exports.values() {
             if let ExportIndex::Func(func_index) = export_index {
@@ -72,15 +76,7 @@ impl ProtectedCaller for Caller {
             "incorrect signature"
         );
 
-        let param_vec: Vec<u64> = params
-            .iter()
-            .map(|val| match val {
-                Value::I32(x) => *x as u64,
-                Value::I64(x) => *x as u64,
-                Value::F32(x) => x.to_bits() as u64,
-                Value::F64(x) => x.to_bits(),
-            })
-            .collect();
+        let param_vec: Vec<u64> = params.iter().map(|val| val.to_bits()).collect();
 
         let mut return_vec = vec![0; signature.returns().len()];
 
@@ -89,7 +85,7 @@ impl ProtectedCaller for Caller {
             .lookup(sig_index)
             .expect("that trampoline doesn't exist");
 
-        call_protected(&self.handler_data, || unsafe {
+        call_protected(&self.handler_data, vmctx_ptr, || unsafe {
             // Leap of faith.
             trampoline(
                 vmctx_ptr,
@@ -102,12 +98,7 @@ impl ProtectedCaller for Caller {
         Ok(return_vec
             .iter()
             .zip(signature.returns().iter())
-            .map(|(&x, ty)| match ty {
-                Type::I32 => Value::I32(x as i32),
-                Type::I64 => Value::I64(x as i64),
-                Type::F32 => Value::F32(f32::from_bits(x as u32)),
-                Type::F64 => Value::F64(f64::from_bits(x as u64)),
-            })
+            .map(|(&x, &ty)| Value::from_bits(ty, x))
             .collect())
     }
 }
@@ -11,11 +11,13 @@ use nix::libc::{c_void, siginfo_t};
 use nix::sys::signal::{Signal, SIGBUS, SIGFPE, SIGILL, SIGSEGV};
 use std::cell::{Cell, UnsafeCell};
 use std::ptr;
-use std::sync::Once;
+use std::sync::{Arc, Once, RwLock};
 use wasmer_runtime_core::{
     error::{RuntimeError, RuntimeResult},
+    memory::find_memory,
     structures::TypedIndex,
     types::{MemoryIndex, TableIndex},
+    vm,
 };
 
 extern "C" {
@@ -39,6 +41,9 @@ pub struct HandlerData {
     trap_data: TrapSink,
     buffer_ptr: *const c_void,
     buffer_size: usize,
+    /// The trap tables of other code that this code can call into,
+    /// like functions that were recompiled after it.
+    linked: RwLock<Vec<Arc<HandlerData>>>,
 }
 
 impl HandlerData {
@@ -47,23 +52,47 @@ impl HandlerData {
             trap_data,
             buffer_ptr,
             buffer_size,
+            linked: RwLock::new(Vec::new()),
         }
     }
 
+    /// Reports traps in the code of `other` as well,
+    /// when they happen under `call_protected` with this.
+    pub fn link(&self, other: Arc<HandlerData>) {
+        self.linked.write().unwrap().push(other);
+    }
+
     pub fn lookup(&self, ip: *const c_void) -> Option<TrapData> {
+        self.find(ip).map(|(trap_data, _, _)| trap_data)
+    }
+
+    /// Looks up the trap at `ip`, along with the start
+    /// and the size of the code that contains it.
+    fn find(&self, ip: *const c_void) -> Option<(TrapData, *const c_void, usize)> {
         let ip = ip as usize;
         let buffer_ptr = self.buffer_ptr as usize;
 
         if buffer_ptr <= ip && ip < buffer_ptr + self.buffer_size {
             let offset = ip - buffer_ptr;
-            self.trap_data.lookup(offset)
+            self.trap_data
+                .lookup(offset)
+                .map(|trap_data| (trap_data, self.buffer_ptr, self.buffer_size))
         } else {
-            None
+            self.linked
+                .read()
+                .unwrap()
+                .iter()
+                .filter_map(|linked| linked.find(ip as *const c_void))
+                .next()
         }
     }
 }
 
-pub fn call_protected<T>(handler_data: &HandlerData, f: impl FnOnce() -> T) -> RuntimeResult<T> {
+pub fn call_protected<T>(
+    handler_data: &HandlerData,
+    vmctx: *const vm::Ctx,
+    f: impl FnOnce() -> T,
+) -> RuntimeResult<T> {
     unsafe {
         let jmp_buf = SETJMP_BUFFER.with(|buf| buf.get());
         let prev_jmp_buf = *jmp_buf;
@@ -75,12 +104,17 @@ pub fn call_protected<T>(handler_data: &HandlerData, f: impl FnOnce() -> T) -> R
         let signum = setjmp(jmp_buf as *mut ::nix::libc::c_void);
         if signum != 0 {
             *jmp_buf = prev_jmp_buf;
-            let (faulting_addr, _) = CAUGHT_ADDRESSES.with(|cell| cell.get());
+            let (faulting_addr, inst_ptr) = CAUGHT_ADDRESSES.with(|cell| cell.get());
 
-            if let Some(TrapData {
-                trapcode,
-                srcloc: _,
-            }) = handler_data.lookup(faulting_addr)
+            if let Some((
+                TrapData {
+                    trapcode,
+                    srcloc: _,
+                    memory,
+                },
+                buffer_ptr,
+                buffer_size,
+            )) = handler_data.find(inst_ptr)
             {
                 Err(match Signal::from_c_int(signum) {
                     Ok(SIGILL) => match trapcode {
@@ -91,43 +125,45 @@ pub fn call_protected<T>(handler_data: &HandlerData, f: impl FnOnce() -> T) -> R
                             table: TableIndex::new(0),
                         },
                         TrapCode::HeapOutOfBounds => {
-                            let addr =
-                                (faulting_addr as usize) - (handler_data.buffer_ptr as usize);
-                            if addr <= handler_data.buffer_size {
+                            let addr = (faulting_addr as usize) - (buffer_ptr as usize);
+                            if addr <= buffer_size {
                                 // in the memory
                                 RuntimeError::OutOfBoundsAccess {
-                                    memory: MemoryIndex::new(0),
+                                    memory: memory.unwrap_or(MemoryIndex::new(0)),
                                     addr: addr as u32,
                                 }
                             } else {
-                                // if there's an invalid access outside of the memory, including guard pages
-                                // just kill the process.
-                                panic!("invalid memory access, way out of bounds")
+                                way_out_of_bounds(faulting_addr)
                             }
                         }
                         TrapCode::TableOutOfBounds => RuntimeError::TableOutOfBounds {
                             table: TableIndex::new(0),
                         },
+                        TrapCode::IntegerOverflow => RuntimeError::IllegalArithmeticOperation,
                         _ => RuntimeError::Unknown {
                             msg: "unknown trap".to_string(),
                         },
                     },
                     Ok(SIGSEGV) | Ok(SIGBUS) => {
-                        let addr = (faulting_addr as usize) - (handler_data.buffer_ptr as usize);
-                        if addr <= handler_data.buffer_size {
-                            // in the memory
-                            RuntimeError::OutOfBoundsAccess {
-                                memory: MemoryIndex::new(0),
+                        // The faulting address is the one that was accessed, so look
+                        // for the memory (or its guard pages) that contains it. The
+                        // context can be null when an imported function is called.
+                        match find_memory(faulting_addr as *const u8) {
+                            Some((local_memory, addr)) => RuntimeError::OutOfBoundsAccess {
+                                memory: vmctx
+                                    .as_ref()
+                                    .and_then(|ctx| ctx.memory_index(local_memory))
+                                    .or(memory)
+                                    .unwrap_or(MemoryIndex::new(0)),
                                 addr: addr as u32,
-                            }
-                        } else {
-                            // if there's an invalid access outside of the memory, including guard pages
-                            // just kill the process.
-                            panic!("invalid memory access, way out of bounds")
+                            },
+                            None => way_out_of_bounds(faulting_addr),
                         }
                     }
                     Ok(SIGFPE) => RuntimeError::IllegalArithmeticOperation,
-                    _ => unimplemented!(),
+                    _ => RuntimeError::Unknown {
+                        msg: format!("trap at {:p} - unknown trapped signal", faulting_addr),
+                    },
                 }
                 .into())
             } else {
@@ -153,6 +189,20 @@ pub fn call_protected<T>(handler_data: &HandlerData, f: impl FnOnce() -> T) -> R
     }
 }
 
+/// An access outside of every memory, including its guard pages.
+///
+/// This is only reached by jumping out of the signal handler, past
+/// frames whose destructors never ran, so it's reported as an error
+/// instead of panicking.
+fn way_out_of_bounds(faulting_addr: *const c_void) -> RuntimeError {
+    RuntimeError::Unknown {
+        msg: format!(
+            "invalid memory access at {:p}, way out of bounds",
+            faulting_addr
+        ),
+    }
+}
+
 /// Unwinds to last protected_call.
 pub unsafe fn do_unwind(signum: i32, siginfo: *mut siginfo_t, ucontext: *const c_void) -> ! {
     // Since do_unwind is only expected to get called from WebAssembly code which doesn't hold any host resources (locks etc.)
@@ -172,17 +222,86 @@ pub unsafe fn do_unwind(signum: i32, siginfo: *mut siginfo_t, ucontext: *const c
 #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
 unsafe fn get_faulting_addr_and_ip(
     siginfo: *mut siginfo_t,
-    _ucontext: *const c_void,
+    ucontext: *const c_void,
 ) -> (*const c_void, *const c_void) {
-    (ptr::null(), ptr::null())
+    use nix::libc::{ucontext_t, REG_RIP};
+
+    // `siginfo_t` on linux is a union, so `libc` doesn't expose `si_addr`.
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct SigInfo {
+        si_signo: i32,
+        si_errno: i32,
+        si_code: i32,
+        si_addr: *const c_void,
+    }
+
+    let si_addr = (*(siginfo as *const SigInfo)).si_addr;
+    let ucontext = ucontext as *const ucontext_t;
+    let rip = (*ucontext).uc_mcontext.gregs[REG_RIP as usize];
+
+    (si_addr, rip as *const c_void)
 }
 
 #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
 unsafe fn get_faulting_addr_and_ip(
     siginfo: *mut siginfo_t,
-    _ucontext: *const c_void,
+    ucontext: *const c_void,
 ) -> (*const c_void, *const c_void) {
-    ((*siginfo).si_addr, ptr::null())
+    // `libc` doesn't describe the machine context on macos.
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct UContext {
+        uc_onstack: u32,
+        uc_sigmask: u32,
+        uc_stack: nix::libc::stack_t,
+        uc_link: *const UContext,
+        uc_mcsize: u64,
+        uc_mcontext: *const MContext,
+    }
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct ExceptionState {
+        trapno: u16,
+        cpu: u16,
+        err: u32,
+        faultvaddr: u64,
+    }
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct ThreadState {
+        rax: u64,
+        rbx: u64,
+        rcx: u64,
+        rdx: u64,
+        rdi: u64,
+        rsi: u64,
+        rbp: u64,
+        rsp: u64,
+        r8: u64,
+        r9: u64,
+        r10: u64,
+        r11: u64,
+        r12: u64,
+        r13: u64,
+        r14: u64,
+        r15: u64,
+        rip: u64,
+    }
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct MContext {
+        es: ExceptionState,
+        ss: ThreadState,
+    }
+
+    let ucontext = ucontext as *const UContext;
+    let rip = (*(*ucontext).uc_mcontext).ss.rip;
+
+    ((*siginfo).si_addr, rip as *const c_void)
 }
 
 #[cfg(not(any(
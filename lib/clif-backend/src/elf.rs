@@ -0,0 +1,405 @@
+//! Writes the x86_64 ELF shared objects that hold code
+//! compiled ahead of time.
+//!
+//! The objects have no relocations and import nothing, so the
+//! dynamic loader only has to map them. They are laid out in three
+//! segments, each starting on its own page:
+//!
+//! ```text
+//! read-only     headers, dynamic symbols, metadata
+//! read+exec     code
+//! read+write    dynamic section, GOT
+//! ```
+//!
+//! The GOT starts out zeroed, and is filled in by the loader of
+//! the object once it's mapped, without ever touching the code.
+
+const PAGE_SIZE: usize = 0x1000;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+const DYN_SIZE: usize = 16;
+
+const NUM_PHDRS: usize = 5;
+/// `DT_HASH`, `DT_STRTAB`, `DT_SYMTAB`, `DT_STRSZ`, `DT_SYMENT`, `DT_NULL`.
+const NUM_DYNS: usize = 6;
+
+/// The exported symbol at the start of the code.
+pub const CODE_SYMBOL: &str = "wasmer_aot_code";
+/// The exported symbol at the start of the GOT.
+pub const GOT_SYMBOL: &str = "wasmer_aot_got";
+/// The exported symbol at the start of the metadata.
+pub const METADATA_SYMBOL: &str = "wasmer_aot_metadata";
+
+/// The contents of a shared object.
+pub struct SharedObject<'a> {
+    pub code: &'a [u8],
+    /// The number of 8-byte slots in the GOT.
+    pub got_slots: usize,
+    pub metadata: &'a [u8],
+}
+
+/// Where the GOT starts relative to the code, which is
+/// what code that refers to it needs to know.
+pub fn got_offset(code_len: usize) -> usize {
+    round_up(code_len.max(1), PAGE_SIZE) + NUM_DYNS * DYN_SIZE
+}
+
+/// Serializes `object` as a shared object.
+pub fn write(object: &SharedObject) -> Vec<u8> {
+    let symbols = [CODE_SYMBOL, GOT_SYMBOL, METADATA_SYMBOL];
+    let num_syms = symbols.len() + 1;
+
+    // Lay out the read-only segment.
+    let hash_addr = EHDR_SIZE + NUM_PHDRS * PHDR_SIZE;
+    let hash_size = 4 * (2 + 1 + num_syms);
+    let dynsym_addr = round_up(hash_addr + hash_size, 8);
+    let dynstr_addr = dynsym_addr + num_syms * SYM_SIZE;
+    let mut dynstr = vec![0u8];
+    let mut name_offsets = Vec::new();
+    for name in symbols.iter() {
+        name_offsets.push(dynstr.len() as u32);
+        dynstr.extend_from_slice(name.as_bytes());
+        dynstr.push(0);
+    }
+    let metadata_addr = round_up(dynstr_addr + dynstr.len(), 8);
+    let rodata_end = metadata_addr + object.metadata.len();
+
+    let code_addr = round_up(rodata_end, PAGE_SIZE);
+    let code_size = object.code.len();
+    let dynamic_addr = code_addr + round_up(code_size.max(1), PAGE_SIZE);
+    let got_addr = code_addr + got_offset(code_size);
+    let got_size = 8 * object.got_slots;
+    let data_end = got_addr + got_size;
+
+    let mut shstrtab = vec![0u8];
+    let mut section_name = |name: &str| {
+        let offset = shstrtab.len() as u32;
+        shstrtab.extend_from_slice(name.as_bytes());
+        shstrtab.push(0);
+        offset
+    };
+    let sections = [
+        Section::null(),
+        Section {
+            name: section_name(".hash"),
+            kind: SHT_HASH,
+            flags: SHF_ALLOC,
+            addr: hash_addr,
+            size: hash_size,
+            link: 2,
+            info: 0,
+            align: 8,
+            entsize: 4,
+        },
+        Section {
+            name: section_name(".dynsym"),
+            kind: SHT_DYNSYM,
+            flags: SHF_ALLOC,
+            addr: dynsym_addr,
+            size: num_syms * SYM_SIZE,
+            link: 3,
+            info: 1,
+            align: 8,
+            entsize: SYM_SIZE,
+        },
+        Section {
+            name: section_name(".dynstr"),
+            kind: SHT_STRTAB,
+            flags: SHF_ALLOC,
+            addr: dynstr_addr,
+            size: dynstr.len(),
+            link: 0,
+            info: 0,
+            align: 1,
+            entsize: 0,
+        },
+        Section {
+            name: section_name(".rodata"),
+            kind: SHT_PROGBITS,
+            flags: SHF_ALLOC,
+            addr: metadata_addr,
+            size: object.metadata.len(),
+            link: 0,
+            info: 0,
+            align: 8,
+            entsize: 0,
+        },
+        Section {
+            name: section_name(".text"),
+            kind: SHT_PROGBITS,
+            flags: SHF_ALLOC | SHF_EXECINSTR,
+            addr: code_addr,
+            size: code_size,
+            link: 0,
+            info: 0,
+            align: 16,
+            entsize: 0,
+        },
+        Section {
+            name: section_name(".dynamic"),
+            kind: SHT_DYNAMIC,
+            flags: SHF_ALLOC | SHF_WRITE,
+            addr: dynamic_addr,
+            size: NUM_DYNS * DYN_SIZE,
+            link: 3,
+            info: 0,
+            align: 8,
+            entsize: DYN_SIZE,
+        },
+        Section {
+            name: section_name(".got"),
+            kind: SHT_PROGBITS,
+            flags: SHF_ALLOC | SHF_WRITE,
+            addr: got_addr,
+            size: got_size,
+            link: 0,
+            info: 0,
+            align: 8,
+            entsize: 8,
+        },
+    ];
+    let shstrtab_name = section_name(".shstrtab");
+    let shstrtab_addr = data_end;
+    let shdrs_addr = round_up(shstrtab_addr + shstrtab.len(), 8);
+    let num_shdrs = sections.len() + 1;
+
+    let mut out = Vec::with_capacity(shdrs_addr + num_shdrs * SHDR_SIZE);
+
+    // The ELF header.
+    out.extend_from_slice(b"\x7fELF");
+    // 64-bit, little-endian, version 1, System V ABI.
+    out.extend_from_slice(&[2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    u16(&mut out, ET_DYN);
+    u16(&mut out, EM_X86_64);
+    u32(&mut out, 1);
+    u64(&mut out, 0);
+    u64(&mut out, EHDR_SIZE);
+    u64(&mut out, shdrs_addr);
+    u32(&mut out, 0);
+    u16(&mut out, EHDR_SIZE as u16);
+    u16(&mut out, PHDR_SIZE as u16);
+    u16(&mut out, NUM_PHDRS as u16);
+    u16(&mut out, SHDR_SIZE as u16);
+    u16(&mut out, num_shdrs as u16);
+    u16(&mut out, (num_shdrs - 1) as u16);
+
+    // The program headers.
+    phdr(&mut out, PT_LOAD, PF_R, 0, rodata_end, PAGE_SIZE);
+    phdr(
+        &mut out,
+        PT_LOAD,
+        PF_R | PF_X,
+        code_addr,
+        code_size,
+        PAGE_SIZE,
+    );
+    phdr(
+        &mut out,
+        PT_LOAD,
+        PF_R | PF_W,
+        dynamic_addr,
+        data_end - dynamic_addr,
+        PAGE_SIZE,
+    );
+    phdr(
+        &mut out,
+        PT_DYNAMIC,
+        PF_R | PF_W,
+        dynamic_addr,
+        NUM_DYNS * DYN_SIZE,
+        8,
+    );
+    // The stack isn't executable.
+    phdr(&mut out, PT_GNU_STACK, PF_R | PF_W, 0, 0, 16);
+
+    // A hash table with a single bucket, chaining every symbol.
+    debug_assert_eq!(out.len(), hash_addr);
+    u32(&mut out, 1);
+    u32(&mut out, num_syms as u32);
+    u32(&mut out, 1);
+    u32(&mut out, 0);
+    for index in 1..num_syms {
+        let next = if index + 1 < num_syms { index + 1 } else { 0 };
+        u32(&mut out, next as u32);
+    }
+
+    pad(&mut out, dynsym_addr);
+    out.extend_from_slice(&[0; SYM_SIZE]);
+    let symbol_targets = [
+        (STT_FUNC, 5, code_addr, code_size),
+        (STT_OBJECT, 7, got_addr, got_size),
+        (STT_OBJECT, 4, metadata_addr, object.metadata.len()),
+    ];
+    for (&name, &(kind, section, addr, size)) in name_offsets.iter().zip(symbol_targets.iter()) {
+        u32(&mut out, name);
+        out.push(STB_GLOBAL << 4 | kind);
+        out.push(STV_DEFAULT);
+        u16(&mut out, section);
+        u64(&mut out, addr);
+        u64(&mut out, size);
+    }
+
+    out.extend_from_slice(&dynstr);
+    pad(&mut out, metadata_addr);
+    out.extend_from_slice(object.metadata);
+
+    // Fill the gap before the code with int3, like the JIT does.
+    pad_with(&mut out, code_addr, 0xcc);
+    out.extend_from_slice(object.code);
+    pad_with(&mut out, dynamic_addr, 0xcc);
+
+    for &(tag, value) in [
+        (DT_HASH, hash_addr),
+        (DT_STRTAB, dynstr_addr),
+        (DT_SYMTAB, dynsym_addr),
+        (DT_STRSZ, dynstr.len()),
+        (DT_SYMENT, SYM_SIZE),
+        (DT_NULL, 0),
+    ]
+    .iter()
+    {
+        u64(&mut out, tag);
+        u64(&mut out, value);
+    }
+    out.resize(data_end, 0);
+
+    out.extend_from_slice(&shstrtab);
+    pad(&mut out, shdrs_addr);
+    for section in sections.iter() {
+        section.write(&mut out);
+    }
+    Section {
+        name: shstrtab_name,
+        kind: SHT_STRTAB,
+        flags: 0,
+        addr: 0,
+        size: shstrtab.len(),
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    }
+    .write_at(&mut out, shstrtab_addr);
+
+    out
+}
+
+struct Section {
+    name: u32,
+    kind: u32,
+    flags: usize,
+    addr: usize,
+    size: usize,
+    link: u32,
+    info: u32,
+    align: usize,
+    entsize: usize,
+}
+
+impl Section {
+    fn null() -> Self {
+        Section {
+            name: 0,
+            kind: 0,
+            flags: 0,
+            addr: 0,
+            size: 0,
+            link: 0,
+            info: 0,
+            align: 0,
+            entsize: 0,
+        }
+    }
+
+    /// Writes the header of a section that's loaded
+    /// at the same offset in the file as in memory.
+    fn write(&self, out: &mut Vec<u8>) {
+        self.write_at(out, self.addr);
+    }
+
+    fn write_at(&self, out: &mut Vec<u8>, offset: usize) {
+        u32(out, self.name);
+        u32(out, self.kind);
+        u64(out, self.flags);
+        u64(out, self.addr);
+        u64(out, offset);
+        u64(out, self.size);
+        u32(out, self.link);
+        u32(out, self.info);
+        u64(out, self.align);
+        u64(out, self.entsize);
+    }
+}
+
+/// Writes the header of a segment that's loaded
+/// at the same offset in the file as in memory.
+fn phdr(out: &mut Vec<u8>, kind: u32, flags: u32, addr: usize, size: usize, align: usize) {
+    u32(out, kind);
+    u32(out, flags);
+    u64(out, addr);
+    u64(out, addr);
+    u64(out, addr);
+    u64(out, size);
+    u64(out, size);
+    u64(out, align);
+}
+
+fn u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn u64(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u64).to_le_bytes());
+}
+
+fn pad(out: &mut Vec<u8>, len: usize) {
+    pad_with(out, len, 0);
+}
+
+fn pad_with(out: &mut Vec<u8>, len: usize, byte: u8) {
+    debug_assert!(out.len() <= len);
+    out.resize(len, byte);
+}
+
+#[inline]
+fn round_up(n: usize, multiple: usize) -> usize {
+    (n + multiple - 1) & !(multiple - 1)
+}
+
+const ET_DYN: u16 = 3;
+const EM_X86_64: u16 = 62;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_GNU_STACK: u32 = 0x6474_e551;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_STRTAB: u32 = 3;
+const SHT_HASH: u32 = 5;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_DYNSYM: u32 = 11;
+const SHF_WRITE: usize = 1;
+const SHF_ALLOC: usize = 2;
+const SHF_EXECINSTR: usize = 4;
+
+const STB_GLOBAL: u8 = 1;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STV_DEFAULT: u8 = 0;
+
+const DT_NULL: usize = 0;
+const DT_HASH: usize = 4;
+const DT_STRTAB: usize = 5;
+const DT_SYMTAB: usize = 6;
+const DT_STRSZ: usize = 10;
+const DT_SYMENT: usize = 11;
@@ -1,25 +1,54 @@
-use crate::{module::Converter, module_env::ModuleEnv, relocation::call_names};
+use crate::{
+    module::Converter,
+    module_env::ModuleEnv,
+    relocation::{call_names, HeapSrcLocs},
+};
 use cranelift_codegen::{
     cursor::FuncCursor,
     ir::{self, InstBuilder},
     isa,
 };
 use cranelift_wasm::{self, FuncEnvironment, ModuleEnvironment};
+use hashbrown::HashMap;
 use std::mem;
 use wasmer_runtime_core::{
     memory::MemoryType,
     structures::TypedIndex,
-    types::{FuncIndex, GlobalIndex, LocalOrImport, MemoryIndex, TableIndex},
+    types::{AnyRef, ElementType, FuncIndex, GlobalIndex, LocalOrImport, MemoryIndex, TableIndex},
     vm,
 };
 
 pub struct FuncEnv<'env, 'module, 'isa> {
     env: &'env ModuleEnv<'module, 'isa>,
+    /// The memory that each heap made by `make_heap` refers to.
+    heap_memories: HashMap<ir::Heap, MemoryIndex>,
 }
 
 impl<'env, 'module, 'isa> FuncEnv<'env, 'module, 'isa> {
     pub fn new(env: &'env ModuleEnv<'module, 'isa>) -> Self {
-        Self { env }
+        Self {
+            env,
+            heap_memories: HashMap::new(),
+        }
+    }
+
+    /// Maps the source location of every heap access in `func`
+    /// to the memory it accesses, so that a heap trap can tell
+    /// which memory it came from.
+    pub fn heap_srclocs(&self, func: &ir::Function) -> HeapSrcLocs {
+        let mut heap_srclocs = HeapSrcLocs::new();
+
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                if let ir::InstructionData::HeapAddr { heap, .. } = func.dfg[inst] {
+                    if let Some(&memory) = self.heap_memories.get(&heap) {
+                        heap_srclocs.insert(func.srclocs[inst], memory);
+                    }
+                }
+            }
+        }
+
+        heap_srclocs
     }
 
     /// Creates a signature with VMContext as the last param
@@ -206,7 +235,7 @@ impl<'env, 'module, 'isa> FuncEnvironment for FuncEnv<'env, 'module, 'isa> {
             )
         };
 
-        match description.memory_type() {
+        let heap = match description.memory_type() {
             mem_type @ MemoryType::Dynamic => {
                 let local_memory_bound = func.create_global_value(ir::GlobalValueData::Load {
                     base: local_memory_ptr,
@@ -235,7 +264,10 @@ impl<'env, 'module, 'isa> FuncEnvironment for FuncEnv<'env, 'module, 'isa> {
                     },
                     index_type: ir::types::I32,
                 }),
-        }
+        };
+
+        self.heap_memories.insert(heap, mem_index);
+        heap
     }
 
     /// Sets up the necessary preamble definitions in `func` to access the table identified
@@ -327,7 +359,11 @@ impl<'env, 'module, 'isa> FuncEnvironment for FuncEnv<'env, 'module, 'isa> {
             base_gv: table_base,
             min_size: (description.minimum as u64).into(),
             bound_gv: table_count,
-            element_size: (vm::Anyfunc::size() as u64).into(),
+            element_size: match description.element {
+                ElementType::Anyfunc => vm::Anyfunc::size() as u64,
+                ElementType::AnyRef => mem::size_of::<AnyRef>() as u64,
+            }
+            .into(),
             index_type: ir::types::I32,
         })
     }
@@ -1,8 +1,12 @@
+mod aot;
+mod bounds;
 mod call;
+mod elf;
 mod func_env;
 mod libcalls;
 mod module;
 mod module_env;
+mod nan;
 mod relocation;
 mod resolver;
 mod trampoline;
@@ -11,13 +15,22 @@ use cranelift_codegen::{
     isa,
     settings::{self, Configurable},
 };
+use std::{path::PathBuf, sync::Arc};
 use target_lexicon::Triple;
 use wasmer_runtime_core::{
     backend::{Compiler, Token},
     error::{CompileError, CompileResult},
     module::ModuleInner,
+    Features,
 };
-use wasmparser::{self, WasmDecoder};
+
+/// The trap handling of this backend, for other backends
+/// that generate native code to share.
+pub mod trap {
+    pub use crate::call::{call_protected, HandlerData};
+    pub use crate::relocation::{TrapData, TrapSink};
+    pub use cranelift_codegen::ir::{SourceLoc, TrapCode};
+}
 
 pub struct CraneliftCompiler {}
 
@@ -25,27 +38,107 @@ impl CraneliftCompiler {
     pub fn new() -> Self {
         Self {}
     }
+
+    /// Compiles a wasm binary like `Compiler::compile` does, and also
+    /// returns the trap tables of the generated code, so that code
+    /// from another backend can call into it and still report its traps.
+    pub fn compile_with_handler_data(
+        &self,
+        wasm: &[u8],
+        features: &Features,
+    ) -> CompileResult<(ModuleInner, Arc<trap::HandlerData>)> {
+        wasmer_runtime_core::validate_with_features(wasm, features)?;
+        check_features(features)?;
+
+        let isa = get_isa();
+
+        let mut module = module::Module::empty();
+        let module_env = module_env::ModuleEnv::new(&mut module, &*isa, features);
+        let (func_bodies, heap_srclocs) = module_env.translate(wasm)?;
+
+        module.compile(&*isa, func_bodies, heap_srclocs, wasm)
+    }
+
+    /// Compiles a wasm binary ahead of time to a native shared object,
+    /// which `ObjectLoader` loads back without generating any code.
+    /// Only x86_64 ELF targets are supported.
+    pub fn compile_to_object(&self, wasm: &[u8], features: &Features) -> CompileResult<Vec<u8>> {
+        wasmer_runtime_core::validate_with_features(wasm, features)?;
+        check_features(features)?;
+
+        let isa = get_isa_with_pic(true);
+
+        let mut module = module::Module::empty();
+        let module_env = module_env::ModuleEnv::new(&mut module, &*isa, features);
+        let (func_bodies, heap_srclocs) = module_env.translate(wasm)?;
+
+        module.compile_object(&*isa, func_bodies, heap_srclocs, wasm, features)
+    }
 }
 
 impl Compiler for CraneliftCompiler {
     // Compiles wasm binary to a wasmer module.
-    fn compile(&self, wasm: &[u8], _: Token) -> CompileResult<ModuleInner> {
-        validate(wasm)?;
+    fn compile(&self, wasm: &[u8], features: &Features, _: Token) -> CompileResult<ModuleInner> {
+        self.compile_with_handler_data(wasm, features)
+            .map(|(module, _)| module)
+    }
+}
+
+/// Loads modules from the shared objects written by
+/// `CraneliftCompiler::compile_to_object`, for targets
+/// where code can't be generated at runtime.
+pub struct ObjectLoader {
+    path: PathBuf,
+}
+
+impl ObjectLoader {
+    /// Loads from the shared object at `path`, which must have
+    /// been compiled from the wasm binary given to `compile`.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Compiler for ObjectLoader {
+    // Loads the code of a wasm binary from a shared object.
+    fn compile(&self, wasm: &[u8], features: &Features, _: Token) -> CompileResult<ModuleInner> {
+        wasmer_runtime_core::validate_with_features(wasm, features)?;
+        check_features(features)?;
 
         let isa = get_isa();
 
+        // The function bodies are only translated to read
+        // the module, and never compiled.
         let mut module = module::Module::empty();
-        let module_env = module_env::ModuleEnv::new(&mut module, &*isa);
-        let func_bodies = module_env.translate(wasm)?;
+        let module_env = module_env::ModuleEnv::new(&mut module, &*isa, features);
+        module_env.translate(wasm)?;
+
+        module.load_object(wasm, features, &self.path)
+    }
+}
 
-        module.compile(&*isa, func_bodies)
+/// Rejects the proposals that validate, but that
+/// cranelift-wasm can't translate yet.
+fn check_features(features: &Features) -> CompileResult<()> {
+    if features.reference_types {
+        Err(CompileError::InternalError {
+            msg: "reference types are not supported by the Cranelift backend".to_string(),
+        })?;
     }
+    Ok(())
 }
 
 fn get_isa() -> Box<isa::TargetIsa> {
+    get_isa_with_pic(false)
+}
+
+fn get_isa_with_pic(is_pic: bool) -> Box<isa::TargetIsa> {
     let flags = {
         let mut builder = settings::builder();
         builder.set("opt_level", "best").unwrap();
+        if is_pic {
+            builder.set("is_pic", "true").unwrap();
+        }
 
         if cfg!(not(test)) {
             builder.set("enable_verifier", "false").unwrap();
@@ -58,19 +151,5 @@ fn get_isa() -> Box<isa::TargetIsa> {
     isa::lookup(Triple::host()).unwrap().finish(flags)
 }
 
-fn validate(bytes: &[u8]) -> CompileResult<()> {
-    let mut parser = wasmparser::ValidatingParser::new(bytes, None);
-    loop {
-        let state = parser.read();
-        match *state {
-            wasmparser::ParserState::EndWasm => break Ok(()),
-            wasmparser::ParserState::Error(err) => Err(CompileError::ValidationError {
-                msg: err.message.to_string(),
-            })?,
-            _ => {}
-        }
-    }
-}
-
 /// The current version of this crate
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
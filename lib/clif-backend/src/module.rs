@@ -1,23 +1,34 @@
-use crate::{call::Caller, resolver::FuncResolverBuilder, trampoline::Trampolines};
+use crate::{
+    aot,
+    call::{Caller, HandlerData},
+    relocation::HeapSrcLocs,
+    resolver::FuncResolverBuilder,
+    trampoline::Trampolines,
+};
 use cranelift_codegen::{ir, isa};
 use cranelift_entity::EntityRef;
 use cranelift_wasm;
 use hashbrown::HashMap;
 use std::{
     ops::{Deref, DerefMut},
+    path::Path,
     ptr::NonNull,
+    sync::Arc,
 };
 use wasmer_runtime_core::{
     backend::SigRegistry,
     backend::{FuncResolver, ProtectedCaller, Token},
     error::{CompileResult, RuntimeResult},
+    jit_debug::DebugInfo,
     module::ModuleInner,
+    perf_map::{self, FuncNames},
     structures::{Map, TypedIndex},
     types::{
         FuncIndex, FuncSig, GlobalIndex, LocalFuncIndex, MemoryIndex, SigIndex, TableIndex, Type,
         Value,
     },
     vm::{self, ImportBacking},
+    Features,
 };
 
 struct Placeholder;
@@ -85,18 +96,71 @@ impl Module {
         mut self,
         isa: &isa::TargetIsa,
         functions: Map<LocalFuncIndex, ir::Function>,
-    ) -> CompileResult<ModuleInner> {
+        heap_srclocs: Map<LocalFuncIndex, HeapSrcLocs>,
+        wasm: &[u8],
+    ) -> CompileResult<(ModuleInner, Arc<HandlerData>)> {
         let imported_functions_len = self.module.imported_functions.len();
-        let (func_resolver_builder, handler_data) =
-            FuncResolverBuilder::new(isa, functions, imported_functions_len)?;
+        let debug_info = DebugInfo::new(wasm);
+        let (mut func_resolver_builder, handler_data) = FuncResolverBuilder::new(
+            isa,
+            functions,
+            heap_srclocs,
+            imported_functions_len,
+            debug_info.is_some(),
+        )?;
+        let srclocs = func_resolver_builder.take_srclocs();
+
+        let mut func_resolver = func_resolver_builder.finalize()?;
+
+        let names = if perf_map::is_enabled() || debug_info.is_some() {
+            Some(FuncNames::new(&self.module, wasm))
+        } else {
+            None
+        };
+        let perf_map_names = names.as_ref().filter(|_| perf_map::is_enabled());
+        if let Some(names) = perf_map_names {
+            func_resolver.record_perf_map(&self.module, names);
+        }
+        if let (Some(debug_info), Some(names)) = (debug_info, &names) {
+            func_resolver.register_debug_info(&self.module, names, &debug_info, &srclocs);
+        }
+
+        self.module.func_resolver = Box::new(func_resolver);
+
+        let trampolines = Trampolines::new(isa, &self.module, perf_map_names);
 
-        self.module.func_resolver = Box::new(func_resolver_builder.finalize()?);
+        let handler_data = Arc::new(handler_data);
+        self.module.protected_caller = Box::new(Caller::new(
+            &self.module,
+            Arc::clone(&handler_data),
+            trampolines,
+        ));
 
-        let trampolines = Trampolines::new(isa, &self.module);
+        Ok((self.module, handler_data))
+    }
 
-        self.module.protected_caller =
-            Box::new(Caller::new(&self.module, handler_data, trampolines));
+    /// Compiles the functions into a shared object
+    /// instead of memory. `isa` must generate PIC.
+    pub fn compile_object(
+        self,
+        isa: &isa::TargetIsa,
+        functions: Map<LocalFuncIndex, ir::Function>,
+        heap_srclocs: Map<LocalFuncIndex, HeapSrcLocs>,
+        wasm: &[u8],
+        features: &Features,
+    ) -> CompileResult<Vec<u8>> {
+        aot::write_object(isa, &self.module, functions, heap_srclocs, wasm, features)
+    }
 
+    /// Uses the functions that `compile_object` compiled from
+    /// `wasm` into the shared object at `path`.
+    pub fn load_object(
+        mut self,
+        wasm: &[u8],
+        features: &Features,
+        path: &Path,
+    ) -> CompileResult<ModuleInner> {
+        aot::load_object(&mut self.module, wasm, features, path)?;
         Ok(self.module)
     }
 }
@@ -1,6 +1,9 @@
 use crate::{
+    bounds,
     func_env::FuncEnv,
     module::{Converter, Module},
+    nan,
+    relocation::HeapSrcLocs,
 };
 use cranelift_codegen::{ir, isa};
 use cranelift_entity::PrimaryMap;
@@ -15,6 +18,7 @@ use wasmer_runtime_core::{
         LocalFuncIndex, LocalOrImport, MemoryDescriptor, SigIndex, TableDescriptor, Value,
     },
     units::Pages,
+    Features,
 };
 
 pub struct ModuleEnv<'module, 'isa> {
@@ -23,27 +27,66 @@ pub struct ModuleEnv<'module, 'isa> {
     pub signatures: Map<SigIndex, ir::Signature>,
     globals: Map<GlobalIndex, cranelift_wasm::Global>,
     func_bodies: Map<LocalFuncIndex, ir::Function>,
+    heap_srclocs: Map<LocalFuncIndex, HeapSrcLocs>,
     pub deduplicated: PrimaryMap<cranelift_wasm::SignatureIndex, SigIndex>,
     duplicated: HashMap<SigIndex, cranelift_wasm::SignatureIndex>,
+    /// Both set in deterministic mode.
+    canonicalize_nans: bool,
+    reserve_maximum: bool,
+    bounds_checks: bool,
+    /// The first part of the module that this backend can't compile,
+    /// since `ModuleEnvironment` has no way to report an error.
+    unsupported: Option<String>,
 }
 
 impl<'module, 'isa> ModuleEnv<'module, 'isa> {
-    pub fn new(module: &'module mut Module, isa: &'isa isa::TargetIsa) -> Self {
+    pub fn new(
+        module: &'module mut Module,
+        isa: &'isa isa::TargetIsa,
+        features: &Features,
+    ) -> Self {
         Self {
             module,
             isa,
             signatures: Map::new(),
             globals: Map::new(),
             func_bodies: Map::new(),
+            heap_srclocs: Map::new(),
             deduplicated: PrimaryMap::new(),
             duplicated: HashMap::new(),
+            canonicalize_nans: features.deterministic,
+            bounds_checks: features.bounds_checks,
+            reserve_maximum: features.deterministic,
+            unsupported: None,
         }
     }
 
-    pub fn translate(mut self, wasm: &[u8]) -> CompileResult<Map<LocalFuncIndex, ir::Function>> {
+    pub fn translate(
+        mut self,
+        wasm: &[u8],
+    ) -> CompileResult<(
+        Map<LocalFuncIndex, ir::Function>,
+        Map<LocalFuncIndex, HeapSrcLocs>,
+    )> {
         translate_module(wasm, &mut self)
             .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
-        Ok(self.func_bodies)
+        if let Some(msg) = self.unsupported {
+            return Err(CompileError::InternalError { msg }.into());
+        }
+        Ok((self.func_bodies, self.heap_srclocs))
+    }
+
+    fn element_type(&mut self, ty: cranelift_wasm::TableElementType) -> ElementType {
+        use cranelift_wasm::TableElementType;
+        match ty {
+            TableElementType::Func => ElementType::Anyfunc,
+            TableElementType::Val(ty) => {
+                self.unsupported.get_or_insert_with(|| {
+                    format!("tables of {} aren't supported by this backend", ty)
+                });
+                ElementType::Anyfunc
+            }
+        }
     }
 }
 
@@ -174,13 +217,10 @@ impl<'module, 'isa, 'data> ModuleEnvironment<'data> for ModuleEnv<'module, 'isa>
 
     /// Declares a table to the environment.
     fn declare_table(&mut self, table: cranelift_wasm::Table) {
-        use cranelift_wasm::TableElementType;
+        let element = self.element_type(table.ty);
         // Add table ir to the list of tables
         self.module.tables.push(TableDescriptor {
-            element: match table.ty {
-                TableElementType::Func => ElementType::Anyfunc,
-                _ => unimplemented!(),
-            },
+            element,
             minimum: table.minimum,
             maximum: table.maximum,
         });
@@ -193,18 +233,13 @@ impl<'module, 'isa, 'data> ModuleEnvironment<'data> for ModuleEnv<'module, 'isa>
         namespace: &'data str,
         name: &'data str,
     ) {
-        use cranelift_wasm::TableElementType;
-
         let import_name = ImportName {
             namespace: namespace.to_string(),
             name: name.to_string(),
         };
 
         let imported_table = TableDescriptor {
-            element: match table.ty {
-                TableElementType::Func => ElementType::Anyfunc,
-                _ => unimplemented!(),
-            },
+            element: self.element_type(table.ty),
             minimum: table.minimum,
             maximum: table.maximum,
         };
@@ -251,11 +286,15 @@ impl<'module, 'isa, 'data> ModuleEnvironment<'data> for ModuleEnv<'module, 'isa>
 
     /// Declares a memory to the environment
     fn declare_memory(&mut self, memory: cranelift_wasm::Memory) {
-        self.module.memories.push(MemoryDescriptor {
-            minimum: Pages(memory.minimum),
-            maximum: memory.maximum.map(|max| Pages(max)),
-            shared: memory.shared,
-        });
+        self.module.memories.push(
+            MemoryDescriptor::new(
+                Pages(memory.minimum),
+                memory.maximum.map(|max| Pages(max)),
+                memory.shared,
+            )
+            .with_bounds_checks(self.bounds_checks)
+            .with_reserved_maximum(self.reserve_maximum),
+        );
     }
 
     /// Declares a memory import to the environment.
@@ -270,11 +309,13 @@ impl<'module, 'isa, 'data> ModuleEnvironment<'data> for ModuleEnv<'module, 'isa>
             name: name.to_string(),
         };
 
-        let memory = MemoryDescriptor {
-            minimum: Pages(memory.minimum),
-            maximum: memory.maximum.map(|max| Pages(max)),
-            shared: memory.shared,
-        };
+        let memory = MemoryDescriptor::new(
+            Pages(memory.minimum),
+            memory.maximum.map(|max| Pages(max)),
+            memory.shared,
+        )
+        .with_bounds_checks(self.bounds_checks)
+        .with_reserved_maximum(self.reserve_maximum);
 
         // Add import names to list of imported memories
         self.module.imported_memories.push((import_name, memory));
@@ -356,7 +397,7 @@ impl<'module, 'isa, 'data> ModuleEnvironment<'data> for ModuleEnv<'module, 'isa>
     fn define_function_body(&mut self, body_bytes: &'data [u8]) -> cranelift_wasm::WasmResult<()> {
         let mut func_translator = FuncTranslator::new();
 
-        let func_body = {
+        let (func_body, heap_srclocs) = {
             let mut func_env = FuncEnv::new(self);
             let func_index = self.func_bodies.next_index();
             let name = ir::ExternalName::user(0, func_index.index() as u32);
@@ -368,12 +409,20 @@ impl<'module, 'isa, 'data> ModuleEnvironment<'data> for ModuleEnv<'module, 'isa>
             let mut func = ir::Function::with_name_signature(name, sig);
 
             func_translator.translate(body_bytes, &mut func, &mut func_env)?;
+            if self.canonicalize_nans {
+                nan::canonicalize_nans(&mut func);
+            }
+            if self.bounds_checks {
+                bounds::check_whole_accesses(&mut func);
+            }
 
-            func
+            let heap_srclocs = func_env.heap_srclocs(&func);
+            (func, heap_srclocs)
         };
 
         // Add function body to list of function bodies.
         self.func_bodies.push(func_body);
+        self.heap_srclocs.push(heap_srclocs);
 
         Ok(())
     }
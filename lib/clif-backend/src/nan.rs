@@ -0,0 +1,70 @@
+//! Canonicalizes the NaNs produced by float instructions, so that
+//! modules compiled in deterministic mode give bit-identical results
+//! on every host. Which NaN an operation produces otherwise depends
+//! on the hardware, and on how its operands were computed.
+
+use cranelift_codegen::{
+    cursor::{Cursor, FuncCursor},
+    ir::{
+        self,
+        condcodes::FloatCC,
+        immediates::{Ieee32, Ieee64},
+        types, InstBuilder, Opcode,
+    },
+};
+
+const CANONICAL_NAN_F32: u32 = 0x7fc0_0000;
+const CANONICAL_NAN_F64: u64 = 0x7ff8_0000_0000_0000;
+
+/// Replaces the result of every instruction in `func` that can
+/// produce a NaN from its operands with the canonical NaN, when
+/// it's a NaN. Uses of the result are redirected to the new value.
+pub fn canonicalize_nans(func: &mut ir::Function) {
+    let mut insts = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if can_produce_nan(func.dfg[inst].opcode()) {
+                insts.push(inst);
+            }
+        }
+    }
+
+    for inst in insts {
+        let result = func.dfg.first_result(inst);
+        let ty = func.dfg.value_type(result);
+        let computed = func.dfg.replace_result(result, ty);
+
+        // A float instruction is never the last one of its EBB,
+        // so this inserts right after it.
+        let mut pos = FuncCursor::new(func).at_inst(inst);
+        pos.next_inst();
+        let is_nan = pos.ins().fcmp(FloatCC::Unordered, computed, computed);
+        let nan = if ty == types::F32 {
+            pos.ins().f32const(Ieee32::with_bits(CANONICAL_NAN_F32))
+        } else {
+            pos.ins().f64const(Ieee64::with_bits(CANONICAL_NAN_F64))
+        };
+        let canonical = pos.ins().select(is_nan, nan, computed);
+
+        pos.func.dfg.change_to_alias(result, canonical);
+    }
+}
+
+fn can_produce_nan(opcode: Opcode) -> bool {
+    match opcode {
+        Opcode::Fadd
+        | Opcode::Fsub
+        | Opcode::Fmul
+        | Opcode::Fdiv
+        | Opcode::Fmin
+        | Opcode::Fmax
+        | Opcode::Sqrt
+        | Opcode::Ceil
+        | Opcode::Floor
+        | Opcode::Trunc
+        | Opcode::Nearest
+        | Opcode::Fdemote
+        | Opcode::Fpromote => true,
+        _ => false,
+    }
+}
@@ -6,7 +6,10 @@ use cranelift_codegen::binemit;
 pub use cranelift_codegen::binemit::Reloc;
 use cranelift_codegen::ir::{self, ExternalName, LibCall, SourceLoc, TrapCode};
 use hashbrown::HashMap;
-use wasmer_runtime_core::{structures::TypedIndex, types::LocalFuncIndex};
+use wasmer_runtime_core::{
+    structures::TypedIndex,
+    types::{LocalFuncIndex, MemoryIndex},
+};
 
 pub mod call_names {
     pub const LOCAL_NAMESPACE: u32 = 1;
@@ -32,7 +35,7 @@ pub struct Relocation {
     pub target: RelocationType,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum VmCallKind {
     StaticMemoryGrow,
     StaticMemorySize,
@@ -44,14 +47,27 @@ pub enum VmCallKind {
     DynamicMemorySize,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl VmCallKind {
+    /// Every kind in declaration order, so that a kind
+    /// can be stored as its position in this list.
+    pub const ALL: [VmCallKind; 6] = [
+        VmCallKind::StaticMemoryGrow,
+        VmCallKind::StaticMemorySize,
+        VmCallKind::SharedStaticMemoryGrow,
+        VmCallKind::SharedStaticMemorySize,
+        VmCallKind::DynamicMemoryGrow,
+        VmCallKind::DynamicMemorySize,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum VmCall {
     Local(VmCallKind),
     Import(VmCallKind),
 }
 
 /// Specify the type of relocation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum RelocationType {
     Normal(LocalFuncIndex),
     Intrinsic(String),
@@ -168,10 +184,16 @@ impl RelocSink {
     }
 }
 
+/// The memory accessed by each heap access in a function,
+/// keyed by the source location of the access.
+pub type HeapSrcLocs = HashMap<SourceLoc, MemoryIndex>;
+
 #[derive(Debug, Clone, Copy)]
 pub struct TrapData {
     pub trapcode: TrapCode,
     pub srcloc: SourceLoc,
+    /// The memory that a `HeapOutOfBounds` trap was accessing.
+    pub memory: Option<MemoryIndex>,
 }
 
 /// Simple implementation of a TrapSink
@@ -191,11 +213,36 @@ impl TrapSink {
         self.trap_datas.get(&offset).cloned()
     }
 
-    pub fn drain_local(&mut self, current_func_offset: usize, local: &mut LocalTrapSink) {
-        local.trap_datas.drain(..).for_each(|(offset, trap_data)| {
-            self.trap_datas
-                .insert(current_func_offset + offset, trap_data);
-        });
+    /// Every recorded trap, with its offset from the start of the code.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &TrapData)> {
+        self.trap_datas
+            .iter()
+            .map(|(&offset, trap_data)| (offset, trap_data))
+    }
+
+    /// Records a trap at `offset` from the start of the code.
+    pub fn insert(&mut self, offset: usize, trap_data: TrapData) {
+        self.trap_datas.insert(offset, trap_data);
+    }
+
+    /// Moves the traps of a function into this sink. `heap_srclocs`
+    /// maps each heap access in the function to the memory it uses.
+    pub fn drain_local(
+        &mut self,
+        current_func_offset: usize,
+        local: &mut LocalTrapSink,
+        heap_srclocs: &HeapSrcLocs,
+    ) {
+        local
+            .trap_datas
+            .drain(..)
+            .for_each(|(offset, mut trap_data)| {
+                if trap_data.trapcode == TrapCode::HeapOutOfBounds {
+                    trap_data.memory = heap_srclocs.get(&trap_data.srcloc).cloned();
+                }
+                self.trap_datas
+                    .insert(current_func_offset + offset, trap_data);
+            });
     }
 }
 
@@ -211,7 +258,13 @@ impl LocalTrapSink {
 
 impl binemit::TrapSink for LocalTrapSink {
     fn trap(&mut self, offset: u32, srcloc: SourceLoc, trapcode: TrapCode) {
-        self.trap_datas
-            .push((offset as usize, TrapData { trapcode, srcloc }));
+        self.trap_datas.push((
+            offset as usize,
+            TrapData {
+                trapcode,
+                srcloc,
+                memory: None,
+            },
+        ));
     }
 }
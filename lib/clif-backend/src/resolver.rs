@@ -1,7 +1,8 @@
 use crate::call::HandlerData;
 use crate::libcalls;
 use crate::relocation::{
-    LocalTrapSink, Reloc, RelocSink, Relocation, RelocationType, TrapSink, VmCall, VmCallKind,
+    HeapSrcLocs, LocalTrapSink, Reloc, RelocSink, Relocation, RelocationType, TrapSink, VmCall,
+    VmCallKind,
 };
 use byteorder::{ByteOrder, LittleEndian};
 use cranelift_codegen::{ir, isa, Context};
@@ -14,6 +15,9 @@ use wasmer_runtime_core::{
         sys::{Memory, Protect},
     },
     error::{CompileError, CompileResult},
+    jit_debug::{DebugInfo, FuncCode, Registration},
+    module::ModuleInner,
+    perf_map::{self, FuncNames},
     structures::{Map, TypedIndex},
     types::LocalFuncIndex,
     vm, vmcalls,
@@ -24,42 +28,108 @@ pub struct FuncResolverBuilder {
     resolver: FuncResolver,
     relocations: Map<LocalFuncIndex, Vec<Relocation>>,
     import_len: usize,
+    srclocs: Map<LocalFuncIndex, Vec<(usize, usize)>>,
 }
 
-impl FuncResolverBuilder {
-    pub fn new(
-        isa: &isa::TargetIsa,
-        function_bodies: Map<LocalFuncIndex, ir::Function>,
-        import_len: usize,
-    ) -> CompileResult<(Self, HandlerData)> {
-        let mut compiled_functions: Vec<Vec<u8>> = Vec::with_capacity(function_bodies.len());
-        let mut relocations = Map::with_capacity(function_bodies.len());
+/// The machine code of every local function of a module,
+/// before it's linked.
+pub struct CompiledFunctions {
+    pub code: Vec<Vec<u8>>,
+    /// The offset of each function from the start of the code.
+    pub offsets: Map<LocalFuncIndex, usize>,
+    pub relocations: Map<LocalFuncIndex, Vec<Relocation>>,
+    pub trap_sink: TrapSink,
+    pub total_size: usize,
+    /// The offset of each instruction from the start of its function,
+    /// and its source location, if `compile_functions` was asked for them.
+    pub srclocs: Map<LocalFuncIndex, Vec<(usize, usize)>>,
+}
 
-        let mut trap_sink = TrapSink::new();
-        let mut local_trap_sink = LocalTrapSink::new();
+/// Compiles every function body, laying them out one after the
+/// other with each one aligned to a pointer.
+pub fn compile_functions(
+    isa: &isa::TargetIsa,
+    function_bodies: Map<LocalFuncIndex, ir::Function>,
+    heap_srclocs: Map<LocalFuncIndex, HeapSrcLocs>,
+    collect_srclocs: bool,
+) -> CompileResult<CompiledFunctions> {
+    let mut code = Vec::with_capacity(function_bodies.len());
+    let mut offsets = Map::with_capacity(function_bodies.len());
+    let mut relocations = Map::with_capacity(function_bodies.len());
+    let mut srclocs = Map::new();
 
-        let mut ctx = Context::new();
-        let mut total_size = 0;
+    let mut trap_sink = TrapSink::new();
+    let mut local_trap_sink = LocalTrapSink::new();
 
-        for (_, func) in function_bodies {
-            ctx.func = func;
-            let mut code_buf = Vec::new();
-            let mut reloc_sink = RelocSink::new();
+    let mut ctx = Context::new();
+    let mut total_size = 0;
 
-            ctx.compile_and_emit(isa, &mut code_buf, &mut reloc_sink, &mut local_trap_sink)
-                .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
-            ctx.clear();
+    for ((_, func), (_, heap_srclocs)) in function_bodies.into_iter().zip(heap_srclocs) {
+        ctx.func = func;
+        let mut code_buf = Vec::new();
+        let mut reloc_sink = RelocSink::new();
+
+        ctx.compile_and_emit(isa, &mut code_buf, &mut reloc_sink, &mut local_trap_sink)
+            .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+        if collect_srclocs {
+            srclocs.push(instruction_srclocs(isa, &ctx.func));
+        }
+        ctx.clear();
 
-            // Clear the local trap sink and consolidate all trap info
-            // into a single location.
-            trap_sink.drain_local(total_size, &mut local_trap_sink);
+        // Clear the local trap sink and consolidate all trap info
+        // into a single location.
+        trap_sink.drain_local(total_size, &mut local_trap_sink, &heap_srclocs);
+        offsets.push(total_size);
 
-            // Round up each function's size to pointer alignment.
-            total_size += round_up(code_buf.len(), mem::size_of::<usize>());
+        // Round up each function's size to pointer alignment.
+        total_size += round_up(code_buf.len(), mem::size_of::<usize>());
 
-            compiled_functions.push(code_buf);
-            relocations.push(reloc_sink.func_relocs);
+        code.push(code_buf);
+        relocations.push(reloc_sink.func_relocs);
+    }
+
+    Ok(CompiledFunctions {
+        code,
+        offsets,
+        relocations,
+        trap_sink,
+        total_size,
+        srclocs,
+    })
+}
+
+/// The offset of every instruction of `func` that has a
+/// source location, after it's compiled, and its location.
+fn instruction_srclocs(isa: &isa::TargetIsa, func: &ir::Function) -> Vec<(usize, usize)> {
+    let encinfo = isa.encoding_info();
+    let mut srclocs = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for (offset, inst, _) in func.inst_offsets(ebb, &encinfo) {
+            let srcloc = func.srclocs[inst];
+            if !srcloc.is_default() {
+                srclocs.push((offset as usize, srcloc.bits() as usize));
+            }
         }
+    }
+    srclocs
+}
+
+impl FuncResolverBuilder {
+    pub fn new(
+        isa: &isa::TargetIsa,
+        function_bodies: Map<LocalFuncIndex, ir::Function>,
+        heap_srclocs: Map<LocalFuncIndex, HeapSrcLocs>,
+        import_len: usize,
+        collect_srclocs: bool,
+    ) -> CompileResult<(Self, HandlerData)> {
+        let CompiledFunctions {
+            code: compiled_functions,
+            offsets: map,
+            relocations,
+            trap_sink,
+            total_size,
+            srclocs,
+        } = compile_functions(isa, function_bodies, heap_srclocs, collect_srclocs)?;
 
         let mut memory = Memory::with_size(total_size)
             .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
@@ -83,31 +153,37 @@ impl FuncResolverBuilder {
             *i = 0xCC;
         }
 
-        let mut map = Map::with_capacity(compiled_functions.len());
-
-        let mut previous_end = 0;
-        for compiled in compiled_functions.iter() {
-            let new_end = previous_end + round_up(compiled.len(), mem::size_of::<usize>());
+        for (compiled, (_, &offset)) in compiled_functions.iter().zip(map.iter()) {
             unsafe {
-                memory.as_slice_mut()[previous_end..previous_end + compiled.len()]
+                memory.as_slice_mut()[offset..offset + compiled.len()]
                     .copy_from_slice(&compiled[..]);
             }
-            map.push(previous_end);
-            previous_end = new_end;
         }
 
         let handler_data = HandlerData::new(trap_sink, memory.as_ptr() as _, memory.size());
 
         Ok((
             Self {
-                resolver: FuncResolver { map, memory },
+                resolver: FuncResolver {
+                    map,
+                    memory,
+                    size: total_size,
+                    debug_registration: None,
+                },
                 relocations,
                 import_len,
+                srclocs,
             },
             handler_data,
         ))
     }
 
+    /// The source locations of the instructions of each
+    /// function, if they were collected.
+    pub fn take_srclocs(&mut self) -> Map<LocalFuncIndex, Vec<(usize, usize)>> {
+        mem::replace(&mut self.srclocs, Map::new())
+    }
+
     pub fn finalize(mut self) -> CompileResult<FuncResolver> {
         for (index, relocs) in self.relocations.iter() {
             for ref reloc in relocs {
@@ -122,57 +198,7 @@ impl FuncResolverBuilder {
 
                         self.resolver.lookup(local_func_index).unwrap().as_ptr() as isize
                     }
-                    RelocationType::LibCall(libcall) => match libcall {
-                        ir::LibCall::CeilF32 => libcalls::ceilf32 as isize,
-                        ir::LibCall::FloorF32 => libcalls::floorf32 as isize,
-                        ir::LibCall::TruncF32 => libcalls::truncf32 as isize,
-                        ir::LibCall::NearestF32 => libcalls::nearbyintf32 as isize,
-                        ir::LibCall::CeilF64 => libcalls::ceilf64 as isize,
-                        ir::LibCall::FloorF64 => libcalls::floorf64 as isize,
-                        ir::LibCall::TruncF64 => libcalls::truncf64 as isize,
-                        ir::LibCall::NearestF64 => libcalls::nearbyintf64 as isize,
-                        ir::LibCall::Probestack => libcalls::__rust_probestack as isize,
-                        _ => Err(CompileError::InternalError {
-                            msg: format!("unexpected libcall: {}", libcall),
-                        })?,
-                    },
-                    RelocationType::Intrinsic(ref name) => Err(CompileError::InternalError {
-                        msg: format!("unexpected intrinsic: {}", name),
-                    })?,
-                    RelocationType::VmCall(vmcall) => match vmcall {
-                        VmCall::Local(kind) => match kind {
-                            VmCallKind::StaticMemoryGrow => vmcalls::local_static_memory_grow as _,
-                            VmCallKind::StaticMemorySize => vmcalls::local_static_memory_size as _,
-
-                            VmCallKind::SharedStaticMemoryGrow => unimplemented!(),
-                            VmCallKind::SharedStaticMemorySize => unimplemented!(),
-
-                            VmCallKind::DynamicMemoryGrow => {
-                                vmcalls::local_dynamic_memory_grow as _
-                            }
-                            VmCallKind::DynamicMemorySize => {
-                                vmcalls::local_dynamic_memory_size as _
-                            }
-                        },
-                        VmCall::Import(kind) => match kind {
-                            VmCallKind::StaticMemoryGrow => {
-                                vmcalls::imported_static_memory_grow as _
-                            }
-                            VmCallKind::StaticMemorySize => {
-                                vmcalls::imported_static_memory_size as _
-                            }
-
-                            VmCallKind::SharedStaticMemoryGrow => unimplemented!(),
-                            VmCallKind::SharedStaticMemorySize => unimplemented!(),
-
-                            VmCallKind::DynamicMemoryGrow => {
-                                vmcalls::imported_dynamic_memory_grow as _
-                            }
-                            VmCallKind::DynamicMemorySize => {
-                                vmcalls::imported_dynamic_memory_size as _
-                            }
-                        },
-                    },
+                    ref target => external_address(target)?,
                 };
 
                 // We need the address of the current function
@@ -218,10 +244,69 @@ impl FuncResolverBuilder {
     }
 }
 
+/// The address of a function that compiled code calls
+/// outside of the module, like a libcall or a vmcall.
+pub fn external_address(target: &RelocationType) -> CompileResult<isize> {
+    Ok(match *target {
+        RelocationType::Normal(_) => Err(CompileError::InternalError {
+            msg: "local functions are not external".to_string(),
+        })?,
+        RelocationType::LibCall(libcall) => match libcall {
+            ir::LibCall::CeilF32 => libcalls::ceilf32 as isize,
+            ir::LibCall::FloorF32 => libcalls::floorf32 as isize,
+            ir::LibCall::TruncF32 => libcalls::truncf32 as isize,
+            ir::LibCall::NearestF32 => libcalls::nearbyintf32 as isize,
+            ir::LibCall::CeilF64 => libcalls::ceilf64 as isize,
+            ir::LibCall::FloorF64 => libcalls::floorf64 as isize,
+            ir::LibCall::TruncF64 => libcalls::truncf64 as isize,
+            ir::LibCall::NearestF64 => libcalls::nearbyintf64 as isize,
+            ir::LibCall::Probestack => libcalls::__rust_probestack as isize,
+            _ => Err(CompileError::InternalError {
+                msg: format!("unexpected libcall: {}", libcall),
+            })?,
+        },
+        RelocationType::Intrinsic(ref name) => Err(CompileError::InternalError {
+            msg: format!("unexpected intrinsic: {}", name),
+        })?,
+        RelocationType::VmCall(vmcall) => match vmcall {
+            VmCall::Local(kind) => match kind {
+                VmCallKind::StaticMemoryGrow => vmcalls::local_static_memory_grow as _,
+                VmCallKind::StaticMemorySize => vmcalls::local_static_memory_size as _,
+
+                VmCallKind::SharedStaticMemoryGrow | VmCallKind::SharedStaticMemorySize => {
+                    Err(CompileError::InternalError {
+                        msg: "shared memories are not supported by this backend".to_string(),
+                    })?
+                }
+
+                VmCallKind::DynamicMemoryGrow => vmcalls::local_dynamic_memory_grow as _,
+                VmCallKind::DynamicMemorySize => vmcalls::local_dynamic_memory_size as _,
+            },
+            VmCall::Import(kind) => match kind {
+                VmCallKind::StaticMemoryGrow => vmcalls::imported_static_memory_grow as _,
+                VmCallKind::StaticMemorySize => vmcalls::imported_static_memory_size as _,
+
+                VmCallKind::SharedStaticMemoryGrow | VmCallKind::SharedStaticMemorySize => {
+                    Err(CompileError::InternalError {
+                        msg: "shared memories are not supported by this backend".to_string(),
+                    })?
+                }
+
+                VmCallKind::DynamicMemoryGrow => vmcalls::imported_dynamic_memory_grow as _,
+                VmCallKind::DynamicMemorySize => vmcalls::imported_dynamic_memory_size as _,
+            },
+        },
+    })
+}
+
 /// Resolves a function index to a function address.
 pub struct FuncResolver {
     map: Map<LocalFuncIndex, usize>,
     memory: Memory,
+    /// The size of the code in `memory`, which is rounded up to a page.
+    size: usize,
+    /// Keeps the code known to debuggers.
+    debug_registration: Option<Registration>,
 }
 
 impl FuncResolver {
@@ -231,6 +316,48 @@ impl FuncResolver {
 
         NonNull::new(ptr).map(|nonnull| nonnull.cast())
     }
+
+    /// Writes every function to the perf map.
+    pub fn record_perf_map(&self, module: &ModuleInner, names: &FuncNames) {
+        let symbols = self
+            .map
+            .iter()
+            .map(|(index, &offset)| (offset, names.get_local(module, index).to_string()))
+            .collect();
+        perf_map::record(self.memory.as_ptr(), self.size, symbols);
+    }
+
+    /// Registers every function with debuggers, using the source
+    /// locations of their instructions to translate `debug_info`.
+    pub fn register_debug_info(
+        &mut self,
+        module: &ModuleInner,
+        names: &FuncNames,
+        debug_info: &DebugInfo,
+        srclocs: &Map<LocalFuncIndex, Vec<(usize, usize)>>,
+    ) {
+        let base = self.memory.as_ptr() as usize;
+        let funcs: Vec<_> = self
+            .map
+            .iter()
+            .zip(srclocs.iter())
+            .map(|((index, &offset), (_, srclocs))| {
+                // Each function extends up to the next one.
+                let end = self
+                    .map
+                    .get(LocalFuncIndex::new(index.index() + 1))
+                    .map_or(self.size, |&next| next);
+                FuncCode {
+                    local_func_index: index,
+                    name: names.get_local(module, index),
+                    address: base + offset,
+                    size: end - offset,
+                    srclocs,
+                }
+            })
+            .collect();
+        self.debug_registration = Some(debug_info.register("wasm", &funcs));
+    }
 }
 
 // Implements FuncResolver trait.
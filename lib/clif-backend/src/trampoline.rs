@@ -9,7 +9,8 @@ use std::{iter, mem};
 use wasmer_runtime_core::{
     backend::sys::{Memory, Protect},
     module::{ExportIndex, ModuleInner},
-    types::{FuncSig, SigIndex, Type},
+    perf_map::{self, FuncNames},
+    types::{FuncIndex, FuncSig, SigIndex, Type},
     vm,
 };
 
@@ -22,47 +23,66 @@ impl RelocSink for NullRelocSink {
 }
 
 pub struct Trampolines {
-    memory: Memory,
+    /// Owns the code, unless it was loaded from a shared object.
+    _memory: Option<Memory>,
+    base: *const u8,
     offsets: HashMap<SigIndex, usize>,
 }
 
+unsafe impl Send for Trampolines {}
+unsafe impl Sync for Trampolines {}
+
+/// Compiles a trampoline for each function that the runtime can
+/// call: the exported ones and the start function.
+pub fn compile_trampolines(
+    isa: &isa::TargetIsa,
+    module: &ModuleInner,
+) -> Vec<(FuncIndex, Vec<u8>)> {
+    let func_index_iter = module
+        .exports
+        .values()
+        .filter_map(|export| match export {
+            ExportIndex::Func(func_index) => Some(func_index),
+            _ => None,
+        })
+        .chain(module.start_func.iter());
+
+    let mut compiled_functions = Vec::new();
+    let mut ctx = Context::new();
+
+    for exported_func_index in func_index_iter {
+        let sig_index = module.func_assoc[*exported_func_index];
+        let func_sig = module.sig_registry.lookup_signature(sig_index);
+
+        let trampoline_func = generate_func(&func_sig);
+
+        ctx.func = trampoline_func;
+
+        let mut code_buf = Vec::new();
+
+        ctx.compile_and_emit(
+            isa,
+            &mut code_buf,
+            &mut NullRelocSink {},
+            &mut NullTrapSink {},
+        )
+        .expect("unable to compile trampolines");
+        ctx.clear();
+
+        compiled_functions.push((*exported_func_index, code_buf));
+    }
+
+    compiled_functions
+}
+
 impl Trampolines {
-    pub fn new(isa: &isa::TargetIsa, module: &ModuleInner) -> Self {
-        let func_index_iter = module
-            .exports
-            .values()
-            .filter_map(|export| match export {
-                ExportIndex::Func(func_index) => Some(func_index),
-                _ => None,
-            })
-            .chain(module.start_func.iter());
-
-        let mut compiled_functions = Vec::new();
-        let mut ctx = Context::new();
-        let mut total_size = 0;
-
-        for exported_func_index in func_index_iter {
-            let sig_index = module.func_assoc[*exported_func_index];
-            let func_sig = module.sig_registry.lookup_signature(sig_index);
-
-            let trampoline_func = generate_func(&func_sig);
-
-            ctx.func = trampoline_func;
-
-            let mut code_buf = Vec::new();
-
-            ctx.compile_and_emit(
-                isa,
-                &mut code_buf,
-                &mut NullRelocSink {},
-                &mut NullTrapSink {},
-            )
-            .expect("unable to compile trampolines");
-            ctx.clear();
-
-            total_size += round_up(code_buf.len(), mem::size_of::<usize>());
-            compiled_functions.push((sig_index, code_buf));
-        }
+    /// Also writes the trampolines to the perf map if `names` is given.
+    pub fn new(isa: &isa::TargetIsa, module: &ModuleInner, names: Option<&FuncNames>) -> Self {
+        let compiled_functions = compile_trampolines(isa, module);
+        let total_size = compiled_functions
+            .iter()
+            .map(|(_, compiled)| round_up(compiled.len(), mem::size_of::<usize>()))
+            .sum();
 
         let mut memory = Memory::with_size(total_size).unwrap();
         unsafe {
@@ -77,14 +97,21 @@ impl Trampolines {
 
         let mut previous_end = 0;
         let mut trampolines = HashMap::with_capacity(compiled_functions.len());
+        let mut symbols = Vec::new();
 
-        for (sig_index, compiled) in compiled_functions.iter() {
+        for (func_index, compiled) in compiled_functions.iter() {
             let new_end = previous_end + round_up(compiled.len(), mem::size_of::<usize>());
             unsafe {
                 memory.as_slice_mut()[previous_end..previous_end + compiled.len()]
                     .copy_from_slice(&compiled[..]);
             }
-            trampolines.insert(*sig_index, previous_end);
+            trampolines.insert(module.func_assoc[*func_index], previous_end);
+            if let Some(names) = names {
+                symbols.push((
+                    previous_end,
+                    format!("trampoline for {}", names.get(*func_index)),
+                ));
+            }
             previous_end = new_end;
         }
 
@@ -92,18 +119,33 @@ impl Trampolines {
             memory.protect(.., Protect::ReadExec).unwrap();
         }
 
+        if names.is_some() {
+            perf_map::record(memory.as_ptr(), total_size, symbols);
+        }
+
         Self {
-            memory,
+            base: memory.as_ptr(),
+            _memory: Some(memory),
             offsets: trampolines,
         }
     }
 
+    /// Uses trampolines that were compiled ahead of time, at `offsets`
+    /// from `base`. The code must outlive these trampolines.
+    pub unsafe fn from_code(base: *const u8, offsets: HashMap<SigIndex, usize>) -> Self {
+        Self {
+            _memory: None,
+            base,
+            offsets,
+        }
+    }
+
     pub fn lookup(
         &self,
         sig_index: SigIndex,
     ) -> Option<unsafe extern "C" fn(*mut vm::Ctx, *const vm::Func, *const u64, *mut u64)> {
         let offset = *self.offsets.get(&sig_index)?;
-        let ptr = unsafe { self.memory.as_ptr().add(offset) };
+        let ptr = unsafe { self.base.add(offset) };
 
         unsafe { Some(mem::transmute(ptr)) }
     }
@@ -168,6 +210,8 @@ fn wasm_ty_to_clif(ty: Type) -> ir::types::Type {
         Type::I64 => ir::types::I64,
         Type::F32 => ir::types::F32,
         Type::F64 => ir::types::F64,
+        // References are passed as pointers.
+        Type::AnyRef | Type::FuncRef => ir::types::I64,
     }
 }
 
@@ -221,6 +265,6 @@ fn generate_export_signature(func_sig: &FuncSig) -> ir::Signature {
 }
 
 #[inline]
-fn round_up(n: usize, multiple: usize) -> usize {
+pub fn round_up(n: usize, multiple: usize) -> usize {
     (n + multiple - 1) & !(multiple - 1)
 }
@@ -0,0 +1,282 @@
+use crate::{code::FuncBody, interp::Interpreter, native::call_native};
+use hashbrown::HashSet;
+use lazy_static::lazy_static;
+use std::{
+    ptr::NonNull,
+    sync::{Arc, RwLock, Weak},
+};
+use wasmer_runtime_core::{
+    backend::{FuncResolver, ProtectedCaller, Token},
+    error::{CompileResult, RuntimeResult},
+    module::{ExportIndex, ModuleInner},
+    structures::{Map, TypedIndex},
+    types::{FuncIndex, FuncSig, LocalFuncIndex, LocalOrImport, Value},
+    vm::{self, ImportBacking},
+};
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+use {
+    crate::native::{write_stub, STUB_SIZE},
+    wasmer_runtime_core::{
+        backend::sys::{Memory, Protect},
+        error::CompileError,
+    },
+};
+
+lazy_static! {
+    /// The address range of the entries of every interpreted module
+    /// that is alive, so a `vm::Func` pointer can be mapped back to
+    /// its function whichever module it comes from.
+    static ref CODES: RwLock<Vec<(usize, usize, Weak<Code>)>> = RwLock::new(Vec::new());
+}
+
+/// The interpreted function that `func` is the entry of,
+/// if it's one, and the translated bodies of its module.
+pub fn lookup(func: *const vm::Func) -> Option<(Arc<Code>, LocalFuncIndex)> {
+    let codes = CODES.read().unwrap();
+    let (_, _, code) = codes
+        .iter()
+        .find(|&&(base, size, _)| base <= func as usize && (func as usize) < base + size)?;
+    let code = code.upgrade()?;
+    let local_func_index = code.local_func(func)?;
+    Some((code, local_func_index))
+}
+
+/// Calls `func` with the bits of each parameter and returns the bits of
+/// its results. Interpreted functions of any module are run directly,
+/// and everything else is called natively.
+pub fn call_func(
+    func: *const vm::Func,
+    vmctx: *mut vm::Ctx,
+    signature: &FuncSig,
+    args: &[u64],
+) -> RuntimeResult<Vec<u64>> {
+    match lookup(func) {
+        Some((code, local_func_index)) => {
+            let module = unsafe { (*vmctx).module() };
+            Interpreter::new(module, &code).call(local_func_index, vmctx, args)
+        }
+        None => Ok(unsafe { call_native(func, vmctx, signature, args)? }
+            .into_iter()
+            .collect()),
+    }
+}
+
+/// The addresses that are handed out as the `vm::Func` pointers of
+/// local functions, one `ENTRY_SIZE` apart. On x86_64 System V targets
+/// each one holds a stub that runs the function in the interpreter,
+/// so native code can call it. Elsewhere they're only tokens.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+struct Entries(Memory);
+
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+const ENTRY_SIZE: usize = STUB_SIZE;
+
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+impl Entries {
+    fn new(count: usize) -> CompileResult<Self> {
+        // Always map at least one page, so the entries have a real address.
+        let mut memory = Memory::with_size((count * ENTRY_SIZE).max(1))
+            .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+
+        unsafe {
+            memory
+                .protect(.., Protect::ReadWrite)
+                .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+            let base = memory.as_ptr() as usize;
+            for (index, stub) in memory.as_slice_mut()[..count * ENTRY_SIZE]
+                .chunks_mut(ENTRY_SIZE)
+                .enumerate()
+            {
+                write_stub(stub, base + index * ENTRY_SIZE);
+            }
+            memory
+                .protect(.., Protect::ReadExec)
+                .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+        }
+
+        Ok(Entries(memory))
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        self.0.as_ptr()
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", not(target_os = "windows"))))]
+struct Entries(Box<[u8]>);
+
+#[cfg(not(all(target_arch = "x86_64", not(target_os = "windows"))))]
+const ENTRY_SIZE: usize = 1;
+
+#[cfg(not(all(target_arch = "x86_64", not(target_os = "windows"))))]
+impl Entries {
+    fn new(count: usize) -> CompileResult<Self> {
+        // Always allocate at least one byte, so the entries have a real address.
+        Ok(Entries(vec![0; count.max(1)].into_boxed_slice()))
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        self.0.as_ptr()
+    }
+}
+
+/// The translated function bodies of a module,
+/// and the entries of its local functions.
+pub struct Code {
+    pub bodies: Map<LocalFuncIndex, FuncBody>,
+    signatures: Map<LocalFuncIndex, Arc<FuncSig>>,
+    entries: Entries,
+}
+
+impl Code {
+    pub fn new(
+        module: &ModuleInner,
+        bodies: Map<LocalFuncIndex, FuncBody>,
+    ) -> CompileResult<Arc<Self>> {
+        let signatures = (0..bodies.len())
+            .map(|index| {
+                let func_index = LocalFuncIndex::new(index).convert_up(module);
+                module
+                    .sig_registry
+                    .lookup_signature(module.func_assoc[func_index])
+            })
+            .collect();
+        let entries = Entries::new(bodies.len())?;
+
+        let code = Arc::new(Self {
+            bodies,
+            signatures,
+            entries,
+        });
+        CODES.write().unwrap().push((
+            code.entries.as_ptr() as usize,
+            code.bodies.len() * ENTRY_SIZE,
+            Arc::downgrade(&code),
+        ));
+        Ok(code)
+    }
+
+    fn entry(&self, local_func_index: LocalFuncIndex) -> Option<NonNull<vm::Func>> {
+        let index = local_func_index.index();
+        if index < self.bodies.len() {
+            let entry = unsafe { self.entries.as_ptr().add(index * ENTRY_SIZE) };
+            NonNull::new(entry as *mut vm::Func)
+        } else {
+            None
+        }
+    }
+
+    /// The local function that `func` is the entry of, if it is one.
+    pub fn local_func(&self, func: *const vm::Func) -> Option<LocalFuncIndex> {
+        let offset = (func as usize).wrapping_sub(self.entries.as_ptr() as usize);
+        if offset % ENTRY_SIZE == 0 && offset / ENTRY_SIZE < self.bodies.len() {
+            Some(LocalFuncIndex::new(offset / ENTRY_SIZE))
+        } else {
+            None
+        }
+    }
+
+    pub fn signature(&self, local_func_index: LocalFuncIndex) -> &FuncSig {
+        &self.signatures[local_func_index]
+    }
+}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        let base = self.entries.as_ptr() as usize;
+        CODES
+            .write()
+            .unwrap()
+            .retain(|&(registered, _, _)| registered != base);
+    }
+}
+
+pub struct Resolver {
+    code: Arc<Code>,
+}
+
+impl Resolver {
+    pub fn new(code: Arc<Code>) -> Self {
+        Self { code }
+    }
+}
+
+impl FuncResolver for Resolver {
+    fn get(
+        &self,
+        _module: &ModuleInner,
+        local_func_index: LocalFuncIndex,
+    ) -> Option<NonNull<vm::Func>> {
+        self.code.entry(local_func_index)
+    }
+}
+
+pub struct Caller {
+    func_export_set: HashSet<FuncIndex>,
+    code: Arc<Code>,
+}
+
+impl Caller {
+    pub fn new(module: &ModuleInner, code: Arc<Code>) -> Self {
+        let mut func_export_set = HashSet::new();
+        for export_index in module.exports.values() {
+            if let ExportIndex::Func(func_index) = export_index {
+                func_export_set.insert(*func_index);
+            }
+        }
+        if let Some(start_func_index) = module.start_func {
+            func_export_set.insert(start_func_index);
+        }
+
+        Self {
+            func_export_set,
+            code,
+        }
+    }
+}
+
+impl ProtectedCaller for Caller {
+    fn call(
+        &self,
+        module: &ModuleInner,
+        func_index: FuncIndex,
+        params: &[Value],
+        import_backing: &ImportBacking,
+        vmctx: *mut vm::Ctx,
+        _: Token,
+    ) -> RuntimeResult<Vec<Value>> {
+        assert!(self.func_export_set.contains(&func_index));
+
+        let signature = module
+            .sig_registry
+            .lookup_signature(module.func_assoc[func_index]);
+
+        assert!(
+            signature.returns().len() <= 1,
+            "multi-value returns not yet supported"
+        );
+
+        assert!(
+            signature.check_param_value_types(params),
+            "incorrect signature"
+        );
+
+        let args: Vec<u64> = params.iter().map(|val| val.to_bits()).collect();
+
+        let returns = match func_index.local_or_import(module) {
+            LocalOrImport::Local(local_func_index) => {
+                Interpreter::new(module, &self.code).call(local_func_index, vmctx, &args)?
+            }
+            LocalOrImport::Import(imported_func_index) => {
+                let imported_func = import_backing.imported_func(imported_func_index);
+                call_func(imported_func.func, imported_func.vmctx, &signature, &args)?
+            }
+        };
+
+        Ok(returns
+            .iter()
+            .zip(signature.returns().iter())
+            .map(|(&x, &ty)| Value::from_bits(ty, x))
+            .collect())
+    }
+}
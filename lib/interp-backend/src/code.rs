@@ -0,0 +1,899 @@
+//! The bytecode that function bodies are translated into.
+//!
+//! The operand stack of a frame starts with its locals, parameters
+//! first, so every stack height counts them as well.
+
+use crate::native::check_signature;
+use wasmer_runtime_core::{
+    error::{CompileError, CompileResult},
+    module::ModuleInner,
+    structures::TypedIndex,
+    types::{FuncIndex, GlobalIndex, SigIndex, TableIndex},
+};
+use wasmparser::{Operator, Type as WpType};
+
+/// A branch with its target resolved.
+#[derive(Debug, Clone, Copy)]
+pub struct Branch {
+    /// The instruction to continue at.
+    pub target: usize,
+    /// The height to unwind the operand stack to.
+    pub height: u32,
+    /// The number of values on top of the stack that are
+    /// moved down to `height` and kept.
+    pub keep: u32,
+}
+
+#[derive(Debug, Clone)]
+pub enum Instr {
+    Unreachable,
+    Br(Branch),
+    BrIf(Branch),
+    /// The last branch is the default one.
+    BrTable(Box<[Branch]>),
+    /// Pops a condition and jumps to the target if it's zero.
+    BrUnless(usize),
+    Return,
+    Call(FuncIndex),
+    CallIndirect(SigIndex, TableIndex),
+    Drop,
+    Select,
+    GetLocal(u32),
+    SetLocal(u32),
+    TeeLocal(u32),
+    GetGlobal(GlobalIndex),
+    SetGlobal(GlobalIndex),
+    Load(LoadOp, u32),
+    Store(StoreOp, u32),
+    MemorySize,
+    MemoryGrow,
+    Const(u64),
+    Unary(UnOp),
+    Binary(BinOp),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LoadOp {
+    I32Load,
+    I64Load,
+    F32Load,
+    F64Load,
+    I32Load8S,
+    I32Load8U,
+    I32Load16S,
+    I32Load16U,
+    I64Load8S,
+    I64Load8U,
+    I64Load16S,
+    I64Load16U,
+    I64Load32S,
+    I64Load32U,
+}
+
+impl LoadOp {
+    /// The number of bytes that are read.
+    pub fn size(self) -> usize {
+        match self {
+            LoadOp::I32Load8S | LoadOp::I32Load8U | LoadOp::I64Load8S | LoadOp::I64Load8U => 1,
+            LoadOp::I32Load16S | LoadOp::I32Load16U | LoadOp::I64Load16S | LoadOp::I64Load16U => 2,
+            LoadOp::I32Load | LoadOp::F32Load | LoadOp::I64Load32S | LoadOp::I64Load32U => 4,
+            LoadOp::I64Load | LoadOp::F64Load => 8,
+        }
+    }
+
+    /// Extends the `raw` bytes that were read into a value.
+    pub fn extend(self, raw: u64) -> u64 {
+        match self {
+            LoadOp::I32Load8S => u64::from(raw as i8 as i32 as u32),
+            LoadOp::I32Load16S => u64::from(raw as i16 as i32 as u32),
+            LoadOp::I64Load8S => raw as i8 as i64 as u64,
+            LoadOp::I64Load16S => raw as i16 as i64 as u64,
+            LoadOp::I64Load32S => raw as i32 as i64 as u64,
+            _ => raw,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum StoreOp {
+    I32Store,
+    I64Store,
+    F32Store,
+    F64Store,
+    I32Store8,
+    I32Store16,
+    I64Store8,
+    I64Store16,
+    I64Store32,
+}
+
+impl StoreOp {
+    /// The number of bytes that are written.
+    pub fn size(self) -> usize {
+        match self {
+            StoreOp::I32Store8 | StoreOp::I64Store8 => 1,
+            StoreOp::I32Store16 | StoreOp::I64Store16 => 2,
+            StoreOp::I32Store | StoreOp::F32Store | StoreOp::I64Store32 => 4,
+            StoreOp::I64Store | StoreOp::F64Store => 8,
+        }
+    }
+}
+
+/// Operators that pop one value and push one value.
+#[derive(Debug, Clone, Copy)]
+pub enum UnOp {
+    I32Eqz,
+    I64Eqz,
+
+    I32Clz,
+    I32Ctz,
+    I32Popcnt,
+    I64Clz,
+    I64Ctz,
+    I64Popcnt,
+
+    F32Abs,
+    F32Neg,
+    F32Ceil,
+    F32Floor,
+    F32Trunc,
+    F32Nearest,
+    F32Sqrt,
+    F64Abs,
+    F64Neg,
+    F64Ceil,
+    F64Floor,
+    F64Trunc,
+    F64Nearest,
+    F64Sqrt,
+
+    I32WrapI64,
+    I32TruncSF32,
+    I32TruncUF32,
+    I32TruncSF64,
+    I32TruncUF64,
+    I64ExtendSI32,
+    I64ExtendUI32,
+    I64TruncSF32,
+    I64TruncUF32,
+    I64TruncSF64,
+    I64TruncUF64,
+    F32ConvertSI32,
+    F32ConvertUI32,
+    F32ConvertSI64,
+    F32ConvertUI64,
+    F32DemoteF64,
+    F64ConvertSI32,
+    F64ConvertUI32,
+    F64ConvertSI64,
+    F64ConvertUI64,
+    F64PromoteF32,
+
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
+
+    I32TruncSSatF32,
+    I32TruncUSatF32,
+    I32TruncSSatF64,
+    I32TruncUSatF64,
+    I64TruncSSatF32,
+    I64TruncUSatF32,
+    I64TruncSSatF64,
+    I64TruncUSatF64,
+
+    /// Not wasm operators: these replace a NaN with the canonical
+    /// one, after every float operator in deterministic mode.
+    F32CanonicalizeNan,
+    F64CanonicalizeNan,
+}
+
+/// Operators that pop two values and push one value.
+#[derive(Debug, Clone, Copy)]
+pub enum BinOp {
+    I32Eq,
+    I32Ne,
+    I32LtS,
+    I32LtU,
+    I32GtS,
+    I32GtU,
+    I32LeS,
+    I32LeU,
+    I32GeS,
+    I32GeU,
+    I64Eq,
+    I64Ne,
+    I64LtS,
+    I64LtU,
+    I64GtS,
+    I64GtU,
+    I64LeS,
+    I64LeU,
+    I64GeS,
+    I64GeU,
+
+    F32Eq,
+    F32Ne,
+    F32Lt,
+    F32Gt,
+    F32Le,
+    F32Ge,
+    F64Eq,
+    F64Ne,
+    F64Lt,
+    F64Gt,
+    F64Le,
+    F64Ge,
+
+    I32Add,
+    I32Sub,
+    I32Mul,
+    I32DivS,
+    I32DivU,
+    I32RemS,
+    I32RemU,
+    I32And,
+    I32Or,
+    I32Xor,
+    I32Shl,
+    I32ShrS,
+    I32ShrU,
+    I32Rotl,
+    I32Rotr,
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I64DivS,
+    I64DivU,
+    I64RemS,
+    I64RemU,
+    I64And,
+    I64Or,
+    I64Xor,
+    I64Shl,
+    I64ShrS,
+    I64ShrU,
+    I64Rotl,
+    I64Rotr,
+
+    F32Add,
+    F32Sub,
+    F32Mul,
+    F32Div,
+    F32Min,
+    F32Max,
+    F32Copysign,
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+    F64Min,
+    F64Max,
+    F64Copysign,
+}
+
+/// A translated function body.
+#[derive(Debug)]
+pub struct FuncBody {
+    /// The number of parameters.
+    pub num_params: usize,
+    /// The number of locals that aren't parameters.
+    pub num_locals: usize,
+    /// The number of results.
+    pub num_returns: usize,
+    pub code: Vec<Instr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ControlKind {
+    Block,
+    Loop,
+    If {
+        else_label: usize,
+    },
+    /// An `if` that has reached its `else`.
+    Else,
+    Func,
+}
+
+#[derive(Debug)]
+struct Control {
+    kind: ControlKind,
+    label: usize,
+    /// The operand stack height when the block was entered.
+    height: u32,
+    /// The number of values the block leaves on the stack.
+    arity: u32,
+    /// Whether the block was entered from unreachable code.
+    entered_unreachable: bool,
+    /// Whether the rest of the block is unreachable.
+    unreachable: bool,
+}
+
+/// Translates the operators of one function body into bytecode.
+///
+/// Branches are emitted with a label as their target, and labels
+/// are replaced with instruction indices once the body is done.
+pub struct FuncCompiler {
+    num_params: usize,
+    num_locals: usize,
+    num_returns: usize,
+    code: Vec<Instr>,
+    controls: Vec<Control>,
+    /// The instruction each label points at, once it's known.
+    labels: Vec<Option<usize>>,
+    height: u32,
+    /// Set in deterministic mode.
+    canonicalize_nans: bool,
+}
+
+impl FuncCompiler {
+    pub fn new(module: &ModuleInner, func_index: FuncIndex, canonicalize_nans: bool) -> Self {
+        let signature = module
+            .sig_registry
+            .lookup_signature(module.func_assoc[func_index]);
+
+        Self {
+            num_params: signature.params().len(),
+            num_locals: 0,
+            num_returns: signature.returns().len(),
+            code: Vec::new(),
+            controls: Vec::new(),
+            labels: Vec::new(),
+            height: 0,
+            canonicalize_nans,
+        }
+    }
+
+    /// Declares the locals of the function, which must
+    /// happen before the first operator is translated.
+    pub fn declare_locals(&mut self, locals: &[(u32, WpType)]) {
+        self.num_locals = locals.iter().map(|&(count, _)| count as usize).sum();
+    }
+
+    /// Translates one operator. `signatures` maps every type
+    /// index in the module to its signature.
+    pub fn translate(
+        &mut self,
+        module: &ModuleInner,
+        signatures: &[SigIndex],
+        op: &Operator,
+    ) -> CompileResult<()> {
+        if self.controls.is_empty() {
+            self.height = (self.num_params + self.num_locals) as u32;
+            let label = self.new_label();
+            let (height, arity) = (self.height, self.num_returns as u32);
+            self.push_control(ControlKind::Func, label, height, arity);
+        }
+
+        if self.is_unreachable() {
+            match *op {
+                Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                    let label = self.new_label();
+                    let height = self.height;
+                    self.push_control(ControlKind::Block, label, height, 0);
+                    return Ok(());
+                }
+                Operator::Else | Operator::End => {}
+                _ => return Ok(()),
+            }
+        }
+
+        match *op {
+            Operator::Unreachable => {
+                self.code.push(Instr::Unreachable);
+                self.set_unreachable();
+            }
+            Operator::Nop => {}
+            Operator::Block { ty } => {
+                let label = self.new_label();
+                let height = self.height;
+                self.push_control(ControlKind::Block, label, height, block_arity(ty)?);
+            }
+            Operator::Loop { ty } => {
+                let label = self.new_label();
+                self.place_label(label);
+                let height = self.height;
+                self.push_control(ControlKind::Loop, label, height, block_arity(ty)?);
+            }
+            Operator::If { ty } => {
+                self.pop(1);
+                let else_label = self.new_label();
+                self.code.push(Instr::BrUnless(else_label));
+                let label = self.new_label();
+                let height = self.height;
+                self.push_control(
+                    ControlKind::If { else_label },
+                    label,
+                    height,
+                    block_arity(ty)?,
+                );
+            }
+            Operator::Else => {
+                let control = self.controls.last_mut().unwrap();
+                if let ControlKind::If { else_label } = control.kind {
+                    let (label, height, arity) = (control.label, control.height, control.arity);
+                    control.kind = ControlKind::Else;
+                    let reachable = !control.unreachable;
+                    control.unreachable = control.entered_unreachable;
+
+                    if reachable {
+                        self.code.push(Instr::Br(Branch {
+                            target: label,
+                            height,
+                            keep: arity,
+                        }));
+                    }
+                    self.place_label(else_label);
+                    self.height = height;
+                }
+            }
+            Operator::End => {
+                let control = self.controls.pop().unwrap();
+                if let ControlKind::If { else_label } = control.kind {
+                    self.place_label(else_label);
+                }
+                if control.kind != ControlKind::Loop {
+                    self.place_label(control.label);
+                }
+                self.height = control.height + control.arity;
+
+                if control.kind == ControlKind::Func {
+                    self.code.push(Instr::Return);
+                }
+            }
+            Operator::Br { relative_depth } => {
+                let branch = self.branch(relative_depth);
+                self.code.push(Instr::Br(branch));
+                self.set_unreachable();
+            }
+            Operator::BrIf { relative_depth } => {
+                self.pop(1);
+                let branch = self.branch(relative_depth);
+                self.code.push(Instr::BrIf(branch));
+            }
+            Operator::BrTable { ref table } => {
+                self.pop(1);
+                let (depths, default) =
+                    table
+                        .read_table()
+                        .map_err(|err| CompileError::InternalError {
+                            msg: err.message.to_string(),
+                        })?;
+                let branches: Vec<Branch> = depths
+                    .iter()
+                    .chain(Some(&default))
+                    .map(|&depth| self.branch(depth))
+                    .collect();
+                self.code.push(Instr::BrTable(branches.into_boxed_slice()));
+                self.set_unreachable();
+            }
+            Operator::Return => {
+                self.code.push(Instr::Return);
+                self.set_unreachable();
+            }
+            Operator::Call { function_index } => {
+                let func_index = FuncIndex::new(function_index as usize);
+                let signature = module
+                    .sig_registry
+                    .lookup_signature(module.func_assoc[func_index]);
+                self.pop(signature.params().len() as u32);
+                self.push(signature.returns().len() as u32);
+                self.code.push(Instr::Call(func_index));
+            }
+            Operator::CallIndirect { index, table_index } => {
+                let sig_index = signatures[index as usize];
+                let signature = module.sig_registry.lookup_signature(sig_index);
+                // The table may hold native functions.
+                check_signature(&signature)?;
+                self.pop(signature.params().len() as u32 + 1);
+                self.push(signature.returns().len() as u32);
+                self.code.push(Instr::CallIndirect(
+                    sig_index,
+                    TableIndex::new(table_index as usize),
+                ));
+            }
+            Operator::Drop => {
+                self.pop(1);
+                self.code.push(Instr::Drop);
+            }
+            Operator::Select => {
+                self.pop(2);
+                self.code.push(Instr::Select);
+            }
+            Operator::GetLocal { local_index } => {
+                self.push(1);
+                self.code.push(Instr::GetLocal(local_index));
+            }
+            Operator::SetLocal { local_index } => {
+                self.pop(1);
+                self.code.push(Instr::SetLocal(local_index));
+            }
+            Operator::TeeLocal { local_index } => {
+                self.code.push(Instr::TeeLocal(local_index));
+            }
+            Operator::GetGlobal { global_index } => {
+                self.push(1);
+                self.code
+                    .push(Instr::GetGlobal(GlobalIndex::new(global_index as usize)));
+            }
+            Operator::SetGlobal { global_index } => {
+                self.pop(1);
+                self.code
+                    .push(Instr::SetGlobal(GlobalIndex::new(global_index as usize)));
+            }
+
+            Operator::I32Load { ref memarg } => self.load(LoadOp::I32Load, memarg.offset),
+            Operator::I64Load { ref memarg } => self.load(LoadOp::I64Load, memarg.offset),
+            Operator::F32Load { ref memarg } => self.load(LoadOp::F32Load, memarg.offset),
+            Operator::F64Load { ref memarg } => self.load(LoadOp::F64Load, memarg.offset),
+            Operator::I32Load8S { ref memarg } => self.load(LoadOp::I32Load8S, memarg.offset),
+            Operator::I32Load8U { ref memarg } => self.load(LoadOp::I32Load8U, memarg.offset),
+            Operator::I32Load16S { ref memarg } => self.load(LoadOp::I32Load16S, memarg.offset),
+            Operator::I32Load16U { ref memarg } => self.load(LoadOp::I32Load16U, memarg.offset),
+            Operator::I64Load8S { ref memarg } => self.load(LoadOp::I64Load8S, memarg.offset),
+            Operator::I64Load8U { ref memarg } => self.load(LoadOp::I64Load8U, memarg.offset),
+            Operator::I64Load16S { ref memarg } => self.load(LoadOp::I64Load16S, memarg.offset),
+            Operator::I64Load16U { ref memarg } => self.load(LoadOp::I64Load16U, memarg.offset),
+            Operator::I64Load32S { ref memarg } => self.load(LoadOp::I64Load32S, memarg.offset),
+            Operator::I64Load32U { ref memarg } => self.load(LoadOp::I64Load32U, memarg.offset),
+
+            Operator::I32Store { ref memarg } => self.store(StoreOp::I32Store, memarg.offset),
+            Operator::I64Store { ref memarg } => self.store(StoreOp::I64Store, memarg.offset),
+            Operator::F32Store { ref memarg } => self.store(StoreOp::F32Store, memarg.offset),
+            Operator::F64Store { ref memarg } => self.store(StoreOp::F64Store, memarg.offset),
+            Operator::I32Store8 { ref memarg } => self.store(StoreOp::I32Store8, memarg.offset),
+            Operator::I32Store16 { ref memarg } => self.store(StoreOp::I32Store16, memarg.offset),
+            Operator::I64Store8 { ref memarg } => self.store(StoreOp::I64Store8, memarg.offset),
+            Operator::I64Store16 { ref memarg } => self.store(StoreOp::I64Store16, memarg.offset),
+            Operator::I64Store32 { ref memarg } => self.store(StoreOp::I64Store32, memarg.offset),
+
+            Operator::MemorySize { .. } => {
+                self.push(1);
+                self.code.push(Instr::MemorySize);
+            }
+            Operator::MemoryGrow { .. } => {
+                self.code.push(Instr::MemoryGrow);
+            }
+
+            Operator::I32Const { value } => self.constant(value as u32 as u64),
+            Operator::I64Const { value } => self.constant(value as u64),
+            Operator::F32Const { ref value } => self.constant(u64::from(value.bits())),
+            Operator::F64Const { ref value } => self.constant(value.bits()),
+
+            // Reinterpreting a value doesn't change its bits.
+            Operator::I32ReinterpretF32
+            | Operator::I64ReinterpretF64
+            | Operator::F32ReinterpretI32
+            | Operator::F64ReinterpretI64 => {}
+
+            ref op => {
+                if let Some(unop) = unary_op(op) {
+                    self.code.push(Instr::Unary(unop));
+                } else if let Some(binop) = binary_op(op) {
+                    self.pop(1);
+                    self.code.push(Instr::Binary(binop));
+                } else {
+                    return Err(CompileError::InternalError {
+                        msg: format!("the interpreter doesn't support {:?}", op),
+                    }
+                    .into());
+                }
+
+                if self.canonicalize_nans {
+                    if let Some(canonicalize) = nan_canonicalization(op) {
+                        self.code.push(Instr::Unary(canonicalize));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolves every label and returns the finished function body.
+    pub fn finish(self) -> FuncBody {
+        let labels = self.labels;
+        let resolve = |label: usize| labels[label].expect("unplaced label");
+        let resolve_branch = |branch: Branch| Branch {
+            target: resolve(branch.target),
+            ..branch
+        };
+
+        let code = self
+            .code
+            .into_iter()
+            .map(|instr| match instr {
+                Instr::Br(branch) => Instr::Br(resolve_branch(branch)),
+                Instr::BrIf(branch) => Instr::BrIf(resolve_branch(branch)),
+                Instr::BrTable(branches) => Instr::BrTable(
+                    branches
+                        .iter()
+                        .map(|&branch| resolve_branch(branch))
+                        .collect::<Vec<_>>()
+                        .into_boxed_slice(),
+                ),
+                Instr::BrUnless(label) => Instr::BrUnless(resolve(label)),
+                instr => instr,
+            })
+            .collect();
+
+        FuncBody {
+            num_params: self.num_params,
+            num_locals: self.num_locals,
+            num_returns: self.num_returns,
+            code,
+        }
+    }
+
+    fn load(&mut self, op: LoadOp, offset: u32) {
+        self.code.push(Instr::Load(op, offset));
+    }
+
+    fn store(&mut self, op: StoreOp, offset: u32) {
+        self.pop(2);
+        self.code.push(Instr::Store(op, offset));
+    }
+
+    fn constant(&mut self, bits: u64) {
+        self.push(1);
+        self.code.push(Instr::Const(bits));
+    }
+
+    /// A branch to the block `relative_depth` levels up,
+    /// with its target still a label.
+    fn branch(&self, relative_depth: u32) -> Branch {
+        let control = &self.controls[self.controls.len() - 1 - relative_depth as usize];
+        Branch {
+            target: control.label,
+            height: control.height,
+            // Branching to a loop goes back to its start,
+            // which takes no values in the MVP.
+            keep: if control.kind == ControlKind::Loop {
+                0
+            } else {
+                control.arity
+            },
+        }
+    }
+
+    fn push_control(&mut self, kind: ControlKind, label: usize, height: u32, arity: u32) {
+        let unreachable = self.is_unreachable();
+        self.controls.push(Control {
+            kind,
+            label,
+            height,
+            arity,
+            entered_unreachable: unreachable,
+            unreachable,
+        });
+    }
+
+    fn is_unreachable(&self) -> bool {
+        self.controls
+            .last()
+            .map_or(false, |control| control.unreachable)
+    }
+
+    fn set_unreachable(&mut self) {
+        let control = self.controls.last_mut().unwrap();
+        control.unreachable = true;
+        self.height = control.height;
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn place_label(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+    }
+
+    fn push(&mut self, count: u32) {
+        self.height += count;
+    }
+
+    fn pop(&mut self, count: u32) {
+        self.height -= count;
+    }
+}
+
+fn block_arity(ty: WpType) -> CompileResult<u32> {
+    match ty {
+        WpType::EmptyBlockType => Ok(0),
+        WpType::I32 | WpType::I64 | WpType::F32 | WpType::F64 => Ok(1),
+        _ => Err(CompileError::InternalError {
+            msg: format!("the interpreter doesn't support {:?} blocks", ty),
+        }
+        .into()),
+    }
+}
+
+/// How to canonicalize the result of the float operators
+/// that can produce a NaN from their operands.
+fn nan_canonicalization(op: &Operator) -> Option<UnOp> {
+    match *op {
+        Operator::F32Add
+        | Operator::F32Sub
+        | Operator::F32Mul
+        | Operator::F32Div
+        | Operator::F32Min
+        | Operator::F32Max
+        | Operator::F32Sqrt
+        | Operator::F32Ceil
+        | Operator::F32Floor
+        | Operator::F32Trunc
+        | Operator::F32Nearest
+        | Operator::F32DemoteF64 => Some(UnOp::F32CanonicalizeNan),
+        Operator::F64Add
+        | Operator::F64Sub
+        | Operator::F64Mul
+        | Operator::F64Div
+        | Operator::F64Min
+        | Operator::F64Max
+        | Operator::F64Sqrt
+        | Operator::F64Ceil
+        | Operator::F64Floor
+        | Operator::F64Trunc
+        | Operator::F64Nearest
+        | Operator::F64PromoteF32 => Some(UnOp::F64CanonicalizeNan),
+        _ => None,
+    }
+}
+
+fn unary_op(op: &Operator) -> Option<UnOp> {
+    Some(match *op {
+        Operator::I32Eqz => UnOp::I32Eqz,
+        Operator::I64Eqz => UnOp::I64Eqz,
+
+        Operator::I32Clz => UnOp::I32Clz,
+        Operator::I32Ctz => UnOp::I32Ctz,
+        Operator::I32Popcnt => UnOp::I32Popcnt,
+        Operator::I64Clz => UnOp::I64Clz,
+        Operator::I64Ctz => UnOp::I64Ctz,
+        Operator::I64Popcnt => UnOp::I64Popcnt,
+
+        Operator::F32Abs => UnOp::F32Abs,
+        Operator::F32Neg => UnOp::F32Neg,
+        Operator::F32Ceil => UnOp::F32Ceil,
+        Operator::F32Floor => UnOp::F32Floor,
+        Operator::F32Trunc => UnOp::F32Trunc,
+        Operator::F32Nearest => UnOp::F32Nearest,
+        Operator::F32Sqrt => UnOp::F32Sqrt,
+        Operator::F64Abs => UnOp::F64Abs,
+        Operator::F64Neg => UnOp::F64Neg,
+        Operator::F64Ceil => UnOp::F64Ceil,
+        Operator::F64Floor => UnOp::F64Floor,
+        Operator::F64Trunc => UnOp::F64Trunc,
+        Operator::F64Nearest => UnOp::F64Nearest,
+        Operator::F64Sqrt => UnOp::F64Sqrt,
+
+        Operator::I32WrapI64 => UnOp::I32WrapI64,
+        Operator::I32TruncSF32 => UnOp::I32TruncSF32,
+        Operator::I32TruncUF32 => UnOp::I32TruncUF32,
+        Operator::I32TruncSF64 => UnOp::I32TruncSF64,
+        Operator::I32TruncUF64 => UnOp::I32TruncUF64,
+        Operator::I64ExtendSI32 => UnOp::I64ExtendSI32,
+        Operator::I64ExtendUI32 => UnOp::I64ExtendUI32,
+        Operator::I64TruncSF32 => UnOp::I64TruncSF32,
+        Operator::I64TruncUF32 => UnOp::I64TruncUF32,
+        Operator::I64TruncSF64 => UnOp::I64TruncSF64,
+        Operator::I64TruncUF64 => UnOp::I64TruncUF64,
+        Operator::F32ConvertSI32 => UnOp::F32ConvertSI32,
+        Operator::F32ConvertUI32 => UnOp::F32ConvertUI32,
+        Operator::F32ConvertSI64 => UnOp::F32ConvertSI64,
+        Operator::F32ConvertUI64 => UnOp::F32ConvertUI64,
+        Operator::F32DemoteF64 => UnOp::F32DemoteF64,
+        Operator::F64ConvertSI32 => UnOp::F64ConvertSI32,
+        Operator::F64ConvertUI32 => UnOp::F64ConvertUI32,
+        Operator::F64ConvertSI64 => UnOp::F64ConvertSI64,
+        Operator::F64ConvertUI64 => UnOp::F64ConvertUI64,
+        Operator::F64PromoteF32 => UnOp::F64PromoteF32,
+
+        Operator::I32Extend8S => UnOp::I32Extend8S,
+        Operator::I32Extend16S => UnOp::I32Extend16S,
+        Operator::I64Extend8S => UnOp::I64Extend8S,
+        Operator::I64Extend16S => UnOp::I64Extend16S,
+        Operator::I64Extend32S => UnOp::I64Extend32S,
+
+        Operator::I32TruncSSatF32 => UnOp::I32TruncSSatF32,
+        Operator::I32TruncUSatF32 => UnOp::I32TruncUSatF32,
+        Operator::I32TruncSSatF64 => UnOp::I32TruncSSatF64,
+        Operator::I32TruncUSatF64 => UnOp::I32TruncUSatF64,
+        Operator::I64TruncSSatF32 => UnOp::I64TruncSSatF32,
+        Operator::I64TruncUSatF32 => UnOp::I64TruncUSatF32,
+        Operator::I64TruncSSatF64 => UnOp::I64TruncSSatF64,
+        Operator::I64TruncUSatF64 => UnOp::I64TruncUSatF64,
+
+        _ => return None,
+    })
+}
+
+fn binary_op(op: &Operator) -> Option<BinOp> {
+    Some(match *op {
+        Operator::I32Eq => BinOp::I32Eq,
+        Operator::I32Ne => BinOp::I32Ne,
+        Operator::I32LtS => BinOp::I32LtS,
+        Operator::I32LtU => BinOp::I32LtU,
+        Operator::I32GtS => BinOp::I32GtS,
+        Operator::I32GtU => BinOp::I32GtU,
+        Operator::I32LeS => BinOp::I32LeS,
+        Operator::I32LeU => BinOp::I32LeU,
+        Operator::I32GeS => BinOp::I32GeS,
+        Operator::I32GeU => BinOp::I32GeU,
+        Operator::I64Eq => BinOp::I64Eq,
+        Operator::I64Ne => BinOp::I64Ne,
+        Operator::I64LtS => BinOp::I64LtS,
+        Operator::I64LtU => BinOp::I64LtU,
+        Operator::I64GtS => BinOp::I64GtS,
+        Operator::I64GtU => BinOp::I64GtU,
+        Operator::I64LeS => BinOp::I64LeS,
+        Operator::I64LeU => BinOp::I64LeU,
+        Operator::I64GeS => BinOp::I64GeS,
+        Operator::I64GeU => BinOp::I64GeU,
+
+        Operator::F32Eq => BinOp::F32Eq,
+        Operator::F32Ne => BinOp::F32Ne,
+        Operator::F32Lt => BinOp::F32Lt,
+        Operator::F32Gt => BinOp::F32Gt,
+        Operator::F32Le => BinOp::F32Le,
+        Operator::F32Ge => BinOp::F32Ge,
+        Operator::F64Eq => BinOp::F64Eq,
+        Operator::F64Ne => BinOp::F64Ne,
+        Operator::F64Lt => BinOp::F64Lt,
+        Operator::F64Gt => BinOp::F64Gt,
+        Operator::F64Le => BinOp::F64Le,
+        Operator::F64Ge => BinOp::F64Ge,
+
+        Operator::I32Add => BinOp::I32Add,
+        Operator::I32Sub => BinOp::I32Sub,
+        Operator::I32Mul => BinOp::I32Mul,
+        Operator::I32DivS => BinOp::I32DivS,
+        Operator::I32DivU => BinOp::I32DivU,
+        Operator::I32RemS => BinOp::I32RemS,
+        Operator::I32RemU => BinOp::I32RemU,
+        Operator::I32And => BinOp::I32And,
+        Operator::I32Or => BinOp::I32Or,
+        Operator::I32Xor => BinOp::I32Xor,
+        Operator::I32Shl => BinOp::I32Shl,
+        Operator::I32ShrS => BinOp::I32ShrS,
+        Operator::I32ShrU => BinOp::I32ShrU,
+        Operator::I32Rotl => BinOp::I32Rotl,
+        Operator::I32Rotr => BinOp::I32Rotr,
+        Operator::I64Add => BinOp::I64Add,
+        Operator::I64Sub => BinOp::I64Sub,
+        Operator::I64Mul => BinOp::I64Mul,
+        Operator::I64DivS => BinOp::I64DivS,
+        Operator::I64DivU => BinOp::I64DivU,
+        Operator::I64RemS => BinOp::I64RemS,
+        Operator::I64RemU => BinOp::I64RemU,
+        Operator::I64And => BinOp::I64And,
+        Operator::I64Or => BinOp::I64Or,
+        Operator::I64Xor => BinOp::I64Xor,
+        Operator::I64Shl => BinOp::I64Shl,
+        Operator::I64ShrS => BinOp::I64ShrS,
+        Operator::I64ShrU => BinOp::I64ShrU,
+        Operator::I64Rotl => BinOp::I64Rotl,
+        Operator::I64Rotr => BinOp::I64Rotr,
+
+        Operator::F32Add => BinOp::F32Add,
+        Operator::F32Sub => BinOp::F32Sub,
+        Operator::F32Mul => BinOp::F32Mul,
+        Operator::F32Div => BinOp::F32Div,
+        Operator::F32Min => BinOp::F32Min,
+        Operator::F32Max => BinOp::F32Max,
+        Operator::F32Copysign => BinOp::F32Copysign,
+        Operator::F64Add => BinOp::F64Add,
+        Operator::F64Sub => BinOp::F64Sub,
+        Operator::F64Mul => BinOp::F64Mul,
+        Operator::F64Div => BinOp::F64Div,
+        Operator::F64Min => BinOp::F64Min,
+        Operator::F64Max => BinOp::F64Max,
+        Operator::F64Copysign => BinOp::F64Copysign,
+
+        _ => return None,
+    })
+}
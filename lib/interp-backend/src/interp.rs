@@ -0,0 +1,778 @@
+//! Executes the bytecode of translated function bodies.
+//!
+//! Every value lives in a 64-bit slot of a single operand stack.
+//! `i32` and `f32` values only use the low 32 bits of their slot,
+//! and the high bits are always zero.
+
+use crate::{
+    call::{call_func, Code},
+    code::{BinOp, Branch, Instr, LoadOp, StoreOp, UnOp},
+};
+use std::ptr;
+use wasmer_runtime_core::{
+    error::{RuntimeError, RuntimeResult},
+    memory::MemoryType,
+    module::ModuleInner,
+    structures::TypedIndex,
+    types::{GlobalIndex, LocalFuncIndex, LocalOrImport, MemoryIndex, SigIndex, TableIndex},
+    units::Pages,
+    vm, vmcalls,
+};
+
+/// The maximum number of nested calls, after which the
+/// call stack is considered to be exhausted.
+const MAX_CALL_DEPTH: usize = 65_536;
+
+const WASM_PAGE_SIZE: usize = 65_536;
+
+struct Frame {
+    func: LocalFuncIndex,
+    /// The next instruction to execute.
+    pc: usize,
+    /// The position of the first local in the operand stack.
+    base: usize,
+    vmctx: *mut vm::Ctx,
+}
+
+pub struct Interpreter<'a> {
+    module: &'a ModuleInner,
+    code: &'a Code,
+    stack: Vec<u64>,
+    /// The frames of every caller of the running function.
+    frames: Vec<Frame>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(module: &'a ModuleInner, code: &'a Code) -> Self {
+        Self {
+            module,
+            code,
+            stack: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Calls a local function with the bits of each
+    /// parameter and returns the bits of its results.
+    pub fn call(
+        mut self,
+        func: LocalFuncIndex,
+        vmctx: *mut vm::Ctx,
+        args: &[u64],
+    ) -> RuntimeResult<Vec<u64>> {
+        self.stack.extend_from_slice(args);
+        let frame = self.enter(func, vmctx)?;
+        self.run(frame)?;
+        Ok(self.stack)
+    }
+
+    /// Creates the frame of a call to a local function,
+    /// whose parameters are on top of the stack.
+    fn enter(&mut self, func: LocalFuncIndex, vmctx: *mut vm::Ctx) -> RuntimeResult<Frame> {
+        if self.frames.len() == MAX_CALL_DEPTH {
+            return Err(RuntimeError::Unknown {
+                msg: "call stack exhausted".to_string(),
+            }
+            .into());
+        }
+
+        let body = &self.code.bodies[func];
+        let base = self.stack.len() - body.num_params;
+        self.stack
+            .resize(base + body.num_params + body.num_locals, 0);
+
+        Ok(Frame {
+            func,
+            pc: 0,
+            base,
+            vmctx,
+        })
+    }
+
+    fn run(&mut self, mut frame: Frame) -> RuntimeResult<()> {
+        let code = self.code;
+
+        loop {
+            let body = &code.bodies[frame.func];
+            let instr = &body.code[frame.pc];
+            frame.pc += 1;
+
+            match *instr {
+                Instr::Unreachable => Err(RuntimeError::Unknown {
+                    msg: "unreachable".to_string(),
+                })?,
+                Instr::Br(branch) => self.branch(&mut frame, branch),
+                Instr::BrIf(branch) => {
+                    if self.pop() as u32 != 0 {
+                        self.branch(&mut frame, branch);
+                    }
+                }
+                Instr::BrTable(ref branches) => {
+                    let index = self.pop() as u32 as usize;
+                    let branch = branches
+                        .get(index)
+                        .unwrap_or_else(|| branches.last().unwrap());
+                    self.branch(&mut frame, *branch);
+                }
+                Instr::BrUnless(target) => {
+                    if self.pop() as u32 == 0 {
+                        frame.pc = target;
+                    }
+                }
+                Instr::Return => {
+                    self.unwind(frame.base, body.num_returns);
+                    match self.frames.pop() {
+                        Some(caller) => frame = caller,
+                        None => return Ok(()),
+                    }
+                }
+                Instr::Call(func_index) => {
+                    let callee = match func_index.local_or_import(self.module) {
+                        LocalOrImport::Local(local_func_index) => {
+                            Some(self.enter(local_func_index, frame.vmctx)?)
+                        }
+                        LocalOrImport::Import(imported_func_index) => {
+                            let imported_func = unsafe {
+                                &*ctx_array::<vm::ImportedFunc>(
+                                    frame.vmctx,
+                                    vm::Ctx::offset_imported_funcs(),
+                                )
+                                .add(imported_func_index.index())
+                            };
+                            let sig_index = self.module.func_assoc[func_index];
+                            self.dispatch(imported_func.func, imported_func.vmctx, sig_index)?
+                        }
+                    };
+                    if let Some(callee) = callee {
+                        self.frames.push(frame);
+                        frame = callee;
+                    }
+                }
+                Instr::CallIndirect(sig_index, table_index) => {
+                    let callee = self.call_indirect(frame.vmctx, sig_index, table_index)?;
+                    if let Some(callee) = callee {
+                        self.frames.push(frame);
+                        frame = callee;
+                    }
+                }
+                Instr::Drop => {
+                    self.pop();
+                }
+                Instr::Select => {
+                    let cond = self.pop();
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.push(if cond as u32 != 0 { a } else { b });
+                }
+                Instr::GetLocal(index) => {
+                    let value = self.stack[frame.base + index as usize];
+                    self.push(value);
+                }
+                Instr::SetLocal(index) => {
+                    let value = self.pop();
+                    self.stack[frame.base + index as usize] = value;
+                }
+                Instr::TeeLocal(index) => {
+                    let value = *self.stack.last().unwrap();
+                    self.stack[frame.base + index as usize] = value;
+                }
+                Instr::GetGlobal(global_index) => {
+                    let global = self.global(frame.vmctx, global_index);
+                    let value = unsafe { (*global).data };
+                    self.push(value);
+                }
+                Instr::SetGlobal(global_index) => {
+                    let global = self.global(frame.vmctx, global_index);
+                    let value = self.pop();
+                    unsafe { (*global).data = value };
+                }
+                Instr::Load(op, offset) => {
+                    let addr = self.pop() as u32;
+                    let value = self.load(frame.vmctx, op, addr, offset)?;
+                    self.push(value);
+                }
+                Instr::Store(op, offset) => {
+                    let value = self.pop();
+                    let addr = self.pop() as u32;
+                    self.store(frame.vmctx, op, addr, offset, value)?;
+                }
+                Instr::MemorySize => {
+                    let bound = unsafe { (*self.memory(frame.vmctx)).bound };
+                    self.push((bound / WASM_PAGE_SIZE) as u64);
+                }
+                Instr::MemoryGrow => {
+                    let delta = Pages(self.pop() as u32);
+                    let old = self.memory_grow(frame.vmctx, delta);
+                    self.push(u64::from(old as u32));
+                }
+                Instr::Const(bits) => self.push(bits),
+                Instr::Unary(op) => {
+                    let value = self.pop();
+                    self.push(unary(op, value)?);
+                }
+                Instr::Binary(op) => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.push(binary(op, a, b)?);
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, value: u64) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> u64 {
+        self.stack.pop().unwrap()
+    }
+
+    fn branch(&mut self, frame: &mut Frame, branch: Branch) {
+        self.unwind(frame.base + branch.height as usize, branch.keep as usize);
+        frame.pc = branch.target;
+    }
+
+    /// Moves the `keep` values on top of the stack down to `height`,
+    /// dropping everything in between.
+    fn unwind(&mut self, height: usize, keep: usize) {
+        let from = self.stack.len() - keep;
+        for i in 0..keep {
+            self.stack[height + i] = self.stack[from + i];
+        }
+        self.stack.truncate(height + keep);
+    }
+
+    /// Calls `func`, which takes the values of `sig_index`. Functions of
+    /// this module get a new frame, which is returned, and everything
+    /// else, including functions of other interpreted modules, runs to
+    /// completion before this returns.
+    fn dispatch(
+        &mut self,
+        func: *const vm::Func,
+        vmctx: *mut vm::Ctx,
+        sig_index: SigIndex,
+    ) -> RuntimeResult<Option<Frame>> {
+        if let Some(local_func_index) = self.code.local_func(func) {
+            return Ok(Some(self.enter(local_func_index, vmctx)?));
+        }
+
+        let signature = self.module.sig_registry.lookup_signature(sig_index);
+        let args = self
+            .stack
+            .split_off(self.stack.len() - signature.params().len());
+
+        let returns = call_func(func, vmctx, &signature, &args)?;
+        self.stack.extend(returns);
+
+        Ok(None)
+    }
+
+    fn call_indirect(
+        &mut self,
+        vmctx: *mut vm::Ctx,
+        sig_index: SigIndex,
+        table_index: TableIndex,
+    ) -> RuntimeResult<Option<Frame>> {
+        let table = unsafe {
+            match table_index.local_or_import(self.module) {
+                LocalOrImport::Local(index) => {
+                    *ctx_array::<*mut vm::LocalTable>(vmctx, vm::Ctx::offset_tables())
+                        .add(index.index())
+                }
+                LocalOrImport::Import(index) => {
+                    *ctx_array::<*mut vm::LocalTable>(vmctx, vm::Ctx::offset_imported_tables())
+                        .add(index.index())
+                }
+            }
+        };
+
+        let callee = self.pop() as u32 as usize;
+        let anyfunc = unsafe {
+            if callee >= (*table).count {
+                return Err(RuntimeError::TableOutOfBounds { table: table_index }.into());
+            }
+            &*((*table).base as *const vm::Anyfunc).add(callee)
+        };
+
+        if anyfunc.func.is_null() {
+            return Err(RuntimeError::IndirectCallToNull { table: table_index }.into());
+        }
+        if anyfunc.sig_id.0 as usize != sig_index.index() {
+            return Err(RuntimeError::IndirectCallSignature { table: table_index }.into());
+        }
+
+        self.dispatch(anyfunc.func, anyfunc.ctx, sig_index)
+    }
+
+    fn global(&self, vmctx: *mut vm::Ctx, global_index: GlobalIndex) -> *mut vm::LocalGlobal {
+        unsafe {
+            match global_index.local_or_import(self.module) {
+                LocalOrImport::Local(index) => {
+                    *ctx_array::<*mut vm::LocalGlobal>(vmctx, vm::Ctx::offset_globals())
+                        .add(index.index())
+                }
+                LocalOrImport::Import(index) => {
+                    *ctx_array::<*mut vm::LocalGlobal>(vmctx, vm::Ctx::offset_imported_globals())
+                        .add(index.index())
+                }
+            }
+        }
+    }
+
+    fn memory(&self, vmctx: *mut vm::Ctx) -> *mut vm::LocalMemory {
+        unsafe {
+            match MemoryIndex::new(0).local_or_import(self.module) {
+                LocalOrImport::Local(index) => {
+                    *ctx_array::<*mut vm::LocalMemory>(vmctx, vm::Ctx::offset_memories())
+                        .add(index.index())
+                }
+                LocalOrImport::Import(index) => {
+                    *ctx_array::<*mut vm::LocalMemory>(vmctx, vm::Ctx::offset_imported_memories())
+                        .add(index.index())
+                }
+            }
+        }
+    }
+
+    fn memory_grow(&self, vmctx: *mut vm::Ctx, delta: Pages) -> i32 {
+        let ctx = unsafe { &mut *vmctx };
+        unsafe {
+            match MemoryIndex::new(0).local_or_import(self.module) {
+                LocalOrImport::Local(index) => match self.module.memories[index].memory_type() {
+                    MemoryType::Dynamic => vmcalls::local_dynamic_memory_grow(index, delta, ctx),
+                    MemoryType::Static | MemoryType::SharedStatic => {
+                        vmcalls::local_static_memory_grow(index, delta, ctx)
+                    }
+                },
+                LocalOrImport::Import(index) => {
+                    match self.module.imported_memories[index].1.memory_type() {
+                        MemoryType::Dynamic => {
+                            vmcalls::imported_dynamic_memory_grow(index, delta, ctx)
+                        }
+                        MemoryType::Static | MemoryType::SharedStatic => {
+                            vmcalls::imported_static_memory_grow(index, delta, ctx)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks that `size` bytes at `addr + offset` are in bounds
+    /// and returns a pointer to them.
+    fn effective_address(
+        &self,
+        vmctx: *mut vm::Ctx,
+        addr: u32,
+        offset: u32,
+        size: usize,
+    ) -> RuntimeResult<*mut u8> {
+        let memory = self.memory(vmctx);
+        let (base, bound) = unsafe { ((*memory).base, (*memory).bound) };
+        let addr = u64::from(addr) + u64::from(offset);
+
+        if addr + size as u64 > bound as u64 {
+            return Err(RuntimeError::OutOfBoundsAccess {
+                memory: MemoryIndex::new(0),
+                addr: addr.min(u64::from(u32::max_value())) as u32,
+            }
+            .into());
+        }
+
+        Ok(unsafe { base.add(addr as usize) })
+    }
+
+    fn load(&self, vmctx: *mut vm::Ctx, op: LoadOp, addr: u32, offset: u32) -> RuntimeResult<u64> {
+        let ptr = self.effective_address(vmctx, addr, offset, op.size())?;
+        let raw = unsafe {
+            match op.size() {
+                1 => u64::from(*ptr),
+                2 => u64::from(u16::from_le(ptr::read_unaligned(ptr as *const u16))),
+                4 => u64::from(u32::from_le(ptr::read_unaligned(ptr as *const u32))),
+                _ => u64::from_le(ptr::read_unaligned(ptr as *const u64)),
+            }
+        };
+        Ok(op.extend(raw))
+    }
+
+    fn store(
+        &self,
+        vmctx: *mut vm::Ctx,
+        op: StoreOp,
+        addr: u32,
+        offset: u32,
+        value: u64,
+    ) -> RuntimeResult<()> {
+        let ptr = self.effective_address(vmctx, addr, offset, op.size())?;
+        unsafe {
+            match op.size() {
+                1 => *ptr = value as u8,
+                2 => ptr::write_unaligned(ptr as *mut u16, (value as u16).to_le()),
+                4 => ptr::write_unaligned(ptr as *mut u32, (value as u32).to_le()),
+                _ => ptr::write_unaligned(ptr as *mut u64, value.to_le()),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the pointer to the array at `offset` in the vmctx.
+unsafe fn ctx_array<T>(vmctx: *mut vm::Ctx, offset: u8) -> *mut T {
+    *((vmctx as *const u8).add(offset as usize) as *const *mut T)
+}
+
+fn from_i32(x: i32) -> u64 {
+    u64::from(x as u32)
+}
+
+fn from_bool(x: bool) -> u64 {
+    x as u64
+}
+
+fn f32_of(bits: u64) -> f32 {
+    f32::from_bits(bits as u32)
+}
+
+fn from_f32(x: f32) -> u64 {
+    u64::from(x.to_bits())
+}
+
+fn f64_of(bits: u64) -> f64 {
+    f64::from_bits(bits)
+}
+
+fn from_f64(x: f64) -> u64 {
+    x.to_bits()
+}
+
+/// The NaNs that float operators produce in deterministic mode.
+const CANONICAL_NAN_F32: u32 = 0x7fc0_0000;
+const CANONICAL_NAN_F64: u64 = 0x7ff8_0000_0000_0000;
+
+fn arithmetic_trap() -> RuntimeError {
+    RuntimeError::IllegalArithmeticOperation
+}
+
+fn unary(op: UnOp, v: u64) -> RuntimeResult<u64> {
+    let (i32v, i64v) = (v as i32, v as i64);
+    let (f32v, f64v) = (f32_of(v), f64_of(v));
+
+    Ok(match op {
+        UnOp::I32Eqz => from_bool(v as u32 == 0),
+        UnOp::I64Eqz => from_bool(v == 0),
+
+        UnOp::I32Clz => u64::from((v as u32).leading_zeros()),
+        UnOp::I32Ctz => u64::from((v as u32).trailing_zeros()),
+        UnOp::I32Popcnt => u64::from((v as u32).count_ones()),
+        UnOp::I64Clz => u64::from(v.leading_zeros()),
+        UnOp::I64Ctz => u64::from(v.trailing_zeros()),
+        UnOp::I64Popcnt => u64::from(v.count_ones()),
+
+        // `abs`, `neg` and `copysign` only touch the sign bit, even for NaNs.
+        UnOp::F32Abs => v & 0x7fff_ffff,
+        UnOp::F32Neg => v ^ 0x8000_0000,
+        UnOp::F32Ceil => from_f32(f32v.ceil()),
+        UnOp::F32Floor => from_f32(f32v.floor()),
+        UnOp::F32Trunc => from_f32(f32v.trunc()),
+        UnOp::F32Nearest => from_f32(nearest_f32(f32v)),
+        UnOp::F32Sqrt => from_f32(f32v.sqrt()),
+        UnOp::F64Abs => v & 0x7fff_ffff_ffff_ffff,
+        UnOp::F64Neg => v ^ 0x8000_0000_0000_0000,
+        UnOp::F64Ceil => from_f64(f64v.ceil()),
+        UnOp::F64Floor => from_f64(f64v.floor()),
+        UnOp::F64Trunc => from_f64(f64v.trunc()),
+        UnOp::F64Nearest => from_f64(nearest_f64(f64v)),
+        UnOp::F64Sqrt => from_f64(f64v.sqrt()),
+
+        UnOp::I32WrapI64 => u64::from(v as u32),
+        UnOp::I32TruncSF32 => {
+            from_i32(trunc(f64::from(f32v), -2_147_483_649.0, 2_147_483_648.0)? as i32)
+        }
+        UnOp::I32TruncUF32 => u64::from(trunc(f64::from(f32v), -1.0, 4_294_967_296.0)? as u32),
+        UnOp::I32TruncSF64 => from_i32(trunc(f64v, -2_147_483_649.0, 2_147_483_648.0)? as i32),
+        UnOp::I32TruncUF64 => u64::from(trunc(f64v, -1.0, 4_294_967_296.0)? as u32),
+        UnOp::I64ExtendSI32 => i32v as i64 as u64,
+        UnOp::I64ExtendUI32 => u64::from(v as u32),
+        UnOp::I64TruncSF32 => trunc_i64(f64::from(f32v))? as u64,
+        UnOp::I64TruncUF32 => trunc_u64(f64::from(f32v))?,
+        UnOp::I64TruncSF64 => trunc_i64(f64v)? as u64,
+        UnOp::I64TruncUF64 => trunc_u64(f64v)?,
+        UnOp::F32ConvertSI32 => from_f32(i32v as f32),
+        UnOp::F32ConvertUI32 => from_f32(v as u32 as f32),
+        UnOp::F32ConvertSI64 => from_f32(i64v as f32),
+        UnOp::F32ConvertUI64 => from_f32(v as f32),
+        UnOp::F32DemoteF64 => from_f32(f64v as f32),
+        UnOp::F64ConvertSI32 => from_f64(f64::from(i32v)),
+        UnOp::F64ConvertUI32 => from_f64(f64::from(v as u32)),
+        UnOp::F64ConvertSI64 => from_f64(i64v as f64),
+        UnOp::F64ConvertUI64 => from_f64(v as f64),
+        UnOp::F64PromoteF32 => from_f64(f64::from(f32v)),
+
+        UnOp::I32Extend8S => from_i32(i32::from(v as i8)),
+        UnOp::I32Extend16S => from_i32(i32::from(v as i16)),
+        UnOp::I64Extend8S => i64::from(v as i8) as u64,
+        UnOp::I64Extend16S => i64::from(v as i16) as u64,
+        UnOp::I64Extend32S => i64::from(v as i32) as u64,
+
+        UnOp::I32TruncSSatF32 => from_i32(sat_i32(f64::from(f32v))),
+        UnOp::I32TruncUSatF32 => u64::from(sat_u32(f64::from(f32v))),
+        UnOp::I32TruncSSatF64 => from_i32(sat_i32(f64v)),
+        UnOp::I32TruncUSatF64 => u64::from(sat_u32(f64v)),
+        UnOp::I64TruncSSatF32 => sat_i64(f64::from(f32v)) as u64,
+        UnOp::I64TruncUSatF32 => sat_u64(f64::from(f32v)),
+        UnOp::I64TruncSSatF64 => sat_i64(f64v) as u64,
+        UnOp::I64TruncUSatF64 => sat_u64(f64v),
+
+        UnOp::F32CanonicalizeNan if f32v.is_nan() => u64::from(CANONICAL_NAN_F32),
+        UnOp::F64CanonicalizeNan if f64v.is_nan() => CANONICAL_NAN_F64,
+        UnOp::F32CanonicalizeNan | UnOp::F64CanonicalizeNan => v,
+    })
+}
+
+fn binary(op: BinOp, a: u64, b: u64) -> RuntimeResult<u64> {
+    let (a32, b32) = (a as i32, b as i32);
+    let (au32, bu32) = (a as u32, b as u32);
+    let (a64, b64) = (a as i64, b as i64);
+    let (af32, bf32) = (f32_of(a), f32_of(b));
+    let (af64, bf64) = (f64_of(a), f64_of(b));
+
+    Ok(match op {
+        BinOp::I32Eq => from_bool(au32 == bu32),
+        BinOp::I32Ne => from_bool(au32 != bu32),
+        BinOp::I32LtS => from_bool(a32 < b32),
+        BinOp::I32LtU => from_bool(au32 < bu32),
+        BinOp::I32GtS => from_bool(a32 > b32),
+        BinOp::I32GtU => from_bool(au32 > bu32),
+        BinOp::I32LeS => from_bool(a32 <= b32),
+        BinOp::I32LeU => from_bool(au32 <= bu32),
+        BinOp::I32GeS => from_bool(a32 >= b32),
+        BinOp::I32GeU => from_bool(au32 >= bu32),
+        BinOp::I64Eq => from_bool(a == b),
+        BinOp::I64Ne => from_bool(a != b),
+        BinOp::I64LtS => from_bool(a64 < b64),
+        BinOp::I64LtU => from_bool(a < b),
+        BinOp::I64GtS => from_bool(a64 > b64),
+        BinOp::I64GtU => from_bool(a > b),
+        BinOp::I64LeS => from_bool(a64 <= b64),
+        BinOp::I64LeU => from_bool(a <= b),
+        BinOp::I64GeS => from_bool(a64 >= b64),
+        BinOp::I64GeU => from_bool(a >= b),
+
+        BinOp::F32Eq => from_bool(af32 == bf32),
+        BinOp::F32Ne => from_bool(af32 != bf32),
+        BinOp::F32Lt => from_bool(af32 < bf32),
+        BinOp::F32Gt => from_bool(af32 > bf32),
+        BinOp::F32Le => from_bool(af32 <= bf32),
+        BinOp::F32Ge => from_bool(af32 >= bf32),
+        BinOp::F64Eq => from_bool(af64 == bf64),
+        BinOp::F64Ne => from_bool(af64 != bf64),
+        BinOp::F64Lt => from_bool(af64 < bf64),
+        BinOp::F64Gt => from_bool(af64 > bf64),
+        BinOp::F64Le => from_bool(af64 <= bf64),
+        BinOp::F64Ge => from_bool(af64 >= bf64),
+
+        BinOp::I32Add => u64::from(au32.wrapping_add(bu32)),
+        BinOp::I32Sub => u64::from(au32.wrapping_sub(bu32)),
+        BinOp::I32Mul => u64::from(au32.wrapping_mul(bu32)),
+        BinOp::I32DivS => from_i32(a32.checked_div(b32).ok_or_else(arithmetic_trap)?),
+        BinOp::I32DivU => u64::from(au32.checked_div(bu32).ok_or_else(arithmetic_trap)?),
+        BinOp::I32RemS => {
+            if b32 == 0 {
+                return Err(arithmetic_trap().into());
+            }
+            from_i32(a32.wrapping_rem(b32))
+        }
+        BinOp::I32RemU => u64::from(au32.checked_rem(bu32).ok_or_else(arithmetic_trap)?),
+        BinOp::I32And => u64::from(au32 & bu32),
+        BinOp::I32Or => u64::from(au32 | bu32),
+        BinOp::I32Xor => u64::from(au32 ^ bu32),
+        BinOp::I32Shl => u64::from(au32.wrapping_shl(bu32)),
+        BinOp::I32ShrS => from_i32(a32.wrapping_shr(bu32)),
+        BinOp::I32ShrU => u64::from(au32.wrapping_shr(bu32)),
+        BinOp::I32Rotl => u64::from(au32.rotate_left(bu32 % 32)),
+        BinOp::I32Rotr => u64::from(au32.rotate_right(bu32 % 32)),
+        BinOp::I64Add => a.wrapping_add(b),
+        BinOp::I64Sub => a.wrapping_sub(b),
+        BinOp::I64Mul => a.wrapping_mul(b),
+        BinOp::I64DivS => a64.checked_div(b64).ok_or_else(arithmetic_trap)? as u64,
+        BinOp::I64DivU => a.checked_div(b).ok_or_else(arithmetic_trap)?,
+        BinOp::I64RemS => {
+            if b64 == 0 {
+                return Err(arithmetic_trap().into());
+            }
+            a64.wrapping_rem(b64) as u64
+        }
+        BinOp::I64RemU => a.checked_rem(b).ok_or_else(arithmetic_trap)?,
+        BinOp::I64And => a & b,
+        BinOp::I64Or => a | b,
+        BinOp::I64Xor => a ^ b,
+        BinOp::I64Shl => a.wrapping_shl(b as u32),
+        BinOp::I64ShrS => a64.wrapping_shr(b as u32) as u64,
+        BinOp::I64ShrU => a.wrapping_shr(b as u32),
+        BinOp::I64Rotl => a.rotate_left((b % 64) as u32),
+        BinOp::I64Rotr => a.rotate_right((b % 64) as u32),
+
+        BinOp::F32Add => from_f32(af32 + bf32),
+        BinOp::F32Sub => from_f32(af32 - bf32),
+        BinOp::F32Mul => from_f32(af32 * bf32),
+        BinOp::F32Div => from_f32(af32 / bf32),
+        BinOp::F32Min => from_f32(min_f32(af32, bf32)),
+        BinOp::F32Max => from_f32(max_f32(af32, bf32)),
+        BinOp::F32Copysign => (a & 0x7fff_ffff) | (b & 0x8000_0000),
+        BinOp::F64Add => from_f64(af64 + bf64),
+        BinOp::F64Sub => from_f64(af64 - bf64),
+        BinOp::F64Mul => from_f64(af64 * bf64),
+        BinOp::F64Div => from_f64(af64 / bf64),
+        BinOp::F64Min => from_f64(min_f64(af64, bf64)),
+        BinOp::F64Max => from_f64(max_f64(af64, bf64)),
+        BinOp::F64Copysign => (a & 0x7fff_ffff_ffff_ffff) | (b & 0x8000_0000_0000_0000),
+    })
+}
+
+/// Truncates `x`, trapping unless the result is strictly
+/// between `lower` and `upper`.
+fn trunc(x: f64, lower: f64, upper: f64) -> RuntimeResult<f64> {
+    if x.is_nan() || x <= lower || x >= upper {
+        return Err(arithmetic_trap().into());
+    }
+    Ok(x.trunc())
+}
+
+fn trunc_i64(x: f64) -> RuntimeResult<i64> {
+    // -2^63 is the smallest value that fits, and the
+    // next smaller `f64` is 2048 below it.
+    Ok(trunc(x, -9_223_372_036_854_777_856.0, 9_223_372_036_854_775_808.0)? as i64)
+}
+
+fn trunc_u64(x: f64) -> RuntimeResult<u64> {
+    Ok(trunc(x, -1.0, 18_446_744_073_709_551_616.0)? as u64)
+}
+
+fn sat_i32(x: f64) -> i32 {
+    if x.is_nan() {
+        0
+    } else if x <= f64::from(i32::min_value()) {
+        i32::min_value()
+    } else if x >= f64::from(i32::max_value()) {
+        i32::max_value()
+    } else {
+        x as i32
+    }
+}
+
+fn sat_u32(x: f64) -> u32 {
+    if x.is_nan() || x <= 0.0 {
+        0
+    } else if x >= f64::from(u32::max_value()) {
+        u32::max_value()
+    } else {
+        x as u32
+    }
+}
+
+fn sat_i64(x: f64) -> i64 {
+    if x.is_nan() {
+        0
+    } else if x <= -9_223_372_036_854_775_808.0 {
+        i64::min_value()
+    } else if x >= 9_223_372_036_854_775_808.0 {
+        i64::max_value()
+    } else {
+        x as i64
+    }
+}
+
+fn sat_u64(x: f64) -> u64 {
+    if x.is_nan() || x <= 0.0 {
+        0
+    } else if x >= 18_446_744_073_709_551_616.0 {
+        u64::max_value()
+    } else {
+        x as u64
+    }
+}
+
+/// Rounds to the nearest integer, with ties going to the even one.
+fn nearest_f32(x: f32) -> f32 {
+    let rounded = x.round();
+    let result = if (x - x.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+        rounded - x.signum()
+    } else {
+        rounded
+    };
+
+    // A zero keeps the sign of `x`.
+    if result == 0.0 {
+        f32::from_bits(x.to_bits() & 0x8000_0000)
+    } else {
+        result
+    }
+}
+
+/// Rounds to the nearest integer, with ties going to the even one.
+fn nearest_f64(x: f64) -> f64 {
+    let rounded = x.round();
+    let result = if (x - x.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+        rounded - x.signum()
+    } else {
+        rounded
+    };
+
+    // A zero keeps the sign of `x`.
+    if result == 0.0 {
+        f64::from_bits(x.to_bits() & 0x8000_0000_0000_0000)
+    } else {
+        result
+    }
+}
+
+// Unlike `f32::min` and friends, these return NaN if either
+// operand is NaN, and order -0 below +0.
+
+fn min_f32(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == b {
+        f32::from_bits(a.to_bits() | b.to_bits())
+    } else if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+fn max_f32(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == b {
+        f32::from_bits(a.to_bits() & b.to_bits())
+    } else if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+fn min_f64(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == b {
+        f64::from_bits(a.to_bits() | b.to_bits())
+    } else if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+fn max_f64(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == b {
+        f64::from_bits(a.to_bits() & b.to_bits())
+    } else if a > b {
+        a
+    } else {
+        b
+    }
+}
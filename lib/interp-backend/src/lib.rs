@@ -0,0 +1,42 @@
+//! A portable WebAssembly interpreter for wasmer.
+//!
+//! Function bodies are translated into a compact bytecode with every
+//! branch resolved ahead of time, and then interpreted. Nothing is
+//! compiled to machine code, so traps in interpreted code are reported
+//! as errors instead of through signals. The only executable memory
+//! is a small stub per function on x86_64 System V targets, which lets
+//! native code call interpreted functions. A trap behind such a call
+//! reaches the native caller as an unknown trap, see `native`.
+
+mod call;
+mod code;
+mod interp;
+mod module;
+mod native;
+
+use wasmer_runtime_core::{
+    backend::{Compiler, Token},
+    error::CompileResult,
+    module::ModuleInner,
+    Features,
+};
+
+pub struct InterpCompiler {}
+
+impl InterpCompiler {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Compiler for InterpCompiler {
+    // Translates a wasm binary into an interpreted wasmer module.
+    fn compile(&self, wasm: &[u8], features: &Features, _: Token) -> CompileResult<ModuleInner> {
+        wasmer_runtime_core::validate_with_features(wasm, features)?;
+
+        module::translate(wasm, features)
+    }
+}
+
+/// The current version of this crate
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,84 @@
+use crate::{
+    call::{Caller, Code, Resolver},
+    code::{FuncBody, FuncCompiler},
+    native::check_signature,
+};
+use std::sync::Arc;
+use wasmer_runtime_core::{
+    error::CompileResult,
+    module::ModuleInner,
+    parse::{self, FuncTranslator},
+    structures::{Map, TypedIndex},
+    types::{LocalFuncIndex, SigIndex},
+    Features,
+};
+use wasmparser::{Operator, Type as WpType};
+
+/// Translates each function body into bytecode.
+struct Translator {
+    bodies: Map<LocalFuncIndex, FuncBody>,
+    func_compiler: Option<FuncCompiler>,
+    canonicalize_nans: bool,
+}
+
+impl FuncTranslator for Translator {
+    fn begin_body(
+        &mut self,
+        module: &ModuleInner,
+        local_func_index: LocalFuncIndex,
+    ) -> CompileResult<()> {
+        let func_index = local_func_index.convert_up(module);
+        self.func_compiler = Some(FuncCompiler::new(
+            module,
+            func_index,
+            self.canonicalize_nans,
+        ));
+        Ok(())
+    }
+
+    fn declare_locals(&mut self, locals: &[(u32, WpType)]) -> CompileResult<()> {
+        self.func_compiler.as_mut().unwrap().declare_locals(locals);
+        Ok(())
+    }
+
+    fn translate(
+        &mut self,
+        module: &ModuleInner,
+        signatures: &[SigIndex],
+        op: &Operator,
+    ) -> CompileResult<()> {
+        self.func_compiler
+            .as_mut()
+            .unwrap()
+            .translate(module, signatures, op)
+    }
+
+    fn end_body(&mut self) -> CompileResult<()> {
+        let body = self.func_compiler.take().unwrap().finish();
+        self.bodies.push(body);
+        Ok(())
+    }
+}
+
+/// Parses a validated wasm binary and translates
+/// every function body in it into bytecode.
+pub fn translate(wasm: &[u8], features: &Features) -> CompileResult<ModuleInner> {
+    let mut translator = Translator {
+        bodies: Map::new(),
+        func_compiler: None,
+        canonicalize_nans: features.deterministic,
+    };
+    let mut module = parse::read_module(wasm, features, &mut translator)?;
+
+    // Imported functions may be native, and native
+    // code may call any local function.
+    for (_, &sig_index) in &module.func_assoc {
+        check_signature(&module.sig_registry.lookup_signature(sig_index))?;
+    }
+
+    let code = Code::new(&module, translator.bodies)?;
+    module.func_resolver = Box::new(Resolver::new(Arc::clone(&code)));
+    module.protected_caller = Box::new(Caller::new(&module, code));
+
+    Ok(module)
+}
@@ -0,0 +1,305 @@
+//! Calls between the interpreter and native code, like host
+//! functions and functions compiled by another backend.
+//!
+//! Both directions follow the System V calling convention, with the
+//! vmctx passed after the parameters, so they are only supported on
+//! x86_64 unix targets. Elsewhere, calling a native function from
+//! the interpreter fails, and interpreted functions can't be called
+//! from native code. No other backend builds on those targets, so
+//! nothing there calls the entries of interpreted functions natively.
+//!
+//! # Known gaps:
+//! * Native code calls an interpreted function through a stub, which
+//!   is the only executable memory the interpreter maps. If the
+//!   function traps, the stub executes `ud2`, so the caller gets
+//!   `RuntimeError::Unknown` instead of the trap the interpreter
+//!   reported. Calls between interpreted functions, even of
+//!   different modules, don't go through stubs and keep their traps.
+//! * At most `MAX_STACK_ARGS` arguments, the vmctx included, can be
+//!   passed on the stack. Modules with a function type that needs
+//!   more are rejected when they're compiled.
+
+use wasmer_runtime_core::{
+    error::{CompileResult, RuntimeError, RuntimeResult},
+    types::FuncSig,
+    vm,
+};
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+use {
+    crate::{call, interp::Interpreter},
+    std::panic::{self, AssertUnwindSafe},
+    wasmer_runtime_core::{error::CompileError, types::Type},
+};
+
+/// The number of integer registers that arguments are passed in.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+const NUM_INT_REGS: usize = 6;
+
+/// The number of float registers that arguments are passed in.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+const NUM_FLOAT_REGS: usize = 8;
+
+/// The number of arguments, the vmctx included, that can be passed
+/// on the stack once the registers of their kind are taken.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+const MAX_STACK_ARGS: usize = 8;
+
+/// Where an argument is passed.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+enum ArgLocation {
+    Int(usize),
+    Float(usize),
+    Stack(usize),
+}
+
+/// Assigns a location to each argument, in order. Integers and floats
+/// use their own registers, and once those run out, arguments of
+/// either kind take the next stack slot.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+#[derive(Default)]
+struct ArgLocations {
+    num_ints: usize,
+    num_floats: usize,
+    num_stack: usize,
+}
+
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+impl ArgLocations {
+    fn next(&mut self, ty: Type) -> RuntimeResult<ArgLocation> {
+        let location = match ty {
+            Type::F32 | Type::F64 if self.num_floats < NUM_FLOAT_REGS => {
+                self.num_floats += 1;
+                ArgLocation::Float(self.num_floats - 1)
+            }
+            Type::F32 | Type::F64 => self.next_stack()?,
+            _ if self.num_ints < NUM_INT_REGS => {
+                self.num_ints += 1;
+                ArgLocation::Int(self.num_ints - 1)
+            }
+            _ => self.next_stack()?,
+        };
+        Ok(location)
+    }
+
+    fn next_stack(&mut self) -> RuntimeResult<ArgLocation> {
+        if self.num_stack == MAX_STACK_ARGS {
+            return Err(RuntimeError::Unknown {
+                msg: "too many parameters to pass to or from native code".to_string(),
+            }
+            .into());
+        }
+        self.num_stack += 1;
+        Ok(ArgLocation::Stack(self.num_stack - 1))
+    }
+}
+
+/// Rejects `signature` if a call with it can't be passed between
+/// the interpreter and native code, because more than
+/// `MAX_STACK_ARGS` of its arguments would go on the stack.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+pub fn check_signature(signature: &FuncSig) -> CompileResult<()> {
+    let mut locations = ArgLocations::default();
+    for &ty in signature.params().iter().chain(Some(&Type::I64)) {
+        if locations.next(ty).is_err() {
+            Err(CompileError::InternalError {
+                msg: format!(
+                    "the interpreter can't pass more than {} arguments \
+                     on the stack to or from native code",
+                    MAX_STACK_ARGS
+                ),
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Native functions can't be called on these targets at all,
+/// so there's nothing to reject ahead of time.
+#[cfg(not(all(target_arch = "x86_64", not(target_os = "windows"))))]
+pub fn check_signature(_signature: &FuncSig) -> CompileResult<()> {
+    Ok(())
+}
+
+/// Keeps the low 32 bits of `bits` for 32-bit types, whose upper
+/// bits are undefined when they're passed in a 64-bit location.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+fn truncate(ty: Type, bits: u64) -> u64 {
+    match ty {
+        Type::I32 | Type::F32 => u64::from(bits as u32),
+        _ => bits,
+    }
+}
+
+/// Calls `func` with `args`, which hold the bits of each parameter,
+/// and returns the bits of its result, if it has one.
+///
+/// Every function is called as if it took six integers, eight floats
+/// and then `MAX_STACK_ARGS` more integers. The first fourteen fill
+/// every argument register, so the rest are always passed on the
+/// stack, in order, which is where the callee looks for the arguments
+/// that didn't fit in registers. This puts each argument where the
+/// callee expects it, so no code has to be generated per signature.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+pub unsafe fn call_native(
+    func: *const vm::Func,
+    vmctx: *mut vm::Ctx,
+    signature: &FuncSig,
+    args: &[u64],
+) -> RuntimeResult<Option<u64>> {
+    #[rustfmt::skip]
+    type IntFn = extern "C" fn(
+        u64, u64, u64, u64, u64, u64,
+        f64, f64, f64, f64, f64, f64, f64, f64,
+        u64, u64, u64, u64, u64, u64, u64, u64,
+    ) -> u64;
+    #[rustfmt::skip]
+    type FloatFn = extern "C" fn(
+        u64, u64, u64, u64, u64, u64,
+        f64, f64, f64, f64, f64, f64, f64, f64,
+        u64, u64, u64, u64, u64, u64, u64, u64,
+    ) -> f64;
+
+    let mut ints = [0u64; NUM_INT_REGS];
+    let mut floats = [0f64; NUM_FLOAT_REGS];
+    let mut stack = [0u64; MAX_STACK_ARGS];
+    let mut locations = ArgLocations::default();
+
+    let params = signature.params().iter().cloned().zip(args.iter().cloned());
+    for (ty, arg) in params.chain(Some((Type::I64, vmctx as u64))) {
+        match locations.next(ty)? {
+            ArgLocation::Int(i) => ints[i] = arg,
+            ArgLocation::Float(i) => floats[i] = f64::from_bits(arg),
+            ArgLocation::Stack(i) => stack[i] = arg,
+        }
+    }
+
+    let [i0, i1, i2, i3, i4, i5] = ints;
+    let [f0, f1, f2, f3, f4, f5, f6, f7] = floats;
+    let [s0, s1, s2, s3, s4, s5, s6, s7] = stack;
+
+    Ok(match signature.returns().first() {
+        None => {
+            let func: IntFn = std::mem::transmute(func);
+            func(
+                i0, i1, i2, i3, i4, i5, f0, f1, f2, f3, f4, f5, f6, f7, s0, s1, s2, s3, s4, s5, s6,
+                s7,
+            );
+            None
+        }
+        Some(&ty @ Type::F32) | Some(&ty @ Type::F64) => {
+            let func: FloatFn = std::mem::transmute(func);
+            let result = func(
+                i0, i1, i2, i3, i4, i5, f0, f1, f2, f3, f4, f5, f6, f7, s0, s1, s2, s3, s4, s5, s6,
+                s7,
+            );
+            Some(truncate(ty, result.to_bits()))
+        }
+        Some(&ty) => {
+            let func: IntFn = std::mem::transmute(func);
+            let result = func(
+                i0, i1, i2, i3, i4, i5, f0, f1, f2, f3, f4, f5, f6, f7, s0, s1, s2, s3, s4, s5, s6,
+                s7,
+            );
+            Some(truncate(ty, result))
+        }
+    })
+}
+
+#[cfg(not(all(target_arch = "x86_64", not(target_os = "windows"))))]
+pub unsafe fn call_native(
+    _func: *const vm::Func,
+    _vmctx: *mut vm::Ctx,
+    _signature: &FuncSig,
+    _args: &[u64],
+) -> RuntimeResult<Option<u64>> {
+    Err(RuntimeError::Unknown {
+        msg: "the interpreter can only call native functions on x86_64 System V targets"
+            .to_string(),
+    }
+    .into())
+}
+
+/// The size of the stub that native code calls an interpreted function
+/// through, rounded up so every stub starts 16-byte aligned.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+pub const STUB_SIZE: usize = 144;
+
+/// Writes the stub of the interpreted function whose `vm::Func`
+/// pointer is `address` into `buf`.
+///
+/// The stub saves every argument register below its frame and calls
+/// `enter` with `address`, the saved registers and the arguments that
+/// were passed on the stack. If the call fails, the stub executes
+/// `ud2`, so the error reaches the caller as a trap.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+pub fn write_stub(buf: &mut [u8], address: usize) {
+    let mut code = Vec::with_capacity(STUB_SIZE);
+
+    // push rbp; mov rbp, rsp; sub rsp, 112
+    code.extend_from_slice(&[0x55, 0x48, 0x89, 0xe5, 0x48, 0x83, 0xec, 0x70]);
+    // mov [rsp], rdi; mov [rsp + 8], rsi; mov [rsp + 16], rdx;
+    // mov [rsp + 24], rcx; mov [rsp + 32], r8; mov [rsp + 40], r9
+    code.extend_from_slice(&[
+        0x48, 0x89, 0x3c, 0x24, 0x48, 0x89, 0x74, 0x24, 0x08, 0x48, 0x89, 0x54, 0x24, 0x10, 0x48,
+        0x89, 0x4c, 0x24, 0x18, 0x4c, 0x89, 0x44, 0x24, 0x20, 0x4c, 0x89, 0x4c, 0x24, 0x28,
+    ]);
+    // movsd [rsp + 48 + 8 * n], xmmn
+    for n in 0..NUM_FLOAT_REGS as u8 {
+        code.extend_from_slice(&[0xf2, 0x0f, 0x11, 0x44 | (n << 3), 0x24, 48 + 8 * n]);
+    }
+    // mov rdi, address
+    code.extend_from_slice(&[0x48, 0xbf]);
+    code.extend((0..8).map(|i| (address >> (8 * i)) as u8));
+    // mov rsi, rsp; lea rdx, [rbp + 16]; mov rax, enter
+    code.extend_from_slice(&[0x48, 0x89, 0xe6, 0x48, 0x8d, 0x55, 0x10, 0x48, 0xb8]);
+    code.extend((0..8).map(|i| (enter as usize >> (8 * i)) as u8));
+    // call rax; test rax, rax; jnz trap;
+    // mov rax, [rsp]; movq xmm0, rax; leave; ret
+    // trap: ud2
+    code.extend_from_slice(&[
+        0xff, 0xd0, 0x48, 0x85, 0xc0, 0x75, 0x0b, 0x48, 0x8b, 0x04, 0x24, 0x66, 0x48, 0x0f, 0x6e,
+        0xc0, 0xc9, 0xc3, 0x0f, 0x0b,
+    ]);
+
+    // Pad with int3.
+    code.resize(STUB_SIZE, 0xcc);
+    buf.copy_from_slice(&code);
+}
+
+/// Runs the interpreted function whose `vm::Func` pointer is `func`,
+/// with the arguments a stub saved in `regs` and the ones passed on
+/// `stack`. The bits of the result go in `regs[0]`, and anything but
+/// zero is returned if the function couldn't be run to completion.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+unsafe extern "C" fn enter(func: *const vm::Func, regs: *mut u64, stack: *const u64) -> u64 {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let (code, local_func_index) = call::lookup(func)?;
+        let signature = code.signature(local_func_index);
+        let mut locations = ArgLocations::default();
+
+        let read = |location| match location {
+            ArgLocation::Int(i) => *regs.add(i),
+            ArgLocation::Float(i) => *regs.add(NUM_INT_REGS + i),
+            ArgLocation::Stack(i) => *stack.add(i),
+        };
+
+        let mut args = Vec::with_capacity(signature.params().len());
+        for &ty in signature.params() {
+            args.push(truncate(ty, read(locations.next(ty).ok()?)));
+        }
+        let vmctx = read(locations.next(Type::I64).ok()?) as *mut vm::Ctx;
+
+        let returns = Interpreter::new((*vmctx).module(), &code)
+            .call(local_func_index, vmctx, &args)
+            .ok()?;
+        Some(returns.first().cloned().unwrap_or(0))
+    }));
+
+    match result {
+        Ok(Some(bits)) => {
+            *regs = bits;
+            0
+        }
+        _ => 1,
+    }
+}
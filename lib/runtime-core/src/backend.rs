@@ -2,6 +2,7 @@ use crate::{
     backing::ImportBacking,
     error::CompileResult,
     error::RuntimeResult,
+    features::Features,
     module::ModuleInner,
     types::{FuncIndex, LocalFuncIndex, Value},
     vm,
@@ -26,10 +27,11 @@ impl Token {
 }
 
 pub trait Compiler {
-    /// Compiles a `Module` from WebAssembly binary format.
+    /// Compiles a `Module` from WebAssembly binary format,
+    /// rejecting any proposal that isn't enabled in `features`.
     /// The `CompileToken` parameter ensures that this can only
     /// be called from inside the runtime.
-    fn compile(&self, wasm: &[u8], _: Token) -> CompileResult<ModuleInner>;
+    fn compile(&self, wasm: &[u8], features: &Features, _: Token) -> CompileResult<ModuleInner>;
 }
 
 /// The functionality exposed by this trait is expected to be used
@@ -1,19 +1,21 @@
 use crate::{
-    error::{LinkError, LinkResult},
+    error::{CreationError, LinkError, LinkResult},
     export::{Context, Export},
     global::Global,
     import::ImportObject,
-    memory::Memory,
+    memory::{Memory, MemoryPool},
     module::{ImportName, ModuleInner},
+    snapshot::InstanceSnapshot,
     structures::{BoxedMap, Map, SliceMap, TypedIndex},
+    sys,
     table::Table,
     types::{
-        ImportedFuncIndex, ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex,
+        FuncIndex, ImportedFuncIndex, ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex,
         Initializer, LocalGlobalIndex, LocalMemoryIndex, LocalOrImport, LocalTableIndex, Value,
     },
     vm,
 };
-use std::slice;
+use std::{ptr, slice};
 
 #[derive(Debug)]
 pub struct LocalBacking {
@@ -36,14 +38,44 @@ pub struct LocalBacking {
 //     }
 // }
 
+/// How the contents of a new `LocalBacking` are initialized.
+pub(crate) enum Initialization<'a> {
+    /// Copy the data segments into the memories and
+    /// apply the element segments to the tables.
+    Segments,
+    /// Like `Segments`, but map memory images built with
+    /// `generate_memory_images` instead of copying data segments
+    /// into local memories.
+    MemoryImages(&'a SliceMap<LocalMemoryIndex, sys::MemoryImage>),
+    /// Restore the contents of a snapshot without
+    /// applying any data or element segments.
+    Snapshot(&'a InstanceSnapshot),
+}
+
 impl LocalBacking {
-    pub(crate) fn new(module: &ModuleInner, imports: &ImportBacking, vmctx: *mut vm::Ctx) -> Self {
-        let mut memories = Self::generate_memories(module);
-        let mut tables = Self::generate_tables(module);
+    pub(crate) fn new(
+        module: &ModuleInner,
+        imports: &ImportBacking,
+        initialization: Initialization,
+        memory_pool: Option<&MemoryPool>,
+        vmctx: *mut vm::Ctx,
+    ) -> Self {
+        let mut memories = Self::generate_memories(module, memory_pool);
+        let mut tables = Self::generate_tables(module, memory_pool);
         let mut globals = Self::generate_globals(module, imports);
 
-        let vm_memories = Self::finalize_memories(module, imports, &mut memories);
-        let vm_tables = Self::finalize_tables(module, imports, &mut tables, vmctx);
+        Self::initialize(
+            module,
+            imports,
+            initialization,
+            &mut memories,
+            &tables,
+            &globals,
+            vmctx,
+        );
+
+        let vm_memories = Self::finalize_memories(&mut memories);
+        let vm_tables = Self::finalize_tables(&mut tables);
         let vm_globals = Self::finalize_globals(&mut globals);
 
         Self {
@@ -57,7 +89,73 @@ impl LocalBacking {
         }
     }
 
-    fn generate_memories(module: &ModuleInner) -> BoxedMap<LocalMemoryIndex, Memory> {
+    /// Bring the memories, tables, and globals back to the state they were
+    /// in right after they were created, then initialize them again.
+    ///
+    /// The memories keep their mappings, so the pointers
+    /// handed out to the `vm::Ctx` stay valid.
+    pub(crate) fn reset(
+        &mut self,
+        module: &ModuleInner,
+        imports: &ImportBacking,
+        initialization: Initialization,
+        vmctx: *mut vm::Ctx,
+    ) -> Result<(), CreationError> {
+        for (_, memory) in self.memories.iter() {
+            memory.reset()?;
+        }
+
+        for (_, table) in self.tables.iter() {
+            table.reset();
+        }
+
+        for (local_global_index, global_init) in module.globals.iter() {
+            if global_init.desc.mutable {
+                self.globals[local_global_index].set(global_value(&global_init.init, imports));
+            }
+        }
+
+        Self::initialize(
+            module,
+            imports,
+            initialization,
+            &mut self.memories,
+            &self.tables,
+            &self.globals,
+            vmctx,
+        );
+
+        Ok(())
+    }
+
+    fn initialize(
+        module: &ModuleInner,
+        imports: &ImportBacking,
+        initialization: Initialization,
+        memories: &mut SliceMap<LocalMemoryIndex, Memory>,
+        tables: &SliceMap<LocalTableIndex, Table>,
+        globals: &SliceMap<LocalGlobalIndex, Global>,
+        vmctx: *mut vm::Ctx,
+    ) {
+        match initialization {
+            Initialization::Segments => {
+                Self::initialize_memories(module, imports, None, memories);
+                Self::initialize_tables(module, imports, tables, vmctx);
+            }
+            Initialization::MemoryImages(memory_images) => {
+                Self::initialize_memories(module, imports, Some(memory_images), memories);
+                Self::initialize_tables(module, imports, tables, vmctx);
+            }
+            Initialization::Snapshot(snapshot) => {
+                snapshot.restore(module, imports, memories, tables, globals, vmctx);
+            }
+        }
+    }
+
+    fn generate_memories(
+        module: &ModuleInner,
+        memory_pool: Option<&MemoryPool>,
+    ) -> BoxedMap<LocalMemoryIndex, Memory> {
         let mut memories = Map::with_capacity(module.memories.len());
 
         for (_, &desc) in &module.memories {
@@ -70,37 +168,89 @@ impl LocalBacking {
             // } else {
             //     Memory::new(memory.minimum, memory.maximum.map(|m| m as u32))
             // };
-            let memory = Memory::new(desc).expect("unable to create memory");
+            let memory = Memory::new_pooled(desc, memory_pool).expect("unable to create memory");
             memories.push(memory);
         }
 
         memories.into_boxed_map()
     }
 
-    fn finalize_memories(
+    /// Build an image of the initial contents of every local memory,
+    /// which can later be mapped copy-on-write by `finalize_memories`
+    /// instead of copying the data segments into each new memory.
+    ///
+    /// Returns `None` if a data segment is placed at the value of an
+    /// imported global, since that value can change between instances.
+    pub(crate) fn generate_memory_images(
         module: &ModuleInner,
         imports: &ImportBacking,
-        memories: &mut SliceMap<LocalMemoryIndex, Memory>,
-    ) -> BoxedMap<LocalMemoryIndex, *mut vm::LocalMemory> {
+    ) -> Result<Option<BoxedMap<LocalMemoryIndex, sys::MemoryImage>>, CreationError> {
+        let uses_imported_global = module.data_initializers.iter().any(|init| match init.base {
+            Initializer::GetGlobal(_) => true,
+            Initializer::Const(_) => false,
+        });
+        if uses_imported_global {
+            return Ok(None);
+        }
+
+        let mut segments: Map<LocalMemoryIndex, Vec<(usize, &[u8])>> =
+            module.memories.iter().map(|_| Vec::new()).collect();
+
+        for init in module
+            .data_initializers
+            .iter()
+            .filter(|init| init.data.len() > 0)
+        {
+            if let LocalOrImport::Local(local_memory_index) =
+                init.memory_index.local_or_import(module)
+            {
+                let init_base = initializer_base(&init.base, imports);
+                let data_top = init_base + init.data.len();
+                assert!(module.memories[local_memory_index].minimum.bytes().0 >= data_top);
+
+                segments[local_memory_index].push((init_base, &init.data[..]));
+            }
+        }
+
+        let mut images = Map::with_capacity(module.memories.len());
+
+        for (local_memory_index, &desc) in &module.memories {
+            let image = sys::MemoryImage::new(
+                desc.minimum.bytes().0,
+                segments[local_memory_index].iter().cloned(),
+            )
+            .map_err(|_| CreationError::UnableToCreateMemory)?;
+            images.push(image);
+        }
+
+        Ok(Some(images.into_boxed_map()))
+    }
+
+    fn initialize_memories(
+        module: &ModuleInner,
+        imports: &ImportBacking,
+        memory_images: Option<&SliceMap<LocalMemoryIndex, sys::MemoryImage>>,
+        memories: &SliceMap<LocalMemoryIndex, Memory>,
+    ) {
+        if let Some(memory_images) = memory_images {
+            for (local_memory_index, image) in memory_images.iter() {
+                memories[local_memory_index]
+                    .map_image(image)
+                    .expect("unable to map memory image");
+            }
+        }
+
         // For each init that has some data...
         for init in module
             .data_initializers
             .iter()
             .filter(|init| init.data.len() > 0)
         {
-            let init_base = match init.base {
-                Initializer::Const(Value::I32(offset)) => offset as u32,
-                Initializer::Const(_) => panic!("a const initializer must be the i32 type"),
-                Initializer::GetGlobal(import_global_index) => {
-                    if let Value::I32(x) = imports.globals[import_global_index].get() {
-                        x as u32
-                    } else {
-                        panic!("unsupported global type for initialzer")
-                    }
-                }
-            } as usize;
+            let init_base = initializer_base(&init.base, imports);
 
             match init.memory_index.local_or_import(module) {
+                // Already part of the memory image.
+                LocalOrImport::Local(_) if memory_images.is_some() => {}
                 LocalOrImport::Local(local_memory_index) => {
                     let memory_desc = module.memories[local_memory_index];
                     let data_top = init_base + init.data.len();
@@ -124,7 +274,11 @@ impl LocalBacking {
                 }
             }
         }
+    }
 
+    fn finalize_memories(
+        memories: &mut SliceMap<LocalMemoryIndex, Memory>,
+    ) -> BoxedMap<LocalMemoryIndex, *mut vm::LocalMemory> {
         memories
             .iter_mut()
             .map(|(_, mem)| mem.vm_local_memory())
@@ -132,111 +286,51 @@ impl LocalBacking {
             .into_boxed_map()
     }
 
-    fn generate_tables(module: &ModuleInner) -> BoxedMap<LocalTableIndex, Table> {
+    fn generate_tables(
+        module: &ModuleInner,
+        memory_pool: Option<&MemoryPool>,
+    ) -> BoxedMap<LocalTableIndex, Table> {
         let mut tables = Map::with_capacity(module.tables.len());
 
         for (_, &table_desc) in module.tables.iter() {
-            let table = Table::new(table_desc).unwrap();
+            let table = Table::new_pooled(table_desc, memory_pool).unwrap();
             tables.push(table);
         }
 
         tables.into_boxed_map()
     }
 
-    #[allow(clippy::cast_ptr_alignment)]
-    fn finalize_tables(
+    fn initialize_tables(
         module: &ModuleInner,
         imports: &ImportBacking,
-        tables: &mut SliceMap<LocalTableIndex, Table>,
+        tables: &SliceMap<LocalTableIndex, Table>,
         vmctx: *mut vm::Ctx,
-    ) -> BoxedMap<LocalTableIndex, *mut vm::LocalTable> {
+    ) {
         for init in &module.elem_initializers {
-            let init_base = match init.base {
-                Initializer::Const(Value::I32(offset)) => offset as u32,
-                Initializer::Const(_) => panic!("a const initializer must be the i32 type"),
-                Initializer::GetGlobal(import_global_index) => {
-                    if let Value::I32(x) = imports.globals[import_global_index].get() {
-                        x as u32
-                    } else {
-                        panic!("unsupported global type for initialzer")
-                    }
-                }
-            } as usize;
+            let init_base = initializer_base(&init.base, imports);
 
-            match init.table_index.local_or_import(module) {
-                LocalOrImport::Local(local_table_index) => {
-                    let table = &tables[local_table_index];
-
-                    if (table.size() as usize) < init_base + init.elements.len() {
-                        let delta = (init_base + init.elements.len()) - table.size() as usize;
-                        // Grow the table if it's too small.
-                        table.grow(delta as u32).expect("couldn't grow table");
-                    }
-
-                    table.anyfunc_direct_access_mut(|elements| {
-                        for (i, &func_index) in init.elements.iter().enumerate() {
-                            let sig_index = module.func_assoc[func_index];
-                            let sig_id = vm::SigId(sig_index.index() as u32);
-
-                            let (func, ctx) = match func_index.local_or_import(module) {
-                                LocalOrImport::Local(local_func_index) => (
-                                    module
-                                        .func_resolver
-                                        .get(module, local_func_index)
-                                        .unwrap()
-                                        .as_ptr()
-                                        as *const vm::Func,
-                                    vmctx,
-                                ),
-                                LocalOrImport::Import(imported_func_index) => {
-                                    let vm::ImportedFunc { func, vmctx } =
-                                        imports.vm_functions[imported_func_index];
-                                    (func, vmctx)
-                                }
-                            };
-
-                            elements[init_base + i] = vm::Anyfunc { func, ctx, sig_id };
-                        }
-                    });
-                }
-                LocalOrImport::Import(import_table_index) => {
-                    let table = &imports.tables[import_table_index];
+            let table = match init.table_index.local_or_import(module) {
+                LocalOrImport::Local(local_table_index) => &tables[local_table_index],
+                LocalOrImport::Import(import_table_index) => &imports.tables[import_table_index],
+            };
 
-                    if (table.size() as usize) < init_base + init.elements.len() {
-                        let delta = (init_base + init.elements.len()) - table.size() as usize;
-                        // Grow the table if it's too small.
-                        table.grow(delta as u32).expect("couldn't grow table");
-                    }
+            if (table.size() as usize) < init_base + init.elements.len() {
+                let delta = (init_base + init.elements.len()) - table.size() as usize;
+                // Grow the table if it's too small.
+                table.grow(delta as u32).expect("couldn't grow table");
+            }
 
-                    table.anyfunc_direct_access_mut(|elements| {
-                        for (i, &func_index) in init.elements.iter().enumerate() {
-                            let sig_index = module.func_assoc[func_index];
-                            let sig_id = vm::SigId(sig_index.index() as u32);
-
-                            let (func, ctx) = match func_index.local_or_import(module) {
-                                LocalOrImport::Local(local_func_index) => (
-                                    module
-                                        .func_resolver
-                                        .get(module, local_func_index)
-                                        .unwrap()
-                                        .as_ptr()
-                                        as *const vm::Func,
-                                    vmctx,
-                                ),
-                                LocalOrImport::Import(imported_func_index) => {
-                                    let vm::ImportedFunc { func, vmctx } =
-                                        imports.vm_functions[imported_func_index];
-                                    (func, vmctx)
-                                }
-                            };
-
-                            elements[init_base + i] = vm::Anyfunc { func, ctx, sig_id };
-                        }
-                    });
+            table.anyfunc_direct_access_mut(|elements| {
+                for (i, &func_index) in init.elements.iter().enumerate() {
+                    elements[init_base + i] = func_anyfunc(module, imports, func_index, vmctx);
                 }
-            }
+            });
         }
+    }
 
+    fn finalize_tables(
+        tables: &mut SliceMap<LocalTableIndex, Table>,
+    ) -> BoxedMap<LocalTableIndex, *mut vm::LocalTable> {
         tables
             .iter_mut()
             .map(|(_, table)| table.vm_local_table())
@@ -251,12 +345,7 @@ impl LocalBacking {
         let mut globals = Map::with_capacity(module.globals.len());
 
         for (_, global_init) in module.globals.iter() {
-            let value = match &global_init.init {
-                Initializer::Const(value) => value.clone(),
-                Initializer::GetGlobal(import_global_index) => {
-                    imports.globals[*import_global_index].get()
-                }
-            };
+            let value = global_value(&global_init.init, imports);
 
             let global = if global_init.desc.mutable {
                 Global::new_mutable(value)
@@ -281,32 +370,36 @@ impl LocalBacking {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ImportBacking {
     pub(crate) memories: BoxedMap<ImportedMemoryIndex, Memory>,
     pub(crate) tables: BoxedMap<ImportedTableIndex, Table>,
     pub(crate) globals: BoxedMap<ImportedGlobalIndex, Global>,
 
     pub(crate) vm_functions: BoxedMap<ImportedFuncIndex, vm::ImportedFunc>,
+    /// The context that each imported function was exported with.
+    pub(crate) func_contexts: BoxedMap<ImportedFuncIndex, Context>,
     pub(crate) vm_memories: BoxedMap<ImportedMemoryIndex, *mut vm::LocalMemory>,
     pub(crate) vm_tables: BoxedMap<ImportedTableIndex, *mut vm::LocalTable>,
     pub(crate) vm_globals: BoxedMap<ImportedGlobalIndex, *mut vm::LocalGlobal>,
 }
 
 impl ImportBacking {
-    pub fn new(
-        module: &ModuleInner,
-        imports: &mut ImportObject,
-        vmctx: *mut vm::Ctx,
-    ) -> LinkResult<Self> {
+    /// Resolves and type-checks every import of `module` against `imports`.
+    ///
+    /// Functions that expect to receive the vmctx of the instance calling them
+    /// (`Context::Internal`) are recorded with a null vmctx, which is filled in
+    /// by `bind_vmctx` once the instance exists.
+    pub fn new(module: &ModuleInner, imports: &ImportObject) -> LinkResult<Self> {
         let mut failed = false;
         let mut link_errors = vec![];
 
-        let vm_functions = import_functions(module, imports, vmctx).unwrap_or_else(|le| {
-            failed = true;
-            link_errors.extend(le);
-            Map::new().into_boxed_map()
-        });
+        let (vm_functions, func_contexts) =
+            import_functions(module, imports).unwrap_or_else(|le| {
+                failed = true;
+                link_errors.extend(le);
+                (Map::new().into_boxed_map(), Map::new().into_boxed_map())
+            });
 
         let (memories, vm_memories) = import_memories(module, imports).unwrap_or_else(|le| {
             failed = true;
@@ -335,6 +428,7 @@ impl ImportBacking {
                 globals,
 
                 vm_functions,
+                func_contexts,
                 vm_memories,
                 vm_tables,
                 vm_globals,
@@ -345,15 +439,32 @@ impl ImportBacking {
     pub fn imported_func(&self, index: ImportedFuncIndex) -> vm::ImportedFunc {
         self.vm_functions[index].clone()
     }
+
+    /// Creates a copy of this backing for a new instance, pointing every
+    /// imported function that wants the caller's context at `vmctx`.
+    pub(crate) fn bind_vmctx(&self, vmctx: *mut vm::Ctx) -> Self {
+        let mut import_backing = self.clone();
+
+        for (index, imported_func) in import_backing.vm_functions.iter_mut() {
+            if let Context::Internal = import_backing.func_contexts[index] {
+                imported_func.vmctx = vmctx;
+            }
+        }
+
+        import_backing
+    }
 }
 
 fn import_functions(
     module: &ModuleInner,
-    imports: &mut ImportObject,
-    vmctx: *mut vm::Ctx,
-) -> LinkResult<BoxedMap<ImportedFuncIndex, vm::ImportedFunc>> {
+    imports: &ImportObject,
+) -> LinkResult<(
+    BoxedMap<ImportedFuncIndex, vm::ImportedFunc>,
+    BoxedMap<ImportedFuncIndex, Context>,
+)> {
     let mut link_errors = vec![];
     let mut functions = Map::with_capacity(module.imported_functions.len());
+    let mut contexts = Map::with_capacity(module.imported_functions.len());
     for (index, ImportName { namespace, name }) in &module.imported_functions {
         let sig_index = module.func_assoc[index.convert_up(module)];
         let expected_sig = module.sig_registry.lookup_signature(sig_index);
@@ -371,9 +482,11 @@ fn import_functions(
                         func: func.inner(),
                         vmctx: match ctx {
                             Context::External(ctx) => ctx,
-                            Context::Internal => vmctx,
+                            // Bound to the importing instance in `bind_vmctx`.
+                            Context::Internal => ptr::null_mut(),
                         },
                     });
+                    contexts.push(ctx);
                 } else {
                     link_errors.push(LinkError::IncorrectImportSignature {
                         namespace: namespace.clone(),
@@ -410,13 +523,13 @@ fn import_functions(
     if link_errors.len() > 0 {
         Err(link_errors)
     } else {
-        Ok(functions.into_boxed_map())
+        Ok((functions.into_boxed_map(), contexts.into_boxed_map()))
     }
 }
 
 fn import_memories(
     module: &ModuleInner,
-    imports: &mut ImportObject,
+    imports: &ImportObject,
 ) -> LinkResult<(
     BoxedMap<ImportedMemoryIndex, Memory>,
     BoxedMap<ImportedMemoryIndex, *mut vm::LocalMemory>,
@@ -477,7 +590,7 @@ fn import_memories(
 
 fn import_tables(
     module: &ModuleInner,
-    imports: &mut ImportObject,
+    imports: &ImportObject,
 ) -> LinkResult<(
     BoxedMap<ImportedTableIndex, Table>,
     BoxedMap<ImportedTableIndex, *mut vm::LocalTable>,
@@ -536,7 +649,7 @@ fn import_tables(
 
 fn import_globals(
     module: &ModuleInner,
-    imports: &mut ImportObject,
+    imports: &ImportObject,
 ) -> LinkResult<(
     BoxedMap<ImportedGlobalIndex, Global>,
     BoxedMap<ImportedGlobalIndex, *mut vm::LocalGlobal>,
@@ -592,3 +705,54 @@ fn import_globals(
         Ok((globals.into_boxed_map(), vm_globals.into_boxed_map()))
     }
 }
+
+fn initializer_base(base: &Initializer, imports: &ImportBacking) -> usize {
+    match *base {
+        Initializer::Const(Value::I32(offset)) => offset as u32 as usize,
+        Initializer::Const(_) => panic!("a const initializer must be the i32 type"),
+        Initializer::GetGlobal(import_global_index) => {
+            if let Value::I32(x) = imports.globals[import_global_index].get() {
+                x as u32 as usize
+            } else {
+                panic!("unsupported global type for initialzer")
+            }
+        }
+    }
+}
+
+fn global_value(init: &Initializer, imports: &ImportBacking) -> Value {
+    match init {
+        Initializer::Const(value) => value.clone(),
+        Initializer::GetGlobal(import_global_index) => imports.globals[*import_global_index].get(),
+    }
+}
+
+/// The table element that refers to `func_index`, called with `vmctx`
+/// if it's a function defined in this module.
+#[allow(clippy::cast_ptr_alignment)]
+pub(crate) fn func_anyfunc(
+    module: &ModuleInner,
+    imports: &ImportBacking,
+    func_index: FuncIndex,
+    vmctx: *mut vm::Ctx,
+) -> vm::Anyfunc {
+    let sig_index = module.func_assoc[func_index];
+    let sig_id = vm::SigId(sig_index.index() as u32);
+
+    let (func, ctx) = match func_index.local_or_import(module) {
+        LocalOrImport::Local(local_func_index) => (
+            module
+                .func_resolver
+                .get(module, local_func_index)
+                .unwrap()
+                .as_ptr() as *const vm::Func,
+            vmctx,
+        ),
+        LocalOrImport::Import(imported_func_index) => {
+            let vm::ImportedFunc { func, vmctx } = imports.vm_functions[imported_func_index];
+            (func, vmctx)
+        }
+    };
+
+    vm::Anyfunc { func, ctx, sig_id }
+}
@@ -17,8 +17,15 @@ pub type ResolveResult<T> = std::result::Result<T, Box<ResolveError>>;
 /// Comparing two `CompileError`s always evaluates to false.
 #[derive(Debug, Clone)]
 pub enum CompileError {
-    ValidationError { msg: String },
-    InternalError { msg: String },
+    /// The module is invalid, or uses a proposal that isn't enabled.
+    /// `offset` is the position in the binary at which this was found.
+    ValidationError {
+        msg: String,
+        offset: usize,
+    },
+    InternalError {
+        msg: String,
+    },
 }
 
 impl PartialEq for CompileError {
@@ -154,6 +161,29 @@ impl PartialEq for CreationError {
     }
 }
 
+/// This error type is produced when taking, decoding,
+/// or restoring an `InstanceSnapshot`.
+///
+/// Comparing two `SnapshotError`s always evaluates to false.
+#[derive(Debug, Clone)]
+pub enum SnapshotError {
+    /// A table contains a function that is neither defined
+    /// in nor imported by the module, like one set by the host.
+    UnknownTableElement { table: TableIndex, index: u32 },
+    /// A global or an anyref table holds a non-null reference.
+    UnsupportedReference { msg: String },
+    /// The snapshot was not taken from an instance of this module.
+    IncompatibleModule { msg: String },
+    /// The bytes are not a valid encoding of a snapshot.
+    Malformed { msg: String },
+}
+
+impl PartialEq for SnapshotError {
+    fn eq(&self, _other: &SnapshotError) -> bool {
+        false
+    }
+}
+
 /// The amalgamation of all errors that can occur
 /// during the compilation, instantiation, or execution
 /// of a webassembly module.
@@ -167,6 +197,7 @@ pub enum Error {
     ResolveError(ResolveError),
     CallError(CallError),
     CreationError(CreationError),
+    SnapshotError(SnapshotError),
 }
 
 impl PartialEq for Error {
@@ -241,6 +272,12 @@ impl From<CreationError> for Box<Error> {
     }
 }
 
+impl From<SnapshotError> for Box<Error> {
+    fn from(snapshot_err: SnapshotError) -> Self {
+        Box::new(Error::SnapshotError(snapshot_err))
+    }
+}
+
 impl From<RuntimeError> for Box<CallError> {
     fn from(runtime_err: RuntimeError) -> Self {
         Box::new(CallError::Runtime(runtime_err))
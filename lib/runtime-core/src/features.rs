@@ -0,0 +1,245 @@
+use wasmparser::{
+    ExternalKind, ImportSectionEntryType, Operator, OperatorValidatorConfig, ParserState,
+    ResizableLimits, ValidatingParserConfig,
+};
+
+/// The WebAssembly proposals that a module is allowed to use.
+///
+/// `Features::default()` only allows the MVP.
+///
+/// # Deterministic mode:
+/// Setting `deterministic` doesn't enable a proposal, but makes every
+/// backend generate code that gives bit-identical results on every host:
+///
+/// * Every float operator that can produce a NaN produces the
+///   canonical one, with only the quiet bit of the payload set.
+/// * Every memory and table, imported or not, must declare a maximum,
+///   so `memory.grow` and `table.grow` fail exactly when they would
+///   go past it. Memories reserve the address space up to their
+///   maximum when they're created, so growing one never has to move
+///   it, and a host that can't fit it fails to instantiate the module
+///   instead of failing some later `memory.grow`.
+/// * `threads` can't be enabled, since the results of racing
+///   accesses to a shared memory differ between hosts.
+/// * Only the functions listed in `allowed_imports` can be imported,
+///   since the host decides what every other import returns.
+///
+/// The allowed functions are run as they are, so the host
+/// is responsible for making them deterministic too.
+///
+/// # Bounds checks:
+/// Setting `bounds_checks` makes every memory of the module
+/// [bounds-checked], so accessing one never relies on guard pages
+/// and a signal handler to detect that it's out of bounds. Memories
+/// then only take as much address space as they need, instead of
+/// the 6GiB reserved for a static memory, which matters where the
+/// address space is limited, like in a container with a low
+/// `RLIMIT_AS`. Imported memories must be bounds-checked too.
+///
+/// [bounds-checked]: types/struct.MemoryDescriptor.html#method.with_bounds_checks
+///
+/// # Usage:
+/// ```
+/// # use wasmer_runtime_core::Features;
+/// let features = Features {
+///     sign_extension: true,
+///     ..Features::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Features {
+    /// The sign-extension operators, like `i32.extend8_s`.
+    pub sign_extension: bool,
+    /// The non-trapping float-to-int conversions, like `i32.trunc_sat_f32_s`.
+    pub sat_float_to_int: bool,
+    /// Importing and exporting mutable globals.
+    pub mutable_global: bool,
+    /// Shared memories and atomic operators.
+    pub threads: bool,
+    /// `anyref`, and tables and operators that hold references.
+    ///
+    /// No backend can compile code that uses references yet. The
+    /// Cranelift backend rejects this flag, and the others reject
+    /// the modules that use references.
+    pub reference_types: bool,
+    /// Functions that return more than one value.
+    pub multi_value: bool,
+    /// Not a proposal: restricts modules, and the code generated
+    /// for them, to what runs the same on every host.
+    pub deterministic: bool,
+    /// The functions, as `(namespace, name)` pairs, that a module
+    /// can import in deterministic mode. Ignored otherwise.
+    pub allowed_imports: Vec<(String, String)>,
+    /// Not a proposal: bounds-checks every memory access
+    /// explicitly, instead of relying on guard pages.
+    pub bounds_checks: bool,
+}
+
+impl Features {
+    /// Allow every proposal that wasmer supports.
+    ///
+    /// This doesn't include `reference_types`, which
+    /// no backend can compile yet.
+    pub fn all() -> Self {
+        Self {
+            sign_extension: true,
+            sat_float_to_int: true,
+            mutable_global: true,
+            threads: true,
+            reference_types: false,
+            multi_value: true,
+            deterministic: false,
+            allowed_imports: Vec::new(),
+            bounds_checks: false,
+        }
+    }
+
+    pub(crate) fn parser_config(&self) -> ValidatingParserConfig {
+        ValidatingParserConfig {
+            operator_config: OperatorValidatorConfig {
+                enable_threads: self.threads,
+                enable_reference_types: self.reference_types,
+            },
+        }
+    }
+}
+
+/// Looks at a module while it is being parsed and rejects
+/// anything that belongs to a proposal that isn't enabled.
+pub(crate) struct FeatureChecker<'a> {
+    features: &'a Features,
+    /// Whether each global, imported ones first, is mutable.
+    global_mutability: Vec<bool>,
+}
+
+impl<'a> FeatureChecker<'a> {
+    pub fn new(features: &'a Features) -> Self {
+        Self {
+            features,
+            global_mutability: Vec::new(),
+        }
+    }
+
+    /// Rejects combinations of features that can't be used together.
+    pub fn check_features(&self) -> Result<(), String> {
+        if self.features.deterministic && self.features.threads {
+            return Err("threads can't be enabled in deterministic mode".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn check(&mut self, state: &ParserState) -> Result<(), String> {
+        match *state {
+            ParserState::ImportSectionEntry {
+                module,
+                field,
+                ty: ImportSectionEntryType::Function(_),
+            } => {
+                if self.features.deterministic && !self.is_allowed_import(module, field) {
+                    return Err(format!(
+                        "`{}` `{}` can't be imported in deterministic mode",
+                        module, field
+                    ));
+                }
+            }
+            ParserState::ImportSectionEntry {
+                ty: ImportSectionEntryType::Table(ref table_type),
+                ..
+            }
+            | ParserState::TableSectionEntry(ref table_type) => {
+                self.check_limits(&table_type.limits, "tables")?;
+            }
+            ParserState::ImportSectionEntry {
+                ty: ImportSectionEntryType::Global(global_type),
+                ..
+            } => {
+                if global_type.mutable && !self.features.mutable_global {
+                    return Err("mutable global imports are not enabled".to_string());
+                }
+                self.global_mutability.push(global_type.mutable);
+            }
+            ParserState::ImportSectionEntry {
+                ty: ImportSectionEntryType::Memory(memory_type),
+                ..
+            }
+            | ParserState::MemorySectionEntry(memory_type) => {
+                if memory_type.shared && !self.features.threads {
+                    return Err("shared memories are not enabled".to_string());
+                }
+                self.check_limits(&memory_type.limits, "memories")?;
+            }
+            ParserState::TypeSectionEntry(ref func_type) => {
+                if func_type.returns.len() > 1 && !self.features.multi_value {
+                    return Err("multiple return values are not enabled".to_string());
+                }
+            }
+            ParserState::BeginGlobalSectionEntry(global_type) => {
+                self.global_mutability.push(global_type.mutable);
+            }
+            ParserState::ExportSectionEntry {
+                kind: ExternalKind::Global,
+                index,
+                ..
+            } => {
+                let mutable = self
+                    .global_mutability
+                    .get(index as usize)
+                    .cloned()
+                    .unwrap_or(false);
+                if mutable && !self.features.mutable_global {
+                    return Err("mutable global exports are not enabled".to_string());
+                }
+            }
+            ParserState::CodeOperator(ref op) => self.check_operator(op)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn is_allowed_import(&self, module: &str, field: &str) -> bool {
+        self.features
+            .allowed_imports
+            .iter()
+            .any(|(namespace, name)| namespace == module && name == field)
+    }
+
+    /// In deterministic mode, growing past the initial size must
+    /// fail at the same size on every host.
+    fn check_limits(&self, limits: &ResizableLimits, what: &str) -> Result<(), String> {
+        if self.features.deterministic && limits.maximum.is_none() {
+            return Err(format!(
+                "{} must declare a maximum in deterministic mode",
+                what
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_operator(&self, op: &Operator) -> Result<(), String> {
+        match *op {
+            Operator::I32Extend8S
+            | Operator::I32Extend16S
+            | Operator::I64Extend8S
+            | Operator::I64Extend16S
+            | Operator::I64Extend32S
+                if !self.features.sign_extension =>
+            {
+                Err("sign-extension operators are not enabled".to_string())
+            }
+            Operator::I32TruncSSatF32
+            | Operator::I32TruncUSatF32
+            | Operator::I32TruncSSatF64
+            | Operator::I32TruncUSatF64
+            | Operator::I64TruncSSatF32
+            | Operator::I64TruncUSatF32
+            | Operator::I64TruncSSatF64
+            | Operator::I64TruncUSatF64
+                if !self.features.sat_float_to_int =>
+            {
+                Err("non-trapping float-to-int conversions are not enabled".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     export::Export,
     import::IsExport,
-    types::{GlobalDescriptor, Type, Value},
+    types::{GlobalDescriptor, Value},
     vm,
 };
 use std::{cell::RefCell, fmt, rc::Rc};
@@ -45,12 +45,7 @@ impl Global {
         };
 
         let local_global = vm::LocalGlobal {
-            data: match value {
-                Value::I32(x) => x as u64,
-                Value::I64(x) => x as u64,
-                Value::F32(x) => x.to_bits() as u64,
-                Value::F64(x) => x.to_bits(),
-            },
+            data: value.to_bits(),
         };
 
         Self {
@@ -74,12 +69,7 @@ impl Global {
         if self.desc.mutable {
             if self.desc.ty == value.ty() {
                 let local_global = vm::LocalGlobal {
-                    data: match value {
-                        Value::I32(x) => x as u64,
-                        Value::I64(x) => x as u64,
-                        Value::F32(x) => x.to_bits() as u64,
-                        Value::F64(x) => x.to_bits(),
-                    },
+                    data: value.to_bits(),
                 };
                 *self.storage.borrow_mut() = local_global;
             } else {
@@ -94,12 +84,7 @@ impl Global {
     pub fn get(&self) -> Value {
         let data = self.storage.borrow().data;
 
-        match self.desc.ty {
-            Type::I32 => Value::I32(data as i32),
-            Type::I64 => Value::I64(data as i64),
-            Type::F32 => Value::F32(f32::from_bits(data as u32)),
-            Type::F64 => Value::F64(f64::from_bits(data)),
-        }
+        Value::from_bits(self.desc.ty, data)
     }
 
     pub(crate) fn vm_local_global(&mut self) -> *mut vm::LocalGlobal {
@@ -108,7 +93,7 @@ impl Global {
 }
 
 impl IsExport for Global {
-    fn to_export(&mut self) -> Export {
+    fn to_export(&self) -> Export {
         Export::Global(self.clone())
     }
 }
@@ -1,16 +1,17 @@
 use crate::export::Export;
 use hashbrown::{hash_map::Entry, HashMap};
+use std::rc::Rc;
 
 pub trait LikeNamespace {
-    fn get_export(&mut self, name: &str) -> Option<Export>;
+    fn get_export(&self, name: &str) -> Option<Export>;
 }
 
 pub trait IsExport {
-    fn to_export(&mut self) -> Export;
+    fn to_export(&self) -> Export;
 }
 
 impl IsExport for Export {
-    fn to_export(&mut self) -> Export {
+    fn to_export(&self) -> Export {
         self.clone()
     }
 }
@@ -36,8 +37,12 @@ impl IsExport for Export {
 ///     n
 /// }
 /// ```
+///
+/// Cloning an `ImportObject` is cheap, since the
+/// namespaces are shared between the clones.
+#[derive(Clone)]
 pub struct ImportObject {
-    map: HashMap<String, Box<dyn LikeNamespace>>,
+    map: HashMap<String, Rc<dyn LikeNamespace>>,
 }
 
 impl ImportObject {
@@ -62,24 +67,22 @@ impl ImportObject {
     ///     // ...
     /// }
     /// ```
-    pub fn register<S, N>(&mut self, name: S, namespace: N) -> Option<Box<dyn LikeNamespace>>
+    pub fn register<S, N>(&mut self, name: S, namespace: N) -> Option<Rc<dyn LikeNamespace>>
     where
         S: Into<String>,
         N: LikeNamespace + 'static,
     {
         match self.map.entry(name.into()) {
             Entry::Vacant(empty) => {
-                empty.insert(Box::new(namespace));
+                empty.insert(Rc::new(namespace));
                 None
             }
-            Entry::Occupied(mut occupied) => Some(occupied.insert(Box::new(namespace))),
+            Entry::Occupied(mut occupied) => Some(occupied.insert(Rc::new(namespace))),
         }
     }
 
-    pub fn get_namespace(&mut self, namespace: &str) -> Option<&mut (dyn LikeNamespace + 'static)> {
-        self.map
-            .get_mut(namespace)
-            .map(|namespace| &mut **namespace)
+    pub fn get_namespace(&self, namespace: &str) -> Option<&(dyn LikeNamespace + 'static)> {
+        self.map.get(namespace).map(|namespace| &**namespace)
     }
 }
 
@@ -104,9 +107,7 @@ impl Namespace {
 }
 
 impl LikeNamespace for Namespace {
-    fn get_export(&mut self, name: &str) -> Option<Export> {
-        self.map
-            .get_mut(name)
-            .map(|is_export| is_export.to_export())
+    fn get_export(&self, name: &str) -> Option<Export> {
+        self.map.get(name).map(|is_export| is_export.to_export())
     }
 }
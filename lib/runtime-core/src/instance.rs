@@ -1,17 +1,23 @@
 use crate::{
     backend::Token,
-    backing::{ImportBacking, LocalBacking},
+    backing::{ImportBacking, Initialization, LocalBacking},
     error::{CallError, CallResult, ResolveError, ResolveResult, Result},
     export::{Context, Export, ExportIter, FuncPointer},
     global::Global,
     import::{ImportObject, LikeNamespace},
-    memory::Memory,
+    memory::{Memory, MemoryPool},
     module::{ExportIndex, Module, ModuleInner},
+    snapshot::InstanceSnapshot,
+    structures::BoxedMap,
+    sys,
     table::Table,
-    types::{FuncIndex, FuncSig, GlobalIndex, LocalOrImport, MemoryIndex, TableIndex, Value},
+    types::{
+        FuncIndex, FuncSig, GlobalIndex, LocalMemoryIndex, LocalOrImport, MemoryIndex, TableIndex,
+        Value,
+    },
     vm,
 };
-use std::{mem, sync::Arc};
+use std::{mem, rc::Rc, sync::Arc};
 
 pub(crate) struct InstanceInner {
     #[allow(dead_code)]
@@ -27,32 +33,155 @@ impl Drop for InstanceInner {
     }
 }
 
-/// An instantiated WebAssembly module.
+/// A WebAssembly module whose imports have been resolved ahead of time.
 ///
-/// An `Instance` represents a WebAssembly module that
-/// has been instantiated with an [`ImportObject`] and is
-/// ready to be called.
+/// Creating a `PreInstance` resolves every import declared by the module
+/// and checks it against the expected signature or descriptor. This only
+/// happens once, so instantiating from a `PreInstance` just has to allocate
+/// the local memories, tables, and globals and run the initializers.
 ///
-/// [`ImportObject`]: struct.ImportObject.html
-pub struct Instance {
+/// `PreInstance` is returned by the [`prelink`] method.
+///
+/// [`prelink`]: struct.Module.html#method.prelink
+pub struct PreInstance {
     module: Arc<ModuleInner>,
-    inner: Box<InstanceInner>,
-    #[allow(dead_code)]
-    imports: Box<ImportObject>,
+    import_backing: ImportBacking,
+    imports: Rc<ImportObject>,
+    memory_images: Option<Rc<BoxedMap<LocalMemoryIndex, sys::MemoryImage>>>,
+    memory_pool: Option<MemoryPool>,
 }
 
-impl Instance {
-    pub(crate) fn new(
-        module: Arc<ModuleInner>,
-        mut imports: Box<ImportObject>,
-    ) -> Result<Instance> {
+impl PreInstance {
+    pub(crate) fn new(module: Arc<ModuleInner>, imports: &ImportObject) -> Result<PreInstance> {
+        let import_backing = ImportBacking::new(&module, imports)?;
+
+        Ok(PreInstance {
+            module,
+            import_backing,
+            // Keeps the namespaces that the imports come from alive.
+            imports: Rc::new(imports.clone()),
+            memory_images: None,
+            memory_pool: None,
+        })
+    }
+
+    /// Build an image of the initial contents of each local memory,
+    /// including the data segments, and map it copy-on-write into
+    /// every instance created afterwards.
+    ///
+    /// Instantiating no longer has to copy the data segments, and pages
+    /// that are never written to are shared between instances. This is
+    /// worthwhile for modules with a large initial heap.
+    ///
+    /// Modules that place a data segment at the value of an imported
+    /// global keep copying their data segments, since the global can
+    /// change between instances.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::Module;
+    /// # use wasmer_runtime_core::imports;
+    /// # fn instantiate_many(module: &Module) -> Result<()> {
+    /// let mut pre_instance = module.prelink(&imports! {})?;
+    /// pre_instance.enable_memory_images()?;
+    ///
+    /// for _ in 0..10 {
+    ///     let instance = pre_instance.instantiate()?;
+    ///     // ...
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enable_memory_images(&mut self) -> Result<()> {
+        if self.memory_images.is_none() {
+            self.memory_images =
+                LocalBacking::generate_memory_images(&self.module, &self.import_backing)?
+                    .map(Rc::new);
+        }
+        Ok(())
+    }
+
+    /// Create the static memories and the tables of every instance
+    /// created afterwards with slots and buffers taken from `pool`.
+    ///
+    /// See [`MemoryPool`] for an example.
+    ///
+    /// [`MemoryPool`]: memory/struct.MemoryPool.html
+    pub fn set_memory_pool(&mut self, pool: MemoryPool) {
+        self.memory_pool = Some(pool);
+    }
+
+    /// Create a new [`Instance`] using the imports
+    /// resolved when this `PreInstance` was created.
+    ///
+    /// [`Instance`]: struct.Instance.html
+    ///
+    /// # Note:
+    /// Instantiating will also call the function designated as `start`
+    /// in the WebAssembly module, if there is one.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::Module;
+    /// # use wasmer_runtime_core::imports;
+    /// # fn instantiate_many(module: &Module) -> Result<()> {
+    /// let import_object = imports! {
+    ///     // ...
+    /// };
+    /// let pre_instance = module.prelink(&import_object)?;
+    ///
+    /// for _ in 0..10 {
+    ///     let instance = pre_instance.instantiate()?;
+    ///     // ...
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn instantiate(&self) -> Result<Instance> {
+        let initialization = match self.memory_images {
+            Some(ref memory_images) => Initialization::MemoryImages(&**memory_images),
+            None => Initialization::Segments,
+        };
+
+        let instance = self.instantiate_with(initialization);
+
+        if let Some(start_index) = instance.module.start_func {
+            instance.call_with_index(start_index, &[])?;
+        }
+
+        Ok(instance)
+    }
+
+    /// Create a new [`Instance`] whose memories, globals, and tables
+    /// hold the contents of `snapshot`.
+    ///
+    /// [`Instance`]: struct.Instance.html
+    ///
+    /// # Note:
+    /// Neither the data and element segments nor the `start` function
+    /// are applied, since their effects are already part of the snapshot.
+    /// Imported memories and tables are left untouched.
+    pub fn instantiate_from_snapshot(&self, snapshot: &InstanceSnapshot) -> Result<Instance> {
+        snapshot.check(&self.module)?;
+        Ok(self.instantiate_with(Initialization::Snapshot(snapshot)))
+    }
+
+    fn instantiate_with(&self, initialization: Initialization) -> Instance {
         // We need the backing and import_backing to create a vm::Ctx, but we need
         // a vm::Ctx to create a backing and an import_backing. The solution is to create an
         // uninitialized vm::Ctx and then initialize it in-place.
         let mut vmctx = unsafe { Box::new(mem::uninitialized()) };
 
-        let import_backing = ImportBacking::new(&module, &mut imports, &mut *vmctx)?;
-        let backing = LocalBacking::new(&module, &import_backing, &mut *vmctx);
+        let import_backing = self.import_backing.bind_vmctx(&mut *vmctx);
+        let backing = LocalBacking::new(
+            &self.module,
+            &import_backing,
+            initialization,
+            self.memory_pool.as_ref(),
+            &mut *vmctx,
+        );
 
         // When Pin is stablized, this will use `Box::pinned` instead of `Box::new`.
         let mut inner = Box::new(InstanceInner {
@@ -64,22 +193,39 @@ impl Instance {
         // Initialize the vm::Ctx in-place after the backing
         // has been boxed.
         unsafe {
-            *inner.vmctx = vm::Ctx::new(&mut inner.backing, &mut inner.import_backing, &module)
+            *inner.vmctx = vm::Ctx::new(&mut inner.backing, &mut inner.import_backing, &self.module)
         };
 
-        let instance = Instance {
-            module,
+        Instance {
+            module: Arc::clone(&self.module),
             inner,
-            imports,
-        };
-
-        if let Some(start_index) = instance.module.start_func {
-            instance.call_with_index(start_index, &[])?;
+            imports: Rc::clone(&self.imports),
+            memory_images: self.memory_images.clone(),
         }
+    }
 
-        Ok(instance)
+    /// The module that this `PreInstance` will instantiate.
+    pub fn module(&self) -> Module {
+        Module::new(Arc::clone(&self.module))
     }
+}
 
+/// An instantiated WebAssembly module.
+///
+/// An `Instance` represents a WebAssembly module that
+/// has been instantiated with an [`ImportObject`] and is
+/// ready to be called.
+///
+/// [`ImportObject`]: struct.ImportObject.html
+pub struct Instance {
+    module: Arc<ModuleInner>,
+    inner: Box<InstanceInner>,
+    #[allow(dead_code)]
+    imports: Rc<ImportObject>,
+    memory_images: Option<Rc<BoxedMap<LocalMemoryIndex, sys::MemoryImage>>>,
+}
+
+impl Instance {
     /// This returns the representation of a function that can be called
     /// safely.
     ///
@@ -194,6 +340,84 @@ impl Instance {
     pub fn module(&self) -> Module {
         Module::new(Arc::clone(&self.module))
     }
+
+    /// Bring this instance back to the state it was in right after
+    /// it was instantiated, so that it can be reused.
+    ///
+    /// The memories shrink back to their minimum size and get their data
+    /// segments again, mutable globals get their initial values, the tables
+    /// are cleared and get their element segments again, and then the
+    /// `start` function is called, if there is one. The existing memory
+    /// mappings are reused, so this is cheaper than instantiating again.
+    ///
+    /// # Note:
+    /// Data and element segments that target imported memories and
+    /// tables are applied again as well. An instance created from a
+    /// snapshot is reset to the state of a fresh instance, not to the
+    /// snapshot.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::Instance;
+    /// # fn serve(instance: &mut Instance) -> Result<()> {
+    /// loop {
+    ///     instance.call("handle_request", &[])?;
+    ///     instance.reset()?;
+    /// }
+    /// # }
+    /// ```
+    pub fn reset(&mut self) -> Result<()> {
+        let initialization = match self.memory_images {
+            Some(ref memory_images) => Initialization::MemoryImages(&**memory_images),
+            None => Initialization::Segments,
+        };
+
+        let inner = &mut *self.inner;
+        inner.backing.reset(
+            &self.module,
+            &inner.import_backing,
+            initialization,
+            inner.vmctx,
+        )?;
+
+        if let Some(start_index) = self.module.start_func {
+            self.call_with_index(start_index, &[])?;
+        }
+
+        Ok(())
+    }
+
+    /// Capture the contents of this instance's memories, mutable globals,
+    /// and tables, so that new instances can start from the same state.
+    ///
+    /// This fails if a table contains a function that is
+    /// neither defined in nor imported by the module.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::Instance;
+    /// # use wasmer_runtime_core::imports;
+    /// # fn snapshot(instance: &Instance) -> Result<()> {
+    /// // Warm up the instance.
+    /// instance.call("init", &[])?;
+    ///
+    /// let snapshot = instance.snapshot()?;
+    /// let warm_instance = instance
+    ///     .module()
+    ///     .instantiate_from_snapshot(&snapshot, imports! {})?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn snapshot(&self) -> Result<InstanceSnapshot> {
+        Ok(InstanceSnapshot::capture(
+            &self.module,
+            &self.inner.backing,
+            &self.inner.import_backing,
+            self.inner.vmctx,
+        )?)
+    }
 }
 
 impl Instance {
@@ -335,7 +559,7 @@ impl InstanceInner {
 }
 
 impl LikeNamespace for Instance {
-    fn get_export(&mut self, name: &str) -> Option<Export> {
+    fn get_export(&self, name: &str) -> Option<Export> {
         let export_index = self.module.exports.get(name)?;
 
         Some(self.inner.get_export_from_index(&self.module, export_index))
@@ -0,0 +1,559 @@
+//! Reads the line tables of a wasm module and writes the
+//! DWARF of its native code.
+
+use hashbrown::HashMap;
+
+/// Reads the little-endian encodings of DWARF and of wasm.
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    /// Reads an unsigned integer of `len` bytes.
+    pub fn uint(&mut self, len: usize) -> Option<u64> {
+        if len > 8 {
+            return None;
+        }
+        let bytes = self.bytes(len)?;
+        Some(
+            bytes
+                .iter()
+                .rev()
+                .fold(0, |value, &byte| (value << 8) | u64::from(byte)),
+        )
+    }
+
+    pub fn uleb(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= u64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    }
+
+    pub fn sleb(&mut self) -> Option<i64> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= i64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Some(value);
+            }
+        }
+    }
+
+    /// Reads a string terminated by a null byte.
+    pub fn cstr(&mut self) -> Option<&'a str> {
+        let len = self.data.get(self.pos..)?.iter().position(|&b| b == 0)?;
+        let bytes = self.bytes(len)?;
+        self.pos += 1;
+        ::std::str::from_utf8(bytes).ok()
+    }
+}
+
+/// A position in the source of a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// The index of the file in `LineTable::files`.
+    pub file: usize,
+    pub line: u64,
+    pub column: u64,
+}
+
+/// The code from `start` up to `end` comes from `position`.
+struct Range {
+    start: u64,
+    end: u64,
+    position: Position,
+}
+
+/// The line tables of every compilation unit of a module, which
+/// map offsets in its code section to source positions.
+pub struct LineTable {
+    pub files: Vec<String>,
+    ranges: Vec<Range>,
+}
+
+impl LineTable {
+    /// Parses the contents of a `.debug_line` section. Line tables
+    /// with a version of DWARF other than 2 to 4 are skipped.
+    pub fn parse(debug_line: &[u8]) -> Option<Self> {
+        let mut table = LineTable {
+            files: Vec::new(),
+            ranges: Vec::new(),
+        };
+        let mut file_indices = HashMap::new();
+
+        let mut reader = Reader::new(debug_line);
+        while !reader.is_empty() {
+            let (unit_length, offset_size) = match reader.uint(4)? {
+                0xffff_ffff => (reader.uint(8)?, 8),
+                unit_length => (unit_length, 4),
+            };
+            let unit_end = reader.pos.checked_add(unit_length as usize)?;
+            let unit = debug_line.get(reader.pos..unit_end)?;
+            reader.pos = unit_end;
+
+            table.parse_unit(unit, offset_size, &mut file_indices);
+        }
+
+        table.ranges.sort_by_key(|range| range.start);
+        Some(table)
+    }
+
+    /// Parses the line table of one unit, after its length.
+    fn parse_unit(
+        &mut self,
+        unit: &[u8],
+        offset_size: usize,
+        file_indices: &mut HashMap<String, usize>,
+    ) -> Option<()> {
+        let mut reader = Reader::new(unit);
+
+        let version = reader.uint(2)?;
+        if version < 2 || version > 4 {
+            return None;
+        }
+        let header_length = reader.uint(offset_size)? as usize;
+        let program_start = reader.pos.checked_add(header_length)?;
+        let min_inst_length = u64::from(reader.u8()?);
+        if version >= 4 {
+            // The maximum number of operations per instruction,
+            // which is only meaningful for VLIW targets.
+            reader.u8()?;
+        }
+        // Whether rows are statements by default, which doesn't
+        // matter to a debugger looking for the code of a line.
+        reader.u8()?;
+        let line_base = i64::from(reader.u8()? as i8);
+        let line_range = u64::from(reader.u8()?);
+        let opcode_base = reader.u8()?;
+        if line_range == 0 || opcode_base == 0 {
+            return None;
+        }
+        let opcode_lengths = reader.bytes(opcode_base as usize - 1)?;
+
+        let mut directories = Vec::new();
+        loop {
+            let directory = reader.cstr()?;
+            if directory.is_empty() {
+                break;
+            }
+            directories.push(directory);
+        }
+
+        // The files of this unit, numbered from 1, as their indices in `self.files`.
+        let mut files = Vec::new();
+        loop {
+            let name = reader.cstr()?;
+            if name.is_empty() {
+                break;
+            }
+            let directory = reader.uleb()?;
+            reader.uleb()?;
+            reader.uleb()?;
+            files.push(self.file_index(name, directory, &directories, file_indices));
+        }
+
+        reader.pos = program_start;
+
+        let mut sequence: Vec<(u64, Option<Position>)> = Vec::new();
+        let mut address = 0;
+        let mut file = 1;
+        let mut line = 1;
+        let mut column = 0;
+
+        while !reader.is_empty() {
+            let mut emit = false;
+            let mut end_sequence = false;
+
+            match reader.u8()? {
+                opcode if opcode >= opcode_base => {
+                    let adjusted = u64::from(opcode - opcode_base);
+                    address += (adjusted / line_range) * min_inst_length;
+                    line = (line as i64 + line_base + (adjusted % line_range) as i64) as u64;
+                    emit = true;
+                }
+                0 => {
+                    let len = reader.uleb()? as usize;
+                    let end = reader.pos.checked_add(len)?;
+                    match reader.u8()? {
+                        // DW_LNE_end_sequence
+                        1 => {
+                            emit = true;
+                            end_sequence = true;
+                        }
+                        // DW_LNE_set_address
+                        2 => address = reader.uint(len.checked_sub(1)?)?,
+                        // DW_LNE_define_file
+                        3 => {
+                            let name = reader.cstr()?;
+                            let directory = reader.uleb()?;
+                            files.push(self.file_index(
+                                name,
+                                directory,
+                                &directories,
+                                file_indices,
+                            ));
+                        }
+                        _ => {}
+                    }
+                    reader.pos = end;
+                }
+                // DW_LNS_copy
+                1 => emit = true,
+                // DW_LNS_advance_pc
+                2 => address += reader.uleb()? * min_inst_length,
+                // DW_LNS_advance_line
+                3 => line = (line as i64 + reader.sleb()?) as u64,
+                // DW_LNS_set_file
+                4 => file = reader.uleb()?,
+                // DW_LNS_set_column
+                5 => column = reader.uleb()?,
+                // DW_LNS_const_add_pc
+                8 => address += (u64::from(255 - opcode_base) / line_range) * min_inst_length,
+                // DW_LNS_fixed_advance_pc
+                9 => address += reader.uint(2)?,
+                opcode => {
+                    for _ in 0..opcode_lengths[opcode as usize - 1] {
+                        reader.uleb()?;
+                    }
+                }
+            }
+
+            if emit {
+                let position = (file as usize)
+                    .checked_sub(1)
+                    .and_then(|index| files.get(index))
+                    .map(|&file| Position { file, line, column });
+                sequence.push((address, position));
+            }
+            if end_sequence {
+                self.add_sequence(&sequence);
+                sequence.clear();
+                address = 0;
+                file = 1;
+                line = 1;
+                column = 0;
+            }
+        }
+
+        Some(())
+    }
+
+    fn file_index(
+        &mut self,
+        name: &str,
+        directory: u64,
+        directories: &[&str],
+        file_indices: &mut HashMap<String, usize>,
+    ) -> usize {
+        // Directory 0 is the one the unit was compiled in, which
+        // isn't in the line table, so those paths stay relative.
+        let path = match directories.get((directory as usize).wrapping_sub(1)) {
+            Some(directory) if !name.starts_with('/') => format!("{}/{}", directory, name),
+            _ => name.to_string(),
+        };
+
+        let files = &mut self.files;
+        *file_indices.entry(path.clone()).or_insert_with(|| {
+            files.push(path);
+            files.len() - 1
+        })
+    }
+
+    fn add_sequence(&mut self, sequence: &[(u64, Option<Position>)]) {
+        // The linker points the line tables of the functions that
+        // it removed at address 0, where no function can start.
+        match sequence.first() {
+            Some(&(0, _)) | None => return,
+            _ => {}
+        }
+
+        for rows in sequence.windows(2) {
+            if let ((start, Some(position)), (end, _)) = (rows[0], rows[1]) {
+                if start < end {
+                    self.ranges.push(Range {
+                        start,
+                        end,
+                        position,
+                    });
+                }
+            }
+        }
+    }
+
+    /// The source position of the code at `address`.
+    pub fn lookup(&self, address: u64) -> Option<Position> {
+        let index = match self
+            .ranges
+            .binary_search_by_key(&address, |range| range.start)
+        {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let range = &self.ranges[index];
+        if address < range.end {
+            Some(range.position)
+        } else {
+            None
+        }
+    }
+}
+
+/// The native code of a function, and the source
+/// position of each instruction that has one.
+pub struct Sequence<'a> {
+    pub name: &'a str,
+    pub start: u64,
+    pub end: u64,
+    /// The address of an instruction, and its position.
+    /// Only the instructions that change the position are there.
+    pub rows: Vec<(u64, Position)>,
+}
+
+/// The `.debug_abbrev`, `.debug_info` and `.debug_line` sections
+/// describing `sequences`, whose positions refer to `files`.
+pub struct Sections {
+    pub debug_abbrev: Vec<u8>,
+    pub debug_info: Vec<u8>,
+    pub debug_line: Vec<u8>,
+}
+
+const DW_TAG_COMPILE_UNIT: u8 = 0x11;
+const DW_TAG_SUBPROGRAM: u8 = 0x2e;
+const DW_AT_NAME: u8 = 0x03;
+const DW_AT_STMT_LIST: u8 = 0x10;
+const DW_AT_LOW_PC: u8 = 0x11;
+const DW_AT_HIGH_PC: u8 = 0x12;
+const DW_FORM_ADDR: u8 = 0x01;
+const DW_FORM_DATA4: u8 = 0x06;
+const DW_FORM_STRING: u8 = 0x08;
+
+/// The line program of the generated code uses the opcodes of DWARF 2.
+const OPCODE_BASE: u8 = 10;
+const STANDARD_OPCODE_LENGTHS: [u8; 9] = [0, 1, 1, 1, 1, 0, 0, 0, 1];
+
+impl Sections {
+    /// Describes `sequences` as a single compilation unit called `name`.
+    pub fn new(name: &str, files: &[String], sequences: &[Sequence]) -> Self {
+        let low_pc = sequences.iter().map(|s| s.start).min().unwrap_or(0);
+        let high_pc = sequences.iter().map(|s| s.end).max().unwrap_or(0);
+
+        let mut debug_abbrev = Writer::new();
+        debug_abbrev.uleb(1);
+        debug_abbrev.u8(DW_TAG_COMPILE_UNIT);
+        debug_abbrev.u8(1);
+        debug_abbrev.bytes(&[
+            DW_AT_NAME,
+            DW_FORM_STRING,
+            DW_AT_STMT_LIST,
+            DW_FORM_DATA4,
+            DW_AT_LOW_PC,
+            DW_FORM_ADDR,
+            DW_AT_HIGH_PC,
+            DW_FORM_ADDR,
+            0,
+            0,
+        ]);
+        debug_abbrev.uleb(2);
+        debug_abbrev.u8(DW_TAG_SUBPROGRAM);
+        debug_abbrev.u8(0);
+        debug_abbrev.bytes(&[
+            DW_AT_NAME,
+            DW_FORM_STRING,
+            DW_AT_LOW_PC,
+            DW_FORM_ADDR,
+            DW_AT_HIGH_PC,
+            DW_FORM_ADDR,
+            0,
+            0,
+        ]);
+        debug_abbrev.u8(0);
+
+        let mut debug_info = Writer::new();
+        let unit_length = debug_info.reserve_u32();
+        debug_info.uint(2, 2);
+        debug_info.uint(0, 4);
+        debug_info.u8(8);
+        debug_info.uleb(1);
+        debug_info.cstr(name);
+        debug_info.uint(0, 4);
+        debug_info.uint(low_pc, 8);
+        debug_info.uint(high_pc, 8);
+        for sequence in sequences {
+            debug_info.uleb(2);
+            debug_info.cstr(sequence.name);
+            debug_info.uint(sequence.start, 8);
+            debug_info.uint(sequence.end, 8);
+        }
+        debug_info.u8(0);
+        debug_info.patch_length(unit_length);
+
+        let mut debug_line = Writer::new();
+        let unit_length = debug_line.reserve_u32();
+        debug_line.uint(2, 2);
+        let header_length = debug_line.reserve_u32();
+        // The minimum instruction length, whether rows are statements,
+        // and the line base and range of the special opcodes, which
+        // aren't used.
+        debug_line.bytes(&[1, 1, -5i8 as u8, 14, OPCODE_BASE]);
+        debug_line.bytes(&STANDARD_OPCODE_LENGTHS);
+        debug_line.u8(0);
+        for file in files {
+            debug_line.cstr(file);
+            debug_line.uleb(0);
+            debug_line.uleb(0);
+            debug_line.uleb(0);
+        }
+        debug_line.u8(0);
+        debug_line.patch_length(header_length);
+
+        for sequence in sequences {
+            let mut file = 0;
+            let mut line = 1;
+            let mut column = 0;
+            for &(address, position) in &sequence.rows {
+                debug_line.set_address(address);
+                if position.file != file {
+                    // DW_LNS_set_file
+                    debug_line.u8(4);
+                    debug_line.uleb(position.file as u64 + 1);
+                    file = position.file;
+                }
+                if position.line != line {
+                    // DW_LNS_advance_line
+                    debug_line.u8(3);
+                    debug_line.sleb(position.line as i64 - line as i64);
+                    line = position.line;
+                }
+                if position.column != column {
+                    // DW_LNS_set_column
+                    debug_line.u8(5);
+                    debug_line.uleb(position.column);
+                    column = position.column;
+                }
+                // DW_LNS_copy
+                debug_line.u8(1);
+            }
+            debug_line.set_address(sequence.end);
+            // DW_LNE_end_sequence
+            debug_line.bytes(&[0, 1, 1]);
+        }
+        debug_line.patch_length(unit_length);
+
+        Self {
+            debug_abbrev: debug_abbrev.data,
+            debug_info: debug_info.data,
+            debug_line: debug_line.data,
+        }
+    }
+}
+
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn new() -> Self {
+        Self { data: Vec::new() }
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    fn uint(&mut self, value: u64, len: usize) {
+        for i in 0..len {
+            self.data.push((value >> (8 * i)) as u8);
+        }
+    }
+
+    fn uleb(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.data.push(byte);
+                return;
+            }
+            self.data.push(byte | 0x80);
+        }
+    }
+
+    fn sleb(&mut self, mut value: i64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+                self.data.push(byte);
+                return;
+            }
+            self.data.push(byte | 0x80);
+        }
+    }
+
+    fn cstr(&mut self, string: &str) {
+        self.data.extend_from_slice(string.as_bytes());
+        self.data.push(0);
+    }
+
+    /// Leaves room for a length that's only known later.
+    fn reserve_u32(&mut self) -> usize {
+        self.uint(0, 4);
+        self.data.len()
+    }
+
+    /// Sets the length reserved before `end` to the
+    /// number of bytes written since.
+    fn patch_length(&mut self, end: usize) {
+        let length = (self.data.len() - end) as u32;
+        for i in 0..4 {
+            self.data[end - 4 + i] = (length >> (8 * i)) as u8;
+        }
+    }
+
+    /// Writes `DW_LNE_set_address`.
+    fn set_address(&mut self, address: u64) {
+        self.bytes(&[0, 9, 2]);
+        self.uint(address, 8);
+    }
+}
@@ -0,0 +1,192 @@
+//! Writes the in-memory object files that describe generated
+//! code to a debugger.
+//!
+//! The code itself stays where it is: the object only has a
+//! `.text` section without contents at the address of the code,
+//! the symbols of its functions, and their debug sections.
+
+const EM_X86_64: u16 = 62;
+const ET_EXEC: u16 = 2;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+
+const STB_GLOBAL: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+const HEADER_SIZE: usize = 64;
+const SECTION_HEADER_SIZE: usize = 64;
+const SYMBOL_SIZE: usize = 24;
+
+/// A function in the `.text` section.
+pub struct Symbol<'a> {
+    pub name: &'a str,
+    pub address: u64,
+    pub size: u64,
+}
+
+struct Section<'a> {
+    name: &'a str,
+    kind: u32,
+    flags: u64,
+    address: u64,
+    data: &'a [u8],
+    /// The size of a section without contents.
+    size: u64,
+    link: u32,
+    info: u32,
+    entry_size: u64,
+}
+
+impl<'a> Section<'a> {
+    fn new(name: &'a str, kind: u32, data: &'a [u8]) -> Self {
+        Self {
+            name,
+            kind,
+            flags: 0,
+            address: 0,
+            data,
+            size: data.len() as u64,
+            link: 0,
+            info: 0,
+            entry_size: 0,
+        }
+    }
+}
+
+/// Writes an object whose `.text` section spans from `text_start`
+/// to `text_end`, with `symbols` and the `debug_sections`, each
+/// of them a name and its contents.
+pub fn write_object(
+    text_start: u64,
+    text_end: u64,
+    symbols: &[Symbol],
+    debug_sections: &[(&str, &[u8])],
+) -> Vec<u8> {
+    // The section indices are fixed by the order below.
+    const TEXT_INDEX: u16 = 1;
+    let strtab_index = 2 + debug_sections.len() as u32 + 1;
+
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; SYMBOL_SIZE];
+    for symbol in symbols {
+        push_u32(&mut symtab, strtab.len() as u32);
+        strtab.extend_from_slice(symbol.name.as_bytes());
+        strtab.push(0);
+        symtab.push((STB_GLOBAL << 4) | STT_FUNC);
+        symtab.push(0);
+        push_u16(&mut symtab, TEXT_INDEX);
+        push_u64(&mut symtab, symbol.address);
+        push_u64(&mut symtab, symbol.size);
+    }
+
+    let mut sections = vec![Section {
+        flags: SHF_ALLOC | SHF_EXECINSTR,
+        address: text_start,
+        size: text_end - text_start,
+        ..Section::new(".text", SHT_NOBITS, &[])
+    }];
+    for &(name, data) in debug_sections {
+        sections.push(Section::new(name, SHT_PROGBITS, data));
+    }
+    sections.push(Section {
+        link: strtab_index,
+        // The index of the first global symbol.
+        info: 1,
+        entry_size: SYMBOL_SIZE as u64,
+        ..Section::new(".symtab", SHT_SYMTAB, &symtab)
+    });
+    sections.push(Section::new(".strtab", SHT_STRTAB, &strtab));
+
+    let mut shstrtab = vec![0];
+    let mut section_names = Vec::with_capacity(sections.len() + 1);
+    for name in sections
+        .iter()
+        .map(|section| section.name)
+        .chain(Some(".shstrtab"))
+    {
+        section_names.push(shstrtab.len() as u32);
+        shstrtab.extend_from_slice(name.as_bytes());
+        shstrtab.push(0);
+    }
+    sections.push(Section::new(".shstrtab", SHT_STRTAB, &shstrtab));
+
+    // The contents of the sections follow the header, each of
+    // them aligned to 8 bytes, and the section headers come last.
+    let mut offsets = Vec::with_capacity(sections.len());
+    let mut offset = HEADER_SIZE;
+    for section in &sections {
+        offsets.push(offset);
+        offset = align(offset + section.data.len());
+    }
+    let section_headers_offset = offset;
+
+    let mut object =
+        Vec::with_capacity(section_headers_offset + (sections.len() + 1) * SECTION_HEADER_SIZE);
+    object.extend_from_slice(b"\x7fELF");
+    // 64-bit, little-endian, version 1, System V ABI.
+    object.extend_from_slice(&[2, 1, 1, 0]);
+    object.resize(16, 0);
+    push_u16(&mut object, ET_EXEC);
+    push_u16(&mut object, EM_X86_64);
+    push_u32(&mut object, 1);
+    // No entry point and no program headers.
+    push_u64(&mut object, 0);
+    push_u64(&mut object, 0);
+    push_u64(&mut object, section_headers_offset as u64);
+    push_u32(&mut object, 0);
+    push_u16(&mut object, HEADER_SIZE as u16);
+    push_u16(&mut object, 0);
+    push_u16(&mut object, 0);
+    push_u16(&mut object, SECTION_HEADER_SIZE as u16);
+    push_u16(&mut object, sections.len() as u16 + 1);
+    push_u16(&mut object, sections.len() as u16);
+
+    for (section, &offset) in sections.iter().zip(&offsets) {
+        object.resize(offset, 0);
+        object.extend_from_slice(section.data);
+    }
+    object.resize(section_headers_offset, 0);
+
+    // The null section comes first.
+    object.resize(object.len() + SECTION_HEADER_SIZE, 0);
+    for ((section, &name), &offset) in sections.iter().zip(&section_names).zip(&offsets) {
+        push_u32(&mut object, name);
+        push_u32(&mut object, section.kind);
+        push_u64(&mut object, section.flags);
+        push_u64(&mut object, section.address);
+        push_u64(&mut object, offset as u64);
+        push_u64(&mut object, section.size);
+        push_u32(&mut object, section.link);
+        push_u32(&mut object, section.info);
+        push_u64(&mut object, if section.kind == SHT_NOBITS { 16 } else { 8 });
+        push_u64(&mut object, section.entry_size);
+    }
+
+    object
+}
+
+fn align(offset: usize) -> usize {
+    (offset + 7) & !7
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&[value as u8, (value >> 8) as u8]);
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    for i in 0..4 {
+        out.push((value >> (8 * i)) as u8);
+    }
+}
+
+fn push_u64(out: &mut Vec<u8>, value: u64) {
+    for i in 0..8 {
+        out.push((value >> (8 * i)) as u8);
+    }
+}
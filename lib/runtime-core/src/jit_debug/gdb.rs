@@ -0,0 +1,123 @@
+//! The interface that GDB and LLDB use to find the object
+//! files of code generated at runtime.
+//!
+//! The debugger puts a breakpoint in `__jit_debug_register_code`,
+//! and reads the entry that was just added to or removed from the
+//! list in `__jit_debug_descriptor` whenever it's hit.
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::ptr;
+
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+#[repr(C)]
+pub struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+#[no_mangle]
+#[inline(never)]
+pub extern "C" fn __jit_debug_register_code() {
+    // Keeps the function from being optimized away, since
+    // the debugger relies on calls to it.
+    unsafe {
+        ptr::read_volatile(&0u8);
+    }
+}
+
+lazy_static! {
+    /// Serializes the changes to the list of entries.
+    static ref LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// An object file that the debugger knows about,
+/// until this is dropped.
+pub struct Registration {
+    entry: *mut JitCodeEntry,
+    object: Box<[u8]>,
+}
+
+unsafe impl Send for Registration {}
+unsafe impl Sync for Registration {}
+
+impl Registration {
+    pub fn new(object: Vec<u8>) -> Self {
+        let object = object.into_boxed_slice();
+        let entry = Box::into_raw(Box::new(JitCodeEntry {
+            next_entry: ptr::null_mut(),
+            prev_entry: ptr::null_mut(),
+            symfile_addr: object.as_ptr(),
+            symfile_size: object.len() as u64,
+        }));
+
+        let _lock = LOCK.lock();
+        unsafe {
+            let first_entry = __jit_debug_descriptor.first_entry;
+            (*entry).next_entry = first_entry;
+            if !first_entry.is_null() {
+                (*first_entry).prev_entry = entry;
+            }
+            __jit_debug_descriptor.first_entry = entry;
+            notify(JIT_REGISTER_FN, entry);
+        }
+
+        Self { entry, object }
+    }
+
+    /// The object file that describes the code.
+    pub fn object(&self) -> &[u8] {
+        &self.object
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let _lock = LOCK.lock();
+        unsafe {
+            let entry = &mut *self.entry;
+            if entry.prev_entry.is_null() {
+                __jit_debug_descriptor.first_entry = entry.next_entry;
+            } else {
+                (*entry.prev_entry).next_entry = entry.next_entry;
+            }
+            if !entry.next_entry.is_null() {
+                (*entry.next_entry).prev_entry = entry.prev_entry;
+            }
+            notify(JIT_UNREGISTER_FN, self.entry);
+
+            drop(Box::from_raw(self.entry));
+        }
+    }
+}
+
+/// Tells the debugger about `entry`. The lock must be held.
+unsafe fn notify(action: u32, entry: *mut JitCodeEntry) {
+    __jit_debug_descriptor.relevant_entry = entry;
+    __jit_debug_descriptor.action_flag = action;
+    __jit_debug_register_code();
+    __jit_debug_descriptor.action_flag = JIT_NOACTION;
+    __jit_debug_descriptor.relevant_entry = ptr::null_mut();
+}
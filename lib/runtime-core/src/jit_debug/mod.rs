@@ -0,0 +1,152 @@
+//! Lets GDB and LLDB debug generated code in the source of the
+//! module it was compiled from.
+//!
+//! When a module has DWARF line tables in a `.debug_line` custom
+//! section, the backends that know which wasm operator each native
+//! instruction comes from register their code through the GDB JIT
+//! interface. The object files they register have the symbols of
+//! the functions and line tables translated to native addresses,
+//! so breakpoints can be set in the original C or Rust source:
+//!
+//! ```text
+//! gdb --args wasmer run foo.wasm
+//! (gdb) break foo.c:12
+//! ```
+//!
+//! Only line tables are translated, so variables can't be printed.
+
+mod dwarf;
+mod elf;
+mod gdb;
+
+pub use self::gdb::Registration;
+
+use self::dwarf::{LineTable, Reader, Sections, Sequence};
+use self::elf::Symbol;
+use crate::{structures::TypedIndex, types::LocalFuncIndex};
+
+/// The native code of a function.
+pub struct FuncCode<'a> {
+    pub local_func_index: LocalFuncIndex,
+    pub name: &'a str,
+    pub address: usize,
+    pub size: usize,
+    /// The offset of each instruction from `address`, and the offset
+    /// of the wasm operator it comes from, from the start of the
+    /// body of the function, which is what Cranelift's source
+    /// locations are.
+    pub srclocs: &'a [(usize, usize)],
+}
+
+/// The debug info of a module.
+pub struct DebugInfo {
+    /// Where the body of each local function starts in the module.
+    body_offsets: Vec<usize>,
+    /// Where the contents of the code section start in the module,
+    /// since the addresses in DWARF are offsets from there.
+    code_offset: usize,
+    lines: LineTable,
+}
+
+impl DebugInfo {
+    /// Reads the line tables of `wasm`, if it has any.
+    pub fn new(wasm: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(wasm);
+        if reader.bytes(4)? != b"\0asm" {
+            return None;
+        }
+        reader.bytes(4)?;
+
+        let mut body_offsets = Vec::new();
+        let mut code_offset = None;
+        let mut debug_line = None;
+        while !reader.is_empty() {
+            let id = reader.u8()?;
+            let size = reader.uleb()? as usize;
+            let end = reader.pos.checked_add(size)?;
+            match id {
+                // A custom section.
+                0 => {
+                    let name_len = reader.uleb()? as usize;
+                    if reader.bytes(name_len)? == b".debug_line" {
+                        debug_line = Some(wasm.get(reader.pos..end)?);
+                    }
+                }
+                // The code section.
+                10 => {
+                    code_offset = Some(reader.pos);
+                    for _ in 0..reader.uleb()? {
+                        let body_size = reader.uleb()? as usize;
+                        body_offsets.push(reader.pos);
+                        reader.bytes(body_size)?;
+                    }
+                }
+                _ => {}
+            }
+            reader.pos = end;
+        }
+
+        Some(Self {
+            body_offsets,
+            code_offset: code_offset?,
+            lines: LineTable::parse(debug_line?)?,
+        })
+    }
+
+    /// Registers `funcs` with the debugger, until the registration
+    /// is dropped. `module_name` names their compilation unit.
+    pub fn register(&self, module_name: &str, funcs: &[FuncCode]) -> Registration {
+        let sequences: Vec<_> = funcs
+            .iter()
+            .map(|func| Sequence {
+                name: func.name,
+                start: func.address as u64,
+                end: (func.address + func.size) as u64,
+                rows: self.rows(func),
+            })
+            .collect();
+        let sections = Sections::new(module_name, &self.lines.files, &sequences);
+
+        let symbols: Vec<_> = funcs
+            .iter()
+            .map(|func| Symbol {
+                name: func.name,
+                address: func.address as u64,
+                size: func.size as u64,
+            })
+            .collect();
+        let text_start = sequences.iter().map(|s| s.start).min().unwrap_or(0);
+        let text_end = sequences.iter().map(|s| s.end).max().unwrap_or(0);
+
+        let object = elf::write_object(
+            text_start,
+            text_end,
+            &symbols,
+            &[
+                (".debug_abbrev", &sections.debug_abbrev[..]),
+                (".debug_info", &sections.debug_info[..]),
+                (".debug_line", &sections.debug_line[..]),
+            ],
+        );
+        Registration::new(object)
+    }
+
+    /// The native addresses in `func` where the source position changes.
+    fn rows(&self, func: &FuncCode) -> Vec<(u64, dwarf::Position)> {
+        let body_offset = match self.body_offsets.get(func.local_func_index.index()) {
+            Some(&body_offset) => body_offset,
+            None => return Vec::new(),
+        };
+
+        let mut rows: Vec<(u64, dwarf::Position)> = Vec::new();
+        for &(native_offset, srcloc) in func.srclocs {
+            let address = (body_offset + srcloc - self.code_offset) as u64;
+            if let Some(position) = self.lines.lookup(address) {
+                if rows.last().map_or(true, |&(_, last)| last != position) {
+                    rows.push(((func.address + native_offset) as u64, position));
+                }
+            }
+        }
+        rows
+    }
+}
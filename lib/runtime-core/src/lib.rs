@@ -9,12 +9,19 @@ pub mod backend;
 mod backing;
 pub mod error;
 pub mod export;
+mod features;
 pub mod global;
 pub mod import;
 pub mod instance;
+pub mod jit_debug;
 pub mod memory;
 pub mod module;
+#[doc(hidden)]
+pub mod parse;
+pub mod perf_map;
+pub mod profiler;
 mod sig_registry;
+pub mod snapshot;
 pub mod structures;
 mod sys;
 pub mod table;
@@ -27,6 +34,7 @@ pub mod vmcalls;
 use self::error::CompileResult;
 #[doc(inline)]
 pub use self::error::Result;
+pub use self::features::Features;
 #[doc(inline)]
 pub use self::instance::Instance;
 #[doc(inline)]
@@ -49,29 +57,69 @@ pub mod prelude {
 /// is necessary to a compile a module before it can be instantiated
 /// and must be used if you wish to use a different backend from the default.
 ///
+/// Only MVP features are allowed, see [`compile_with_features`].
+///
 /// [`Module`]: struct.Module.html
+/// [`compile_with_features`]: fn.compile_with_features.html
 pub fn compile_with(
     wasm: &[u8],
     compiler: &dyn backend::Compiler,
+) -> CompileResult<module::Module> {
+    compile_with_features(wasm, compiler, &Features::default())
+}
+
+/// Like [`compile_with`], but allows the module to use
+/// the WebAssembly proposals enabled in `features`.
+///
+/// [`compile_with`]: fn.compile_with.html
+pub fn compile_with_features(
+    wasm: &[u8],
+    compiler: &dyn backend::Compiler,
+    features: &Features,
 ) -> CompileResult<module::Module> {
     let token = backend::Token::generate();
     compiler
-        .compile(wasm, token)
+        .compile(wasm, features, token)
         .map(|inner| module::Module::new(Arc::new(inner)))
 }
 
 /// Perform validation as defined by the
-/// WebAssembly specification. Returns `true` if validation
-/// succeeded, `false` if validation failed.
-pub fn validate(wasm: &[u8]) -> bool {
+/// WebAssembly specification.
+///
+/// Only MVP features are allowed, see [`validate_with_features`].
+///
+/// # Errors:
+/// If the module is invalid, this returns a `CompileError::ValidationError`
+/// that describes the first problem found and where it is.
+///
+/// [`validate_with_features`]: fn.validate_with_features.html
+pub fn validate(wasm: &[u8]) -> CompileResult<()> {
+    validate_with_features(wasm, &Features::default())
+}
+
+/// Like [`validate`], but allows the module to use
+/// the WebAssembly proposals enabled in `features`.
+///
+/// [`validate`]: fn.validate.html
+pub fn validate_with_features(wasm: &[u8], features: &Features) -> CompileResult<()> {
     use wasmparser::WasmDecoder;
-    let mut parser = wasmparser::ValidatingParser::new(wasm, None);
+    let mut parser = wasmparser::ValidatingParser::new(wasm, Some(features.parser_config()));
+    let mut checker = features::FeatureChecker::new(features);
+    checker
+        .check_features()
+        .map_err(|msg| error::CompileError::ValidationError { msg, offset: 0 })?;
     loop {
+        let offset = parser.current_position();
         let state = parser.read();
         match *state {
-            wasmparser::ParserState::EndWasm => break true,
-            wasmparser::ParserState::Error(_) => break false,
-            _ => {}
+            wasmparser::ParserState::EndWasm => break Ok(()),
+            wasmparser::ParserState::Error(err) => Err(error::CompileError::ValidationError {
+                msg: err.message.to_string(),
+                offset: err.offset,
+            })?,
+            ref state => checker
+                .check(state)
+                .map_err(|msg| error::CompileError::ValidationError { msg, offset })?,
         }
     }
 }
@@ -21,10 +21,15 @@ pub const DYNAMIC_GUARD_SIZE: usize = 4096;
 /// While, a dynamic memory could use a vector of some sort as its
 /// backing memory, we use mmap (or the platform-equivalent) to allow
 /// us to add a guard-page at the end to help elide some bounds-checks.
+///
+/// If its descriptor asks to `reserve_maximum`, the address space up to
+/// its maximum is mapped when it's created, and it grows in place.
 pub struct DynamicMemory {
     memory: sys::Memory,
     current: Pages,
     max: Option<Pages>,
+    /// Whether `memory` is large enough for `max` pages.
+    reserved: bool,
 }
 
 impl DynamicMemory {
@@ -33,8 +38,12 @@ impl DynamicMemory {
         local: &mut vm::LocalMemory,
     ) -> Result<Box<Self>, CreationError> {
         let min_bytes: Bytes = desc.minimum.into();
+        let reserved_pages = match desc.maximum {
+            Some(max) if desc.reserves_maximum() => max,
+            _ => desc.minimum,
+        };
         let memory = {
-            let mut memory = sys::Memory::with_size(min_bytes.0 + DYNAMIC_GUARD_SIZE)
+            let mut memory = sys::Memory::with_size(reserved_pages.bytes().0 + DYNAMIC_GUARD_SIZE)
                 .map_err(|_| CreationError::UnableToCreateMemory)?;
             if desc.minimum != Pages(0) {
                 unsafe {
@@ -51,6 +60,7 @@ impl DynamicMemory {
             memory,
             current: desc.minimum,
             max: desc.maximum,
+            reserved: desc.reserves_maximum() && desc.maximum.is_some(),
         });
         let storage_ptr: *mut DynamicMemory = &mut *storage;
 
@@ -78,6 +88,22 @@ impl DynamicMemory {
             }
         }
 
+        if self.reserved {
+            unsafe {
+                self.memory
+                    .protect(
+                        self.current.bytes().0..new_pages.bytes().0,
+                        sys::Protect::ReadWrite,
+                    )
+                    .ok()?;
+            }
+            local.bound = new_pages.bytes().0;
+
+            let old_pages = self.current;
+            self.current = new_pages;
+            return Some(old_pages);
+        }
+
         let mut new_memory =
             sys::Memory::with_size(new_pages.bytes().0 + DYNAMIC_GUARD_SIZE).ok()?;
 
@@ -100,6 +126,37 @@ impl DynamicMemory {
         Some(old_pages)
     }
 
+    pub(super) fn map_image(&mut self, image: &sys::MemoryImage) -> Result<(), CreationError> {
+        assert!(image.size() <= self.current.bytes().0);
+        unsafe {
+            self.memory
+                .map_image(image)
+                .map_err(|_| CreationError::UnableToCreateMemory)
+        }
+    }
+
+    pub(super) fn reset(
+        &mut self,
+        minimum: Pages,
+        local: &mut vm::LocalMemory,
+    ) -> Result<(), CreationError> {
+        unsafe {
+            self.memory
+                .reset(self.current.bytes().0)
+                .map_err(|_| CreationError::UnableToCreateMemory)?;
+            if minimum != Pages(0) {
+                self.memory
+                    .protect(0..minimum.bytes().0, sys::Protect::ReadWrite)
+                    .map_err(|_| CreationError::UnableToCreateMemory)?;
+            }
+        }
+
+        local.bound = minimum.bytes().0;
+        self.current = minimum;
+
+        Ok(())
+    }
+
     pub fn as_slice(&self) -> &[u8] {
         unsafe { &self.memory.as_slice()[0..self.current.bytes().0] }
     }
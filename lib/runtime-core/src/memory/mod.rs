@@ -4,6 +4,7 @@ use crate::{
     import::IsExport,
     memory::dynamic::DYNAMIC_GUARD_SIZE,
     memory::static_::{SAFE_STATIC_GUARD_SIZE, SAFE_STATIC_HEAP_SIZE},
+    sys,
     types::{MemoryDescriptor, ValueType},
     units::Pages,
     vm,
@@ -11,11 +12,47 @@ use crate::{
 use std::{cell::RefCell, fmt, mem, ptr, rc::Rc, slice};
 
 pub use self::dynamic::DynamicMemory;
+pub use self::pool::MemoryPool;
 pub use self::static_::{SharedStaticMemory, StaticMemory};
 
 mod dynamic;
+mod pool;
 mod static_;
 
+thread_local! {
+    /// Every memory on this thread that is still alive, so that a
+    /// fault can be traced back to the memory it happened in. Memories
+    /// can't be sent to other threads, so the code accessing them
+    /// always runs on the thread that created them.
+    static MEMORIES: RefCell<Vec<(*const vm::LocalMemory, MemoryType)>> = RefCell::new(Vec::new());
+}
+
+/// Finds the memory that `addr` points into, counting its guard pages,
+/// among every memory that is alive on this thread. Returns that memory
+/// and the offset of `addr` inside of it.
+#[doc(hidden)]
+pub fn find_memory(addr: *const u8) -> Option<(*const vm::LocalMemory, usize)> {
+    let addr = addr as usize;
+
+    MEMORIES.with(|memories| {
+        memories
+            .borrow()
+            .iter()
+            .find_map(|&(local_memory, mem_type)| {
+                let (base, bound) =
+                    unsafe { ((*local_memory).base as usize, (*local_memory).bound) };
+                let size = mem_type.bounds().map_or(bound, |bounds| bounds as usize)
+                    + mem_type.guard_size() as usize;
+
+                if base <= addr && addr < base + size {
+                    Some((local_memory, addr - base))
+                } else {
+                    None
+                }
+            })
+    })
+}
+
 pub struct Memory {
     desc: MemoryDescriptor,
     storage: Rc<RefCell<(MemoryStorage, Box<vm::LocalMemory>)>>,
@@ -34,17 +71,22 @@ impl Memory {
     /// # use wasmer_runtime_core::error::Result;
     /// # use wasmer_runtime_core::units::Pages;
     /// # fn create_memory() -> Result<()> {
-    /// let descriptor = MemoryDescriptor {
-    ///     minimum: Pages(10),
-    ///     maximum: None,
-    ///     shared: false,
-    /// };
+    /// let descriptor = MemoryDescriptor::new(Pages(10), None, false);
     ///
     /// let memory = Memory::new(descriptor)?;
     /// # Ok(())
     /// # }
     /// ```
     pub fn new(desc: MemoryDescriptor) -> Result<Self, CreationError> {
+        Self::new_pooled(desc, None)
+    }
+
+    /// Create a new `Memory`, taking its address space
+    /// from `pool` if it's a static memory.
+    pub(crate) fn new_pooled(
+        desc: MemoryDescriptor,
+        pool: Option<&MemoryPool>,
+    ) -> Result<Self, CreationError> {
         let mut vm_local_memory = Box::new(vm::LocalMemory {
             base: ptr::null_mut(),
             bound: 0,
@@ -56,11 +98,18 @@ impl Memory {
                 MemoryStorage::Dynamic(DynamicMemory::new(desc, &mut vm_local_memory)?)
             }
             MemoryType::Static => {
-                MemoryStorage::Static(StaticMemory::new(desc, &mut vm_local_memory)?)
+                MemoryStorage::Static(StaticMemory::new(desc, &mut vm_local_memory, pool)?)
             }
             MemoryType::SharedStatic => unimplemented!("shared memories are not yet implemented"),
         };
 
+        let local_memory: *const vm::LocalMemory = &*vm_local_memory;
+        MEMORIES.with(|memories| {
+            memories
+                .borrow_mut()
+                .push((local_memory, desc.memory_type()))
+        });
+
         Ok(Memory {
             desc,
             storage: Rc::new(RefCell::new((memory_storage, vm_local_memory))),
@@ -98,7 +147,6 @@ impl Memory {
     }
 
     pub fn read<T: ValueType>(&self, offset: u32) -> Result<T, ()> {
-        let offset = offset as usize;
         let borrow_ref = self.storage.borrow();
         let memory_storage = &borrow_ref.0;
 
@@ -108,15 +156,11 @@ impl Memory {
             MemoryStorage::SharedStatic(_) => panic!("cannot slice a shared memory"),
         };
 
-        if offset + mem::size_of::<T>() <= mem_slice.len() {
-            T::from_le(&mem_slice[offset..]).map_err(|_| ())
-        } else {
-            Err(())
-        }
+        let offset = checked_offset(offset, mem::size_of::<T>(), mem_slice.len())?;
+        T::from_le(&mem_slice[offset..]).map_err(|_| ())
     }
 
     pub fn write<T: ValueType>(&self, offset: u32, value: T) -> Result<(), ()> {
-        let offset = offset as usize;
         let mut borrow_ref = self.storage.borrow_mut();
         let memory_storage = &mut borrow_ref.0;
 
@@ -126,16 +170,12 @@ impl Memory {
             MemoryStorage::SharedStatic(_) => panic!("cannot slice a shared memory"),
         };
 
-        if offset + mem::size_of::<T>() <= mem_slice.len() {
-            value.into_le(&mut mem_slice[offset..]);
-            Ok(())
-        } else {
-            Err(())
-        }
+        let offset = checked_offset(offset, mem::size_of::<T>(), mem_slice.len())?;
+        value.into_le(&mut mem_slice[offset..]);
+        Ok(())
     }
 
     pub fn read_many<T: ValueType>(&self, offset: u32, count: usize) -> Result<Vec<T>, ()> {
-        let offset = offset as usize;
         let borrow_ref = self.storage.borrow();
         let memory_storage = &borrow_ref.0;
 
@@ -145,24 +185,20 @@ impl Memory {
             MemoryStorage::SharedStatic(_) => panic!("cannot slice a shared memory"),
         };
 
-        let bytes_size = count * mem::size_of::<T>();
-
-        if offset + bytes_size <= mem_slice.len() {
-            let buffer = &mem_slice[offset..offset + bytes_size];
-            let value_type_buffer = unsafe {
-                slice::from_raw_parts(
-                    buffer.as_ptr() as *const T,
-                    buffer.len() / mem::size_of::<T>(),
-                )
-            };
-            Ok(value_type_buffer.to_vec())
-        } else {
-            Err(())
-        }
+        let bytes_size = count.checked_mul(mem::size_of::<T>()).ok_or(())?;
+        let offset = checked_offset(offset, bytes_size, mem_slice.len())?;
+
+        let buffer = &mem_slice[offset..offset + bytes_size];
+        let value_type_buffer = unsafe {
+            slice::from_raw_parts(
+                buffer.as_ptr() as *const T,
+                buffer.len() / mem::size_of::<T>(),
+            )
+        };
+        Ok(value_type_buffer.to_vec())
     }
 
     pub fn write_many<T: ValueType>(&self, offset: u32, values: &[T]) -> Result<(), ()> {
-        let offset = offset as usize;
         let mut borrow_ref = self.storage.borrow_mut();
         let memory_storage = &mut borrow_ref.0;
 
@@ -174,14 +210,11 @@ impl Memory {
 
         let bytes_size = values.len() * mem::size_of::<T>();
 
-        if offset + bytes_size <= mem_slice.len() {
-            let u8_buffer =
-                unsafe { slice::from_raw_parts(values.as_ptr() as *const u8, bytes_size) };
-            mem_slice[offset..offset + bytes_size].copy_from_slice(u8_buffer);
-            Ok(())
-        } else {
-            Err(())
-        }
+        let offset = checked_offset(offset, bytes_size, mem_slice.len())?;
+
+        let u8_buffer = unsafe { slice::from_raw_parts(values.as_ptr() as *const u8, bytes_size) };
+        mem_slice[offset..offset + bytes_size].copy_from_slice(u8_buffer);
+        Ok(())
     }
 
     pub fn direct_access<T: ValueType, F, R>(&self, f: F) -> R
@@ -230,13 +263,47 @@ impl Memory {
         f(t_buffer)
     }
 
+    /// Shrink this memory back to its minimum size and clear
+    /// its contents, keeping the existing mapping.
+    pub(crate) fn reset(&self) -> Result<(), CreationError> {
+        match &mut *self.storage.borrow_mut() {
+            (MemoryStorage::Dynamic(ref mut dynamic_memory), ref mut local) => {
+                dynamic_memory.reset(self.desc.minimum, local)
+            }
+            (MemoryStorage::Static(ref mut static_memory), ref mut local) => {
+                static_memory.reset(self.desc.minimum, local)
+            }
+            (MemoryStorage::SharedStatic(_), _) => Err(CreationError::UnableToCreateMemory),
+        }
+    }
+
+    /// Replace the initial contents of this memory with a
+    /// copy-on-write mapping of `image`.
+    pub(crate) fn map_image(&self, image: &sys::MemoryImage) -> Result<(), CreationError> {
+        match &mut self.storage.borrow_mut().0 {
+            MemoryStorage::Dynamic(ref mut dynamic_memory) => dynamic_memory.map_image(image),
+            MemoryStorage::Static(ref mut static_memory) => static_memory.map_image(image),
+            MemoryStorage::SharedStatic(_) => Err(CreationError::UnableToCreateMemory),
+        }
+    }
+
     pub(crate) fn vm_local_memory(&mut self) -> *mut vm::LocalMemory {
         &mut *self.storage.borrow_mut().1
     }
 }
 
+/// Returns `offset` as a `usize` if `size` bytes starting at
+/// `offset` fit inside a memory that is `len` bytes long.
+fn checked_offset(offset: u32, size: usize, len: usize) -> Result<usize, ()> {
+    let offset = offset as usize;
+    match offset.checked_add(size) {
+        Some(end) if end <= len => Ok(offset),
+        _ => Err(()),
+    }
+}
+
 impl IsExport for Memory {
-    fn to_export(&mut self) -> Export {
+    fn to_export(&self) -> Export {
         Export::Memory(self.clone())
     }
 }
@@ -250,6 +317,19 @@ impl Clone for Memory {
     }
 }
 
+impl Drop for Memory {
+    fn drop(&mut self) {
+        if Rc::strong_count(&self.storage) == 1 {
+            let local_memory: *const vm::LocalMemory = &*self.storage.borrow().1;
+            MEMORIES.with(|memories| {
+                memories
+                    .borrow_mut()
+                    .retain(|&(registered, _)| registered != local_memory)
+            });
+        }
+    }
+}
+
 pub enum MemoryStorage {
     Dynamic(Box<DynamicMemory>),
     Static(Box<StaticMemory>),
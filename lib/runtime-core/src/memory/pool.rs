@@ -0,0 +1,153 @@
+use crate::{
+    error::CreationError,
+    memory::static_::{SAFE_STATIC_GUARD_SIZE, SAFE_STATIC_HEAP_SIZE},
+    sys,
+    types::AnyRef,
+    vm,
+};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// A pool of address space reservations for static memories,
+/// and of element buffers for tables.
+///
+/// Creating a static memory normally reserves 6 GiB of virtual
+/// memory with a fresh `mmap` and releases it again when the
+/// memory is dropped. A `MemoryPool` makes all its reservations
+/// up front, hands them out as static memories are created, and
+/// takes them back, emptied, when those memories are dropped.
+///
+/// Tables don't reserve any address space, so their buffers aren't
+/// allocated up front. Instead, the element buffer of a dropped table
+/// is cleared and kept in the pool, and the next table created with
+/// the pool reuses it instead of allocating a new one.
+///
+/// When the pool runs out of slots, static memories and tables
+/// are allocated the usual way.
+///
+/// `MemoryPool` can be cheaply cloned and shared between threads.
+/// Every clone refers to the same set of slots.
+///
+/// # Usage:
+/// ```
+/// # use wasmer_runtime_core::error::Result;
+/// # use wasmer_runtime_core::Module;
+/// # use wasmer_runtime_core::imports;
+/// # use wasmer_runtime_core::memory::MemoryPool;
+/// # fn instantiate_many(module: &Module) -> Result<()> {
+/// let pool = MemoryPool::new(16)?;
+///
+/// let mut pre_instance = module.prelink(&imports! {})?;
+/// pre_instance.set_memory_pool(pool);
+///
+/// for _ in 0..1000 {
+///     let instance = pre_instance.instantiate()?;
+///     // ...
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MemoryPool {
+    slots: Arc<Mutex<Vec<sys::Memory>>>,
+    anyfunc_backings: Arc<Mutex<Vec<TableBacking<vm::Anyfunc>>>>,
+    anyref_backings: Arc<Mutex<Vec<TableBacking<AnyRef>>>>,
+    count: usize,
+}
+
+/// The element buffer of a dropped table.
+///
+/// Only empty buffers are kept in the pool, so they don't hold
+/// on to any pointers and can be moved between threads.
+pub(crate) struct TableBacking<T>(Vec<T>);
+
+unsafe impl<T> Send for TableBacking<T> {}
+
+/// The types of table elements whose buffers are pooled.
+pub(crate) trait PooledElement: Sized {
+    fn backings(pool: &MemoryPool) -> &Mutex<Vec<TableBacking<Self>>>;
+}
+
+impl PooledElement for vm::Anyfunc {
+    fn backings(pool: &MemoryPool) -> &Mutex<Vec<TableBacking<Self>>> {
+        &pool.anyfunc_backings
+    }
+}
+
+impl PooledElement for AnyRef {
+    fn backings(pool: &MemoryPool) -> &Mutex<Vec<TableBacking<Self>>> {
+        &pool.anyref_backings
+    }
+}
+
+impl MemoryPool {
+    /// Reserve `count` slots, each large enough to hold a static memory.
+    ///
+    /// Up to `count` table buffers of each element type are kept
+    /// for reuse as well.
+    pub fn new(count: usize) -> Result<Self, CreationError> {
+        let slots = (0..count)
+            .map(|_| sys::Memory::with_size(SAFE_STATIC_HEAP_SIZE + SAFE_STATIC_GUARD_SIZE))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| CreationError::UnableToCreateMemory)?;
+
+        Ok(Self {
+            slots: Arc::new(Mutex::new(slots)),
+            anyfunc_backings: Arc::new(Mutex::new(Vec::new())),
+            anyref_backings: Arc::new(Mutex::new(Vec::new())),
+            count,
+        })
+    }
+
+    /// The number of slots that are not currently in use.
+    pub fn available(&self) -> usize {
+        self.slots.lock().unwrap().len()
+    }
+
+    /// The number of table buffers that are ready to be reused.
+    pub fn available_tables(&self) -> usize {
+        self.anyfunc_backings.lock().unwrap().len() + self.anyref_backings.lock().unwrap().len()
+    }
+
+    pub(crate) fn take(&self) -> Option<sys::Memory> {
+        self.slots.lock().unwrap().pop()
+    }
+
+    /// Empty out the first `len` bytes of `memory`,
+    /// which are all that has been used, and return it to the pool.
+    pub(crate) fn give_back(&self, mut memory: sys::Memory, len: usize) {
+        // If the slot can't be reset, it's simply unmapped.
+        if unsafe { memory.reset(len) }.is_ok() {
+            self.slots.lock().unwrap().push(memory);
+        }
+    }
+
+    pub(crate) fn take_table_backing<T: PooledElement>(&self) -> Option<Vec<T>> {
+        T::backings(self)
+            .lock()
+            .unwrap()
+            .pop()
+            .map(|backing| backing.0)
+    }
+
+    /// Clear the element buffer of a dropped table and
+    /// keep it for reuse, unless the pool is already full.
+    pub(crate) fn give_back_table_backing<T: PooledElement>(&self, mut backing: Vec<T>) {
+        backing.clear();
+        let mut backings = T::backings(self).lock().unwrap();
+        if backings.len() < self.count {
+            backings.push(TableBacking(backing));
+        }
+    }
+}
+
+impl fmt::Debug for MemoryPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryPool")
+            .field("available", &self.available())
+            .field("available_tables", &self.available_tables())
+            .finish()
+    }
+}
@@ -1,11 +1,15 @@
 use crate::{
     error::CreationError,
-    memory::static_::{SAFE_STATIC_GUARD_SIZE, SAFE_STATIC_HEAP_SIZE},
+    memory::{
+        static_::{SAFE_STATIC_GUARD_SIZE, SAFE_STATIC_HEAP_SIZE},
+        MemoryPool,
+    },
     sys,
     types::MemoryDescriptor,
     units::Pages,
     vm,
 };
+use std::mem;
 
 /// This is an internal-only api.
 ///
@@ -18,35 +22,47 @@ use crate::{
 /// Static memories take a relatively long time to create, so if memories are short-lived,
 /// it's recommended that a dynamic memory is used. There is currently no user-facing api that
 /// allows them to select the type of memory used however.
+///
+/// If a static memory is created with a [`MemoryPool`] that has a free slot,
+/// it uses that slot instead and hands it back to the pool when dropped.
+///
+/// [`MemoryPool`]: struct.MemoryPool.html
 pub struct StaticMemory {
     memory: sys::Memory,
     current: Pages,
     max: Option<Pages>,
+    pool: Option<MemoryPool>,
 }
 
 impl StaticMemory {
     pub(in crate::memory) fn new(
         desc: MemoryDescriptor,
         local: &mut vm::LocalMemory,
+        pool: Option<&MemoryPool>,
     ) -> Result<Box<Self>, CreationError> {
-        let memory = {
-            let mut memory = sys::Memory::with_size(SAFE_STATIC_HEAP_SIZE + SAFE_STATIC_GUARD_SIZE)
-                .map_err(|_| CreationError::UnableToCreateMemory)?;
-            if desc.minimum != Pages(0) {
-                unsafe {
-                    memory
-                        .protect(0..desc.minimum.bytes().0, sys::Protect::ReadWrite)
-                        .map_err(|_| CreationError::UnableToCreateMemory)?;
-                }
-            }
-
-            memory
+        // Take a slot from the pool if there is one available.
+        let (mut memory, pool) = match pool.and_then(|pool| Some((pool.take()?, pool.clone()))) {
+            Some((slot, pool)) => (slot, Some(pool)),
+            None => (
+                sys::Memory::with_size(SAFE_STATIC_HEAP_SIZE + SAFE_STATIC_GUARD_SIZE)
+                    .map_err(|_| CreationError::UnableToCreateMemory)?,
+                None,
+            ),
         };
 
+        if desc.minimum != Pages(0) {
+            unsafe {
+                memory
+                    .protect(0..desc.minimum.bytes().0, sys::Protect::ReadWrite)
+                    .map_err(|_| CreationError::UnableToCreateMemory)?;
+            }
+        }
+
         let mut storage = Box::new(StaticMemory {
             memory,
             current: desc.minimum,
             max: desc.maximum,
+            pool,
         });
         let storage_ptr: *mut StaticMemory = &mut *storage;
 
@@ -92,6 +108,40 @@ impl StaticMemory {
         Some(old_pages)
     }
 
+    pub(in crate::memory) fn map_image(
+        &mut self,
+        image: &sys::MemoryImage,
+    ) -> Result<(), CreationError> {
+        assert!(image.size() <= self.current.bytes().0);
+        unsafe {
+            self.memory
+                .map_image(image)
+                .map_err(|_| CreationError::UnableToCreateMemory)
+        }
+    }
+
+    pub(in crate::memory) fn reset(
+        &mut self,
+        minimum: Pages,
+        local: &mut vm::LocalMemory,
+    ) -> Result<(), CreationError> {
+        unsafe {
+            self.memory
+                .reset(self.current.bytes().0)
+                .map_err(|_| CreationError::UnableToCreateMemory)?;
+            if minimum != Pages(0) {
+                self.memory
+                    .protect(0..minimum.bytes().0, sys::Protect::ReadWrite)
+                    .map_err(|_| CreationError::UnableToCreateMemory)?;
+            }
+        }
+
+        local.bound = minimum.bytes().0;
+        self.current = minimum;
+
+        Ok(())
+    }
+
     pub fn as_slice(&self) -> &[u8] {
         unsafe { &self.memory.as_slice()[0..self.current.bytes().0] }
     }
@@ -100,3 +150,12 @@ impl StaticMemory {
         unsafe { &mut self.memory.as_slice_mut()[0..self.current.bytes().0] }
     }
 }
+
+impl Drop for StaticMemory {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            let memory = mem::replace(&mut self.memory, sys::Memory::default());
+            pool.give_back(memory, self.current.bytes().0);
+        }
+    }
+}
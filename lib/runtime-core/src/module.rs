@@ -2,7 +2,9 @@ use crate::{
     backend::{FuncResolver, ProtectedCaller},
     error::Result,
     import::ImportObject,
+    instance::PreInstance,
     sig_registry::SigRegistry,
+    snapshot::InstanceSnapshot,
     structures::Map,
     types::{
         FuncIndex, GlobalDescriptor, GlobalIndex, GlobalInit, ImportedFuncIndex,
@@ -80,7 +82,63 @@ impl Module {
     /// # }
     /// ```
     pub fn instantiate(&self, import_object: ImportObject) -> Result<Instance> {
-        Instance::new(Arc::clone(&self.0), Box::new(import_object))
+        self.prelink(&import_object)?.instantiate()
+    }
+
+    /// Resolve the imports of this module against the provided [`ImportObject`]
+    /// without instantiating it.
+    ///
+    /// The returned [`PreInstance`] can be instantiated any number of times
+    /// without having to look up and type-check the imports again.
+    ///
+    /// [`ImportObject`]: struct.ImportObject.html
+    /// [`PreInstance`]: struct.PreInstance.html
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::Module;
+    /// # use wasmer_runtime_core::imports;
+    /// # fn prelink(module: &Module) -> Result<()> {
+    /// let import_object = imports! {
+    ///     // ...
+    /// };
+    /// let pre_instance = module.prelink(&import_object)?;
+    /// let instance = pre_instance.instantiate()?;
+    /// // ...
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prelink(&self, import_object: &ImportObject) -> Result<PreInstance> {
+        PreInstance::new(Arc::clone(&self.0), import_object)
+    }
+
+    /// Instantiate this module with the provided [`ImportObject`],
+    /// starting from the state captured in `snapshot` instead of
+    /// running the initializers and the `start` function.
+    ///
+    /// [`ImportObject`]: struct.ImportObject.html
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::Module;
+    /// # use wasmer_runtime_core::snapshot::InstanceSnapshot;
+    /// # use wasmer_runtime_core::imports;
+    /// # fn restore(module: &Module, bytes: &[u8]) -> Result<()> {
+    /// let snapshot = InstanceSnapshot::from_bytes(bytes)?;
+    /// let instance = module.instantiate_from_snapshot(&snapshot, imports! {})?;
+    /// // ...
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn instantiate_from_snapshot(
+        &self,
+        snapshot: &InstanceSnapshot,
+        import_object: ImportObject,
+    ) -> Result<Instance> {
+        self.prelink(&import_object)?
+            .instantiate_from_snapshot(snapshot)
     }
 }
 
@@ -0,0 +1,346 @@
+//! Reads the sections of a wasm binary into a `ModuleInner`, for
+//! backends that translate function bodies straight from `wasmparser`.
+
+use crate::{
+    backend::{FuncResolver, ProtectedCaller, SigRegistry, Token},
+    error::{CompileError, CompileResult, RuntimeResult},
+    module::{DataInitializer, ExportIndex, ImportName, ModuleInner, TableInitializer},
+    structures::{Map, TypedIndex},
+    types::{
+        ElementType, FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, GlobalInit, Initializer,
+        LocalFuncIndex, MemoryDescriptor, MemoryIndex, SigIndex, TableDescriptor, TableIndex, Type,
+        Value,
+    },
+    units::Pages,
+    vm::{self, ImportBacking},
+    Features,
+};
+use hashbrown::HashMap;
+use std::ptr::NonNull;
+use wasmparser::{
+    ExternalKind, GlobalType, ImportSectionEntryType, MemoryType, Operator, Parser, ParserState,
+    TableType, Type as WpType, WasmDecoder,
+};
+
+/// Translates the function bodies of a module as they're read.
+pub trait FuncTranslator {
+    /// Starts the body of the local function `local_func_index`.
+    fn begin_body(
+        &mut self,
+        module: &ModuleInner,
+        local_func_index: LocalFuncIndex,
+    ) -> CompileResult<()>;
+
+    /// Declares the locals of the current body, after its parameters.
+    fn declare_locals(&mut self, locals: &[(u32, WpType)]) -> CompileResult<()>;
+
+    /// Translates an operator of the current body. `signatures` maps
+    /// every type index of the module to its signature.
+    fn translate(
+        &mut self,
+        module: &ModuleInner,
+        signatures: &[SigIndex],
+        op: &Operator,
+    ) -> CompileResult<()>;
+
+    /// Finishes the current body.
+    fn end_body(&mut self) -> CompileResult<()>;
+}
+
+struct Placeholder;
+
+impl FuncResolver for Placeholder {
+    fn get(
+        &self,
+        _module: &ModuleInner,
+        _local_func_index: LocalFuncIndex,
+    ) -> Option<NonNull<vm::Func>> {
+        None
+    }
+}
+
+impl ProtectedCaller for Placeholder {
+    fn call(
+        &self,
+        _module: &ModuleInner,
+        _func_index: FuncIndex,
+        _params: &[Value],
+        _import_backing: &ImportBacking,
+        _vmctx: *mut vm::Ctx,
+        _: Token,
+    ) -> RuntimeResult<Vec<Value>> {
+        Ok(vec![])
+    }
+}
+
+/// Parses a validated wasm binary and hands every function body in
+/// it to `translator`. The `func_resolver` and `protected_caller` of
+/// the returned module are placeholders that the backend must replace.
+pub fn read_module(
+    wasm: &[u8],
+    features: &Features,
+    translator: &mut dyn FuncTranslator,
+) -> CompileResult<ModuleInner> {
+    let mut module = ModuleInner {
+        func_resolver: Box::new(Placeholder),
+        protected_caller: Box::new(Placeholder),
+
+        memories: Map::new(),
+        globals: Map::new(),
+        tables: Map::new(),
+
+        imported_functions: Map::new(),
+        imported_memories: Map::new(),
+        imported_tables: Map::new(),
+        imported_globals: Map::new(),
+
+        exports: HashMap::new(),
+
+        data_initializers: Vec::new(),
+        elem_initializers: Vec::new(),
+
+        start_func: None,
+
+        func_assoc: Map::new(),
+        sig_registry: SigRegistry,
+    };
+
+    // Every type index in the module, mapped to its signature.
+    let mut signatures: Vec<SigIndex> = Vec::new();
+    let mut num_bodies = 0;
+
+    // The state of the global, element or data segment being parsed.
+    let mut global_desc: Option<GlobalDescriptor> = None;
+    let mut init: Option<Initializer> = None;
+    let mut segment_index = 0;
+    let mut elements: Vec<FuncIndex> = Vec::new();
+    let mut data: Vec<u8> = Vec::new();
+
+    let mut parser = Parser::new(wasm);
+    loop {
+        match *parser.read() {
+            ParserState::EndWasm => break,
+            ParserState::Error(err) => Err(CompileError::InternalError {
+                msg: err.message.to_string(),
+            })?,
+
+            ParserState::TypeSectionEntry(ref func_type) => {
+                let params = convert_types(&func_type.params)?;
+                let returns = convert_types(&func_type.returns)?;
+                let sig_index = module
+                    .sig_registry
+                    .lookup_sig_index(FuncSig::new(params, returns));
+                signatures.push(sig_index);
+            }
+            ParserState::ImportSectionEntry {
+                module: namespace,
+                field,
+                ref ty,
+            } => {
+                let import_name = ImportName {
+                    namespace: namespace.to_string(),
+                    name: field.to_string(),
+                };
+
+                match *ty {
+                    ImportSectionEntryType::Function(sig) => {
+                        module.func_assoc.push(signatures[sig as usize]);
+                        module.imported_functions.push(import_name);
+                    }
+                    ImportSectionEntryType::Table(table_type) => {
+                        let desc = table_descriptor(table_type)?;
+                        module.imported_tables.push((import_name, desc));
+                    }
+                    ImportSectionEntryType::Memory(memory_type) => {
+                        let desc = memory_descriptor(memory_type, features);
+                        module.imported_memories.push((import_name, desc));
+                    }
+                    ImportSectionEntryType::Global(global_type) => {
+                        let desc = global_descriptor(global_type)?;
+                        module.imported_globals.push((import_name, desc));
+                    }
+                }
+            }
+            ParserState::FunctionSectionEntry(sig) => {
+                module.func_assoc.push(signatures[sig as usize]);
+            }
+            ParserState::TableSectionEntry(table_type) => {
+                module.tables.push(table_descriptor(table_type)?);
+            }
+            ParserState::MemorySectionEntry(memory_type) => {
+                module
+                    .memories
+                    .push(memory_descriptor(memory_type, features));
+            }
+
+            ParserState::BeginGlobalSectionEntry(global_type) => {
+                global_desc = Some(global_descriptor(global_type)?);
+            }
+            ParserState::InitExpressionOperator(ref op) => {
+                init = Some(initializer(&module, op)?);
+            }
+            ParserState::EndGlobalSectionEntry => {
+                module.globals.push(GlobalInit {
+                    desc: global_desc.take().unwrap(),
+                    init: take_initializer(&mut init)?,
+                });
+            }
+
+            ParserState::ExportSectionEntry {
+                field,
+                ref kind,
+                index,
+            } => {
+                let index = index as usize;
+                let export_index = match *kind {
+                    ExternalKind::Function => ExportIndex::Func(FuncIndex::new(index)),
+                    ExternalKind::Table => ExportIndex::Table(TableIndex::new(index)),
+                    ExternalKind::Memory => ExportIndex::Memory(MemoryIndex::new(index)),
+                    ExternalKind::Global => ExportIndex::Global(GlobalIndex::new(index)),
+                };
+                module.exports.insert(field.to_string(), export_index);
+            }
+            ParserState::StartSectionEntry(func_index) => {
+                module.start_func = Some(FuncIndex::new(func_index as usize));
+            }
+
+            ParserState::BeginElementSectionEntry(table_index) => {
+                segment_index = table_index as usize;
+            }
+            ParserState::ElementSectionEntryBody(ref func_indices) => {
+                elements = func_indices
+                    .iter()
+                    .map(|&func_index| FuncIndex::new(func_index as usize))
+                    .collect();
+            }
+            ParserState::EndElementSectionEntry => {
+                module.elem_initializers.push(TableInitializer {
+                    table_index: TableIndex::new(segment_index),
+                    base: take_initializer(&mut init)?,
+                    elements: elements.drain(..).collect(),
+                });
+            }
+
+            ParserState::BeginDataSectionEntry(memory_index) => {
+                segment_index = memory_index as usize;
+            }
+            ParserState::DataSectionEntryBodyChunk(chunk) => {
+                data.extend_from_slice(chunk);
+            }
+            ParserState::EndDataSectionEntry => {
+                module.data_initializers.push(DataInitializer {
+                    memory_index: MemoryIndex::new(segment_index),
+                    base: take_initializer(&mut init)?,
+                    data: data.drain(..).collect(),
+                });
+            }
+
+            ParserState::BeginFunctionBody { .. } => {
+                translator.begin_body(&module, LocalFuncIndex::new(num_bodies))?;
+            }
+            ParserState::FunctionBodyLocals { ref locals } => {
+                for &(_, ty) in locals.iter() {
+                    convert_type(ty)?;
+                }
+                translator.declare_locals(locals)?;
+            }
+            ParserState::CodeOperator(ref op) => {
+                translator.translate(&module, &signatures, op)?;
+            }
+            ParserState::EndFunctionBody => {
+                translator.end_body()?;
+                num_bodies += 1;
+            }
+
+            _ => {}
+        }
+    }
+
+    Ok(module)
+}
+
+/// Converts a value type, rejecting the ones that
+/// don't fit in a 64-bit slot.
+fn convert_type(ty: WpType) -> CompileResult<Type> {
+    match ty {
+        WpType::I32 => Ok(Type::I32),
+        WpType::I64 => Ok(Type::I64),
+        WpType::F32 => Ok(Type::F32),
+        WpType::F64 => Ok(Type::F64),
+        _ => Err(CompileError::InternalError {
+            msg: format!("{:?} values aren't supported by this backend", ty),
+        }
+        .into()),
+    }
+}
+
+fn convert_types(types: &[WpType]) -> CompileResult<Vec<Type>> {
+    types.iter().map(|&ty| convert_type(ty)).collect()
+}
+
+fn table_descriptor(table_type: TableType) -> CompileResult<TableDescriptor> {
+    let element = match table_type.element_type {
+        WpType::AnyFunc => ElementType::Anyfunc,
+        ty => Err(CompileError::InternalError {
+            msg: format!("tables of {:?} aren't supported by this backend", ty),
+        })?,
+    };
+
+    Ok(TableDescriptor {
+        element,
+        minimum: table_type.limits.initial,
+        maximum: table_type.limits.maximum,
+    })
+}
+
+fn memory_descriptor(memory_type: MemoryType, features: &Features) -> MemoryDescriptor {
+    MemoryDescriptor::new(
+        Pages(memory_type.limits.initial),
+        memory_type.limits.maximum.map(Pages),
+        memory_type.shared,
+    )
+    .with_bounds_checks(features.bounds_checks)
+    .with_reserved_maximum(features.deterministic)
+}
+
+fn global_descriptor(global_type: GlobalType) -> CompileResult<GlobalDescriptor> {
+    Ok(GlobalDescriptor {
+        mutable: global_type.mutable,
+        ty: convert_type(global_type.content_type)?,
+    })
+}
+
+/// Converts the operator of a constant expression.
+fn initializer(module: &ModuleInner, op: &Operator) -> CompileResult<Initializer> {
+    Ok(match *op {
+        Operator::I32Const { value } => Initializer::Const(Value::I32(value)),
+        Operator::I64Const { value } => Initializer::Const(Value::I64(value)),
+        Operator::F32Const { value } => {
+            Initializer::Const(Value::F32(f32::from_bits(value.bits())))
+        }
+        Operator::F64Const { value } => {
+            Initializer::Const(Value::F64(f64::from_bits(value.bits())))
+        }
+        Operator::GetGlobal { global_index } => {
+            let imported_global_index = GlobalIndex::new(global_index as usize)
+                .local_or_import(module)
+                .import()
+                .ok_or_else(|| CompileError::InternalError {
+                    msg: "constant expressions can only read imported globals".to_string(),
+                })?;
+            Initializer::GetGlobal(imported_global_index)
+        }
+        ref op => Err(CompileError::InternalError {
+            msg: format!("{:?} is not a constant expression", op),
+        })?,
+    })
+}
+
+fn take_initializer(init: &mut Option<Initializer>) -> CompileResult<Initializer> {
+    init.take().ok_or_else(|| {
+        CompileError::InternalError {
+            msg: "missing constant expression".to_string(),
+        }
+        .into()
+    })
+}
@@ -0,0 +1,159 @@
+//! Lets `perf` symbolize the code generated for wasm functions.
+//!
+//! When enabled, the backends that generate machine code append a
+//! line to `/tmp/perf-<pid>.map` for every function and trampoline
+//! that they place in memory, in the format `perf report` reads:
+//!
+//! ```text
+//! <start address in hex> <size in hex> <name>
+//! ```
+//!
+//! Functions are named after the `name` section of the module, then
+//! after their exports, and get `wasm-function[<index>]` otherwise.
+//!
+//! Code loaded from a shared object compiled ahead of time is
+//! recorded too, since the object only has symbols for the whole
+//! code, and not for each function.
+//!
+//! The same symbols let the built-in [`profiler`] name the functions
+//! it samples.
+//!
+//! [`profiler`]: ../profiler/index.html
+
+use crate::{
+    module::{ExportIndex, ModuleInner},
+    profiler,
+    structures::{Map, TypedIndex},
+    types::{FuncIndex, LocalFuncIndex},
+};
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use wasmparser::{NameEntry, Parser, ParserInput, ParserState, SectionCode, WasmDecoder};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Makes every module compiled from now on write its code to the perf map.
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Whether the backends should record the code they generate,
+/// for the perf map or for a running profiler.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst) || profiler::is_active()
+}
+
+/// The names to give to the functions of a module in the perf map.
+pub struct FuncNames {
+    names: Map<FuncIndex, String>,
+}
+
+impl FuncNames {
+    pub fn new(module: &ModuleInner, wasm: &[u8]) -> Self {
+        let num_funcs = module.func_assoc.len();
+        let mut names: Map<FuncIndex, Option<String>> = (0..num_funcs).map(|_| None).collect();
+
+        let mut parser = Parser::new(wasm);
+        loop {
+            match *parser.read() {
+                ParserState::NameSectionEntry(NameEntry::Function(ref namings)) => {
+                    for naming in namings.iter() {
+                        if let Some(name) = names.get_mut(FuncIndex::new(naming.index as usize)) {
+                            *name = Some(naming.name.to_string());
+                        }
+                    }
+                }
+                ParserState::BeginSection {
+                    code: SectionCode::Code,
+                    ..
+                }
+                | ParserState::BeginSection {
+                    code: SectionCode::Data,
+                    ..
+                } => {
+                    parser.push_input(ParserInput::SkipSection);
+                    continue;
+                }
+                // A malformed name section only costs us the names.
+                ParserState::EndWasm | ParserState::Error(_) => break,
+                _ => {}
+            }
+            parser.push_input(ParserInput::Default);
+        }
+
+        // Functions without a name in the name section are named after
+        // their exports. Exports are a `HashMap`, so pick the smallest
+        // name to stay the same from one run to the next.
+        let mut export_names: Map<FuncIndex, Option<&String>> =
+            (0..num_funcs).map(|_| None).collect();
+        for (export_name, export) in module.exports.iter() {
+            if let ExportIndex::Func(func_index) = export {
+                let name = &mut export_names[*func_index];
+                if name.map_or(true, |name| export_name < name) {
+                    *name = Some(export_name);
+                }
+            }
+        }
+
+        let names = names
+            .into_iter()
+            .map(|(func_index, name)| {
+                name.or_else(|| export_names[func_index].cloned())
+                    .unwrap_or_else(|| format!("wasm-function[{}]", func_index.index()))
+            })
+            .collect();
+
+        Self { names }
+    }
+
+    pub fn get(&self, func_index: FuncIndex) -> &str {
+        &self.names[func_index]
+    }
+
+    pub fn get_local(&self, module: &ModuleInner, local_func_index: LocalFuncIndex) -> &str {
+        self.get(local_func_index.convert_up(module))
+    }
+}
+
+/// Records the code at `base`, `len` bytes long, as `symbols`: the
+/// offset from `base` that each one starts at, and its name. Each
+/// symbol extends up to the next one.
+///
+/// Profiling must never break a program, so failing to write the
+/// map is ignored.
+pub fn record(base: *const u8, len: usize, mut symbols: Vec<(usize, String)>) {
+    symbols.sort_by_key(|&(offset, _)| offset);
+
+    let mut ranges = Vec::with_capacity(symbols.len());
+    for (i, (offset, name)) in symbols.iter().enumerate() {
+        let end = symbols.get(i + 1).map_or(len, |&(next, _)| next);
+        if end > *offset {
+            ranges.push((base as usize + offset, end - offset, &name[..]));
+        }
+    }
+
+    if profiler::is_active() {
+        profiler::register(&ranges);
+    }
+    if !ENABLED.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let mut lines = String::new();
+    for (address, size, name) in ranges {
+        lines.push_str(&format!("{:x} {:x} {}\n", address, size, name));
+    }
+
+    let path = format!("/tmp/perf-{}.map", process::id());
+    // A single write to a file opened for appending can't be
+    // interleaved with the lines of modules compiled on other threads.
+    let _ = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()));
+}
@@ -0,0 +1,385 @@
+//! A sampling profiler for guest code, for hosts where `perf`
+//! can't be installed.
+//!
+//! While a [`Profiler`] runs, a `SIGPROF` timer interrupts the
+//! program after every interval of CPU time and records the stack
+//! of the thread it lands on by following its frame pointers. The
+//! native addresses are mapped back to wasm functions when the
+//! profiler stops, with the same symbols that the backends write to
+//! the perf map, so only the code of modules compiled, or loaded from
+//! a shared object, while the profiler runs gets named. Samples that
+//! land anywhere else, like in the runtime or in host functions, are
+//! counted as `[native]`.
+//!
+//! Only x86_64 Linux is supported. Code run by the interpreter
+//! backend always shows up as `[native]`.
+//!
+//! [`Profiler`]: struct.Profiler.html
+
+use crate::sys::{Memory, Protect};
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::{
+    io::{self, Write},
+    mem, ptr, slice,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+/// The most frames recorded for a sample, counting from the leaf.
+const MAX_DEPTH: usize = 64;
+/// Each sample is its depth followed by its frames.
+const SAMPLE_WORDS: usize = MAX_DEPTH + 1;
+/// The samples taken after these many are dropped.
+const MAX_SAMPLES: usize = 1 << 16;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static BUFFER: AtomicPtr<usize> = AtomicPtr::new(ptr::null_mut());
+static NEXT_SAMPLE: AtomicUsize = AtomicUsize::new(0);
+static IN_HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    /// Every function compiled while profiling, as its start
+    /// and end addresses, and its name.
+    static ref CODE: Mutex<Vec<(usize, usize, String)>> = Mutex::new(Vec::new());
+}
+
+/// Whether a profiler is running, so the backends should
+/// register the code they generate.
+pub(crate) fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Registers `symbols`, each of them an address, a size and a name.
+pub(crate) fn register(symbols: &[(usize, usize, &str)]) {
+    let mut code = CODE.lock();
+    for &(address, size, name) in symbols {
+        code.push((address, address + size, name.to_string()));
+    }
+}
+
+/// Samples the stacks of guest code until it's stopped.
+///
+/// # Usage:
+/// ```no_run
+/// # use std::{io, time::Duration};
+/// # use wasmer_runtime_core::profiler::Profiler;
+/// let profiler = Profiler::start(Duration::from_millis(10))?;
+/// // compile and run some modules...
+/// let profile = profiler.stop();
+/// profile.write_folded(&mut io::stdout()).map_err(|e| e.to_string())?;
+/// # Ok::<(), String>(())
+/// ```
+pub struct Profiler {
+    /// `MAX_SAMPLES` samples of `SAMPLE_WORDS` words.
+    buffer: Memory,
+    timer: Option<imp::Timer>,
+}
+
+impl Profiler {
+    /// Starts sampling after every `interval` of CPU time used
+    /// by the process. Only one profiler can run at a time.
+    pub fn start(interval: Duration) -> Result<Self, String> {
+        if interval < Duration::from_micros(1) {
+            return Err("the interval must be at least a microsecond".to_string());
+        }
+        if ACTIVE
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err("a profiler is already running".to_string());
+        }
+
+        // The code registered for an earlier profiler may be gone by now.
+        CODE.lock().clear();
+
+        // The buffer is mapped rather than allocated, so that only
+        // the pages that samples get written to take any memory.
+        let buffer = match Self::map_buffer() {
+            Ok(buffer) => buffer,
+            Err(msg) => {
+                ACTIVE.store(false, Ordering::SeqCst);
+                return Err(msg);
+            }
+        };
+        NEXT_SAMPLE.store(0, Ordering::SeqCst);
+        BUFFER.store(buffer.as_ptr() as *mut usize, Ordering::SeqCst);
+
+        match unsafe { imp::Timer::start(interval) } {
+            Ok(timer) => Ok(Self {
+                buffer,
+                timer: Some(timer),
+            }),
+            Err(msg) => {
+                BUFFER.store(ptr::null_mut(), Ordering::SeqCst);
+                ACTIVE.store(false, Ordering::SeqCst);
+                Err(msg)
+            }
+        }
+    }
+
+    fn map_buffer() -> Result<Memory, String> {
+        let size = MAX_SAMPLES * SAMPLE_WORDS * mem::size_of::<usize>();
+        let mut buffer = Memory::with_size(size)?;
+        unsafe {
+            buffer.protect(.., Protect::ReadWrite)?;
+        }
+        Ok(buffer)
+    }
+
+    /// Stops sampling, and maps the samples to wasm functions.
+    pub fn stop(mut self) -> Profile {
+        self.disarm();
+
+        let mut code = CODE.lock().clone();
+        code.sort_by_key(|&(start, _, _)| start);
+
+        let num_samples = NEXT_SAMPLE.load(Ordering::SeqCst);
+        let buffer = unsafe {
+            slice::from_raw_parts(
+                self.buffer.as_ptr() as *const usize,
+                MAX_SAMPLES * SAMPLE_WORDS,
+            )
+        };
+        let mut stacks = HashMap::new();
+        for sample in buffer
+            .chunks(SAMPLE_WORDS)
+            .take(num_samples.min(MAX_SAMPLES))
+        {
+            let depth = sample[0];
+            let stack = symbolize(&code, &sample[1..=depth]);
+            *stacks.entry(stack).or_insert(0) += 1;
+        }
+
+        Profile {
+            stacks,
+            dropped: num_samples.saturating_sub(MAX_SAMPLES),
+        }
+    }
+
+    fn disarm(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            unsafe { timer.stop() };
+
+            // A signal may still be handled on another thread.
+            BUFFER.store(ptr::null_mut(), Ordering::SeqCst);
+            while IN_HANDLER.load(Ordering::SeqCst) != 0 {
+                thread::yield_now();
+            }
+
+            ACTIVE.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        self.disarm();
+    }
+}
+
+/// Maps the frames of a sample, leaf first, to the names of their
+/// functions, root first. The stack stops at the first frame that
+/// isn't in guest code, since the frame pointers of native code
+/// can't be trusted.
+fn symbolize(code: &[(usize, usize, String)], frames: &[usize]) -> Vec<String> {
+    let mut stack = Vec::new();
+    for (i, &address) in frames.iter().enumerate() {
+        // Return addresses point after the call.
+        let address = if i == 0 { address } else { address - 1 };
+        let index = match code.binary_search_by_key(&address, |&(start, _, _)| start) {
+            Ok(index) => index,
+            Err(0) => break,
+            Err(index) => index - 1,
+        };
+        let (_, end, ref name) = code[index];
+        if address >= end {
+            break;
+        }
+        stack.push(name.clone());
+    }
+    if stack.is_empty() {
+        stack.push("[native]".to_string());
+    }
+    stack.reverse();
+    stack
+}
+
+/// The stacks sampled by a `Profiler`.
+pub struct Profile {
+    /// The names of the functions in each stack, root first,
+    /// and how many times it was sampled.
+    stacks: HashMap<Vec<String>, usize>,
+    dropped: usize,
+}
+
+impl Profile {
+    /// How many samples were recorded.
+    pub fn num_samples(&self) -> usize {
+        self.stacks.values().sum()
+    }
+
+    /// How many samples were dropped because the profiler was full.
+    pub fn num_dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Iterates over every stack that was sampled, root first,
+    /// with how many times it was.
+    pub fn stacks(&self) -> impl Iterator<Item = (&[String], usize)> {
+        self.stacks
+            .iter()
+            .map(|(stack, &count)| (&stack[..], count))
+    }
+
+    /// Writes the stacks in the "folded" format of `flamegraph.pl`
+    /// and `inferno`: one line per stack, with the names of its
+    /// functions separated by `;` and followed by its count.
+    pub fn write_folded<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut lines: Vec<_> = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| format!("{} {}\n", stack.join(";"), count))
+            .collect();
+        lines.sort();
+        for line in lines {
+            out.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod imp {
+    use super::{BUFFER, IN_HANDLER, MAX_SAMPLES, NEXT_SAMPLE, SAMPLE_WORDS};
+    use nix::libc::{self, c_int, c_void, siginfo_t, ucontext_t, REG_RBP, REG_RIP};
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, SIGPROF};
+    use std::{mem, ptr, slice, sync::atomic::Ordering, time::Duration};
+
+    pub struct Timer {
+        previous_action: SigAction,
+    }
+
+    impl Timer {
+        pub unsafe fn start(interval: Duration) -> Result<Self, String> {
+            let action = SigAction::new(
+                SigHandler::SigAction(sample),
+                SaFlags::SA_RESTART,
+                SigSet::empty(),
+            );
+            let previous_action = sigaction(SIGPROF, &action).map_err(|e| e.to_string())?;
+
+            let interval = libc::timeval {
+                tv_sec: interval.as_secs() as _,
+                tv_usec: interval.subsec_micros() as _,
+            };
+            if set_timer(interval).is_err() {
+                let _ = sigaction(SIGPROF, &previous_action);
+                return Err(format!(
+                    "can't start the profiling timer: {}",
+                    ::std::io::Error::last_os_error()
+                ));
+            }
+
+            Ok(Self { previous_action })
+        }
+
+        pub unsafe fn stop(self) {
+            let _ = set_timer(libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            });
+            let _ = sigaction(SIGPROF, &self.previous_action);
+        }
+    }
+
+    unsafe fn set_timer(interval: libc::timeval) -> Result<(), ()> {
+        let timer = libc::itimerval {
+            it_interval: interval,
+            it_value: interval,
+        };
+        if libc::setitimer(libc::ITIMER_PROF, &timer, ptr::null_mut()) == 0 {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    extern "C" fn sample(_: c_int, _: *mut siginfo_t, ucontext: *mut c_void) {
+        IN_HANDLER.fetch_add(1, Ordering::SeqCst);
+
+        let buffer = BUFFER.load(Ordering::SeqCst);
+        if !buffer.is_null() {
+            let index = NEXT_SAMPLE.fetch_add(1, Ordering::SeqCst);
+            if index < MAX_SAMPLES {
+                unsafe {
+                    let sample =
+                        slice::from_raw_parts_mut(buffer.add(index * SAMPLE_WORDS), SAMPLE_WORDS);
+                    let gregs = &(*(ucontext as *const ucontext_t)).uc_mcontext.gregs;
+                    sample[0] = walk(
+                        gregs[REG_RIP as usize] as usize,
+                        gregs[REG_RBP as usize] as usize,
+                        &mut sample[1..],
+                    );
+                }
+            }
+        }
+
+        IN_HANDLER.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Follows the frame pointers from the interrupted instruction,
+    /// filling `frames` with the return addresses, and returns how
+    /// many there are.
+    ///
+    /// The frame pointer of native code can be anything, so the
+    /// stack is read with `process_vm_readv`, which fails instead of
+    /// faulting on an unmapped address.
+    unsafe fn walk(ip: usize, mut fp: usize, frames: &mut [usize]) -> usize {
+        frames[0] = ip;
+        let mut depth = 1;
+        while depth < frames.len() && fp % mem::size_of::<usize>() == 0 {
+            // The saved frame pointer, then the return address.
+            let mut frame = [0usize; 2];
+            let local = libc::iovec {
+                iov_base: frame.as_mut_ptr() as *mut c_void,
+                iov_len: mem::size_of_val(&frame),
+            };
+            let remote = libc::iovec {
+                iov_base: fp as *mut c_void,
+                iov_len: mem::size_of_val(&frame),
+            };
+            let read = libc::process_vm_readv(libc::getpid(), &local, 1, &remote, 1, 0);
+            if read != mem::size_of_val(&frame) as isize {
+                break;
+            }
+
+            let (next_fp, return_address) = (frame[0], frame[1]);
+            // The stack grows down, so callers have higher frames.
+            if return_address == 0 || next_fp <= fp {
+                break;
+            }
+            frames[depth] = return_address;
+            depth += 1;
+            fp = next_fp;
+        }
+        depth
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+mod imp {
+    use std::time::Duration;
+
+    pub struct Timer;
+
+    impl Timer {
+        pub unsafe fn start(_interval: Duration) -> Result<Self, String> {
+            Err("the profiler is only supported on x86_64 Linux".to_string())
+        }
+
+        pub unsafe fn stop(self) {}
+    }
+}
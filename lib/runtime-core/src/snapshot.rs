@@ -0,0 +1,433 @@
+use crate::{
+    backing::{func_anyfunc, ImportBacking, LocalBacking},
+    error::SnapshotError,
+    global::Global,
+    memory::Memory,
+    module::ModuleInner,
+    structures::{SliceMap, TypedIndex},
+    table::Table,
+    types::{
+        ElementType, FuncIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, Type, Value,
+    },
+    units::{Bytes, Pages},
+    vm,
+};
+use hashbrown::HashMap;
+
+const MAGIC: &[u8] = b"\0wasmer-snapshot";
+const VERSION: u32 = 1;
+
+/// The state of an [`Instance`] at some point in time.
+///
+/// A snapshot holds the contents of the instance's local memories,
+/// the values of its mutable globals, and the elements of its local
+/// tables. Imported memories, tables, and globals belong to whoever
+/// provided them, so they are not part of the snapshot. Neither are
+/// references to host values, so an instance whose globals or anyref
+/// tables hold any can't be captured.
+///
+/// Snapshots are taken with [`Instance::snapshot`] and restored
+/// with [`Module::instantiate_from_snapshot`]. They can be converted
+/// to and from bytes to be stored or sent elsewhere.
+///
+/// [`Instance`]: ../instance/struct.Instance.html
+/// [`Instance::snapshot`]: ../instance/struct.Instance.html#method.snapshot
+/// [`Module::instantiate_from_snapshot`]: ../module/struct.Module.html#method.instantiate_from_snapshot
+#[derive(Debug, Clone)]
+pub struct InstanceSnapshot {
+    memories: Vec<Vec<u8>>,
+    globals: Vec<Value>,
+    tables: Vec<Vec<Option<FuncIndex>>>,
+}
+
+impl InstanceSnapshot {
+    pub(crate) fn capture(
+        module: &ModuleInner,
+        backing: &LocalBacking,
+        imports: &ImportBacking,
+        vmctx: *mut vm::Ctx,
+    ) -> Result<Self, SnapshotError> {
+        let memories = backing
+            .memories
+            .iter()
+            .map(|(_, memory)| memory.direct_access(|contents: &[u8]| contents.to_vec()))
+            .collect();
+
+        let globals = module
+            .globals
+            .iter()
+            .filter(|(_, global_init)| global_init.desc.mutable)
+            .map(|(local_global_index, _)| {
+                let value = backing.globals[local_global_index].get();
+                if is_null_or_not_ref(&value) {
+                    Ok(value)
+                } else {
+                    Err(SnapshotError::UnsupportedReference {
+                        msg: format!(
+                            "global {:?} holds a reference",
+                            local_global_index.convert_up(module)
+                        ),
+                    })
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        // Table elements only hold function pointers, so work out which
+        // function each one refers to. The context is part of the key, since
+        // another instance of this module has the same code but its own
+        // context, and a host function can be imported more than once.
+        let func_indices: HashMap<(*const vm::Func, *mut vm::Ctx), FuncIndex> = module
+            .func_assoc
+            .iter()
+            .map(|(func_index, _)| {
+                let anyfunc = func_anyfunc(module, imports, func_index, vmctx);
+                ((anyfunc.func, anyfunc.ctx), func_index)
+            })
+            .collect();
+
+        let mut tables = Vec::with_capacity(backing.tables.len());
+
+        for (local_table_index, table) in backing.tables.iter() {
+            if table.descriptor().element == ElementType::AnyRef {
+                let elements = table.anyref_direct_access_mut(|elements| {
+                    match elements.iter().position(|anyref| !anyref.is_null()) {
+                        Some(index) => Err(SnapshotError::UnsupportedReference {
+                            msg: format!(
+                                "element {} of table {:?} holds a reference",
+                                index,
+                                local_table_index.convert_up(module)
+                            ),
+                        }),
+                        None => Ok(vec![None; elements.len()]),
+                    }
+                })?;
+
+                tables.push(elements);
+                continue;
+            }
+
+            let elements = table.anyfunc_direct_access_mut(|elements| {
+                elements
+                    .iter()
+                    .enumerate()
+                    .map(|(index, anyfunc)| {
+                        if anyfunc.func.is_null() {
+                            return Ok(None);
+                        }
+
+                        func_indices
+                            .get(&(anyfunc.func, anyfunc.ctx))
+                            .map(|&i| Some(i))
+                            .ok_or_else(|| SnapshotError::UnknownTableElement {
+                                table: local_table_index.convert_up(module),
+                                index: index as u32,
+                            })
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })?;
+
+            tables.push(elements);
+        }
+
+        Ok(Self {
+            memories,
+            globals,
+            tables,
+        })
+    }
+
+    /// Make sure this snapshot can be restored into an instance of `module`.
+    pub(crate) fn check(&self, module: &ModuleInner) -> Result<(), SnapshotError> {
+        let incompatible = |msg: String| Err(SnapshotError::IncompatibleModule { msg });
+
+        if self.memories.len() != module.memories.len() {
+            return incompatible(format!(
+                "expected {} memories, found {}",
+                module.memories.len(),
+                self.memories.len()
+            ));
+        }
+
+        for ((_, desc), contents) in module.memories.iter().zip(&self.memories) {
+            let pages = Pages::from(Bytes(contents.len()));
+
+            if pages.bytes().0 != contents.len()
+                || pages < desc.minimum
+                || desc.maximum.map_or(false, |max| pages > max)
+            {
+                return incompatible(format!(
+                    "memory of {} bytes does not match {:?}",
+                    contents.len(),
+                    desc
+                ));
+            }
+        }
+
+        let mutable_globals: Vec<Type> = module
+            .globals
+            .iter()
+            .filter(|(_, global_init)| global_init.desc.mutable)
+            .map(|(_, global_init)| global_init.desc.ty)
+            .collect();
+
+        let global_types: Vec<Type> = self.globals.iter().map(|value| value.ty()).collect();
+
+        if global_types != mutable_globals {
+            return incompatible(format!(
+                "expected mutable globals of types {:?}, found {:?}",
+                mutable_globals, global_types
+            ));
+        }
+
+        if self.tables.len() != module.tables.len() {
+            return incompatible(format!(
+                "expected {} tables, found {}",
+                module.tables.len(),
+                self.tables.len()
+            ));
+        }
+
+        for ((_, desc), elements) in module.tables.iter().zip(&self.tables) {
+            if desc
+                .maximum
+                .map_or(false, |max| elements.len() > max as usize)
+            {
+                return incompatible(format!(
+                    "table of {} elements does not match {:?}",
+                    elements.len(),
+                    desc
+                ));
+            }
+
+            let func_count = module.func_assoc.len();
+            if let Some(func_index) = elements
+                .iter()
+                .filter_map(|&element| element)
+                .find(|func_index| func_index.index() >= func_count)
+            {
+                return incompatible(format!("function {:?} does not exist", func_index));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the contents of this snapshot into the
+    /// freshly created memories, tables, and globals of an instance.
+    ///
+    /// The snapshot must have been checked against `module` first.
+    pub(crate) fn restore(
+        &self,
+        module: &ModuleInner,
+        imports: &ImportBacking,
+        memories: &mut SliceMap<LocalMemoryIndex, Memory>,
+        tables: &SliceMap<LocalTableIndex, Table>,
+        globals: &SliceMap<LocalGlobalIndex, Global>,
+        vmctx: *mut vm::Ctx,
+    ) {
+        for ((_, memory), contents) in memories.iter_mut().zip(&self.memories) {
+            let pages = Pages::from(Bytes(contents.len()));
+            memory
+                .grow(pages - memory.size())
+                .expect("unable to grow memory");
+            memory.direct_access_mut(|memory: &mut [u8]| memory.copy_from_slice(contents));
+        }
+
+        let mutable_globals = module
+            .globals
+            .iter()
+            .filter(|(_, global_init)| global_init.desc.mutable);
+
+        for ((local_global_index, _), value) in mutable_globals.zip(&self.globals) {
+            globals[local_global_index].set(value.clone());
+        }
+
+        for ((_, table), elements) in tables.iter().zip(&self.tables) {
+            if (table.size() as usize) < elements.len() {
+                let delta = elements.len() - table.size() as usize;
+                table.grow(delta as u32).expect("couldn't grow table");
+            }
+
+            // Anyref tables can only be captured when all their
+            // elements are null, which is how they start out.
+            if table.descriptor().element == ElementType::AnyRef {
+                continue;
+            }
+
+            table.anyfunc_direct_access_mut(|table_elements| {
+                for (table_element, element) in table_elements.iter_mut().zip(elements) {
+                    *table_element = match *element {
+                        Some(func_index) => func_anyfunc(module, imports, func_index, vmctx),
+                        None => vm::Anyfunc::null(),
+                    };
+                }
+            });
+        }
+    }
+
+    /// Encode this snapshot as bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        write_u32(&mut bytes, VERSION);
+
+        write_u32(&mut bytes, self.memories.len() as u32);
+        for contents in &self.memories {
+            write_u64(&mut bytes, contents.len() as u64);
+            bytes.extend_from_slice(contents);
+        }
+
+        write_u32(&mut bytes, self.globals.len() as u32);
+        for value in &self.globals {
+            let tag = match value.ty() {
+                Type::I32 => 0,
+                Type::I64 => 1,
+                Type::F32 => 2,
+                Type::F64 => 3,
+                Type::AnyRef => 4,
+                Type::FuncRef => 5,
+            };
+            bytes.push(tag);
+            write_u64(&mut bytes, value.to_bits());
+        }
+
+        write_u32(&mut bytes, self.tables.len() as u32);
+        for elements in &self.tables {
+            write_u32(&mut bytes, elements.len() as u32);
+            for element in elements {
+                let func_index =
+                    element.map_or(u32::max_value(), |func_index| func_index.index() as u32);
+                write_u32(&mut bytes, func_index);
+            }
+        }
+
+        bytes
+    }
+
+    /// Decode a snapshot that was encoded with [`to_bytes`].
+    ///
+    /// [`to_bytes`]: #method.to_bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader { bytes };
+
+        if reader.read_bytes(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::Malformed {
+                msg: "not a snapshot".to_string(),
+            });
+        }
+
+        let version = reader.read_u32()?;
+        if version != VERSION {
+            return Err(SnapshotError::Malformed {
+                msg: format!("unsupported snapshot version {}", version),
+            });
+        }
+
+        let memory_count = reader.read_u32()?;
+        let mut memories = Vec::new();
+        for _ in 0..memory_count {
+            let len = reader.read_u64()? as usize;
+            memories.push(reader.read_bytes(len)?.to_vec());
+        }
+
+        let global_count = reader.read_u32()?;
+        let mut globals = Vec::new();
+        for _ in 0..global_count {
+            let tag = reader.read_bytes(1)?[0];
+            let ty = match tag {
+                0 => Type::I32,
+                1 => Type::I64,
+                2 => Type::F32,
+                3 => Type::F64,
+                4 => Type::AnyRef,
+                5 => Type::FuncRef,
+                _ => {
+                    return Err(SnapshotError::Malformed {
+                        msg: format!("unknown global type {}", tag),
+                    });
+                }
+            };
+
+            let value = Value::from_bits(ty, reader.read_u64()?);
+            if !is_null_or_not_ref(&value) {
+                return Err(SnapshotError::Malformed {
+                    msg: "a snapshot cannot contain references".to_string(),
+                });
+            }
+            globals.push(value);
+        }
+
+        let table_count = reader.read_u32()?;
+        let mut tables = Vec::new();
+        for _ in 0..table_count {
+            let len = reader.read_u32()?;
+            let mut elements = Vec::new();
+            for _ in 0..len {
+                let func_index = reader.read_u32()?;
+                elements.push(if func_index == u32::max_value() {
+                    None
+                } else {
+                    Some(FuncIndex::new(func_index as usize))
+                });
+            }
+            tables.push(elements);
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(SnapshotError::Malformed {
+                msg: "trailing bytes after snapshot".to_string(),
+            });
+        }
+
+        Ok(Self {
+            memories,
+            globals,
+            tables,
+        })
+    }
+}
+
+fn is_null_or_not_ref(value: &Value) -> bool {
+    match value {
+        Value::AnyRef(anyref) => anyref.is_null(),
+        Value::FuncRef(funcref) => funcref.is_null(),
+        _ => true,
+    }
+}
+
+fn write_u32(bytes: &mut Vec<u8>, x: u32) {
+    bytes.extend_from_slice(&x.to_le_bytes());
+}
+
+fn write_u64(bytes: &mut Vec<u8>, x: u64) {
+    bytes.extend_from_slice(&x.to_le_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::Malformed {
+                msg: "unexpected end of snapshot".to_string(),
+            });
+        }
+
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, SnapshotError> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.read_bytes(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, SnapshotError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+}
@@ -2,6 +2,7 @@ use errno;
 use nix::libc;
 use page_size;
 use std::ops::{Bound, RangeBounds};
+use std::os::unix::io::RawFd;
 use std::{ptr, slice};
 
 unsafe impl Send for Memory {}
@@ -11,6 +12,7 @@ unsafe impl Sync for Memory {}
 pub struct Memory {
     ptr: *mut u8,
     size: usize,
+    image_size: usize,
 }
 
 impl Memory {
@@ -19,6 +21,7 @@ impl Memory {
             return Ok(Self {
                 ptr: ptr::null_mut(),
                 size: 0,
+                image_size: 0,
             });
         }
 
@@ -41,6 +44,7 @@ impl Memory {
             Ok(Self {
                 ptr: ptr as *mut u8,
                 size,
+                image_size: 0,
             })
         }
     }
@@ -94,6 +98,82 @@ impl Memory {
     pub fn as_ptr(&self) -> *mut u8 {
         self.ptr
     }
+
+    /// Map `image` copy-on-write over the start of this memory.
+    ///
+    /// The mapped pages are readable and writable. Writes are private
+    /// to this memory and never reach the image.
+    pub unsafe fn map_image(&mut self, image: &MemoryImage) -> Result<(), String> {
+        if image.size == 0 {
+            return Ok(());
+        }
+        assert!(image.size <= self.size);
+
+        let ptr = libc::mmap(
+            self.ptr as _,
+            image.size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_FIXED,
+            image.fd,
+            0,
+        );
+
+        if ptr == -1 as _ {
+            Err(errno::errno().to_string())
+        } else {
+            self.image_size = image.size;
+            Ok(())
+        }
+    }
+
+    /// Discard the contents of the first `len` bytes of this memory and
+    /// make them inaccessible again, while keeping the address range reserved.
+    pub unsafe fn reset(&mut self, len: usize) -> Result<(), String> {
+        let len = round_up_to_page_size(len, page_size::get());
+        assert!(len <= self.size);
+
+        // `madvise` would bring back the contents of a file
+        // mapping, so replace the image with anonymous memory.
+        if self.image_size != 0 {
+            let ptr = libc::mmap(
+                self.ptr as _,
+                self.image_size,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+                -1,
+                0,
+            );
+            if ptr == -1 as _ {
+                return Err(errno::errno().to_string());
+            }
+            self.image_size = 0;
+        }
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        if libc::madvise(self.ptr as _, len, libc::MADV_DONTNEED) == -1 {
+            return Err(errno::errno().to_string());
+        }
+
+        if libc::mprotect(self.ptr as _, len, libc::PROT_NONE) == -1 {
+            return Err(errno::errno().to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// A memory without any mapping.
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            ptr: ptr::null_mut(),
+            size: 0,
+            image_size: 0,
+        }
+    }
 }
 
 impl Drop for Memory {
@@ -105,6 +185,93 @@ impl Drop for Memory {
     }
 }
 
+/// The initial contents of a memory, stored in an anonymous file
+/// so that it can be mapped copy-on-write into any number of memories.
+#[derive(Debug)]
+pub struct MemoryImage {
+    fd: RawFd,
+    size: usize,
+}
+
+impl MemoryImage {
+    /// Create an image of `size` bytes. Everything not covered by `segments`,
+    /// which are written in order, is zero.
+    pub fn new<'a>(
+        size: usize,
+        segments: impl IntoIterator<Item = (usize, &'a [u8])>,
+    ) -> Result<Self, String> {
+        let size = round_up_to_page_size(size, page_size::get());
+        let image = Self {
+            fd: create_anonymous_file()?,
+            size,
+        };
+
+        unsafe {
+            if libc::ftruncate(image.fd, size as libc::off_t) == -1 {
+                return Err(errno::errno().to_string());
+            }
+
+            for (offset, data) in segments {
+                assert!(offset + data.len() <= size);
+                let mut written = 0;
+                while written < data.len() {
+                    let count = libc::pwrite(
+                        image.fd,
+                        data[written..].as_ptr() as _,
+                        data.len() - written,
+                        (offset + written) as libc::off_t,
+                    );
+                    if count == -1 {
+                        return Err(errno::errno().to_string());
+                    }
+                    written += count as usize;
+                }
+            }
+        }
+
+        Ok(image)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for MemoryImage {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn create_anonymous_file() -> Result<RawFd, String> {
+    const MFD_CLOEXEC: libc::c_uint = 0x0001;
+    let name = b"wasmer-memory-image\0";
+
+    let fd = unsafe { libc::syscall(libc::SYS_memfd_create, name.as_ptr(), MFD_CLOEXEC) };
+
+    if fd == -1 {
+        Err(errno::errno().to_string())
+    } else {
+        Ok(fd as RawFd)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn create_anonymous_file() -> Result<RawFd, String> {
+    let mut template = *b"/tmp/wasmer-memory-image-XXXXXX\0";
+
+    unsafe {
+        let fd = libc::mkstemp(template.as_mut_ptr() as _);
+        if fd == -1 {
+            return Err(errno::errno().to_string());
+        }
+        // The file stays alive as long as the descriptor is open.
+        libc::unlink(template.as_ptr() as _);
+        Ok(fd)
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum Protect {
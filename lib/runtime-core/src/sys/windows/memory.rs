@@ -1,6 +1,6 @@
 use winapi::um::memoryapi::{
-    VirtualAlloc,
-    MEM_RESERVE, MEM_COMMIT,
+    VirtualAlloc, VirtualFree,
+    MEM_RESERVE, MEM_COMMIT, MEM_DECOMMIT,
     PAGE_NOACCESS, PAGE_EXECUTE_READ, PAGE_READWRITE, PAGE_READONLY,
 };
 use page_size;
@@ -98,6 +98,68 @@ impl Memory {
     pub fn as_ptr(&self) -> *mut u8 {
         self.ptr
     }
+
+    /// Discard the contents of the first `len` bytes of this memory and
+    /// make them inaccessible again, while keeping the address range reserved.
+    pub unsafe fn reset(&mut self, len: usize) -> Result<(), String> {
+        let len = round_up_to_page_size(len, page_size::get());
+        assert!(len <= self.size);
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        if VirtualFree(self.ptr as _, len, MEM_DECOMMIT) == 0 {
+            Err("unable to reset memory".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Copy `image` over the start of this memory.
+    ///
+    /// There is no copy-on-write mapping here yet, so this
+    /// just commits the pages and copies the contents.
+    pub unsafe fn map_image(&mut self, image: &MemoryImage) -> Result<(), String> {
+        if image.size() == 0 {
+            return Ok(());
+        }
+        self.protect(0..image.size(), Protect::ReadWrite)?;
+        self.as_slice_mut()[..image.size()].copy_from_slice(&image.data);
+        Ok(())
+    }
+}
+
+/// The initial contents of a memory.
+#[derive(Debug)]
+pub struct MemoryImage {
+    data: Vec<u8>,
+}
+
+impl MemoryImage {
+    /// Create an image of `size` bytes. Everything not covered by `segments`,
+    /// which are written in order, is zero.
+    pub fn new<'a>(size: usize, segments: impl IntoIterator<Item = (usize, &'a [u8])>) -> Result<Self, String> {
+        let mut data = vec![0; round_up_to_page_size(size, page_size::get())];
+        for (offset, segment) in segments {
+            data[offset..offset + segment.len()].copy_from_slice(segment);
+        }
+        Ok(Self { data })
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// A memory without any mapping.
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            ptr: ptr::null_mut(),
+            size: 0,
+        }
+    }
 }
 
 impl Drop for Memory {
@@ -1 +1 @@
-pub use self::memory::{Memory, Protect};
+pub use self::memory::{Memory, MemoryImage, Protect};
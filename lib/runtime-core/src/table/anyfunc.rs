@@ -1,13 +1,14 @@
 use crate::{
     error::CreationError,
     instance::Function,
+    memory::MemoryPool,
     sig_registry::SigRegistry,
     structures::TypedIndex,
     types::{FuncSig, TableDescriptor},
     vm,
 };
 
-use std::{ptr, sync::Arc};
+use std::{mem, ptr, sync::Arc};
 
 enum AnyfuncInner<'a> {
     Host {
@@ -46,21 +47,25 @@ impl<'a> From<Function<'a>> for Anyfunc<'a> {
 pub struct AnyfuncTable {
     backing: Vec<vm::Anyfunc>,
     max: Option<u32>,
+    pool: Option<MemoryPool>,
 }
 
 impl AnyfuncTable {
     pub fn new(
         desc: TableDescriptor,
         local: &mut vm::LocalTable,
+        pool: Option<&MemoryPool>,
     ) -> Result<Box<Self>, CreationError> {
-        let initial_table_backing_len = match desc.maximum {
-            Some(max) => max,
-            None => desc.minimum,
-        } as usize;
+        // Reuse the buffer of a dropped table if the pool has one.
+        let mut backing = pool
+            .and_then(|pool| pool.take_table_backing())
+            .unwrap_or_default();
+        backing.resize(Self::initial_len(desc), vm::Anyfunc::null());
 
         let mut storage = Box::new(AnyfuncTable {
-            backing: vec![vm::Anyfunc::null(); initial_table_backing_len],
+            backing,
             max: desc.maximum,
+            pool: pool.cloned(),
         });
 
         let storage_ptr: *mut AnyfuncTable = &mut *storage;
@@ -72,6 +77,11 @@ impl AnyfuncTable {
         Ok(storage)
     }
 
+    /// The number of elements a table described by `desc` starts with.
+    fn initial_len(desc: TableDescriptor) -> usize {
+        desc.maximum.unwrap_or(desc.minimum) as usize
+    }
+
     pub fn current_size(&self) -> u32 {
         self.backing.len() as u32
     }
@@ -99,6 +109,17 @@ impl AnyfuncTable {
         Some(starting_len)
     }
 
+    /// Shrink or grow this table back to its
+    /// initial size and clear every element.
+    pub fn reset(&mut self, desc: TableDescriptor, local: &mut vm::LocalTable) {
+        self.backing.clear();
+        self.backing
+            .resize(Self::initial_len(desc), vm::Anyfunc::null());
+
+        local.base = self.backing.as_mut_ptr() as *mut u8;
+        local.count = self.backing.len();
+    }
+
     pub fn set(&mut self, index: u32, element: Anyfunc) -> Result<(), ()> {
         if let Some(slot) = self.backing.get_mut(index as usize) {
             let anyfunc = match element.inner {
@@ -132,3 +153,11 @@ impl AnyfuncTable {
         }
     }
 }
+
+impl Drop for AnyfuncTable {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.give_back_table_backing(mem::replace(&mut self.backing, Vec::new()));
+        }
+    }
+}
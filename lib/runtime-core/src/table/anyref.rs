@@ -0,0 +1,99 @@
+use crate::{
+    error::CreationError,
+    memory::MemoryPool,
+    types::{AnyRef, TableDescriptor},
+    vm,
+};
+use std::mem;
+
+pub struct AnyRefTable {
+    backing: Vec<AnyRef>,
+    max: Option<u32>,
+    pool: Option<MemoryPool>,
+}
+
+impl AnyRefTable {
+    pub fn new(
+        desc: TableDescriptor,
+        local: &mut vm::LocalTable,
+        pool: Option<&MemoryPool>,
+    ) -> Result<Box<Self>, CreationError> {
+        // Reuse the buffer of a dropped table if the pool has one.
+        let mut backing = pool
+            .and_then(|pool| pool.take_table_backing())
+            .unwrap_or_default();
+        backing.resize(desc.minimum as usize, AnyRef::null());
+
+        let mut storage = Box::new(AnyRefTable {
+            backing,
+            max: desc.maximum,
+            pool: pool.cloned(),
+        });
+
+        let storage_ptr: *mut AnyRefTable = &mut *storage;
+
+        local.base = storage.backing.as_mut_ptr() as *mut u8;
+        local.count = storage.backing.len();
+        local.table = storage_ptr as *mut ();
+
+        Ok(storage)
+    }
+
+    pub fn current_size(&self) -> u32 {
+        self.backing.len() as u32
+    }
+
+    pub fn internal_buffer(&mut self) -> &mut [AnyRef] {
+        &mut self.backing
+    }
+
+    pub fn grow(&mut self, delta: u32, local: &mut vm::LocalTable) -> Option<u32> {
+        let starting_len = self.backing.len() as u32;
+
+        let new_len = starting_len.checked_add(delta)?;
+
+        if let Some(max) = self.max {
+            if new_len > max {
+                return None;
+            }
+        }
+
+        self.backing.resize(new_len as usize, AnyRef::null());
+
+        local.base = self.backing.as_mut_ptr() as *mut u8;
+        local.count = self.backing.len();
+
+        Some(starting_len)
+    }
+
+    /// Shrink this table back to its
+    /// initial size and clear every element.
+    pub fn reset(&mut self, desc: TableDescriptor, local: &mut vm::LocalTable) {
+        self.backing.clear();
+        self.backing.resize(desc.minimum as usize, AnyRef::null());
+
+        local.base = self.backing.as_mut_ptr() as *mut u8;
+        local.count = self.backing.len();
+    }
+
+    pub fn get(&self, index: u32) -> Option<AnyRef> {
+        self.backing.get(index as usize).cloned()
+    }
+
+    pub fn set(&mut self, index: u32, element: AnyRef) -> Result<(), ()> {
+        if let Some(slot) = self.backing.get_mut(index as usize) {
+            *slot = element;
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl Drop for AnyRefTable {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.give_back_table_backing(mem::replace(&mut self.backing, Vec::new()));
+        }
+    }
+}
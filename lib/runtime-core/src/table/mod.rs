@@ -2,24 +2,30 @@ use crate::{
     error::CreationError,
     export::Export,
     import::IsExport,
-    types::{ElementType, TableDescriptor},
+    memory::MemoryPool,
+    types::{AnyRef, ElementType, TableDescriptor},
     vm,
 };
 use std::{cell::RefCell, fmt, ptr, rc::Rc};
 
 mod anyfunc;
+mod anyref;
 
 pub use self::anyfunc::Anyfunc;
-use self::anyfunc::AnyfuncTable;
+pub(crate) use self::anyfunc::AnyfuncTable;
+pub(crate) use self::anyref::AnyRefTable;
 
 pub enum Element<'a> {
     Anyfunc(Anyfunc<'a>),
+    AnyRef(AnyRef),
 }
 
 // #[derive(Debug)]
 pub enum TableStorage {
     /// This is intended to be a caller-checked Anyfunc.
     Anyfunc(Box<AnyfuncTable>),
+    /// Opaque references to host values.
+    AnyRef(Box<AnyRefTable>),
 }
 
 pub struct Table {
@@ -50,6 +56,15 @@ impl Table {
     /// # }
     /// ```
     pub fn new(desc: TableDescriptor) -> Result<Self, CreationError> {
+        Self::new_pooled(desc, None)
+    }
+
+    /// Create a new `Table`, reusing an element
+    /// buffer from `pool` if it has one.
+    pub(crate) fn new_pooled(
+        desc: TableDescriptor,
+        pool: Option<&MemoryPool>,
+    ) -> Result<Self, CreationError> {
         let mut local = vm::LocalTable {
             base: ptr::null_mut(),
             count: 0,
@@ -57,7 +72,10 @@ impl Table {
         };
 
         let storage = match desc.element {
-            ElementType::Anyfunc => TableStorage::Anyfunc(AnyfuncTable::new(desc, &mut local)?),
+            ElementType::Anyfunc => {
+                TableStorage::Anyfunc(AnyfuncTable::new(desc, &mut local, pool)?)
+            }
+            ElementType::AnyRef => TableStorage::AnyRef(AnyRefTable::new(desc, &mut local, pool)?),
         };
 
         Ok(Self {
@@ -72,14 +90,28 @@ impl Table {
     }
 
     /// Set the element at index.
+    ///
+    /// This method will panic if the element is
+    /// the wrong type for this table.
     pub fn set(&self, index: u32, element: Element) -> Result<(), ()> {
         match &mut *self.storage.borrow_mut() {
-            (TableStorage::Anyfunc(ref mut anyfunc_table), _) => {
-                match element {
-                    Element::Anyfunc(anyfunc) => anyfunc_table.set(index, anyfunc),
-                    // _ => panic!("wrong element type for anyfunc table"),
-                }
-            }
+            (TableStorage::Anyfunc(ref mut anyfunc_table), _) => match element {
+                Element::Anyfunc(anyfunc) => anyfunc_table.set(index, anyfunc),
+                _ => panic!("wrong element type for anyfunc table"),
+            },
+            (TableStorage::AnyRef(ref mut anyref_table), _) => match element {
+                Element::AnyRef(anyref) => anyref_table.set(index, anyref),
+                _ => panic!("wrong element type for anyref table"),
+            },
+        }
+    }
+
+    /// Get the reference held by the element at index,
+    /// if this is an anyref table.
+    pub fn get_anyref(&self, index: u32) -> Option<AnyRef> {
+        match &*self.storage.borrow() {
+            (TableStorage::AnyRef(ref anyref_table), _) => anyref_table.get(index),
+            _ => None,
         }
     }
 
@@ -89,6 +121,17 @@ impl Table {
     {
         match &mut *self.storage.borrow_mut() {
             (TableStorage::Anyfunc(ref mut anyfunc_table), _) => f(anyfunc_table.internal_buffer()),
+            (TableStorage::AnyRef(_), _) => panic!("not an anyfunc table"),
+        }
+    }
+
+    pub(crate) fn anyref_direct_access_mut<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut [AnyRef]) -> R,
+    {
+        match &mut *self.storage.borrow_mut() {
+            (TableStorage::AnyRef(ref mut anyref_table), _) => f(anyref_table.internal_buffer()),
+            (TableStorage::Anyfunc(_), _) => panic!("not an anyref table"),
         }
     }
 
@@ -96,6 +139,7 @@ impl Table {
     pub fn size(&self) -> u32 {
         match &*self.storage.borrow() {
             (TableStorage::Anyfunc(ref anyfunc_table), _) => anyfunc_table.current_size(),
+            (TableStorage::AnyRef(ref anyref_table), _) => anyref_table.current_size(),
         }
     }
 
@@ -109,6 +153,22 @@ impl Table {
             (TableStorage::Anyfunc(ref mut anyfunc_table), ref mut local) => {
                 anyfunc_table.grow(delta, local)
             }
+            (TableStorage::AnyRef(ref mut anyref_table), ref mut local) => {
+                anyref_table.grow(delta, local)
+            }
+        }
+    }
+
+    /// Bring this table back to the size it was created
+    /// with and clear every element.
+    pub(crate) fn reset(&self) {
+        match &mut *self.storage.borrow_mut() {
+            (TableStorage::Anyfunc(ref mut anyfunc_table), ref mut local) => {
+                anyfunc_table.reset(self.desc, local)
+            }
+            (TableStorage::AnyRef(ref mut anyref_table), ref mut local) => {
+                anyref_table.reset(self.desc, local)
+            }
         }
     }
 
@@ -118,7 +178,7 @@ impl Table {
 }
 
 impl IsExport for Table {
-    fn to_export(&mut self) -> Export {
+    fn to_export(&self) -> Export {
         Export::Table(self.clone())
     }
 }
@@ -1,4 +1,4 @@
-use crate::{memory::MemoryType, module::ModuleInner, structures::TypedIndex, units::Pages};
+use crate::{memory::MemoryType, module::ModuleInner, structures::TypedIndex, units::Pages, vm};
 use std::{borrow::Cow, mem};
 
 /// Represents a WebAssembly type.
@@ -12,6 +12,10 @@ pub enum Type {
     F32,
     /// The `f64` type.
     F64,
+    /// The `anyref` type.
+    AnyRef,
+    /// The `funcref` type.
+    FuncRef,
 }
 
 /// Represents a WebAssembly value.
@@ -28,6 +32,10 @@ pub enum Value {
     F32(f32),
     /// The `f64` type.
     F64(f64),
+    /// The `anyref` type.
+    AnyRef(AnyRef),
+    /// The `funcref` type.
+    FuncRef(FuncRef),
 }
 
 impl Value {
@@ -37,8 +45,95 @@ impl Value {
             Value::I64(_) => Type::I64,
             Value::F32(_) => Type::F32,
             Value::F64(_) => Type::F64,
+            Value::AnyRef(_) => Type::AnyRef,
+            Value::FuncRef(_) => Type::FuncRef,
         }
     }
+
+    /// The representation of this value in a
+    /// 64-bit slot, like a global or a call argument.
+    #[doc(hidden)]
+    pub fn to_bits(&self) -> u64 {
+        match *self {
+            Value::I32(x) => x as u64,
+            Value::I64(x) => x as u64,
+            Value::F32(x) => x.to_bits() as u64,
+            Value::F64(x) => x.to_bits(),
+            Value::AnyRef(x) => x.0 as u64,
+            Value::FuncRef(x) => x.0 as u64,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn from_bits(ty: Type, bits: u64) -> Self {
+        match ty {
+            Type::I32 => Value::I32(bits as i32),
+            Type::I64 => Value::I64(bits as i64),
+            Type::F32 => Value::F32(f32::from_bits(bits as u32)),
+            Type::F64 => Value::F64(f64::from_bits(bits)),
+            Type::AnyRef => Value::AnyRef(AnyRef(bits as usize)),
+            Type::FuncRef => Value::FuncRef(FuncRef(bits as usize)),
+        }
+    }
+}
+
+/// An opaque reference to a host value, or null.
+///
+/// WebAssembly code can store an `AnyRef` in tables and globals and
+/// pass it around, but it can't look inside. Keeping the referenced
+/// value alive for as long as WebAssembly code might hold on to the
+/// reference is up to the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AnyRef(usize);
+
+impl AnyRef {
+    /// The null reference.
+    pub fn null() -> Self {
+        AnyRef(0)
+    }
+
+    /// Create a reference to the value that `ptr` points to.
+    pub fn from_ptr<T>(ptr: *const T) -> Self {
+        AnyRef(ptr as usize)
+    }
+
+    /// The pointer this reference was created from.
+    pub fn as_ptr<T>(self) -> *const T {
+        self.0 as *const T
+    }
+
+    pub fn is_null(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// A reference to a function, or null.
+///
+/// A non-null `FuncRef` points to the `vm::Anyfunc` that describes
+/// the function, which the host must keep alive and in place for as
+/// long as WebAssembly code might hold on to the reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FuncRef(usize);
+
+impl FuncRef {
+    /// The null reference.
+    pub fn null() -> Self {
+        FuncRef(0)
+    }
+
+    /// Create a reference to the function described by `anyfunc`.
+    pub fn from_anyfunc(anyfunc: *const vm::Anyfunc) -> Self {
+        FuncRef(anyfunc as usize)
+    }
+
+    /// The `vm::Anyfunc` this reference points to, or null.
+    pub fn as_anyfunc(self) -> *const vm::Anyfunc {
+        self.0 as *const vm::Anyfunc
+    }
+
+    pub fn is_null(self) -> bool {
+        self.0 == 0
+    }
 }
 
 impl From<i32> for Value {
@@ -125,6 +220,10 @@ impl ValueType for f64 {
 pub enum ElementType {
     /// Any wasm function.
     Anyfunc,
+    /// Any host value, see [`AnyRef`].
+    ///
+    /// [`AnyRef`]: struct.AnyRef.html
+    AnyRef,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -173,6 +272,13 @@ pub struct GlobalInit {
 }
 
 /// A wasm memory.
+///
+/// Explicit bounds checks and reserving the maximum are off by
+/// default, and turned on with [`with_bounds_checks`] and
+/// [`with_reserved_maximum`].
+///
+/// [`with_bounds_checks`]: #method.with_bounds_checks
+/// [`with_reserved_maximum`]: #method.with_reserved_maximum
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MemoryDescriptor {
     /// The minimum number of allowed pages.
@@ -181,10 +287,54 @@ pub struct MemoryDescriptor {
     pub maximum: Option<Pages>,
     /// This memory can be shared between wasm threads.
     pub shared: bool,
+    bounds_checked: bool,
+    reserve_maximum: bool,
 }
 
 impl MemoryDescriptor {
+    pub fn new(minimum: Pages, maximum: Option<Pages>, shared: bool) -> Self {
+        Self {
+            minimum,
+            maximum,
+            shared,
+            bounds_checked: false,
+            reserve_maximum: false,
+        }
+    }
+
+    /// Sets whether every access to this memory is bounds-checked
+    /// explicitly, instead of relying on guard pages, so it only takes
+    /// as much address space as its current size.
+    pub fn with_bounds_checks(self, bounds_checked: bool) -> Self {
+        Self {
+            bounds_checked,
+            ..self
+        }
+    }
+
+    /// Sets whether the address space up to `maximum` is reserved when
+    /// the memory is created, so growing it never moves it, and only
+    /// fails when it would go past the maximum.
+    pub fn with_reserved_maximum(self, reserve_maximum: bool) -> Self {
+        Self {
+            reserve_maximum,
+            ..self
+        }
+    }
+
+    pub fn is_bounds_checked(self) -> bool {
+        self.bounds_checked
+    }
+
+    pub fn reserves_maximum(self) -> bool {
+        self.reserve_maximum
+    }
+
     pub fn memory_type(self) -> MemoryType {
+        if self.bounds_checked && !self.shared {
+            return MemoryType::Dynamic;
+        }
+
         match (self.maximum.is_some(), self.shared) {
             (true, true) => MemoryType::SharedStatic,
             (true, false) => MemoryType::Static,
@@ -197,7 +347,9 @@ impl MemoryDescriptor {
         let imported_max = imported.maximum.unwrap_or(Pages(65_536));
         let self_max = self.maximum.unwrap_or(Pages(65_536));
 
+        // The code accessing the memory depends on how it's bounds-checked.
         self.shared == imported.shared
+            && self.bounds_checked == imported.bounds_checked
             && imported_max <= self_max
             && self.minimum <= imported.minimum
     }
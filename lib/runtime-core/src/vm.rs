@@ -127,6 +127,33 @@ impl Ctx {
             },
         }
     }
+
+    /// The module that this is the context of an instance of.
+    #[doc(hidden)]
+    pub fn module(&self) -> &ModuleInner {
+        unsafe { &*self.module }
+    }
+
+    /// The index that `local_memory` has in this
+    /// instance, if the instance has that memory.
+    #[doc(hidden)]
+    pub fn memory_index(&self, local_memory: *const LocalMemory) -> Option<MemoryIndex> {
+        let module = unsafe { &*self.module };
+
+        let local_memories = module.memories.iter().map(|(index, _)| {
+            let ptr = unsafe { *self.memories.add(index.index()) };
+            (index.convert_up(module), ptr)
+        });
+        let imported_memories = module.imported_memories.iter().map(|(index, _)| {
+            let ptr = unsafe { *self.imported_memories.add(index.index()) };
+            (index.convert_up(module), ptr)
+        });
+
+        local_memories
+            .chain(imported_memories)
+            .find(|&(_, ptr)| ptr as *const LocalMemory == local_memory)
+            .map(|(index, _)| index)
+    }
 }
 
 #[doc(hidden)]
@@ -202,7 +229,8 @@ pub struct LocalTable {
     pub base: *mut u8,
     /// Number of elements in the table (NOT necessarily the size of the table in bytes!).
     pub count: usize,
-    /// The table that this represents. At the moment, this can only be `*mut AnyfuncTable`.
+    /// The table that this represents. This is a `*mut AnyfuncTable`
+    /// or a `*mut AnyRefTable`, depending on the type of its elements.
     pub table: *mut (),
 }
 
@@ -464,6 +492,7 @@ mod vm_ctx_tests {
             globals: Map::new().into_boxed_map(),
 
             vm_functions: Map::new().into_boxed_map(),
+            func_contexts: Map::new().into_boxed_map(),
             vm_memories: Map::new().into_boxed_map(),
             vm_tables: Map::new().into_boxed_map(),
             vm_globals: Map::new().into_boxed_map(),
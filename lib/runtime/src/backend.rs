@@ -0,0 +1,141 @@
+use std::{fmt, str::FromStr};
+use wasmer_runtime_core::backend::Compiler;
+
+/// The compiler backends a module can be compiled with.
+///
+/// Each backend is only available when `wasmer-runtime` is built
+/// with the cargo feature named after its crate, e.g.
+/// `wasmer-singlepass-backend`. Only Cranelift is enabled by default.
+/// The single-pass and tiered backends are never available on targets
+/// other than x86_64 unix, even with their feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// Optimizing compiler, from `wasmer-clif-backend`.
+    Cranelift,
+    /// Fast single-pass x86_64 compiler, from `wasmer-singlepass-backend`.
+    SinglePass,
+    /// Starts in the single-pass backend and recompiles hot functions
+    /// with Cranelift, from `wasmer-tiered-backend`.
+    Tiered,
+    /// Portable interpreter, from `wasmer-interp-backend`.
+    Interpreter,
+}
+
+impl Backend {
+    /// Every backend, whether it's enabled or not.
+    pub const ALL: [Backend; 4] = [
+        Backend::Cranelift,
+        Backend::SinglePass,
+        Backend::Tiered,
+        Backend::Interpreter,
+    ];
+
+    /// The name used to select this backend, e.g. on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Cranelift => "cranelift",
+            Backend::SinglePass => "singlepass",
+            Backend::Tiered => "tiered",
+            Backend::Interpreter => "interpreter",
+        }
+    }
+
+    /// Whether this build of `wasmer-runtime` includes the backend.
+    pub fn is_enabled(self) -> bool {
+        self.compiler().is_some()
+    }
+
+    /// The backends included in this build.
+    pub fn enabled() -> Vec<Backend> {
+        Self::ALL
+            .iter()
+            .cloned()
+            .filter(|backend| backend.is_enabled())
+            .collect()
+    }
+
+    /// The compiler of this backend, or `None` if it isn't enabled.
+    pub fn compiler(self) -> Option<Box<dyn Compiler>> {
+        match self {
+            #[cfg(feature = "wasmer-clif-backend")]
+            Backend::Cranelift => Some(Box::new(wasmer_clif_backend::CraneliftCompiler::new())),
+            #[cfg(all(feature = "wasmer-singlepass-backend", target_arch = "x86_64", unix))]
+            Backend::SinglePass => Some(Box::new(
+                wasmer_singlepass_backend::SinglePassCompiler::new(),
+            )),
+            #[cfg(all(feature = "wasmer-tiered-backend", target_arch = "x86_64", unix))]
+            Backend::Tiered => Some(Box::new(wasmer_tiered_backend::TieredCompiler::new())),
+            #[cfg(feature = "wasmer-interp-backend")]
+            Backend::Interpreter => Some(Box::new(wasmer_interp_backend::InterpCompiler::new())),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend::Cranelift
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .cloned()
+            .find(|backend| backend.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|backend| backend.name()).collect();
+                format!(
+                    "unknown backend `{}`, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile_with_backend;
+
+    // (module)
+    static EMPTY_MODULE: &[u8] = &[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn test_from_str() {
+        for &backend in Backend::ALL.iter() {
+            assert_eq!(backend.name().parse::<Backend>(), Ok(backend));
+            assert_eq!(backend.to_string(), backend.name());
+        }
+
+        let err = "lightbeam".parse::<Backend>().unwrap_err();
+        assert_eq!(
+            err,
+            "unknown backend `lightbeam`, expected one of: cranelift, singlepass, tiered, interpreter"
+        );
+        assert!("Cranelift".parse::<Backend>().is_err());
+    }
+
+    #[test]
+    fn test_compile_with_backend() {
+        for &backend in Backend::ALL.iter() {
+            let result = compile_with_backend(EMPTY_MODULE, backend);
+            assert_eq!(result.is_ok(), backend.is_enabled(), "{}", backend);
+        }
+        assert_eq!(
+            Backend::enabled().contains(&Backend::Cranelift),
+            cfg!(feature = "wasmer-clif-backend")
+        );
+    }
+}
@@ -65,24 +65,35 @@
 //! # Additional Notes:
 //!
 //! The `wasmer-runtime` is build to support compiler multiple backends.
-//! Currently, we support the [Cranelift] compiler with the [`wasmer-clif-backend`] crate.
+//! By default, we use the [Cranelift] compiler with the [`wasmer-clif-backend`] crate.
+//! The other backends are enabled with the cargo feature named after their crate.
 //!
-//! You can specify the compiler you wish to use with the [`compile_with`] function.
+//! You can pick one of the enabled backends with the [`compile_with_backend`] function,
+//! or pass any compiler to the [`compile_with`] function.
 //!
 //! [Cranelift]: https://github.com/CraneStation/cranelift
 //! [`wasmer-clif-backend`]: https://crates.io/crates/wasmer-clif-backend
+//! [`compile_with_backend`]: fn.compile_with_backend.html
 //! [`compile_with`]: fn.compile_with.html
 
+mod backend;
+
+pub use crate::backend::Backend;
+
 pub use wasmer_runtime_core::global::Global;
 pub use wasmer_runtime_core::import::ImportObject;
-pub use wasmer_runtime_core::instance::{Function, Instance};
-pub use wasmer_runtime_core::memory::Memory;
+pub use wasmer_runtime_core::instance::{Function, Instance, PreInstance};
+pub use wasmer_runtime_core::memory::{Memory, MemoryPool};
 pub use wasmer_runtime_core::module::Module;
+pub use wasmer_runtime_core::snapshot::InstanceSnapshot;
 pub use wasmer_runtime_core::table::Table;
 pub use wasmer_runtime_core::types::Value;
 pub use wasmer_runtime_core::vm::Ctx;
 
-pub use wasmer_runtime_core::{compile_with, validate};
+pub use wasmer_runtime_core::Features;
+pub use wasmer_runtime_core::{
+    compile_with, compile_with_features, validate, validate_with_features,
+};
 
 pub use wasmer_runtime_core::error;
 pub use wasmer_runtime_core::{func, imports};
@@ -93,7 +104,9 @@ pub mod wasm {
     pub use wasmer_runtime_core::instance::Function;
     pub use wasmer_runtime_core::memory::Memory;
     pub use wasmer_runtime_core::table::Table;
-    pub use wasmer_runtime_core::types::{FuncSig, MemoryDescriptor, TableDescriptor, Type, Value};
+    pub use wasmer_runtime_core::types::{
+        AnyRef, FuncRef, FuncSig, MemoryDescriptor, TableDescriptor, Type, Value,
+    };
 }
 
 pub mod units {
@@ -117,8 +130,32 @@ pub mod units {
 /// If the operation fails, the function returns `Err(error::CompileError::...)`.
 #[cfg(feature = "wasmer-clif-backend")]
 pub fn compile(wasm: &[u8]) -> error::CompileResult<Module> {
-    use wasmer_clif_backend::CraneliftCompiler;
-    wasmer_runtime_core::compile_with(&wasm[..], &CraneliftCompiler::new())
+    compile_with_backend(wasm, Backend::Cranelift)
+}
+
+/// Compile WebAssembly binary code into a [`Module`]
+/// with the given compiler [`Backend`].
+///
+/// [`Module`]: struct.Module.html
+/// [`Backend`]: enum.Backend.html
+///
+/// # Params:
+/// * `wasm`: A `&[u8]` containing the
+///   binary code of the wasm module you want to compile.
+/// * `backend`: The backend to compile the module with.
+/// # Errors:
+/// If the operation fails, or the backend isn't enabled in this build,
+/// the function returns `Err(error::CompileError::...)`.
+pub fn compile_with_backend(wasm: &[u8], backend: Backend) -> error::CompileResult<Module> {
+    let compiler = backend
+        .compiler()
+        .ok_or_else(|| error::CompileError::InternalError {
+            msg: format!(
+                "the {} backend isn't enabled, build wasmer-runtime with its cargo feature",
+                backend
+            ),
+        })?;
+    wasmer_runtime_core::compile_with(wasm, &*compiler)
 }
 
 /// Compile and instantiate WebAssembly code without
@@ -0,0 +1,191 @@
+use crate::tier::Tiers;
+use hashbrown::{HashMap, HashSet};
+use std::{ptr::NonNull, sync::Arc};
+use wasmer_clif_backend::trap::{call_protected, HandlerData, TrapSink};
+use wasmer_runtime_core::{
+    backend::{
+        sys::{Memory, Protect},
+        FuncResolver, ProtectedCaller, Token,
+    },
+    error::{CompileError, CompileResult, RuntimeResult},
+    module::{ExportIndex, ModuleInner},
+    structures::{Map, TypedIndex},
+    types::{FuncIndex, LocalFuncIndex, LocalOrImport, SigIndex, Value},
+    vm::{self, ImportBacking},
+};
+
+type Trampoline = unsafe extern "C" fn(*mut vm::Ctx, *const vm::Func, *const u64, *mut u64);
+
+/// The machine code of a module, with the offsets of its
+/// functions and trampolines. When compiled for tiering, the
+/// offsets of the functions are the ones of their stubs.
+pub struct Code {
+    memory: Memory,
+    func_offsets: Map<LocalFuncIndex, usize>,
+    trampolines: HashMap<SigIndex, usize>,
+    handler_data: HandlerData,
+    tiers: Option<Tiers>,
+}
+
+impl Code {
+    pub fn new(
+        code: &[u8],
+        func_offsets: Map<LocalFuncIndex, usize>,
+        trampolines: HashMap<SigIndex, usize>,
+        trap_sink: TrapSink,
+        tiers: Option<Tiers>,
+    ) -> CompileResult<Self> {
+        // Always map at least one page, so the code has a real address.
+        let mut memory = Memory::with_size(code.len().max(1))
+            .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+
+        unsafe {
+            memory
+                .protect(.., Protect::ReadWrite)
+                .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+            memory.as_slice_mut()[..code.len()].copy_from_slice(code);
+            memory
+                .protect(.., Protect::ReadExec)
+                .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+        }
+
+        let handler_data = HandlerData::new(trap_sink, memory.as_ptr() as _, memory.size());
+        if let Some(ref tiers) = tiers {
+            tiers.rebase(memory.as_ptr());
+        }
+
+        Ok(Self {
+            memory,
+            func_offsets,
+            trampolines,
+            handler_data,
+            tiers,
+        })
+    }
+
+    pub fn handler_data(&self) -> &HandlerData {
+        &self.handler_data
+    }
+
+    pub fn tiers(&self) -> Option<&Tiers> {
+        self.tiers.as_ref()
+    }
+
+    /// The address that the offsets of the code are from.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.memory.as_ptr()
+    }
+
+    fn func(&self, local_func_index: LocalFuncIndex) -> Option<NonNull<vm::Func>> {
+        let offset = *self.func_offsets.get(local_func_index)?;
+        NonNull::new(unsafe { self.memory.as_ptr().add(offset) } as *mut vm::Func)
+    }
+
+    fn trampoline(&self, sig_index: SigIndex) -> Option<Trampoline> {
+        let offset = *self.trampolines.get(&sig_index)?;
+        Some(unsafe { std::mem::transmute(self.memory.as_ptr().add(offset)) })
+    }
+}
+
+pub struct Resolver {
+    code: Arc<Code>,
+}
+
+impl Resolver {
+    pub fn new(code: Arc<Code>) -> Self {
+        Self { code }
+    }
+}
+
+impl FuncResolver for Resolver {
+    fn get(
+        &self,
+        _module: &ModuleInner,
+        local_func_index: LocalFuncIndex,
+    ) -> Option<NonNull<vm::Func>> {
+        self.code.func(local_func_index)
+    }
+}
+
+pub struct Caller {
+    func_export_set: HashSet<FuncIndex>,
+    code: Arc<Code>,
+}
+
+impl Caller {
+    pub fn new(module: &ModuleInner, code: Arc<Code>) -> Self {
+        let mut func_export_set = HashSet::new();
+        for export_index in module.exports.values() {
+            if let ExportIndex::Func(func_index) = export_index {
+                func_export_set.insert(*func_index);
+            }
+        }
+        if let Some(start_func_index) = module.start_func {
+            func_export_set.insert(start_func_index);
+        }
+
+        Self {
+            func_export_set,
+            code,
+        }
+    }
+}
+
+impl ProtectedCaller for Caller {
+    fn call(
+        &self,
+        module: &ModuleInner,
+        func_index: FuncIndex,
+        params: &[Value],
+        import_backing: &ImportBacking,
+        vmctx: *mut vm::Ctx,
+        _: Token,
+    ) -> RuntimeResult<Vec<Value>> {
+        assert!(self.func_export_set.contains(&func_index));
+
+        let sig_index = module.func_assoc[func_index];
+        let signature = module.sig_registry.lookup_signature(sig_index);
+
+        assert!(
+            signature.returns().len() <= 1,
+            "multi-value returns not yet supported"
+        );
+
+        assert!(
+            signature.check_param_value_types(params),
+            "incorrect signature"
+        );
+
+        let (func_ptr, vmctx_ptr) = match func_index.local_or_import(module) {
+            LocalOrImport::Local(local_func_index) => (
+                self.code
+                    .func(local_func_index)
+                    .expect("broken invariant, func resolver not synced with module.exports")
+                    .as_ptr() as *const vm::Func,
+                vmctx,
+            ),
+            LocalOrImport::Import(imported_func_index) => {
+                let imported_func = import_backing.imported_func(imported_func_index);
+                (imported_func.func, imported_func.vmctx)
+            }
+        };
+
+        let args: Vec<u64> = params.iter().map(|val| val.to_bits()).collect();
+        let mut returns = vec![0u64; signature.returns().len()];
+
+        let trampoline = self
+            .code
+            .trampoline(sig_index)
+            .expect("that trampoline doesn't exist");
+
+        call_protected(&self.code.handler_data, vmctx_ptr, || unsafe {
+            trampoline(vmctx_ptr, func_ptr, args.as_ptr(), returns.as_mut_ptr());
+        })?;
+
+        Ok(returns
+            .iter()
+            .zip(signature.returns().iter())
+            .map(|(&x, &ty)| Value::from_bits(ty, x))
+            .collect())
+    }
+}
@@ -0,0 +1,1519 @@
+//! Emits machine code for function bodies, one operator at a time.
+//!
+//! The generated code is a plain stack machine: every local and
+//! every operand lives in an 8-byte slot of the native frame, and
+//! each operator loads its operands into fixed registers, computes
+//! its result and stores it back. The operand stack height at each
+//! operator is known statically, so every slot has a fixed address.
+//!
+//! The frame of a function looks like this:
+//!
+//! ```text
+//! [rbp + 16 + 8k]          stack arguments
+//! [rbp + 8]                return address
+//! [rbp]                    saved rbp
+//! [rbp - 8]                vmctx
+//! [rbp - 16 - 8i]          local i, parameters first
+//! [rbp - 16 - 8(l + h)]    operand slot h, with l locals
+//! ```
+//!
+//! 32-bit values are always stored zero-extended.
+
+use crate::{
+    emitter::{mem, Alu, Assembler, Cond, Label, Mem, Reg, Shift, SseOp, Xmm},
+    helpers,
+};
+use hashbrown::HashMap;
+use std::sync::Arc;
+use wasmer_clif_backend::trap::{SourceLoc, TrapCode, TrapData, TrapSink};
+use wasmer_runtime_core::{
+    error::{CompileError, CompileResult},
+    memory::MemoryType,
+    module::ModuleInner,
+    structures::TypedIndex,
+    types::{
+        FuncIndex, FuncSig, GlobalIndex, LocalFuncIndex, LocalOrImport, MemoryIndex, SigIndex,
+        TableIndex, Type,
+    },
+    vm, vmcalls,
+};
+use wasmparser::{Operator, Type as WpType};
+
+const INT_ARG_REGS: [Reg; 6] = [Reg::RDI, Reg::RSI, Reg::RDX, Reg::RCX, Reg::R8, Reg::R9];
+const FLOAT_ARG_REGS: usize = 8;
+
+/// Static memories are followed by a 2GiB guard region, so accesses
+/// with a smaller offset can rely on it instead of a bounds check.
+const GUARDED_OFFSET: u32 = 0x7fff_0000;
+
+/// The widest access to memory, which the guard region must cover
+/// past `GUARDED_OFFSET`.
+const MAX_ACCESS_SIZE: u64 = 8;
+
+/// Where an argument is passed on the System V ABI.
+#[derive(Debug, Clone, Copy)]
+enum ArgLoc {
+    Int(Reg),
+    Float(Xmm),
+    /// The index of the argument among the ones passed on the stack.
+    Stack(usize),
+}
+
+/// Assigns a location to each parameter of `signature`, followed by
+/// the vmctx, and returns them with the number of stack arguments.
+fn arg_locs(signature: &FuncSig) -> (Vec<ArgLoc>, usize) {
+    let (mut ints, mut floats, mut stack) = (0, 0, 0);
+    let mut locs = Vec::with_capacity(signature.params().len() + 1);
+
+    for is_float in signature
+        .params()
+        .iter()
+        .map(|&ty| ty == Type::F32 || ty == Type::F64)
+        .chain(Some(false))
+    {
+        let loc = if is_float && floats < FLOAT_ARG_REGS {
+            floats += 1;
+            ArgLoc::Float(Xmm(floats as u8 - 1))
+        } else if !is_float && ints < INT_ARG_REGS.len() {
+            ints += 1;
+            ArgLoc::Int(INT_ARG_REGS[ints - 1])
+        } else {
+            stack += 1;
+            ArgLoc::Stack(stack - 1)
+        };
+        locs.push(loc);
+    }
+
+    (locs, stack)
+}
+
+/// Where the code of a module compiled for tiering finds the
+/// entry point of each local function, and counts its calls.
+/// Both are arrays of 8-byte words, indexed by local function.
+#[derive(Debug, Clone, Copy)]
+pub struct TierTables {
+    pub entries: usize,
+    pub counts: usize,
+}
+
+/// The code of every function in a module, and the traps in it.
+pub struct ModuleCodegen {
+    pub asm: Assembler,
+    pub trap_sink: TrapSink,
+    func_labels: Vec<Label>,
+    stub_labels: Vec<Label>,
+    /// The offset of the trampoline for each signature.
+    pub trampolines: HashMap<SigIndex, usize>,
+    /// Set when compiling for tiering, before any function body.
+    pub tier_tables: Option<TierTables>,
+    /// Set in deterministic mode.
+    pub canonicalize_nans: bool,
+}
+
+impl ModuleCodegen {
+    pub fn new() -> Self {
+        Self {
+            asm: Assembler::new(),
+            trap_sink: TrapSink::new(),
+            func_labels: Vec::new(),
+            stub_labels: Vec::new(),
+            trampolines: HashMap::new(),
+            tier_tables: None,
+            canonicalize_nans: false,
+        }
+    }
+
+    /// The label at the entry point of a local function,
+    /// which may not have been compiled yet.
+    pub fn func_label(&mut self, local_func_index: LocalFuncIndex) -> Label {
+        while self.func_labels.len() <= local_func_index.index() {
+            let label = self.asm.new_label();
+            self.func_labels.push(label);
+        }
+        self.func_labels[local_func_index.index()]
+    }
+
+    /// The label that calls to a local function go through: its
+    /// stub when compiling for tiering, and its body otherwise.
+    pub fn entry_label(&mut self, local_func_index: LocalFuncIndex) -> Label {
+        if self.tier_tables.is_none() {
+            return self.func_label(local_func_index);
+        }
+        while self.stub_labels.len() <= local_func_index.index() {
+            let label = self.asm.new_label();
+            self.stub_labels.push(label);
+        }
+        self.stub_labels[local_func_index.index()]
+    }
+
+    /// Emits the stubs of the first `num_funcs` local functions when
+    /// compiling for tiering. Each one jumps to the current entry point
+    /// of its function, so that it can be replaced while the code runs.
+    pub fn stubs(&mut self, num_funcs: usize) {
+        let tables = match self.tier_tables {
+            Some(tables) => tables,
+            None => return,
+        };
+        for index in 0..num_funcs {
+            let label = self.entry_label(LocalFuncIndex::new(index));
+            let asm = &mut self.asm;
+            asm.bind(label);
+            asm.mov_imm64(Reg::RAX, (tables.entries + 8 * index) as u64);
+            asm.jmp_m(mem(Reg::RAX, 0));
+        }
+    }
+
+    fn trap_at(&mut self, offset: usize, trapcode: TrapCode, memory: Option<MemoryIndex>) {
+        self.trap_sink.insert(
+            offset,
+            TrapData {
+                trapcode,
+                srcloc: SourceLoc::default(),
+                memory,
+            },
+        );
+    }
+
+    fn ud2(&mut self, trapcode: TrapCode) {
+        let offset = self.asm.ud2();
+        self.trap_at(offset, trapcode, None);
+    }
+
+    /// Emits a trampoline that calls functions with `signature` on
+    /// behalf of the runtime. Trampolines have the signature
+    /// `extern "C" fn(vmctx, func, args: *const u64, returns: *mut u64)`.
+    pub fn trampoline(&mut self, sig_index: SigIndex, signature: &FuncSig) {
+        let asm = &mut self.asm;
+        self.trampolines.insert(sig_index, asm.offset());
+
+        asm.push_r(Reg::RBP);
+        asm.mov_rr(true, Reg::RBP, Reg::RSP);
+        asm.push_r(Reg::RBX);
+        asm.push_r(Reg::R12);
+        asm.mov_rr(true, Reg::RBX, Reg::RDI);
+        asm.mov_rr(true, Reg::R10, Reg::RSI);
+        asm.mov_rr(true, Reg::R11, Reg::RDX);
+        asm.mov_rr(true, Reg::R12, Reg::RCX);
+
+        let (locs, num_stack) = arg_locs(signature);
+        let num_params = signature.params().len();
+        if num_stack % 2 == 1 {
+            asm.alu_ri(Alu::Sub, true, Reg::RSP, 8);
+        }
+        for (i, &loc) in locs.iter().enumerate().rev() {
+            if let ArgLoc::Stack(_) = loc {
+                if i == num_params {
+                    asm.push_r(Reg::RBX);
+                } else {
+                    asm.push_m(mem(Reg::R11, 8 * i as i32));
+                }
+            }
+        }
+        for (i, &loc) in locs.iter().enumerate() {
+            match loc {
+                ArgLoc::Int(reg) if i == num_params => asm.mov_rr(true, reg, Reg::RBX),
+                ArgLoc::Int(reg) => asm.mov_load(true, reg, mem(Reg::R11, 8 * i as i32)),
+                ArgLoc::Float(xmm) => asm.movq_load(xmm, mem(Reg::R11, 8 * i as i32)),
+                ArgLoc::Stack(_) => {}
+            }
+        }
+        asm.call_r(Reg::R10);
+
+        match signature.returns().first() {
+            Some(&Type::F32) | Some(&Type::F64) => asm.movq_store(mem(Reg::R12, 0), Xmm(0)),
+            Some(_) => asm.mov_store(true, mem(Reg::R12, 0), Reg::RAX),
+            None => {}
+        }
+
+        asm.lea(Reg::RSP, mem(Reg::RBP, -16));
+        asm.pop_r(Reg::R12);
+        asm.pop_r(Reg::RBX);
+        asm.pop_r(Reg::RBP);
+        asm.ret();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ControlKind {
+    Block,
+    Loop,
+    If {
+        else_label: Label,
+    },
+    /// An `if` that has reached its `else`.
+    Else,
+    Func,
+}
+
+#[derive(Debug)]
+struct Control {
+    kind: ControlKind,
+    /// The end of the block, or the start of a loop.
+    label: Label,
+    /// The operand stack height when the block was entered.
+    height: u32,
+    /// The number of values the block leaves on the stack.
+    arity: u32,
+    /// Whether the block was entered from unreachable code.
+    entered_unreachable: bool,
+    /// Whether the rest of the block is unreachable.
+    unreachable: bool,
+}
+
+/// The callee of a call, once its arguments are on the operand stack.
+enum Callee {
+    /// A local function, called with the caller's vmctx.
+    Local(Label),
+    /// A function whose address is in `r10` and vmctx in `r11`.
+    Loaded,
+}
+
+/// Emits the code of one function body.
+pub struct FuncCodegen {
+    local_func_index: LocalFuncIndex,
+    signature: Arc<FuncSig>,
+    num_locals: usize,
+    controls: Vec<Control>,
+    height: u32,
+    max_height: u32,
+    /// Where the size of the frame goes, once it's known.
+    frame_size_at: usize,
+}
+
+impl FuncCodegen {
+    pub fn new(
+        codegen: &mut ModuleCodegen,
+        module: &ModuleInner,
+        local_func_index: LocalFuncIndex,
+    ) -> Self {
+        let func_index = FuncIndex::new(module.imported_functions.len() + local_func_index.index());
+        let signature = module
+            .sig_registry
+            .lookup_signature(module.func_assoc[func_index]);
+
+        let label = codegen.func_label(local_func_index);
+        codegen.asm.bind(label);
+
+        Self {
+            local_func_index,
+            num_locals: signature.params().len(),
+            signature,
+            controls: Vec::new(),
+            height: 0,
+            max_height: 0,
+            frame_size_at: 0,
+        }
+    }
+
+    /// Declares the locals of the function, which must
+    /// happen before the first operator is translated.
+    pub fn declare_locals(&mut self, locals: &[(u32, WpType)]) {
+        self.num_locals += locals
+            .iter()
+            .map(|&(count, _)| count as usize)
+            .sum::<usize>();
+    }
+
+    /// Translates one operator. `signatures` maps every type
+    /// index in the module to its signature.
+    pub fn translate(
+        &mut self,
+        codegen: &mut ModuleCodegen,
+        module: &ModuleInner,
+        signatures: &[SigIndex],
+        op: &Operator,
+    ) -> CompileResult<()> {
+        if self.controls.is_empty() {
+            self.prologue(codegen);
+        }
+
+        if self.is_unreachable() {
+            match *op {
+                Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                    let label = codegen.asm.new_label();
+                    let height = self.height;
+                    self.push_control(ControlKind::Block, label, height, 0);
+                    return Ok(());
+                }
+                Operator::Else | Operator::End => {}
+                _ => return Ok(()),
+            }
+        }
+
+        match *op {
+            Operator::Unreachable => {
+                codegen.ud2(TrapCode::UnreachableCodeReached);
+                self.set_unreachable();
+            }
+            Operator::Nop => {}
+            Operator::Block { ty } => {
+                let label = codegen.asm.new_label();
+                let height = self.height;
+                self.push_control(ControlKind::Block, label, height, block_arity(ty)?);
+            }
+            Operator::Loop { ty } => {
+                let label = codegen.asm.new_label();
+                codegen.asm.bind(label);
+                let height = self.height;
+                self.push_control(ControlKind::Loop, label, height, block_arity(ty)?);
+            }
+            Operator::If { ty } => {
+                self.pop(1);
+                let else_label = codegen.asm.new_label();
+                let asm = &mut codegen.asm;
+                asm.mov_load(false, Reg::RAX, self.slot(self.height));
+                asm.test_rr(false, Reg::RAX, Reg::RAX);
+                asm.jcc(Cond::E, else_label);
+
+                let label = asm.new_label();
+                let height = self.height;
+                self.push_control(
+                    ControlKind::If { else_label },
+                    label,
+                    height,
+                    block_arity(ty)?,
+                );
+            }
+            Operator::Else => {
+                let control = self.controls.last_mut().unwrap();
+                if let ControlKind::If { else_label } = control.kind {
+                    control.kind = ControlKind::Else;
+                    let reachable = !control.unreachable;
+                    control.unreachable = control.entered_unreachable;
+                    let (label, height) = (control.label, control.height);
+
+                    if reachable {
+                        codegen.asm.jmp(label);
+                    }
+                    codegen.asm.bind(else_label);
+                    self.height = height;
+                }
+            }
+            Operator::End => {
+                let control = self.controls.pop().unwrap();
+                if let ControlKind::If { else_label } = control.kind {
+                    codegen.asm.bind(else_label);
+                }
+                if control.kind != ControlKind::Loop {
+                    codegen.asm.bind(control.label);
+                }
+                self.height = control.height + control.arity;
+
+                if control.kind == ControlKind::Func {
+                    self.epilogue(codegen);
+                }
+            }
+            Operator::Br { relative_depth } => {
+                self.branch(codegen, relative_depth);
+                self.set_unreachable();
+            }
+            Operator::BrIf { relative_depth } => {
+                self.pop(1);
+                let skip = codegen.asm.new_label();
+                let asm = &mut codegen.asm;
+                asm.mov_load(false, Reg::RAX, self.slot(self.height));
+                asm.test_rr(false, Reg::RAX, Reg::RAX);
+                asm.jcc(Cond::E, skip);
+                self.branch(codegen, relative_depth);
+                codegen.asm.bind(skip);
+            }
+            Operator::BrTable { ref table } => {
+                self.pop(1);
+                let (depths, default) =
+                    table
+                        .read_table()
+                        .map_err(|err| CompileError::InternalError {
+                            msg: err.message.to_string(),
+                        })?;
+
+                // The branches only use rax, so the index stays in rcx.
+                codegen
+                    .asm
+                    .mov_load(false, Reg::RCX, self.slot(self.height));
+                for (i, &depth) in depths.iter().enumerate() {
+                    let next = codegen.asm.new_label();
+                    codegen.asm.alu_ri(Alu::Cmp, false, Reg::RCX, i as i32);
+                    codegen.asm.jcc(Cond::NE, next);
+                    self.branch(codegen, depth);
+                    codegen.asm.bind(next);
+                }
+                self.branch(codegen, default);
+                self.set_unreachable();
+            }
+            Operator::Return => {
+                let depth = self.controls.len() as u32 - 1;
+                self.branch(codegen, depth);
+                self.set_unreachable();
+            }
+            Operator::Call { function_index } => {
+                let func_index = FuncIndex::new(function_index as usize);
+                let signature = module
+                    .sig_registry
+                    .lookup_signature(module.func_assoc[func_index]);
+
+                let callee = match func_index.local_or_import(module) {
+                    LocalOrImport::Local(local_func_index) => {
+                        Callee::Local(codegen.entry_label(local_func_index))
+                    }
+                    LocalOrImport::Import(imported_func_index) => {
+                        let asm = &mut codegen.asm;
+                        let offset = imported_func_index.index() as i32
+                            * i32::from(vm::ImportedFunc::size());
+                        asm.mov_load(true, Reg::RAX, self.vmctx());
+                        asm.mov_load(
+                            true,
+                            Reg::RAX,
+                            mem(Reg::RAX, i32::from(vm::Ctx::offset_imported_funcs())),
+                        );
+                        asm.mov_load(
+                            true,
+                            Reg::R10,
+                            mem(
+                                Reg::RAX,
+                                offset + i32::from(vm::ImportedFunc::offset_func()),
+                            ),
+                        );
+                        asm.mov_load(
+                            true,
+                            Reg::R11,
+                            mem(
+                                Reg::RAX,
+                                offset + i32::from(vm::ImportedFunc::offset_vmctx()),
+                            ),
+                        );
+                        Callee::Loaded
+                    }
+                };
+                self.call(codegen, &signature, callee);
+            }
+            Operator::CallIndirect { index, .. } => {
+                let sig_index = signatures[index as usize];
+                let signature = module.sig_registry.lookup_signature(sig_index);
+
+                self.pop(1);
+                self.load_table(codegen, module, Reg::RAX);
+                let ok = codegen.asm.new_label();
+                let asm = &mut codegen.asm;
+                asm.mov_load(false, Reg::RCX, self.slot(self.height));
+                asm.alu_rm(
+                    Alu::Cmp,
+                    true,
+                    Reg::RCX,
+                    mem(Reg::RAX, i32::from(vm::LocalTable::offset_count())),
+                );
+                asm.jcc(Cond::B, ok);
+                codegen.ud2(TrapCode::TableOutOfBounds);
+                codegen.asm.bind(ok);
+
+                let not_null = codegen.asm.new_label();
+                let asm = &mut codegen.asm;
+                asm.imul_rri(true, Reg::RCX, Reg::RCX, i32::from(vm::Anyfunc::size()));
+                asm.alu_rm(
+                    Alu::Add,
+                    true,
+                    Reg::RCX,
+                    mem(Reg::RAX, i32::from(vm::LocalTable::offset_base())),
+                );
+                asm.mov_load(
+                    true,
+                    Reg::R10,
+                    mem(Reg::RCX, i32::from(vm::Anyfunc::offset_func())),
+                );
+                asm.test_rr(true, Reg::R10, Reg::R10);
+                asm.jcc(Cond::NE, not_null);
+                codegen.ud2(TrapCode::IndirectCallToNull);
+                codegen.asm.bind(not_null);
+
+                let same_sig = codegen.asm.new_label();
+                let asm = &mut codegen.asm;
+                asm.alu_mi(
+                    Alu::Cmp,
+                    false,
+                    mem(Reg::RCX, i32::from(vm::Anyfunc::offset_sig_id())),
+                    sig_index.index() as i32,
+                );
+                asm.jcc(Cond::E, same_sig);
+                codegen.ud2(TrapCode::BadSignature);
+                codegen.asm.bind(same_sig);
+                codegen.asm.mov_load(
+                    true,
+                    Reg::R11,
+                    mem(Reg::RCX, i32::from(vm::Anyfunc::offset_vmctx())),
+                );
+
+                self.call(codegen, &signature, Callee::Loaded);
+            }
+            Operator::Drop => self.pop(1),
+            Operator::Select => {
+                self.pop(2);
+                let keep = codegen.asm.new_label();
+                let asm = &mut codegen.asm;
+                asm.mov_load(false, Reg::RAX, self.slot(self.height + 1));
+                asm.test_rr(false, Reg::RAX, Reg::RAX);
+                asm.jcc(Cond::NE, keep);
+                asm.mov_load(true, Reg::RAX, self.slot(self.height));
+                asm.mov_store(true, self.slot(self.height - 1), Reg::RAX);
+                asm.bind(keep);
+            }
+            Operator::GetLocal { local_index } => {
+                let asm = &mut codegen.asm;
+                asm.mov_load(true, Reg::RAX, self.local(local_index));
+                asm.mov_store(true, self.slot(self.height), Reg::RAX);
+                self.push(1);
+            }
+            Operator::SetLocal { local_index } => {
+                self.pop(1);
+                let asm = &mut codegen.asm;
+                asm.mov_load(true, Reg::RAX, self.slot(self.height));
+                asm.mov_store(true, self.local(local_index), Reg::RAX);
+            }
+            Operator::TeeLocal { local_index } => {
+                let asm = &mut codegen.asm;
+                asm.mov_load(true, Reg::RAX, self.slot(self.height - 1));
+                asm.mov_store(true, self.local(local_index), Reg::RAX);
+            }
+            Operator::GetGlobal { global_index } => {
+                self.load_global(codegen, module, global_index, Reg::RCX);
+                let asm = &mut codegen.asm;
+                asm.mov_load(
+                    true,
+                    Reg::RAX,
+                    mem(Reg::RCX, i32::from(vm::LocalGlobal::offset_data())),
+                );
+                asm.mov_store(true, self.slot(self.height), Reg::RAX);
+                self.push(1);
+            }
+            Operator::SetGlobal { global_index } => {
+                self.pop(1);
+                self.load_global(codegen, module, global_index, Reg::RCX);
+                let asm = &mut codegen.asm;
+                asm.mov_load(true, Reg::RAX, self.slot(self.height));
+                asm.mov_store(
+                    true,
+                    mem(Reg::RCX, i32::from(vm::LocalGlobal::offset_data())),
+                    Reg::RAX,
+                );
+            }
+
+            Operator::I32Load { ref memarg } | Operator::F32Load { ref memarg } => {
+                self.load(codegen, module, memarg.offset, 4, |asm, at| {
+                    asm.mov_load(false, Reg::RAX, at)
+                })
+            }
+            Operator::I64Load { ref memarg } | Operator::F64Load { ref memarg } => {
+                self.load(codegen, module, memarg.offset, 8, |asm, at| {
+                    asm.mov_load(true, Reg::RAX, at)
+                })
+            }
+            Operator::I32Load8S { ref memarg } => {
+                self.load(codegen, module, memarg.offset, 1, |asm, at| {
+                    asm.movsx8_load(false, Reg::RAX, at)
+                })
+            }
+            Operator::I32Load16S { ref memarg } => {
+                self.load(codegen, module, memarg.offset, 2, |asm, at| {
+                    asm.movsx16_load(false, Reg::RAX, at)
+                })
+            }
+            Operator::I64Load8S { ref memarg } => {
+                self.load(codegen, module, memarg.offset, 1, |asm, at| {
+                    asm.movsx8_load(true, Reg::RAX, at)
+                })
+            }
+            Operator::I64Load16S { ref memarg } => {
+                self.load(codegen, module, memarg.offset, 2, |asm, at| {
+                    asm.movsx16_load(true, Reg::RAX, at)
+                })
+            }
+            Operator::I64Load32S { ref memarg } => {
+                self.load(codegen, module, memarg.offset, 4, |asm, at| {
+                    asm.movsxd_load(Reg::RAX, at)
+                })
+            }
+            Operator::I32Load8U { ref memarg } | Operator::I64Load8U { ref memarg } => {
+                self.load(codegen, module, memarg.offset, 1, |asm, at| {
+                    asm.movzx8_load(Reg::RAX, at)
+                })
+            }
+            Operator::I32Load16U { ref memarg } | Operator::I64Load16U { ref memarg } => {
+                self.load(codegen, module, memarg.offset, 2, |asm, at| {
+                    asm.movzx16_load(Reg::RAX, at)
+                })
+            }
+            Operator::I64Load32U { ref memarg } => {
+                self.load(codegen, module, memarg.offset, 4, |asm, at| {
+                    asm.mov_load(false, Reg::RAX, at)
+                })
+            }
+
+            Operator::I32Store { ref memarg }
+            | Operator::F32Store { ref memarg }
+            | Operator::I64Store32 { ref memarg } => {
+                self.store(codegen, module, memarg.offset, 4, |asm, at| {
+                    asm.mov_store(false, at, Reg::RDX)
+                })
+            }
+            Operator::I64Store { ref memarg } | Operator::F64Store { ref memarg } => {
+                self.store(codegen, module, memarg.offset, 8, |asm, at| {
+                    asm.mov_store(true, at, Reg::RDX)
+                })
+            }
+            Operator::I32Store8 { ref memarg } | Operator::I64Store8 { ref memarg } => {
+                self.store(codegen, module, memarg.offset, 1, |asm, at| {
+                    asm.mov_store8(at, Reg::RDX)
+                })
+            }
+            Operator::I32Store16 { ref memarg } | Operator::I64Store16 { ref memarg } => self
+                .store(codegen, module, memarg.offset, 2, |asm, at| {
+                    asm.mov_store16(at, Reg::RDX)
+                }),
+
+            Operator::MemorySize { .. } => {
+                self.load_memory(codegen, module, Reg::RCX);
+                let asm = &mut codegen.asm;
+                asm.mov_load(
+                    true,
+                    Reg::RAX,
+                    mem(Reg::RCX, i32::from(vm::LocalMemory::offset_bound())),
+                );
+                asm.shift_ri(Shift::Shr, true, Reg::RAX, 16);
+                asm.mov_store(true, self.slot(self.height), Reg::RAX);
+                self.push(1);
+            }
+            Operator::MemoryGrow { .. } => {
+                let (index, grow) = match MemoryIndex::new(0).local_or_import(module) {
+                    LocalOrImport::Local(index) => (
+                        index.index(),
+                        match module.memories[index].memory_type() {
+                            MemoryType::Dynamic => vmcalls::local_dynamic_memory_grow as usize,
+                            MemoryType::Static | MemoryType::SharedStatic => {
+                                vmcalls::local_static_memory_grow as usize
+                            }
+                        },
+                    ),
+                    LocalOrImport::Import(index) => (
+                        index.index(),
+                        match module.imported_memories[index].1.memory_type() {
+                            MemoryType::Dynamic => vmcalls::imported_dynamic_memory_grow as usize,
+                            MemoryType::Static | MemoryType::SharedStatic => {
+                                vmcalls::imported_static_memory_grow as usize
+                            }
+                        },
+                    ),
+                };
+
+                let asm = &mut codegen.asm;
+                asm.mov_imm32(Reg::RDI, index as u32);
+                asm.mov_load(false, Reg::RSI, self.slot(self.height - 1));
+                asm.mov_load(true, Reg::RDX, self.vmctx());
+                asm.mov_imm64(Reg::RAX, grow as u64);
+                asm.call_r(Reg::RAX);
+                asm.mov_rr(false, Reg::RAX, Reg::RAX);
+                asm.mov_store(true, self.slot(self.height - 1), Reg::RAX);
+            }
+
+            Operator::I32Const { value } => {
+                let asm = &mut codegen.asm;
+                asm.mov_imm32(Reg::RAX, value as u32);
+                asm.mov_store(true, self.slot(self.height), Reg::RAX);
+                self.push(1);
+            }
+            Operator::F32Const { ref value } => {
+                let asm = &mut codegen.asm;
+                asm.mov_imm32(Reg::RAX, value.bits());
+                asm.mov_store(true, self.slot(self.height), Reg::RAX);
+                self.push(1);
+            }
+            Operator::I64Const { value } => {
+                let asm = &mut codegen.asm;
+                asm.mov_imm64(Reg::RAX, value as u64);
+                asm.mov_store(true, self.slot(self.height), Reg::RAX);
+                self.push(1);
+            }
+            Operator::F64Const { ref value } => {
+                let asm = &mut codegen.asm;
+                asm.mov_imm64(Reg::RAX, value.bits());
+                asm.mov_store(true, self.slot(self.height), Reg::RAX);
+                self.push(1);
+            }
+
+            // Reinterpreting a value doesn't change its bits.
+            Operator::I32ReinterpretF32
+            | Operator::I64ReinterpretF64
+            | Operator::F32ReinterpretI32
+            | Operator::F64ReinterpretI64 => {}
+
+            Operator::I32Eqz => self.unary_int(codegen, false, |asm| {
+                asm.alu_ri(Alu::Cmp, false, Reg::RAX, 0);
+                asm.setcc_eax(Cond::E);
+            }),
+            Operator::I64Eqz => self.unary_int(codegen, true, |asm| {
+                asm.alu_ri(Alu::Cmp, true, Reg::RAX, 0);
+                asm.setcc_eax(Cond::E);
+            }),
+            Operator::I32WrapI64 | Operator::I64ExtendUI32 => {
+                self.unary_int(codegen, false, |_| {})
+            }
+            Operator::I64ExtendSI32 | Operator::I64Extend32S => {
+                let asm = &mut codegen.asm;
+                let slot = self.slot(self.height - 1);
+                asm.movsxd_load(Reg::RAX, slot);
+                asm.mov_store(true, slot, Reg::RAX);
+            }
+            Operator::I32Extend8S => {
+                self.extend(codegen, |asm, at| asm.movsx8_load(false, Reg::RAX, at))
+            }
+            Operator::I32Extend16S => {
+                self.extend(codegen, |asm, at| asm.movsx16_load(false, Reg::RAX, at))
+            }
+            Operator::I64Extend8S => {
+                self.extend(codegen, |asm, at| asm.movsx8_load(true, Reg::RAX, at))
+            }
+            Operator::I64Extend16S => {
+                self.extend(codegen, |asm, at| asm.movsx16_load(true, Reg::RAX, at))
+            }
+
+            // `abs` and `neg` only touch the sign bit, even for NaNs.
+            Operator::F32Abs => self.unary_int(codegen, false, |asm| {
+                asm.alu_ri(Alu::And, false, Reg::RAX, 0x7fff_ffff)
+            }),
+            Operator::F32Neg => self.unary_int(codegen, false, |asm| {
+                asm.alu_ri(Alu::Xor, false, Reg::RAX, 0x8000_0000u32 as i32)
+            }),
+            Operator::F64Abs => self.unary_int(codegen, true, |asm| {
+                asm.mov_imm64(Reg::RCX, 0x7fff_ffff_ffff_ffff);
+                asm.alu_rr(Alu::And, true, Reg::RAX, Reg::RCX);
+            }),
+            Operator::F64Neg => self.unary_int(codegen, true, |asm| {
+                asm.mov_imm64(Reg::RCX, 0x8000_0000_0000_0000);
+                asm.alu_rr(Alu::Xor, true, Reg::RAX, Reg::RCX);
+            }),
+            Operator::F32Sqrt => self.sqrt(codegen, false),
+            Operator::F64Sqrt => self.sqrt(codegen, true),
+
+            Operator::I32Eq => self.compare(codegen, false, Cond::E),
+            Operator::I32Ne => self.compare(codegen, false, Cond::NE),
+            Operator::I32LtS => self.compare(codegen, false, Cond::L),
+            Operator::I32LtU => self.compare(codegen, false, Cond::B),
+            Operator::I32GtS => self.compare(codegen, false, Cond::G),
+            Operator::I32GtU => self.compare(codegen, false, Cond::A),
+            Operator::I32LeS => self.compare(codegen, false, Cond::LE),
+            Operator::I32LeU => self.compare(codegen, false, Cond::BE),
+            Operator::I32GeS => self.compare(codegen, false, Cond::GE),
+            Operator::I32GeU => self.compare(codegen, false, Cond::AE),
+            Operator::I64Eq => self.compare(codegen, true, Cond::E),
+            Operator::I64Ne => self.compare(codegen, true, Cond::NE),
+            Operator::I64LtS => self.compare(codegen, true, Cond::L),
+            Operator::I64LtU => self.compare(codegen, true, Cond::B),
+            Operator::I64GtS => self.compare(codegen, true, Cond::G),
+            Operator::I64GtU => self.compare(codegen, true, Cond::A),
+            Operator::I64LeS => self.compare(codegen, true, Cond::LE),
+            Operator::I64LeU => self.compare(codegen, true, Cond::BE),
+            Operator::I64GeS => self.compare(codegen, true, Cond::GE),
+            Operator::I64GeU => self.compare(codegen, true, Cond::AE),
+
+            Operator::I32Add => self.binary_alu(codegen, Alu::Add, false),
+            Operator::I32Sub => self.binary_alu(codegen, Alu::Sub, false),
+            Operator::I32And => self.binary_alu(codegen, Alu::And, false),
+            Operator::I32Or => self.binary_alu(codegen, Alu::Or, false),
+            Operator::I32Xor => self.binary_alu(codegen, Alu::Xor, false),
+            Operator::I64Add => self.binary_alu(codegen, Alu::Add, true),
+            Operator::I64Sub => self.binary_alu(codegen, Alu::Sub, true),
+            Operator::I64And => self.binary_alu(codegen, Alu::And, true),
+            Operator::I64Or => self.binary_alu(codegen, Alu::Or, true),
+            Operator::I64Xor => self.binary_alu(codegen, Alu::Xor, true),
+            Operator::I32Mul => {
+                self.binary_int(codegen, false, |asm| asm.imul_rr(false, Reg::RAX, Reg::RCX))
+            }
+            Operator::I64Mul => {
+                self.binary_int(codegen, true, |asm| asm.imul_rr(true, Reg::RAX, Reg::RCX))
+            }
+            Operator::I32Shl => self.shift(codegen, Shift::Shl, false),
+            Operator::I32ShrS => self.shift(codegen, Shift::Sar, false),
+            Operator::I32ShrU => self.shift(codegen, Shift::Shr, false),
+            Operator::I32Rotl => self.shift(codegen, Shift::Rol, false),
+            Operator::I32Rotr => self.shift(codegen, Shift::Ror, false),
+            Operator::I64Shl => self.shift(codegen, Shift::Shl, true),
+            Operator::I64ShrS => self.shift(codegen, Shift::Sar, true),
+            Operator::I64ShrU => self.shift(codegen, Shift::Shr, true),
+            Operator::I64Rotl => self.shift(codegen, Shift::Rol, true),
+            Operator::I64Rotr => self.shift(codegen, Shift::Ror, true),
+            Operator::I32DivS => self.divide(codegen, true, false, false),
+            Operator::I32DivU => self.divide(codegen, false, false, false),
+            Operator::I32RemS => self.divide(codegen, true, false, true),
+            Operator::I32RemU => self.divide(codegen, false, false, true),
+            Operator::I64DivS => self.divide(codegen, true, true, false),
+            Operator::I64DivU => self.divide(codegen, false, true, false),
+            Operator::I64RemS => self.divide(codegen, true, true, true),
+            Operator::I64RemU => self.divide(codegen, false, true, true),
+
+            Operator::F32Add => self.binary_float(codegen, SseOp::Add, false),
+            Operator::F32Sub => self.binary_float(codegen, SseOp::Sub, false),
+            Operator::F32Mul => self.binary_float(codegen, SseOp::Mul, false),
+            Operator::F32Div => self.binary_float(codegen, SseOp::Div, false),
+            Operator::F64Add => self.binary_float(codegen, SseOp::Add, true),
+            Operator::F64Sub => self.binary_float(codegen, SseOp::Sub, true),
+            Operator::F64Mul => self.binary_float(codegen, SseOp::Mul, true),
+            Operator::F64Div => self.binary_float(codegen, SseOp::Div, true),
+
+            ref op => {
+                if let Some(helper) = unary_helper(op) {
+                    self.call_unary(codegen, helper);
+                } else if let Some(helper) = binary_helper(op) {
+                    self.call_binary(codegen, helper);
+                } else if let Some(helper) = checked_helper(op) {
+                    self.call_checked(codegen, helper);
+                } else {
+                    return Err(CompileError::InternalError {
+                        msg: format!("the single-pass backend doesn't support {:?}", op),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        if codegen.canonicalize_nans {
+            if let Some(helper) = nan_canonicalization(op) {
+                self.call_unary(codegen, helper);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn prologue(&mut self, codegen: &mut ModuleCodegen) {
+        let label = codegen.asm.new_label();
+        let arity = self.signature.returns().len() as u32;
+        self.push_control(ControlKind::Func, label, 0, arity);
+
+        let asm = &mut codegen.asm;
+        if let Some(tables) = codegen.tier_tables {
+            let counter = tables.counts + 8 * self.local_func_index.index();
+            asm.mov_imm64(Reg::RAX, counter as u64);
+            asm.alu_mi(Alu::Add, true, mem(Reg::RAX, 0), 1);
+        }
+        asm.push_r(Reg::RBP);
+        asm.mov_rr(true, Reg::RBP, Reg::RSP);
+        asm.alu_ri(Alu::Sub, true, Reg::RSP, 0);
+        self.frame_size_at = asm.offset() - 4;
+
+        let (locs, _) = arg_locs(&self.signature);
+        let params = self.signature.params();
+        for (i, &loc) in locs.iter().enumerate() {
+            let (dst, wide) = if i == params.len() {
+                (self.vmctx(), true)
+            } else {
+                let ty = params[i];
+                (self.local(i as u32), ty == Type::I64 || ty == Type::F64)
+            };
+            match loc {
+                ArgLoc::Int(reg) => asm.mov_rr(wide, Reg::RAX, reg),
+                ArgLoc::Float(xmm) if wide => {
+                    asm.movq_store(dst, xmm);
+                    continue;
+                }
+                ArgLoc::Float(xmm) => asm.movd_to_reg(Reg::RAX, xmm),
+                ArgLoc::Stack(k) => asm.mov_load(wide, Reg::RAX, mem(Reg::RBP, 16 + 8 * k as i32)),
+            }
+            asm.mov_store(true, dst, Reg::RAX);
+        }
+        for i in params.len()..self.num_locals {
+            asm.mov_store_imm32(true, self.local(i as u32), 0);
+        }
+    }
+
+    fn epilogue(&mut self, codegen: &mut ModuleCodegen) {
+        let asm = &mut codegen.asm;
+        match self.signature.returns().first() {
+            Some(&Type::F32) | Some(&Type::F64) => asm.movq_load(Xmm(0), self.slot(0)),
+            Some(_) => asm.mov_load(true, Reg::RAX, self.slot(0)),
+            None => {}
+        }
+        asm.mov_rr(true, Reg::RSP, Reg::RBP);
+        asm.pop_r(Reg::RBP);
+        asm.ret();
+
+        // The vmctx, the locals and the operands, keeping
+        // the stack 16-byte aligned for calls.
+        let size = 8 + 8 * (self.num_locals + self.max_height as usize);
+        let size = (size + 15) & !15;
+        asm.patch_u32(self.frame_size_at, size as u32);
+    }
+
+    fn vmctx(&self) -> Mem {
+        mem(Reg::RBP, -8)
+    }
+
+    fn local(&self, local_index: u32) -> Mem {
+        mem(Reg::RBP, -16 - 8 * local_index as i32)
+    }
+
+    fn slot(&self, height: u32) -> Mem {
+        mem(Reg::RBP, -16 - 8 * (self.num_locals as i32 + height as i32))
+    }
+
+    /// Copies the result of the branch, if there is one, and
+    /// jumps to the block `relative_depth` levels up.
+    fn branch(&mut self, codegen: &mut ModuleCodegen, relative_depth: u32) {
+        let control = &self.controls[self.controls.len() - 1 - relative_depth as usize];
+        // Branching to a loop goes back to its start,
+        // which takes no values in the MVP.
+        if control.kind != ControlKind::Loop && control.arity == 1 {
+            let (from, to) = (self.height - 1, control.height);
+            if from != to {
+                let asm = &mut codegen.asm;
+                asm.mov_load(true, Reg::RAX, self.slot(from));
+                asm.mov_store(true, self.slot(to), Reg::RAX);
+            }
+        }
+        codegen.asm.jmp(control.label);
+    }
+
+    /// Calls `callee` with the arguments on top of the operand
+    /// stack, and replaces them with the result.
+    fn call(&mut self, codegen: &mut ModuleCodegen, signature: &FuncSig, callee: Callee) {
+        let num_params = signature.params().len() as u32;
+        self.pop(num_params);
+        let base = self.height;
+
+        let (locs, num_stack) = arg_locs(signature);
+        let padding = num_stack % 2;
+        let asm = &mut codegen.asm;
+        if padding == 1 {
+            asm.alu_ri(Alu::Sub, true, Reg::RSP, 8);
+        }
+        for (i, &loc) in locs.iter().enumerate().rev() {
+            if let ArgLoc::Stack(_) = loc {
+                if i == num_params as usize {
+                    match callee {
+                        Callee::Local(_) => asm.push_m(self.vmctx()),
+                        Callee::Loaded => asm.push_r(Reg::R11),
+                    }
+                } else {
+                    asm.push_m(self.slot(base + i as u32));
+                }
+            }
+        }
+        for (i, &loc) in locs.iter().enumerate() {
+            match loc {
+                ArgLoc::Int(reg) if i == num_params as usize => match callee {
+                    Callee::Local(_) => asm.mov_load(true, reg, self.vmctx()),
+                    Callee::Loaded => asm.mov_rr(true, reg, Reg::R11),
+                },
+                ArgLoc::Int(reg) => asm.mov_load(true, reg, self.slot(base + i as u32)),
+                ArgLoc::Float(xmm) => asm.movq_load(xmm, self.slot(base + i as u32)),
+                ArgLoc::Stack(_) => {}
+            }
+        }
+
+        match callee {
+            Callee::Local(label) => asm.call(label),
+            Callee::Loaded => asm.call_r(Reg::R10),
+        }
+        if num_stack + padding > 0 {
+            asm.alu_ri(Alu::Add, true, Reg::RSP, 8 * (num_stack + padding) as i32);
+        }
+
+        if let Some(&ty) = signature.returns().first() {
+            let slot = self.slot(base);
+            match ty {
+                Type::F32 | Type::F64 => Self::store_float(asm, ty == Type::F64, slot, Xmm(0)),
+                Type::I32 => {
+                    asm.mov_rr(false, Reg::RAX, Reg::RAX);
+                    asm.mov_store(true, slot, Reg::RAX);
+                }
+                _ => asm.mov_store(true, slot, Reg::RAX),
+            }
+            self.push(1);
+        }
+    }
+
+    /// Loads a pointer to the `LocalGlobal` of a global into `dst`.
+    fn load_global(
+        &self,
+        codegen: &mut ModuleCodegen,
+        module: &ModuleInner,
+        global_index: u32,
+        dst: Reg,
+    ) {
+        let (offset, index) = match GlobalIndex::new(global_index as usize).local_or_import(module)
+        {
+            LocalOrImport::Local(index) => (vm::Ctx::offset_globals(), index.index()),
+            LocalOrImport::Import(index) => (vm::Ctx::offset_imported_globals(), index.index()),
+        };
+        self.load_from_ctx_array(codegen, offset, index, dst);
+    }
+
+    /// Loads a pointer to the `LocalMemory` of memory 0 into `dst`.
+    fn load_memory(&self, codegen: &mut ModuleCodegen, module: &ModuleInner, dst: Reg) {
+        let (offset, index) = match MemoryIndex::new(0).local_or_import(module) {
+            LocalOrImport::Local(index) => (vm::Ctx::offset_memories(), index.index()),
+            LocalOrImport::Import(index) => (vm::Ctx::offset_imported_memories(), index.index()),
+        };
+        self.load_from_ctx_array(codegen, offset, index, dst);
+    }
+
+    /// Loads a pointer to the `LocalTable` of table 0 into `dst`.
+    fn load_table(&self, codegen: &mut ModuleCodegen, module: &ModuleInner, dst: Reg) {
+        let (offset, index) = match TableIndex::new(0).local_or_import(module) {
+            LocalOrImport::Local(index) => (vm::Ctx::offset_tables(), index.index()),
+            LocalOrImport::Import(index) => (vm::Ctx::offset_imported_tables(), index.index()),
+        };
+        self.load_from_ctx_array(codegen, offset, index, dst);
+    }
+
+    fn load_from_ctx_array(&self, codegen: &mut ModuleCodegen, offset: u8, index: usize, dst: Reg) {
+        let asm = &mut codegen.asm;
+        asm.mov_load(true, dst, self.vmctx());
+        asm.mov_load(true, dst, mem(dst, i32::from(offset)));
+        asm.mov_load(true, dst, mem(dst, 8 * index as i32));
+    }
+
+    /// Whether accesses to memory 0 can skip the bounds check when their
+    /// offset is below `GUARDED_OFFSET`. That holds when the memory reserves
+    /// every address a 32-bit index can reach, followed by a guard region
+    /// that covers the offset, and it wasn't asked to be bounds-checked.
+    fn memory_is_guarded(&self, module: &ModuleInner) -> bool {
+        let desc = match MemoryIndex::new(0).local_or_import(module) {
+            LocalOrImport::Local(index) => module.memories[index],
+            LocalOrImport::Import(index) => module.imported_memories[index].1,
+        };
+        let memory_type = desc.memory_type();
+        !desc.is_bounds_checked()
+            && memory_type.bounds() == Some(1 << 32)
+            && memory_type.guard_size() >= u64::from(GUARDED_OFFSET) + MAX_ACCESS_SIZE
+    }
+
+    /// Computes the address of a `size`-byte access at the
+    /// address in operand slot `height` into `rax`, and returns
+    /// the operand that the access should use.
+    fn address(
+        &self,
+        codegen: &mut ModuleCodegen,
+        module: &ModuleInner,
+        height: u32,
+        offset: u32,
+        size: i32,
+    ) -> Mem {
+        self.load_memory(codegen, module, Reg::RCX);
+        codegen.asm.mov_load(false, Reg::RAX, self.slot(height));
+
+        if offset < GUARDED_OFFSET && self.memory_is_guarded(module) {
+            codegen.asm.alu_rm(
+                Alu::Add,
+                true,
+                Reg::RAX,
+                mem(Reg::RCX, i32::from(vm::LocalMemory::offset_base())),
+            );
+            return mem(Reg::RAX, offset as i32);
+        }
+
+        let in_bounds = codegen.asm.new_label();
+        let asm = &mut codegen.asm;
+        asm.mov_imm32(Reg::RDX, offset);
+        asm.alu_rr(Alu::Add, true, Reg::RAX, Reg::RDX);
+        asm.lea(Reg::RDX, mem(Reg::RAX, size));
+        asm.alu_rm(
+            Alu::Cmp,
+            true,
+            Reg::RDX,
+            mem(Reg::RCX, i32::from(vm::LocalMemory::offset_bound())),
+        );
+        asm.jcc(Cond::BE, in_bounds);
+        let trap = asm.ud2();
+        codegen.trap_at(trap, TrapCode::HeapOutOfBounds, Some(MemoryIndex::new(0)));
+        let asm = &mut codegen.asm;
+        asm.bind(in_bounds);
+        asm.alu_rm(
+            Alu::Add,
+            true,
+            Reg::RAX,
+            mem(Reg::RCX, i32::from(vm::LocalMemory::offset_base())),
+        );
+        mem(Reg::RAX, 0)
+    }
+
+    /// Records that the instruction about to be emitted accesses
+    /// memory, so a fault in it is reported as an out-of-bounds access.
+    fn heap_access(&self, codegen: &mut ModuleCodegen) {
+        let offset = codegen.asm.offset();
+        codegen.trap_at(offset, TrapCode::HeapOutOfBounds, Some(MemoryIndex::new(0)));
+    }
+
+    fn load(
+        &mut self,
+        codegen: &mut ModuleCodegen,
+        module: &ModuleInner,
+        offset: u32,
+        size: i32,
+        emit: impl FnOnce(&mut Assembler, Mem),
+    ) {
+        let at = self.address(codegen, module, self.height - 1, offset, size);
+        self.heap_access(codegen);
+        emit(&mut codegen.asm, at);
+        codegen
+            .asm
+            .mov_store(true, self.slot(self.height - 1), Reg::RAX);
+    }
+
+    /// Stores the value on top of the operand stack, which
+    /// `emit` finds in `rdx`.
+    fn store(
+        &mut self,
+        codegen: &mut ModuleCodegen,
+        module: &ModuleInner,
+        offset: u32,
+        size: i32,
+        emit: impl FnOnce(&mut Assembler, Mem),
+    ) {
+        self.pop(2);
+        let at = self.address(codegen, module, self.height, offset, size);
+        codegen
+            .asm
+            .mov_load(true, Reg::RDX, self.slot(self.height + 1));
+        self.heap_access(codegen);
+        emit(&mut codegen.asm, at);
+    }
+
+    /// Applies `emit` to the operand on top of the stack in `rax`.
+    fn unary_int(
+        &mut self,
+        codegen: &mut ModuleCodegen,
+        wide: bool,
+        emit: impl FnOnce(&mut Assembler),
+    ) {
+        let asm = &mut codegen.asm;
+        let slot = self.slot(self.height - 1);
+        asm.mov_load(wide, Reg::RAX, slot);
+        emit(asm);
+        asm.mov_store(true, slot, Reg::RAX);
+    }
+
+    /// Sign-extends the operand on top of the stack with the load `emit`.
+    fn extend(&mut self, codegen: &mut ModuleCodegen, emit: impl FnOnce(&mut Assembler, Mem)) {
+        let asm = &mut codegen.asm;
+        let slot = self.slot(self.height - 1);
+        emit(asm, slot);
+        asm.mov_store(true, slot, Reg::RAX);
+    }
+
+    /// Applies `emit` to the two operands on top of the
+    /// stack in `rax` and `rcx`, leaving the result in `rax`.
+    fn binary_int(
+        &mut self,
+        codegen: &mut ModuleCodegen,
+        wide: bool,
+        emit: impl FnOnce(&mut Assembler),
+    ) {
+        self.pop(1);
+        let asm = &mut codegen.asm;
+        let (lhs, rhs) = (self.slot(self.height - 1), self.slot(self.height));
+        asm.mov_load(wide, Reg::RAX, lhs);
+        asm.mov_load(wide, Reg::RCX, rhs);
+        emit(asm);
+        asm.mov_store(true, lhs, Reg::RAX);
+    }
+
+    fn binary_alu(&mut self, codegen: &mut ModuleCodegen, op: Alu, wide: bool) {
+        self.binary_int(codegen, wide, |asm| {
+            asm.alu_rr(op, wide, Reg::RAX, Reg::RCX)
+        });
+    }
+
+    fn shift(&mut self, codegen: &mut ModuleCodegen, op: Shift, wide: bool) {
+        // The count is masked to the width of the operand,
+        // just like wasm specifies.
+        self.binary_int(codegen, wide, |asm| asm.shift_cl(op, wide, Reg::RAX));
+    }
+
+    fn compare(&mut self, codegen: &mut ModuleCodegen, wide: bool, cond: Cond) {
+        self.binary_int(codegen, wide, |asm| {
+            asm.alu_rr(Alu::Cmp, wide, Reg::RAX, Reg::RCX);
+            asm.setcc_eax(cond);
+        });
+    }
+
+    /// Division and remainder, which raise `SIGFPE` when dividing
+    /// by zero, and trap on signed overflow.
+    fn divide(&mut self, codegen: &mut ModuleCodegen, signed: bool, wide: bool, remainder: bool) {
+        self.pop(1);
+        let (lhs, rhs) = (self.slot(self.height - 1), self.slot(self.height));
+        let done = codegen.asm.new_label();
+        let asm = &mut codegen.asm;
+        asm.mov_load(wide, Reg::RAX, lhs);
+        asm.mov_load(wide, Reg::RCX, rhs);
+
+        // `idiv` faults when `x` is the smallest integer and the
+        // divisor is -1, so that case is handled separately: `x % -1`
+        // is 0, and `x / -1` is `0 - x`, which only overflows then.
+        let divide = codegen.asm.new_label();
+        if signed {
+            let asm = &mut codegen.asm;
+            asm.alu_ri(Alu::Cmp, wide, Reg::RCX, -1);
+            asm.jcc(Cond::NE, divide);
+            asm.alu_rr(Alu::Xor, false, Reg::RDX, Reg::RDX);
+            if !remainder {
+                let negated = asm.new_label();
+                asm.alu_rr(Alu::Sub, wide, Reg::RDX, Reg::RAX);
+                asm.jcc(Cond::NO, negated);
+                codegen.ud2(TrapCode::IntegerOverflow);
+                let asm = &mut codegen.asm;
+                asm.bind(negated);
+                asm.mov_rr(true, Reg::RAX, Reg::RDX);
+            }
+            codegen.asm.jmp(done);
+        }
+        let asm = &mut codegen.asm;
+        asm.bind(divide);
+
+        if signed {
+            asm.cdq(wide);
+        } else {
+            asm.alu_rr(Alu::Xor, false, Reg::RDX, Reg::RDX);
+        }
+        let div_at = asm.offset();
+        asm.div(signed, wide, Reg::RCX);
+        codegen.trap_at(div_at, TrapCode::IntegerDivisionByZero, None);
+
+        let asm = &mut codegen.asm;
+        asm.bind(done);
+        let result = if remainder { Reg::RDX } else { Reg::RAX };
+        if !wide {
+            asm.mov_rr(false, result, result);
+        }
+        asm.mov_store(true, lhs, result);
+    }
+
+    fn load_float(asm: &mut Assembler, double: bool, dst: Xmm, at: Mem) {
+        if double {
+            asm.movq_load(dst, at);
+        } else {
+            asm.movd_load(dst, at);
+        }
+    }
+
+    fn store_float(asm: &mut Assembler, double: bool, at: Mem, src: Xmm) {
+        if double {
+            asm.movq_store(at, src);
+        } else {
+            asm.movd_to_reg(Reg::RAX, src);
+            asm.mov_store(true, at, Reg::RAX);
+        }
+    }
+
+    fn sqrt(&mut self, codegen: &mut ModuleCodegen, double: bool) {
+        let asm = &mut codegen.asm;
+        let slot = self.slot(self.height - 1);
+        Self::load_float(asm, double, Xmm(0), slot);
+        asm.sse_rr(SseOp::Sqrt, double, Xmm(0), Xmm(0));
+        Self::store_float(asm, double, slot, Xmm(0));
+    }
+
+    fn binary_float(&mut self, codegen: &mut ModuleCodegen, op: SseOp, double: bool) {
+        self.pop(1);
+        let asm = &mut codegen.asm;
+        let (lhs, rhs) = (self.slot(self.height - 1), self.slot(self.height));
+        Self::load_float(asm, double, Xmm(0), lhs);
+        Self::load_float(asm, double, Xmm(1), rhs);
+        asm.sse_rr(op, double, Xmm(0), Xmm(1));
+        Self::store_float(asm, double, lhs, Xmm(0));
+    }
+
+    fn call_unary(&mut self, codegen: &mut ModuleCodegen, helper: helpers::Unary) {
+        let asm = &mut codegen.asm;
+        let slot = self.slot(self.height - 1);
+        asm.mov_load(true, Reg::RDI, slot);
+        asm.mov_imm64(Reg::RAX, helper as usize as u64);
+        asm.call_r(Reg::RAX);
+        asm.mov_store(true, slot, Reg::RAX);
+    }
+
+    fn call_binary(&mut self, codegen: &mut ModuleCodegen, helper: helpers::Binary) {
+        self.pop(1);
+        let asm = &mut codegen.asm;
+        let (lhs, rhs) = (self.slot(self.height - 1), self.slot(self.height));
+        asm.mov_load(true, Reg::RDI, lhs);
+        asm.mov_load(true, Reg::RSI, rhs);
+        asm.mov_imm64(Reg::RAX, helper as usize as u64);
+        asm.call_r(Reg::RAX);
+        asm.mov_store(true, lhs, Reg::RAX);
+    }
+
+    /// Calls a helper that can trap, and raises `SIGFPE`
+    /// with a division by zero if it does.
+    fn call_checked(&mut self, codegen: &mut ModuleCodegen, helper: helpers::Checked) {
+        let ok = codegen.asm.new_label();
+        let asm = &mut codegen.asm;
+        let slot = self.slot(self.height - 1);
+        asm.mov_load(true, Reg::RDI, slot);
+        asm.lea(Reg::RSI, slot);
+        asm.mov_imm64(Reg::RAX, helper as usize as u64);
+        asm.call_r(Reg::RAX);
+        asm.test_rr(false, Reg::RAX, Reg::RAX);
+        asm.jcc(Cond::NE, ok);
+        asm.alu_rr(Alu::Xor, false, Reg::RCX, Reg::RCX);
+        let div_at = asm.offset();
+        asm.div(false, false, Reg::RCX);
+        codegen.trap_at(div_at, TrapCode::BadConversionToInteger, None);
+        codegen.asm.bind(ok);
+    }
+
+    fn push_control(&mut self, kind: ControlKind, label: Label, height: u32, arity: u32) {
+        let unreachable = self.is_unreachable();
+        self.controls.push(Control {
+            kind,
+            label,
+            height,
+            arity,
+            entered_unreachable: unreachable,
+            unreachable,
+        });
+    }
+
+    fn is_unreachable(&self) -> bool {
+        self.controls
+            .last()
+            .map_or(false, |control| control.unreachable)
+    }
+
+    fn set_unreachable(&mut self) {
+        let control = self.controls.last_mut().unwrap();
+        control.unreachable = true;
+        self.height = control.height;
+    }
+
+    fn push(&mut self, count: u32) {
+        self.height += count;
+        self.max_height = self.max_height.max(self.height);
+    }
+
+    fn pop(&mut self, count: u32) {
+        self.height -= count;
+    }
+}
+
+fn block_arity(ty: WpType) -> CompileResult<u32> {
+    match ty {
+        WpType::EmptyBlockType => Ok(0),
+        WpType::I32 | WpType::I64 | WpType::F32 | WpType::F64 => Ok(1),
+        _ => Err(CompileError::InternalError {
+            msg: format!("the single-pass backend doesn't support {:?} blocks", ty),
+        }
+        .into()),
+    }
+}
+
+/// The helper that canonicalizes the result of the float
+/// operators that can produce a NaN from their operands.
+fn nan_canonicalization(op: &Operator) -> Option<helpers::Unary> {
+    match *op {
+        Operator::F32Add
+        | Operator::F32Sub
+        | Operator::F32Mul
+        | Operator::F32Div
+        | Operator::F32Min
+        | Operator::F32Max
+        | Operator::F32Sqrt
+        | Operator::F32Ceil
+        | Operator::F32Floor
+        | Operator::F32Trunc
+        | Operator::F32Nearest
+        | Operator::F32DemoteF64 => Some(helpers::f32_canonicalize_nan),
+        Operator::F64Add
+        | Operator::F64Sub
+        | Operator::F64Mul
+        | Operator::F64Div
+        | Operator::F64Min
+        | Operator::F64Max
+        | Operator::F64Sqrt
+        | Operator::F64Ceil
+        | Operator::F64Floor
+        | Operator::F64Trunc
+        | Operator::F64Nearest
+        | Operator::F64PromoteF32 => Some(helpers::f64_canonicalize_nan),
+        _ => None,
+    }
+}
+
+fn unary_helper(op: &Operator) -> Option<helpers::Unary> {
+    Some(match *op {
+        Operator::I32Clz => helpers::i32_clz,
+        Operator::I32Ctz => helpers::i32_ctz,
+        Operator::I32Popcnt => helpers::i32_popcnt,
+        Operator::I64Clz => helpers::i64_clz,
+        Operator::I64Ctz => helpers::i64_ctz,
+        Operator::I64Popcnt => helpers::i64_popcnt,
+
+        Operator::F32Ceil => helpers::f32_ceil,
+        Operator::F32Floor => helpers::f32_floor,
+        Operator::F32Trunc => helpers::f32_trunc,
+        Operator::F32Nearest => helpers::f32_nearest,
+        Operator::F64Ceil => helpers::f64_ceil,
+        Operator::F64Floor => helpers::f64_floor,
+        Operator::F64Trunc => helpers::f64_trunc,
+        Operator::F64Nearest => helpers::f64_nearest,
+
+        Operator::F32ConvertSI32 => helpers::f32_convert_s_i32,
+        Operator::F32ConvertUI32 => helpers::f32_convert_u_i32,
+        Operator::F32ConvertSI64 => helpers::f32_convert_s_i64,
+        Operator::F32ConvertUI64 => helpers::f32_convert_u_i64,
+        Operator::F32DemoteF64 => helpers::f32_demote_f64,
+        Operator::F64ConvertSI32 => helpers::f64_convert_s_i32,
+        Operator::F64ConvertUI32 => helpers::f64_convert_u_i32,
+        Operator::F64ConvertSI64 => helpers::f64_convert_s_i64,
+        Operator::F64ConvertUI64 => helpers::f64_convert_u_i64,
+        Operator::F64PromoteF32 => helpers::f64_promote_f32,
+
+        Operator::I32TruncSSatF32 => helpers::i32_trunc_s_sat_f32,
+        Operator::I32TruncUSatF32 => helpers::i32_trunc_u_sat_f32,
+        Operator::I32TruncSSatF64 => helpers::i32_trunc_s_sat_f64,
+        Operator::I32TruncUSatF64 => helpers::i32_trunc_u_sat_f64,
+        Operator::I64TruncSSatF32 => helpers::i64_trunc_s_sat_f32,
+        Operator::I64TruncUSatF32 => helpers::i64_trunc_u_sat_f32,
+        Operator::I64TruncSSatF64 => helpers::i64_trunc_s_sat_f64,
+        Operator::I64TruncUSatF64 => helpers::i64_trunc_u_sat_f64,
+
+        _ => return None,
+    })
+}
+
+fn binary_helper(op: &Operator) -> Option<helpers::Binary> {
+    Some(match *op {
+        Operator::F32Eq => helpers::f32_eq,
+        Operator::F32Ne => helpers::f32_ne,
+        Operator::F32Lt => helpers::f32_lt,
+        Operator::F32Gt => helpers::f32_gt,
+        Operator::F32Le => helpers::f32_le,
+        Operator::F32Ge => helpers::f32_ge,
+        Operator::F64Eq => helpers::f64_eq,
+        Operator::F64Ne => helpers::f64_ne,
+        Operator::F64Lt => helpers::f64_lt,
+        Operator::F64Gt => helpers::f64_gt,
+        Operator::F64Le => helpers::f64_le,
+        Operator::F64Ge => helpers::f64_ge,
+
+        Operator::F32Min => helpers::f32_min,
+        Operator::F32Max => helpers::f32_max,
+        Operator::F32Copysign => helpers::f32_copysign,
+        Operator::F64Min => helpers::f64_min,
+        Operator::F64Max => helpers::f64_max,
+        Operator::F64Copysign => helpers::f64_copysign,
+
+        _ => return None,
+    })
+}
+
+fn checked_helper(op: &Operator) -> Option<helpers::Checked> {
+    Some(match *op {
+        Operator::I32TruncSF32 => helpers::i32_trunc_s_f32,
+        Operator::I32TruncUF32 => helpers::i32_trunc_u_f32,
+        Operator::I32TruncSF64 => helpers::i32_trunc_s_f64,
+        Operator::I32TruncUF64 => helpers::i32_trunc_u_f64,
+        Operator::I64TruncSF32 => helpers::i64_trunc_s_f32,
+        Operator::I64TruncUF32 => helpers::i64_trunc_u_f32,
+        Operator::I64TruncSF64 => helpers::i64_trunc_s_f64,
+        Operator::I64TruncUF64 => helpers::i64_trunc_u_f64,
+
+        _ => return None,
+    })
+}
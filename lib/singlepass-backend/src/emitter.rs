@@ -0,0 +1,405 @@
+//! A minimal x86_64 assembler, covering the handful of
+//! instruction forms that the code generator uses.
+//!
+//! Every memory operand is encoded as `[base + disp32]`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Reg {
+    RAX = 0,
+    RCX = 1,
+    RDX = 2,
+    RBX = 3,
+    RSP = 4,
+    RBP = 5,
+    RSI = 6,
+    RDI = 7,
+    R8 = 8,
+    R9 = 9,
+    R10 = 10,
+    R11 = 11,
+    R12 = 12,
+    R13 = 13,
+    R14 = 14,
+    R15 = 15,
+}
+
+/// An SSE register, `xmm0` to `xmm7`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Xmm(pub u8);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Mem {
+    pub base: Reg,
+    pub disp: i32,
+}
+
+pub fn mem(base: Reg, disp: i32) -> Mem {
+    Mem { base, disp }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Alu {
+    Add,
+    Or,
+    And,
+    Sub,
+    Xor,
+    Cmp,
+}
+
+impl Alu {
+    /// The opcode of the `r/m, r` form.
+    fn opcode(self) -> u8 {
+        match self {
+            Alu::Add => 0x01,
+            Alu::Or => 0x09,
+            Alu::And => 0x21,
+            Alu::Sub => 0x29,
+            Alu::Xor => 0x31,
+            Alu::Cmp => 0x39,
+        }
+    }
+
+    /// The opcode extension of the `r/m, imm32` form.
+    fn digit(self) -> u8 {
+        match self {
+            Alu::Add => 0,
+            Alu::Or => 1,
+            Alu::And => 4,
+            Alu::Sub => 5,
+            Alu::Xor => 6,
+            Alu::Cmp => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Shift {
+    Rol = 0,
+    Ror = 1,
+    Shl = 4,
+    Shr = 5,
+    Sar = 7,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Cond {
+    NO = 0x1,
+    B = 0x2,
+    AE = 0x3,
+    E = 0x4,
+    NE = 0x5,
+    BE = 0x6,
+    A = 0x7,
+    L = 0xc,
+    GE = 0xd,
+    LE = 0xe,
+    G = 0xf,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SseOp {
+    Sqrt = 0x51,
+    Add = 0x58,
+    Mul = 0x59,
+    Sub = 0x5c,
+    Div = 0x5e,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+#[derive(Default)]
+pub struct Assembler {
+    code: Vec<u8>,
+    /// The offset of each label, once it's bound.
+    labels: Vec<Option<usize>>,
+    /// The `rel32` operands that refer to a label.
+    fixups: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn offset(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    pub fn bind(&mut self, label: Label) {
+        self.labels[label.0] = Some(self.code.len());
+    }
+
+    pub fn label_offset(&self, label: Label) -> Option<usize> {
+        self.labels[label.0]
+    }
+
+    /// Patches every reference to a label, and returns the code.
+    pub fn finish(mut self) -> Vec<u8> {
+        for &(at, label) in &self.fixups {
+            let target = self.labels[label.0].expect("unbound label");
+            let rel = target as i64 - (at as i64 + 4);
+            self.code[at..at + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+        }
+        self.code
+    }
+
+    /// Overwrites the 32-bit value at `at`.
+    pub fn patch_u32(&mut self, at: usize, value: u32) {
+        self.code[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn byte(&mut self, byte: u8) {
+        self.code.push(byte);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn rel32(&mut self, label: Label) {
+        self.fixups.push((self.code.len(), label));
+        self.u32(0);
+    }
+
+    fn rex(&mut self, w: bool, reg: u8, rm: u8) {
+        let rex = 0x40 | (w as u8) << 3 | (reg >> 3 & 1) << 2 | (rm >> 3 & 1);
+        if rex != 0x40 {
+            self.byte(rex);
+        }
+    }
+
+    /// Emits an instruction with a `[base + disp32]` operand.
+    fn op_mem(&mut self, prefix: Option<u8>, w: bool, opcode: &[u8], reg: u8, mem: Mem) {
+        if let Some(prefix) = prefix {
+            self.byte(prefix);
+        }
+        let base = mem.base as u8;
+        self.rex(w, reg, base);
+        self.bytes(opcode);
+        self.byte(0x80 | (reg & 7) << 3 | (base & 7));
+        if base & 7 == Reg::RSP as u8 {
+            self.byte(0x24);
+        }
+        self.u32(mem.disp as u32);
+    }
+
+    /// Emits an instruction with a register operand.
+    fn op_reg(&mut self, prefix: Option<u8>, w: bool, opcode: &[u8], reg: u8, rm: u8) {
+        if let Some(prefix) = prefix {
+            self.byte(prefix);
+        }
+        self.rex(w, reg, rm);
+        self.bytes(opcode);
+        self.byte(0xc0 | (reg & 7) << 3 | (rm & 7));
+    }
+
+    pub fn mov_load(&mut self, w: bool, dst: Reg, mem: Mem) {
+        self.op_mem(None, w, &[0x8b], dst as u8, mem);
+    }
+
+    pub fn mov_store(&mut self, w: bool, mem: Mem, src: Reg) {
+        self.op_mem(None, w, &[0x89], src as u8, mem);
+    }
+
+    /// Stores the low byte of `src`, which must be one of the first four registers.
+    pub fn mov_store8(&mut self, mem: Mem, src: Reg) {
+        debug_assert!((src as u8) < 4);
+        self.op_mem(None, false, &[0x88], src as u8, mem);
+    }
+
+    pub fn mov_store16(&mut self, mem: Mem, src: Reg) {
+        self.op_mem(Some(0x66), false, &[0x89], src as u8, mem);
+    }
+
+    pub fn mov_store_imm32(&mut self, w: bool, mem: Mem, imm: i32) {
+        self.op_mem(None, w, &[0xc7], 0, mem);
+        self.u32(imm as u32);
+    }
+
+    pub fn movzx8_load(&mut self, dst: Reg, mem: Mem) {
+        self.op_mem(None, false, &[0x0f, 0xb6], dst as u8, mem);
+    }
+
+    pub fn movzx16_load(&mut self, dst: Reg, mem: Mem) {
+        self.op_mem(None, false, &[0x0f, 0xb7], dst as u8, mem);
+    }
+
+    pub fn movsx8_load(&mut self, w: bool, dst: Reg, mem: Mem) {
+        self.op_mem(None, w, &[0x0f, 0xbe], dst as u8, mem);
+    }
+
+    pub fn movsx16_load(&mut self, w: bool, dst: Reg, mem: Mem) {
+        self.op_mem(None, w, &[0x0f, 0xbf], dst as u8, mem);
+    }
+
+    pub fn movsxd_load(&mut self, dst: Reg, mem: Mem) {
+        self.op_mem(None, true, &[0x63], dst as u8, mem);
+    }
+
+    pub fn lea(&mut self, dst: Reg, mem: Mem) {
+        self.op_mem(None, true, &[0x8d], dst as u8, mem);
+    }
+
+    pub fn mov_rr(&mut self, w: bool, dst: Reg, src: Reg) {
+        self.op_reg(None, w, &[0x89], src as u8, dst as u8);
+    }
+
+    pub fn mov_imm32(&mut self, dst: Reg, imm: u32) {
+        self.rex(false, 0, dst as u8);
+        self.byte(0xb8 | (dst as u8 & 7));
+        self.u32(imm);
+    }
+
+    pub fn mov_imm64(&mut self, dst: Reg, imm: u64) {
+        self.rex(true, 0, dst as u8);
+        self.byte(0xb8 | (dst as u8 & 7));
+        self.bytes(&imm.to_le_bytes());
+    }
+
+    pub fn alu_rr(&mut self, op: Alu, w: bool, dst: Reg, src: Reg) {
+        self.op_reg(None, w, &[op.opcode()], src as u8, dst as u8);
+    }
+
+    pub fn alu_ri(&mut self, op: Alu, w: bool, dst: Reg, imm: i32) {
+        self.op_reg(None, w, &[0x81], op.digit(), dst as u8);
+        self.u32(imm as u32);
+    }
+
+    pub fn alu_mi(&mut self, op: Alu, w: bool, mem: Mem, imm: i32) {
+        self.op_mem(None, w, &[0x81], op.digit(), mem);
+        self.u32(imm as u32);
+    }
+
+    /// `op dst, [mem]`.
+    pub fn alu_rm(&mut self, op: Alu, w: bool, dst: Reg, mem: Mem) {
+        // The `r, r/m` form of each opcode is two above the `r/m, r` one.
+        self.op_mem(None, w, &[op.opcode() + 2], dst as u8, mem);
+    }
+
+    pub fn test_rr(&mut self, w: bool, a: Reg, b: Reg) {
+        self.op_reg(None, w, &[0x85], b as u8, a as u8);
+    }
+
+    pub fn imul_rr(&mut self, w: bool, dst: Reg, src: Reg) {
+        self.op_reg(None, w, &[0x0f, 0xaf], dst as u8, src as u8);
+    }
+
+    pub fn imul_rri(&mut self, w: bool, dst: Reg, src: Reg, imm: i32) {
+        self.op_reg(None, w, &[0x69], dst as u8, src as u8);
+        self.u32(imm as u32);
+    }
+
+    /// Shifts or rotates `dst` by `cl`.
+    pub fn shift_cl(&mut self, op: Shift, w: bool, dst: Reg) {
+        self.op_reg(None, w, &[0xd3], op as u8, dst as u8);
+    }
+
+    pub fn shift_ri(&mut self, op: Shift, w: bool, dst: Reg, imm: u8) {
+        self.op_reg(None, w, &[0xc1], op as u8, dst as u8);
+        self.byte(imm);
+    }
+
+    /// Sign-extends `eax` into `edx`, or `rax` into `rdx`.
+    pub fn cdq(&mut self, w: bool) {
+        self.rex(w, 0, 0);
+        self.byte(0x99);
+    }
+
+    /// Divides `edx:eax` or `rdx:rax` by `src`.
+    pub fn div(&mut self, signed: bool, w: bool, src: Reg) {
+        self.op_reg(None, w, &[0xf7], if signed { 7 } else { 6 }, src as u8);
+    }
+
+    /// Sets `eax` to 1 if `cond` holds, or 0 otherwise.
+    pub fn setcc_eax(&mut self, cond: Cond) {
+        self.bytes(&[0x0f, 0x90 | cond as u8, 0xc0]);
+        // movzx eax, al
+        self.bytes(&[0x0f, 0xb6, 0xc0]);
+    }
+
+    pub fn jmp(&mut self, label: Label) {
+        self.byte(0xe9);
+        self.rel32(label);
+    }
+
+    /// `jmp [mem]`.
+    pub fn jmp_m(&mut self, mem: Mem) {
+        self.op_mem(None, false, &[0xff], 4, mem);
+    }
+
+    pub fn jcc(&mut self, cond: Cond, label: Label) {
+        self.bytes(&[0x0f, 0x80 | cond as u8]);
+        self.rel32(label);
+    }
+
+    pub fn call(&mut self, label: Label) {
+        self.byte(0xe8);
+        self.rel32(label);
+    }
+
+    pub fn call_r(&mut self, target: Reg) {
+        self.op_reg(None, false, &[0xff], 2, target as u8);
+    }
+
+    pub fn push_r(&mut self, src: Reg) {
+        self.rex(false, 0, src as u8);
+        self.byte(0x50 | (src as u8 & 7));
+    }
+
+    pub fn push_m(&mut self, mem: Mem) {
+        self.op_mem(None, false, &[0xff], 6, mem);
+    }
+
+    pub fn pop_r(&mut self, dst: Reg) {
+        self.rex(false, 0, dst as u8);
+        self.byte(0x58 | (dst as u8 & 7));
+    }
+
+    pub fn ret(&mut self) {
+        self.byte(0xc3);
+    }
+
+    /// Emits an undefined instruction, which raises `SIGILL`,
+    /// and returns its offset.
+    pub fn ud2(&mut self) -> usize {
+        let offset = self.offset();
+        self.bytes(&[0x0f, 0x0b]);
+        offset
+    }
+
+    /// Loads the low 32 bits of `xmm` from memory.
+    pub fn movd_load(&mut self, dst: Xmm, mem: Mem) {
+        self.op_mem(Some(0x66), false, &[0x0f, 0x6e], dst.0, mem);
+    }
+
+    /// Moves the low 32 bits of `src` into a register, zero-extending them.
+    pub fn movd_to_reg(&mut self, dst: Reg, src: Xmm) {
+        self.op_reg(Some(0x66), false, &[0x0f, 0x7e], src.0, dst as u8);
+    }
+
+    pub fn movq_load(&mut self, dst: Xmm, mem: Mem) {
+        self.op_mem(Some(0xf3), false, &[0x0f, 0x7e], dst.0, mem);
+    }
+
+    pub fn movq_store(&mut self, mem: Mem, src: Xmm) {
+        self.op_mem(Some(0x66), false, &[0x0f, 0xd6], src.0, mem);
+    }
+
+    /// `op dst, src` on scalar singles, or doubles if `double` is set.
+    pub fn sse_rr(&mut self, op: SseOp, double: bool, dst: Xmm, src: Xmm) {
+        let prefix = if double { 0xf2 } else { 0xf3 };
+        self.op_reg(Some(prefix), false, &[0x0f, op as u8], dst.0, src.0);
+    }
+}
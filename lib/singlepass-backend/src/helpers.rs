@@ -0,0 +1,432 @@
+//! Functions that compiled code calls for the operators
+//! that aren't worth emitting inline.
+//!
+//! Every value is passed and returned as the bits of its
+//! 64-bit slot, with 32-bit values in the low half.
+
+pub type Unary = extern "C" fn(u64) -> u64;
+pub type Binary = extern "C" fn(u64, u64) -> u64;
+/// Stores the result in `out` and returns 1, or returns 0 if
+/// the operation traps.
+pub type Checked = extern "C" fn(u64, *mut u64) -> u32;
+
+fn f32_of(bits: u64) -> f32 {
+    f32::from_bits(bits as u32)
+}
+
+fn from_f32(x: f32) -> u64 {
+    u64::from(x.to_bits())
+}
+
+fn f64_of(bits: u64) -> f64 {
+    f64::from_bits(bits)
+}
+
+fn from_f64(x: f64) -> u64 {
+    x.to_bits()
+}
+
+fn from_bool(x: bool) -> u64 {
+    x as u64
+}
+
+pub extern "C" fn i32_clz(v: u64) -> u64 {
+    u64::from((v as u32).leading_zeros())
+}
+
+pub extern "C" fn i32_ctz(v: u64) -> u64 {
+    u64::from((v as u32).trailing_zeros())
+}
+
+pub extern "C" fn i32_popcnt(v: u64) -> u64 {
+    u64::from((v as u32).count_ones())
+}
+
+pub extern "C" fn i64_clz(v: u64) -> u64 {
+    u64::from(v.leading_zeros())
+}
+
+pub extern "C" fn i64_ctz(v: u64) -> u64 {
+    u64::from(v.trailing_zeros())
+}
+
+pub extern "C" fn i64_popcnt(v: u64) -> u64 {
+    u64::from(v.count_ones())
+}
+
+pub extern "C" fn f32_ceil(v: u64) -> u64 {
+    from_f32(f32_of(v).ceil())
+}
+
+pub extern "C" fn f32_floor(v: u64) -> u64 {
+    from_f32(f32_of(v).floor())
+}
+
+pub extern "C" fn f32_trunc(v: u64) -> u64 {
+    from_f32(f32_of(v).trunc())
+}
+
+pub extern "C" fn f32_nearest(v: u64) -> u64 {
+    from_f32(nearest_f32(f32_of(v)))
+}
+
+pub extern "C" fn f64_ceil(v: u64) -> u64 {
+    from_f64(f64_of(v).ceil())
+}
+
+pub extern "C" fn f64_floor(v: u64) -> u64 {
+    from_f64(f64_of(v).floor())
+}
+
+pub extern "C" fn f64_trunc(v: u64) -> u64 {
+    from_f64(f64_of(v).trunc())
+}
+
+pub extern "C" fn f64_nearest(v: u64) -> u64 {
+    from_f64(nearest_f64(f64_of(v)))
+}
+
+pub extern "C" fn f32_convert_s_i32(v: u64) -> u64 {
+    from_f32(v as i32 as f32)
+}
+
+pub extern "C" fn f32_convert_u_i32(v: u64) -> u64 {
+    from_f32(v as u32 as f32)
+}
+
+pub extern "C" fn f32_convert_s_i64(v: u64) -> u64 {
+    from_f32(v as i64 as f32)
+}
+
+pub extern "C" fn f32_convert_u_i64(v: u64) -> u64 {
+    from_f32(v as f32)
+}
+
+pub extern "C" fn f32_demote_f64(v: u64) -> u64 {
+    from_f32(f64_of(v) as f32)
+}
+
+pub extern "C" fn f64_convert_s_i32(v: u64) -> u64 {
+    from_f64(f64::from(v as i32))
+}
+
+pub extern "C" fn f64_convert_u_i32(v: u64) -> u64 {
+    from_f64(f64::from(v as u32))
+}
+
+pub extern "C" fn f64_convert_s_i64(v: u64) -> u64 {
+    from_f64(v as i64 as f64)
+}
+
+pub extern "C" fn f64_convert_u_i64(v: u64) -> u64 {
+    from_f64(v as f64)
+}
+
+pub extern "C" fn f64_promote_f32(v: u64) -> u64 {
+    from_f64(f64::from(f32_of(v)))
+}
+
+/// Replaces a NaN with the canonical one, in deterministic mode.
+pub extern "C" fn f32_canonicalize_nan(v: u64) -> u64 {
+    if f32_of(v).is_nan() {
+        0x7fc0_0000
+    } else {
+        v
+    }
+}
+
+/// Replaces a NaN with the canonical one, in deterministic mode.
+pub extern "C" fn f64_canonicalize_nan(v: u64) -> u64 {
+    if f64_of(v).is_nan() {
+        0x7ff8_0000_0000_0000
+    } else {
+        v
+    }
+}
+
+pub extern "C" fn i32_trunc_s_sat_f32(v: u64) -> u64 {
+    u64::from(sat_i32(f64::from(f32_of(v))) as u32)
+}
+
+pub extern "C" fn i32_trunc_u_sat_f32(v: u64) -> u64 {
+    u64::from(sat_u32(f64::from(f32_of(v))))
+}
+
+pub extern "C" fn i32_trunc_s_sat_f64(v: u64) -> u64 {
+    u64::from(sat_i32(f64_of(v)) as u32)
+}
+
+pub extern "C" fn i32_trunc_u_sat_f64(v: u64) -> u64 {
+    u64::from(sat_u32(f64_of(v)))
+}
+
+pub extern "C" fn i64_trunc_s_sat_f32(v: u64) -> u64 {
+    sat_i64(f64::from(f32_of(v))) as u64
+}
+
+pub extern "C" fn i64_trunc_u_sat_f32(v: u64) -> u64 {
+    sat_u64(f64::from(f32_of(v)))
+}
+
+pub extern "C" fn i64_trunc_s_sat_f64(v: u64) -> u64 {
+    sat_i64(f64_of(v)) as u64
+}
+
+pub extern "C" fn i64_trunc_u_sat_f64(v: u64) -> u64 {
+    sat_u64(f64_of(v))
+}
+
+pub extern "C" fn f32_eq(a: u64, b: u64) -> u64 {
+    from_bool(f32_of(a) == f32_of(b))
+}
+
+pub extern "C" fn f32_ne(a: u64, b: u64) -> u64 {
+    from_bool(f32_of(a) != f32_of(b))
+}
+
+pub extern "C" fn f32_lt(a: u64, b: u64) -> u64 {
+    from_bool(f32_of(a) < f32_of(b))
+}
+
+pub extern "C" fn f32_gt(a: u64, b: u64) -> u64 {
+    from_bool(f32_of(a) > f32_of(b))
+}
+
+pub extern "C" fn f32_le(a: u64, b: u64) -> u64 {
+    from_bool(f32_of(a) <= f32_of(b))
+}
+
+pub extern "C" fn f32_ge(a: u64, b: u64) -> u64 {
+    from_bool(f32_of(a) >= f32_of(b))
+}
+
+pub extern "C" fn f64_eq(a: u64, b: u64) -> u64 {
+    from_bool(f64_of(a) == f64_of(b))
+}
+
+pub extern "C" fn f64_ne(a: u64, b: u64) -> u64 {
+    from_bool(f64_of(a) != f64_of(b))
+}
+
+pub extern "C" fn f64_lt(a: u64, b: u64) -> u64 {
+    from_bool(f64_of(a) < f64_of(b))
+}
+
+pub extern "C" fn f64_gt(a: u64, b: u64) -> u64 {
+    from_bool(f64_of(a) > f64_of(b))
+}
+
+pub extern "C" fn f64_le(a: u64, b: u64) -> u64 {
+    from_bool(f64_of(a) <= f64_of(b))
+}
+
+pub extern "C" fn f64_ge(a: u64, b: u64) -> u64 {
+    from_bool(f64_of(a) >= f64_of(b))
+}
+
+// Unlike `f32::min` and friends, these return NaN if either
+// operand is NaN, and order -0 below +0.
+
+pub extern "C" fn f32_min(a: u64, b: u64) -> u64 {
+    let (a, b) = (f32_of(a), f32_of(b));
+    from_f32(if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == b {
+        f32::from_bits(a.to_bits() | b.to_bits())
+    } else if a < b {
+        a
+    } else {
+        b
+    })
+}
+
+pub extern "C" fn f32_max(a: u64, b: u64) -> u64 {
+    let (a, b) = (f32_of(a), f32_of(b));
+    from_f32(if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == b {
+        f32::from_bits(a.to_bits() & b.to_bits())
+    } else if a > b {
+        a
+    } else {
+        b
+    })
+}
+
+pub extern "C" fn f64_min(a: u64, b: u64) -> u64 {
+    let (a, b) = (f64_of(a), f64_of(b));
+    from_f64(if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == b {
+        f64::from_bits(a.to_bits() | b.to_bits())
+    } else if a < b {
+        a
+    } else {
+        b
+    })
+}
+
+pub extern "C" fn f64_max(a: u64, b: u64) -> u64 {
+    let (a, b) = (f64_of(a), f64_of(b));
+    from_f64(if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == b {
+        f64::from_bits(a.to_bits() & b.to_bits())
+    } else if a > b {
+        a
+    } else {
+        b
+    })
+}
+
+pub extern "C" fn f32_copysign(a: u64, b: u64) -> u64 {
+    (a & 0x7fff_ffff) | (b & 0x8000_0000)
+}
+
+pub extern "C" fn f64_copysign(a: u64, b: u64) -> u64 {
+    (a & 0x7fff_ffff_ffff_ffff) | (b & 0x8000_0000_0000_0000)
+}
+
+pub extern "C" fn i32_trunc_s_f32(v: u64, out: *mut u64) -> u32 {
+    let x = trunc(f64::from(f32_of(v)), -2_147_483_649.0, 2_147_483_648.0);
+    store(out, x.map(|x| u64::from(x as i32 as u32)))
+}
+
+pub extern "C" fn i32_trunc_u_f32(v: u64, out: *mut u64) -> u32 {
+    let x = trunc(f64::from(f32_of(v)), -1.0, 4_294_967_296.0);
+    store(out, x.map(|x| u64::from(x as u32)))
+}
+
+pub extern "C" fn i32_trunc_s_f64(v: u64, out: *mut u64) -> u32 {
+    let x = trunc(f64_of(v), -2_147_483_649.0, 2_147_483_648.0);
+    store(out, x.map(|x| u64::from(x as i32 as u32)))
+}
+
+pub extern "C" fn i32_trunc_u_f64(v: u64, out: *mut u64) -> u32 {
+    let x = trunc(f64_of(v), -1.0, 4_294_967_296.0);
+    store(out, x.map(|x| u64::from(x as u32)))
+}
+
+pub extern "C" fn i64_trunc_s_f32(v: u64, out: *mut u64) -> u32 {
+    store(out, trunc_i64(f64::from(f32_of(v))))
+}
+
+pub extern "C" fn i64_trunc_u_f32(v: u64, out: *mut u64) -> u32 {
+    store(out, trunc_u64(f64::from(f32_of(v))))
+}
+
+pub extern "C" fn i64_trunc_s_f64(v: u64, out: *mut u64) -> u32 {
+    store(out, trunc_i64(f64_of(v)))
+}
+
+pub extern "C" fn i64_trunc_u_f64(v: u64, out: *mut u64) -> u32 {
+    store(out, trunc_u64(f64_of(v)))
+}
+
+fn store(out: *mut u64, result: Option<u64>) -> u32 {
+    match result {
+        Some(bits) => {
+            unsafe { *out = bits };
+            1
+        }
+        None => 0,
+    }
+}
+
+/// Truncates `x`, or returns `None` unless the result
+/// is strictly between `lower` and `upper`.
+fn trunc(x: f64, lower: f64, upper: f64) -> Option<f64> {
+    if x.is_nan() || x <= lower || x >= upper {
+        None
+    } else {
+        Some(x.trunc())
+    }
+}
+
+fn trunc_i64(x: f64) -> Option<u64> {
+    // -2^63 is the smallest value that fits, and the
+    // next smaller `f64` is 2048 below it.
+    trunc(x, -9_223_372_036_854_777_856.0, 9_223_372_036_854_775_808.0).map(|x| x as i64 as u64)
+}
+
+fn trunc_u64(x: f64) -> Option<u64> {
+    trunc(x, -1.0, 18_446_744_073_709_551_616.0).map(|x| x as u64)
+}
+
+fn sat_i32(x: f64) -> i32 {
+    if x.is_nan() {
+        0
+    } else if x <= f64::from(i32::min_value()) {
+        i32::min_value()
+    } else if x >= f64::from(i32::max_value()) {
+        i32::max_value()
+    } else {
+        x as i32
+    }
+}
+
+fn sat_u32(x: f64) -> u32 {
+    if x.is_nan() || x <= 0.0 {
+        0
+    } else if x >= f64::from(u32::max_value()) {
+        u32::max_value()
+    } else {
+        x as u32
+    }
+}
+
+fn sat_i64(x: f64) -> i64 {
+    if x.is_nan() {
+        0
+    } else if x <= -9_223_372_036_854_775_808.0 {
+        i64::min_value()
+    } else if x >= 9_223_372_036_854_775_808.0 {
+        i64::max_value()
+    } else {
+        x as i64
+    }
+}
+
+fn sat_u64(x: f64) -> u64 {
+    if x.is_nan() || x <= 0.0 {
+        0
+    } else if x >= 18_446_744_073_709_551_616.0 {
+        u64::max_value()
+    } else {
+        x as u64
+    }
+}
+
+/// Rounds to the nearest integer, with ties going to the even one.
+fn nearest_f32(x: f32) -> f32 {
+    let rounded = x.round();
+    let result = if (x - x.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+        rounded - x.signum()
+    } else {
+        rounded
+    };
+
+    // A zero keeps the sign of `x`.
+    if result == 0.0 {
+        f32::from_bits(x.to_bits() & 0x8000_0000)
+    } else {
+        result
+    }
+}
+
+/// Rounds to the nearest integer, with ties going to the even one.
+fn nearest_f64(x: f64) -> f64 {
+    let rounded = x.round();
+    let result = if (x - x.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+        rounded - x.signum()
+    } else {
+        rounded
+    };
+
+    // A zero keeps the sign of `x`.
+    if result == 0.0 {
+        f64::from_bits(x.to_bits() & 0x8000_0000_0000_0000)
+    } else {
+        result
+    }
+}
@@ -0,0 +1,67 @@
+//! A single-pass x86_64 compiler backend for wasmer.
+//!
+//! Machine code is emitted directly while the function bodies are
+//! parsed, without building any intermediate representation, so
+//! modules compile far faster than with Cranelift at the cost of
+//! much slower code. The generated code uses the same `vm::Ctx`
+//! layout, trap tables and signal handling as the Cranelift backend.
+//!
+//! Modules can also be compiled for tiering, where every local
+//! function is called through an entry that can be redirected to
+//! optimized code while the module runs, see [`FuncTable`].
+//!
+//! Only x86_64 targets with the System V calling convention are
+//! supported.
+
+#[cfg(not(all(target_arch = "x86_64", unix)))]
+compile_error!("the single-pass backend only supports x86_64 System V targets");
+
+mod call;
+mod codegen;
+mod emitter;
+mod helpers;
+mod module;
+mod tier;
+
+pub use crate::tier::FuncTable;
+
+use wasmer_runtime_core::{
+    backend::{Compiler, Token},
+    error::CompileResult,
+    module::ModuleInner,
+    Features,
+};
+
+pub struct SinglePassCompiler {}
+
+impl SinglePassCompiler {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Compiles a wasm binary for tiering. Each local function counts
+    /// its calls, and is called through an entry that the returned
+    /// table can redirect to optimized code.
+    pub fn compile_tiered(
+        &self,
+        wasm: &[u8],
+        features: &Features,
+    ) -> CompileResult<(ModuleInner, FuncTable)> {
+        wasmer_runtime_core::validate_with_features(wasm, features)?;
+
+        let (module, code) = module::translate(wasm, features, true)?;
+        Ok((module, FuncTable::new(&code)))
+    }
+}
+
+impl Compiler for SinglePassCompiler {
+    // Compiles a wasm binary to a wasmer module in a single pass.
+    fn compile(&self, wasm: &[u8], features: &Features, _: Token) -> CompileResult<ModuleInner> {
+        wasmer_runtime_core::validate_with_features(wasm, features)?;
+
+        module::translate(wasm, features, false).map(|(module, _)| module)
+    }
+}
+
+/// The current version of this crate
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
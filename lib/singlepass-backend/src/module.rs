@@ -0,0 +1,176 @@
+use crate::{
+    call::{Caller, Code, Resolver},
+    codegen::{FuncCodegen, ModuleCodegen},
+    tier::Tiers,
+};
+use hashbrown::HashSet;
+use std::sync::Arc;
+use wasmer_runtime_core::{
+    error::{CompileError, CompileResult},
+    module::ModuleInner,
+    parse::{self, FuncTranslator},
+    perf_map::{self, FuncNames},
+    structures::TypedIndex,
+    types::{LocalFuncIndex, SigIndex},
+    Features,
+};
+use wasmparser::{Operator, Type as WpType};
+
+/// Compiles each function body as soon as it's read.
+struct Translator {
+    codegen: ModuleCodegen,
+    func_codegen: Option<FuncCodegen>,
+    tiered: bool,
+    tiers: Option<Tiers>,
+    num_bodies: usize,
+}
+
+impl FuncTranslator for Translator {
+    fn begin_body(
+        &mut self,
+        module: &ModuleInner,
+        local_func_index: LocalFuncIndex,
+    ) -> CompileResult<()> {
+        if self.tiered && self.tiers.is_none() {
+            let num_funcs = module.func_assoc.len() - module.imported_functions.len();
+            let tiers = Tiers::new(num_funcs);
+            self.codegen.tier_tables = Some(tiers.tables());
+            self.tiers = Some(tiers);
+        }
+        self.func_codegen = Some(FuncCodegen::new(
+            &mut self.codegen,
+            module,
+            local_func_index,
+        ));
+        Ok(())
+    }
+
+    fn declare_locals(&mut self, locals: &[(u32, WpType)]) -> CompileResult<()> {
+        self.func_codegen.as_mut().unwrap().declare_locals(locals);
+        Ok(())
+    }
+
+    fn translate(
+        &mut self,
+        module: &ModuleInner,
+        signatures: &[SigIndex],
+        op: &Operator,
+    ) -> CompileResult<()> {
+        self.func_codegen
+            .as_mut()
+            .unwrap()
+            .translate(&mut self.codegen, module, signatures, op)
+    }
+
+    fn end_body(&mut self) -> CompileResult<()> {
+        self.func_codegen = None;
+        self.num_bodies += 1;
+        Ok(())
+    }
+}
+
+/// Parses a validated wasm binary and compiles every
+/// function body in it as soon as it's read. With `tiered`,
+/// local functions are called through replaceable entries.
+pub fn translate(
+    wasm: &[u8],
+    features: &Features,
+    tiered: bool,
+) -> CompileResult<(ModuleInner, Arc<Code>)> {
+    let mut codegen = ModuleCodegen::new();
+    codegen.canonicalize_nans = features.deterministic;
+    let mut translator = Translator {
+        codegen,
+        func_codegen: None,
+        tiered,
+        tiers: None,
+        num_bodies: 0,
+    };
+    let mut module = parse::read_module(wasm, features, &mut translator)?;
+    // Every memory access is compiled against memory 0.
+    if module.memories.len() + module.imported_memories.len() > 1 {
+        Err(CompileError::InternalError {
+            msg: "multiple memories aren't supported by this backend".to_string(),
+        })?;
+    }
+    let Translator {
+        mut codegen,
+        tiers,
+        num_bodies,
+        ..
+    } = translator;
+
+    // The runtime calls every function, local or imported,
+    // through the trampoline of its signature.
+    let mut sig_indices = HashSet::new();
+    for (_, &sig_index) in module.func_assoc.iter() {
+        if sig_indices.insert(sig_index) {
+            let signature = module.sig_registry.lookup_signature(sig_index);
+            codegen.trampoline(sig_index, &signature);
+        }
+    }
+
+    codegen.stubs(num_bodies);
+
+    let func_offsets = (0..num_bodies)
+        .map(|index| {
+            let local_func_index = LocalFuncIndex::new(index);
+            if let Some(ref tiers) = tiers {
+                let label = codegen.func_label(local_func_index);
+                tiers.set_offset(local_func_index, codegen.asm.label_offset(label).unwrap());
+            }
+            let label = codegen.entry_label(local_func_index);
+            codegen.asm.label_offset(label).unwrap()
+        })
+        .collect();
+
+    let symbols = if perf_map::is_enabled() {
+        let names = FuncNames::new(&module, wasm);
+        let mut symbols = Vec::new();
+        for index in 0..num_bodies {
+            let local_func_index = LocalFuncIndex::new(index);
+            let name = names.get_local(&module, local_func_index);
+            let label = codegen.func_label(local_func_index);
+            symbols.push((codegen.asm.label_offset(label).unwrap(), name.to_string()));
+            if tiers.is_some() {
+                let label = codegen.entry_label(local_func_index);
+                symbols.push((
+                    codegen.asm.label_offset(label).unwrap(),
+                    format!("stub for {}", name),
+                ));
+            }
+        }
+        for (sig_index, &offset) in codegen.trampolines.iter() {
+            symbols.push((
+                offset,
+                format!("trampoline for signature {}", sig_index.index()),
+            ));
+        }
+        Some(symbols)
+    } else {
+        None
+    };
+
+    let ModuleCodegen {
+        asm,
+        trap_sink,
+        trampolines,
+        ..
+    } = codegen;
+
+    let machine_code = asm.finish();
+    let code = Arc::new(Code::new(
+        &machine_code,
+        func_offsets,
+        trampolines,
+        trap_sink,
+        tiers,
+    )?);
+    if let Some(symbols) = symbols {
+        perf_map::record(code.as_ptr(), machine_code.len(), symbols);
+    }
+    module.func_resolver = Box::new(Resolver::new(Arc::clone(&code)));
+    module.protected_caller = Box::new(Caller::new(&module, Arc::clone(&code)));
+
+    Ok((module, code))
+}
@@ -0,0 +1,152 @@
+use crate::{call::Code, codegen::TierTables};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex, Weak,
+};
+use wasmer_clif_backend::trap::HandlerData;
+use wasmer_runtime_core::{module::ModuleInner, structures::TypedIndex, types::LocalFuncIndex};
+
+/// The entry points and call counters of the local functions
+/// of a module compiled for tiering.
+pub struct Tiers {
+    /// The code each function's stub jumps to. These hold offsets
+    /// in the baseline code until it's loaded.
+    entries: Box<[AtomicUsize]>,
+    /// How many times each function's baseline code was entered.
+    counts: Box<[AtomicUsize]>,
+    /// Whether each function was redirected to optimized code.
+    redirected: Box<[AtomicBool]>,
+    /// The modules that functions were redirected to,
+    /// which must live as long as this one.
+    optimized: Mutex<Vec<Arc<ModuleInner>>>,
+}
+
+impl Tiers {
+    pub fn new(num_funcs: usize) -> Self {
+        let zeroes = || -> Box<[AtomicUsize]> {
+            (0..num_funcs)
+                .map(|_| AtomicUsize::new(0))
+                .collect::<Vec<_>>()
+                .into_boxed_slice()
+        };
+        Self {
+            entries: zeroes(),
+            counts: zeroes(),
+            redirected: (0..num_funcs)
+                .map(|_| AtomicBool::new(false))
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+            optimized: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The addresses the generated code reads the entries
+    /// from and counts calls at.
+    pub fn tables(&self) -> TierTables {
+        TierTables {
+            entries: self.entries.as_ptr() as usize,
+            counts: self.counts.as_ptr() as usize,
+        }
+    }
+
+    pub fn set_offset(&self, local_func_index: LocalFuncIndex, offset: usize) {
+        self.entries[local_func_index.index()].store(offset, Ordering::SeqCst);
+    }
+
+    /// Turns the offsets in the entries into addresses,
+    /// once the code is loaded at `base`.
+    pub fn rebase(&self, base: *const u8) {
+        for entry in self.entries.iter() {
+            entry.fetch_add(base as usize, Ordering::SeqCst);
+        }
+    }
+}
+
+/// The functions of a module compiled with
+/// `SinglePassCompiler::compile_tiered`, which can be
+/// redirected to optimized code while the module runs.
+///
+/// This doesn't keep the module alive.
+#[derive(Clone)]
+pub struct FuncTable {
+    code: Weak<Code>,
+}
+
+impl FuncTable {
+    pub(crate) fn new(code: &Arc<Code>) -> Self {
+        Self {
+            code: Arc::downgrade(code),
+        }
+    }
+
+    /// How many times the baseline code of each local function
+    /// was entered, or `None` once the module is dropped.
+    ///
+    /// The counters aren't synchronized between threads,
+    /// so concurrent calls can be missed.
+    pub fn call_counts(&self) -> Option<Vec<usize>> {
+        let code = self.code.upgrade()?;
+        Some(match code.tiers() {
+            Some(tiers) => tiers
+                .counts
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect(),
+            None => Vec::new(),
+        })
+    }
+
+    /// Whether each local function was redirected to
+    /// optimized code, or `None` once the module is dropped.
+    pub fn redirected(&self) -> Option<Vec<bool>> {
+        let code = self.code.upgrade()?;
+        Some(match code.tiers() {
+            Some(tiers) => tiers
+                .redirected
+                .iter()
+                .map(|redirected| redirected.load(Ordering::SeqCst))
+                .collect(),
+            None => Vec::new(),
+        })
+    }
+
+    /// Redirects each function of `funcs` to its code in `optimized`,
+    /// which must be compiled from the same wasm binary. Traps in that
+    /// code are reported with `handler_data`, and `optimized` is kept
+    /// alive as long as this module.
+    ///
+    /// Returns false once the module is dropped.
+    pub fn replace(
+        &self,
+        optimized: &Arc<ModuleInner>,
+        handler_data: &Arc<HandlerData>,
+        funcs: &[LocalFuncIndex],
+    ) -> bool {
+        let code = match self.code.upgrade() {
+            Some(code) => code,
+            None => return false,
+        };
+        let tiers = match code.tiers() {
+            Some(tiers) => tiers,
+            None => return true,
+        };
+
+        {
+            let mut retained = tiers.optimized.lock().unwrap();
+            if !retained.iter().any(|module| Arc::ptr_eq(module, optimized)) {
+                code.handler_data().link(Arc::clone(handler_data));
+                retained.push(Arc::clone(optimized));
+            }
+        }
+
+        for &local_func_index in funcs {
+            if let Some(func) = optimized.func_resolver.get(optimized, local_func_index) {
+                tiers.entries[local_func_index.index()]
+                    .store(func.as_ptr() as usize, Ordering::SeqCst);
+                tiers.redirected[local_func_index.index()].store(true, Ordering::SeqCst);
+            }
+        }
+
+        true
+    }
+}
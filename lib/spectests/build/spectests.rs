@@ -74,10 +74,24 @@ const TESTS: &[&str] = &[
     "spectests/unwind.wast",
 ];
 
+/// The backends that every spec test runs with, and the
+/// expression that creates the compiler of each one. The
+/// single-pass backends only run on x86_64 unix targets.
+const BACKENDS: &[(&str, &str)] = &[
+    ("clif", "wasmer_clif_backend::CraneliftCompiler::new()"),
+    (
+        "singlepass",
+        "wasmer_singlepass_backend::SinglePassCompiler::new()",
+    ),
+    ("interp", "wasmer_interp_backend::InterpCompiler::new()"),
+    ("tiered", "wasmer_tiered_backend::TieredCompiler::new()"),
+];
+
 static COMMON: &'static str = r##"
 use std::{{f32, f64}};
 use wabt::wat2wasm;
 use wasmer_clif_backend::CraneliftCompiler;
+use wasmer_runtime_core::backend::Compiler;
 use wasmer_runtime_core::import::ImportObject;
 use wasmer_runtime_core::types::Value;
 use wasmer_runtime_core::{{Instance, module::Module}};
@@ -357,7 +371,7 @@ fn test_module_{}() {{
     let module_str = \"{}\";
     println!(\"{{}}\", module_str);
     let wasm_binary = wat2wasm(module_str.as_bytes()).expect(\"WAST not valid or malformed\");
-    let module = wasmer_runtime_core::compile_with(&wasm_binary[..], &CraneliftCompiler::new()).expect(\"WASM can't be compiled\");
+    let module = wasmer_runtime_core::compile_with(&wasm_binary[..], &compiler()).expect(\"WASM can't be compiled\");
     module.instantiate(generate_imports()).expect(\"WASM can't be instantiated\")
 }}\n",
                 self.last_module,
@@ -397,7 +411,7 @@ fn test_module_{}() {{
                 "#[test]
 fn {}_assert_invalid() {{
     let wasm_binary = {:?};
-    let module = wasmer_runtime_core::compile_with(&wasm_binary, &CraneliftCompiler::new());
+    let module = wasmer_runtime_core::compile_with(&wasm_binary, &compiler());
     assert!(module.is_err(), \"WASM should not compile as is invalid\");
 }}\n",
                 command_name,
@@ -528,7 +542,7 @@ fn {}_assert_invalid() {{
                 "#[test]
 fn {}_assert_malformed() {{
     let wasm_binary = {:?};
-    let compilation = wasmer_runtime_core::compile_with(&wasm_binary, &CraneliftCompiler::new());
+    let compilation = wasmer_runtime_core::compile_with(&wasm_binary, &compiler());
     assert!(compilation.is_err(), \"WASM should not compile as is malformed\");
 }}\n",
                 command_name,
@@ -742,18 +756,20 @@ fn {}() {{
     }
 }
 
-fn generate_spectest(out: &mut File, test_name: &str, wast: &PathBuf) -> std::io::Result<()> {
+/// Generates the tests of a wast file, or nothing if it has too many
+/// commands to compile in a reasonable time.
+fn generate_spectest(test_name: &str, wast: &PathBuf) -> Option<String> {
     let mut generator = WastTestGenerator::new(wast);
     generator.consume();
-    let generated_script = generator.finalize();
-
-    if !generator.is_fat_test() {
-        out.write(format!("mod test_{} {{\nuse super::*;\n", test_name).as_bytes())?;
-        out.write(generated_script.as_bytes())?;
-        out.write("\n}\n".as_bytes())?;
+    if generator.is_fat_test() {
+        return None;
     }
 
-    Ok(())
+    Some(format!(
+        "mod test_{} {{\nuse super::*;\n{}\n}}\n",
+        test_name,
+        generator.finalize()
+    ))
 }
 
 pub fn build() -> std::io::Result<()> {
@@ -761,14 +777,35 @@ pub fn build() -> std::io::Result<()> {
 
     out_file.write(COMMON.as_bytes())?;
 
+    let mut tests = Vec::new();
     for test in TESTS.iter() {
         let mut wast_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         wast_path.push(test);
-        generate_spectest(
-            &mut out_file,
+        tests.extend(generate_spectest(
             test.split("/").last().unwrap().split(".").next().unwrap(),
             &wast_path,
-        )?
+        ));
+    }
+
+    let single_pass = env::var("CARGO_CFG_TARGET_ARCH").map_or(false, |arch| arch == "x86_64")
+        && env::var("CARGO_CFG_UNIX").is_ok();
+
+    // The same tests run once per backend, each in its own module.
+    for (backend, compiler) in BACKENDS.iter() {
+        if !single_pass && (*backend == "singlepass" || *backend == "tiered") {
+            continue;
+        }
+        out_file.write(
+            format!(
+                "mod {} {{\nuse super::*;\nfn compiler() -> impl Compiler {{\n    {}\n}}\n",
+                backend, compiler
+            )
+            .as_bytes(),
+        )?;
+        for test in tests.iter() {
+            out_file.write(test.as_bytes())?;
+        }
+        out_file.write("\n}\n".as_bytes())?;
     }
 
     Ok(())
@@ -0,0 +1,47 @@
+#[cfg(test)]
+mod tests {
+    use wasmer_runtime_core::{
+        table::{Element, Table},
+        types::{AnyRef, ElementType, TableDescriptor},
+    };
+
+    #[test]
+    fn test_anyref_table() {
+        let table = Table::new(TableDescriptor {
+            element: ElementType::AnyRef,
+            minimum: 2,
+            maximum: Some(3),
+        })
+        .expect("table can't be created");
+        assert_eq!(table.size(), 2);
+        assert_eq!(table.get_anyref(0), Some(AnyRef::null()));
+
+        let value = 42u32;
+        let reference = AnyRef::from_ptr(&value);
+        table.set(1, Element::AnyRef(reference)).unwrap();
+        assert_eq!(table.get_anyref(1), Some(reference));
+        assert_eq!(unsafe { *table.get_anyref(1).unwrap().as_ptr::<u32>() }, 42);
+
+        // Out-of-bounds accesses fail instead of touching anything.
+        assert!(table.set(2, Element::AnyRef(reference)).is_err());
+        assert_eq!(table.get_anyref(2), None);
+
+        // Growing keeps the existing elements and nulls the new ones.
+        assert_eq!(table.grow(1), Some(2));
+        assert_eq!(table.size(), 3);
+        assert_eq!(table.get_anyref(1), Some(reference));
+        assert_eq!(table.get_anyref(2), Some(AnyRef::null()));
+        assert_eq!(table.grow(1), None);
+    }
+
+    #[test]
+    fn test_anyfunc_table_has_no_anyrefs() {
+        let table = Table::new(TableDescriptor {
+            element: ElementType::Anyfunc,
+            minimum: 1,
+            maximum: None,
+        })
+        .expect("table can't be created");
+        assert_eq!(table.get_anyref(0), None);
+    }
+}
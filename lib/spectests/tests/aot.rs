@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::{CraneliftCompiler, ObjectLoader};
+    use wasmer_runtime_core::{
+        error::{CallError, RuntimeError},
+        import::ImportObject,
+        types::Value,
+        Features,
+    };
+
+    #[test]
+    fn test_aot_shared_object() {
+        let module_str = r#"(module
+      (memory 1)
+      (func $fac (export "fac") (param i64) (result i64)
+        (if (result i64) (i64.eqz (get_local 0))
+          (then (i64.const 1))
+          (else (i64.mul (get_local 0) (call $fac (i64.sub (get_local 0) (i64.const 1)))))))
+      (func (export "ceil") (param f64) (result f64)
+        (f64.ceil (get_local 0)))
+      (func (export "grow") (param i32) (result i32)
+        (memory.grow (get_local 0)))
+      (func (export "load") (param i32) (result i32)
+        (i32.load (get_local 0))))
+    "#;
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+
+        let object = CraneliftCompiler::new()
+            .compile_to_object(&wasm_binary, &Features::default())
+            .expect("WASM can't be compiled to a shared object");
+        let path = env::temp_dir().join(format!("wasmer-aot-test-{}.so", process::id()));
+        fs::write(&path, object).unwrap();
+
+        let module = wasmer_runtime_core::compile_with(&wasm_binary[..], &ObjectLoader::new(&path))
+            .expect("shared object can't be loaded");
+        let instance = module
+            .instantiate(ImportObject::new())
+            .expect("WASM can't be instantiated");
+
+        let result = instance.call("fac", &[Value::I64(20)]).unwrap();
+        assert_eq!(result, vec![Value::I64(2_432_902_008_176_640_000)]);
+        let result = instance.call("ceil", &[Value::F64(1.25)]).unwrap();
+        assert_eq!(result, vec![Value::F64(2.0)]);
+        let result = instance.call("grow", &[Value::I32(1)]).unwrap();
+        assert_eq!(result, vec![Value::I32(1)]);
+        match *instance
+            .call("load", &[Value::I32(2 * 65_536)])
+            .unwrap_err()
+        {
+            CallError::Runtime(RuntimeError::OutOfBoundsAccess { .. }) => {}
+            _ => panic!("expected an out-of-bounds access"),
+        }
+
+        // An object can only be loaded for the module it was compiled from.
+        let other = wat2wasm("(module)").unwrap();
+        assert!(wasmer_runtime_core::compile_with(&other[..], &ObjectLoader::new(&path)).is_err());
+
+        // And only with the features it was compiled with.
+        let features = Features {
+            bounds_checks: true,
+            ..Features::default()
+        };
+        assert!(wasmer_runtime_core::compile_with_features(
+            &wasm_binary[..],
+            &ObjectLoader::new(&path),
+            &features
+        )
+        .is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_interp_backend::InterpCompiler;
+    use wasmer_runtime_core::{
+        backend::Compiler,
+        compile_with_features,
+        error::{CallError, RuntimeError},
+        import::ImportObject,
+        memory::MemoryType,
+        types::{MemoryDescriptor, Value},
+        units::Pages,
+        Features,
+    };
+    #[cfg(all(target_arch = "x86_64", unix))]
+    use wasmer_singlepass_backend::SinglePassCompiler;
+
+    #[test]
+    fn test_bounds_checks() {
+        let module_str = r#"(module
+      (memory 1 4)
+      (func (export "load") (param i32) (result i32)
+        (i32.load (get_local 0)))
+      (func (export "load_offset") (param i32) (result i64)
+        (i64.load offset=8 (get_local 0)))
+      (func (export "grow") (param i32) (result i32)
+        (memory.grow (get_local 0))))
+    "#;
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+        let features = Features {
+            bounds_checks: true,
+            ..Features::default()
+        };
+
+        #[allow(unused_mut)]
+        let mut compilers: Vec<Box<dyn Compiler>> = vec![
+            Box::new(CraneliftCompiler::new()),
+            Box::new(InterpCompiler::new()),
+        ];
+        #[cfg(all(target_arch = "x86_64", unix))]
+        compilers.push(Box::new(SinglePassCompiler::new()));
+        for compiler in compilers {
+            let module = compile_with_features(&wasm_binary[..], &*compiler, &features)
+                .expect("WASM can't be compiled");
+            let instance = module
+                .instantiate(ImportObject::new())
+                .expect("WASM can't be instantiated");
+
+            let out_of_bounds = |name: &str, address: i32| match *instance
+                .call(name, &[Value::I32(address)])
+                .unwrap_err()
+            {
+                CallError::Runtime(RuntimeError::OutOfBoundsAccess { .. }) => {}
+                _ => panic!("expected an out-of-bounds access"),
+            };
+
+            // Accesses that start in bounds, but end past the memory.
+            assert!(instance.call("load", &[Value::I32(65_532)]).is_ok());
+            out_of_bounds("load", 65_534);
+            assert!(instance.call("load_offset", &[Value::I32(65_520)]).is_ok());
+            out_of_bounds("load_offset", 65_524);
+
+            let result = instance.call("grow", &[Value::I32(1)]).unwrap();
+            assert_eq!(result, vec![Value::I32(1)]);
+            assert!(instance.call("load", &[Value::I32(65_534)]).is_ok());
+            out_of_bounds("load", 2 * 65_536 - 2);
+
+            let result = instance.call("grow", &[Value::I32(3)]).unwrap();
+            assert_eq!(result, vec![Value::I32(-1)]);
+        }
+    }
+
+    #[test]
+    fn test_bounds_checked_memories_are_dynamic() {
+        let desc = MemoryDescriptor::new(Pages(1), Some(Pages(4)), false).with_bounds_checks(true);
+        assert_eq!(desc.memory_type(), MemoryType::Dynamic);
+        assert_eq!(
+            desc.with_bounds_checks(false).memory_type(),
+            MemoryType::Static
+        );
+    }
+}
@@ -0,0 +1,196 @@
+#[cfg(test)]
+mod tests {
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_interp_backend::InterpCompiler;
+    use wasmer_runtime_core::{
+        backend::Compiler,
+        compile_with_features,
+        import::ImportObject,
+        imports,
+        memory::Memory,
+        types::{MemoryDescriptor, Value},
+        units::Pages,
+        validate_with_features, Features,
+    };
+    #[cfg(all(target_arch = "x86_64", unix))]
+    use wasmer_singlepass_backend::SinglePassCompiler;
+
+    fn deterministic() -> Features {
+        Features {
+            deterministic: true,
+            ..Features::default()
+        }
+    }
+
+    #[test]
+    fn test_deterministic_nans() {
+        let module_str = r#"(module
+      (func (export "div32") (param f32 f32) (result i32)
+        (i32.reinterpret/f32 (f32.div (get_local 0) (get_local 1))))
+      (func (export "add64") (param i64 f64) (result i64)
+        (i64.reinterpret/f64 (f64.add (f64.reinterpret/i64 (get_local 0)) (get_local 1))))
+      (func (export "sqrt64") (param f64) (result i64)
+        (i64.reinterpret/f64 (f64.sqrt (get_local 0)))))
+    "#;
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+
+        #[allow(unused_mut)]
+        let mut compilers: Vec<Box<dyn Compiler>> = vec![
+            Box::new(CraneliftCompiler::new()),
+            Box::new(InterpCompiler::new()),
+        ];
+        #[cfg(all(target_arch = "x86_64", unix))]
+        compilers.push(Box::new(SinglePassCompiler::new()));
+        for compiler in compilers {
+            let module = compile_with_features(&wasm_binary[..], &*compiler, &deterministic())
+                .expect("WASM can't be compiled");
+            let instance = module
+                .instantiate(ImportObject::new())
+                .expect("WASM can't be instantiated");
+
+            let result = instance
+                .call("div32", &[Value::F32(0.0), Value::F32(0.0)])
+                .unwrap();
+            assert_eq!(result, vec![Value::I32(0x7fc0_0000)]);
+
+            // A NaN with a payload and its sign bit set.
+            let result = instance
+                .call(
+                    "add64",
+                    &[Value::I64(0xfff0_0000_dead_beefu64 as i64), Value::F64(1.0)],
+                )
+                .unwrap();
+            assert_eq!(result, vec![Value::I64(0x7ff8_0000_0000_0000)]);
+
+            let result = instance.call("sqrt64", &[Value::F64(-1.0)]).unwrap();
+            assert_eq!(result, vec![Value::I64(0x7ff8_0000_0000_0000)]);
+
+            // Other results are left alone.
+            let result = instance
+                .call("div32", &[Value::F32(1.0), Value::F32(-2.0)])
+                .unwrap();
+            assert_eq!(result, vec![Value::I32((-0.5f32).to_bits() as i32)]);
+        }
+    }
+
+    #[test]
+    fn test_deterministic_validation() {
+        let unbounded_memory = wat2wasm("(module (memory 1))").unwrap();
+        let bounded_memory = wat2wasm("(module (memory 1 2))").unwrap();
+        let unbounded_import =
+            wat2wasm(r#"(module (import "env" "table" (table 1 anyfunc)))"#).unwrap();
+
+        assert!(validate_with_features(&unbounded_memory, &Features::default()).is_ok());
+        assert!(validate_with_features(&unbounded_memory, &deterministic()).is_err());
+        assert!(validate_with_features(&unbounded_import, &deterministic()).is_err());
+        assert!(validate_with_features(&bounded_memory, &deterministic()).is_ok());
+
+        let threads = Features {
+            threads: true,
+            ..deterministic()
+        };
+        assert!(validate_with_features(&bounded_memory, &threads).is_err());
+    }
+
+    #[test]
+    fn test_deterministic_imports() {
+        let imports_now = wat2wasm(
+            r#"(module
+          (import "env" "now" (func (result f64)))
+          (import "env" "memory" (memory 1 1)))
+        "#,
+        )
+        .unwrap();
+
+        assert!(validate_with_features(&imports_now, &Features::default()).is_ok());
+        assert!(validate_with_features(&imports_now, &deterministic()).is_err());
+
+        let allowed = Features {
+            allowed_imports: vec![("env".to_string(), "now".to_string())],
+            ..deterministic()
+        };
+        assert!(validate_with_features(&imports_now, &allowed).is_ok());
+
+        let other_namespace = Features {
+            allowed_imports: vec![("host".to_string(), "now".to_string())],
+            ..deterministic()
+        };
+        let err = compile_with_features(&imports_now, &CraneliftCompiler::new(), &other_namespace)
+            .unwrap_err();
+        assert!(format!("{:?}", err).contains("can't be imported in deterministic mode"));
+    }
+
+    #[test]
+    fn test_deterministic_imports_host_memory() {
+        let wasm_binary = wat2wasm(
+            r#"(module
+          (import "env" "memory" (memory 1 2))
+          (func (export "grow") (param i32) (result i32)
+            (memory.grow (get_local 0))))
+        "#,
+        )
+        .unwrap();
+        let module =
+            compile_with_features(&wasm_binary, &CraneliftCompiler::new(), &deterministic())
+                .expect("WASM can't be compiled");
+
+        // Reserving the maximum is up to whoever creates the memory,
+        // so it doesn't decide whether the memory can be imported.
+        let memory = Memory::new(MemoryDescriptor::new(Pages(1), Some(Pages(2)), false))
+            .expect("memory can't be created");
+        let instance = module
+            .instantiate(imports! {
+                "env" => {
+                    "memory" => memory,
+                },
+            })
+            .expect("WASM can't be instantiated");
+
+        let result = instance.call("grow", &[Value::I32(1)]).unwrap();
+        assert_eq!(result, vec![Value::I32(1)]);
+    }
+
+    #[test]
+    fn test_deterministic_memory_grow() {
+        let module_str = r#"(module
+      (memory (export "memory") 1 3)
+      (func (export "grow") (param i32) (result i32)
+        (memory.grow (get_local 0)))
+      (func (export "store") (param i32 i32)
+        (i32.store (get_local 0) (get_local 1)))
+      (func (export "load") (param i32) (result i32)
+        (i32.load (get_local 0))))
+    "#;
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+
+        // Bounds-checked memories are the ones that would otherwise move when they grow.
+        for &bounds_checks in [false, true].iter() {
+            let features = Features {
+                bounds_checks,
+                ..deterministic()
+            };
+            let module =
+                compile_with_features(&wasm_binary[..], &CraneliftCompiler::new(), &features)
+                    .expect("WASM can't be compiled");
+            let instance = module
+                .instantiate(ImportObject::new())
+                .expect("WASM can't be instantiated");
+
+            instance
+                .call("store", &[Value::I32(8), Value::I32(42)])
+                .unwrap();
+            let result = instance.call("grow", &[Value::I32(2)]).unwrap();
+            assert_eq!(result, vec![Value::I32(1)]);
+            let result = instance.call("grow", &[Value::I32(1)]).unwrap();
+            assert_eq!(result, vec![Value::I32(-1)]);
+
+            let result = instance.call("load", &[Value::I32(8)]).unwrap();
+            assert_eq!(result, vec![Value::I32(42)]);
+            instance
+                .call("store", &[Value::I32(3 * 65_536 - 4), Value::I32(1)])
+                .unwrap();
+            assert!(instance.call("load", &[Value::I32(3 * 65_536)]).is_err());
+        }
+    }
+}
@@ -0,0 +1,204 @@
+#[cfg(test)]
+mod tests {
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_interp_backend::InterpCompiler;
+    use wasmer_runtime_core::{
+        backend::Compiler, compile_with_features, error::CompileError, import::ImportObject,
+        types::Value, validate, validate_with_features, Features,
+    };
+    #[cfg(all(target_arch = "x86_64", unix))]
+    use wasmer_singlepass_backend::SinglePassCompiler;
+
+    // (module (func (param i32) (result i32) get_local 0 i32.extend8_s))
+    static SIGN_EXTENSION: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x07, 0x01, 0x05, 0x00, 0x20, 0x00, 0xc0, 0x0b,
+    ];
+
+    // (module (func (param f32) (result i32) get_local 0 i32.trunc_sat_f32_s))
+    static SAT_FLOAT_TO_INT: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7d, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x08, 0x01, 0x06, 0x00, 0x20, 0x00, 0xfc, 0x00, 0x0b,
+    ];
+
+    // (module (func (export "f") (param i32) (result i32) get_local 0 i32.extend8_s))
+    static EXTEND8_S: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00, 0x0a, 0x07, 0x01,
+        0x05, 0x00, 0x20, 0x00, 0xc0, 0x0b,
+    ];
+
+    // (module (func (export "f") (param f32) (result i32) get_local 0 i32.trunc_sat_f32_s))
+    static TRUNC_SAT_F32_S: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7d, 0x01,
+        0x7f, 0x03, 0x02, 0x01, 0x00, 0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00, 0x0a, 0x08, 0x01,
+        0x06, 0x00, 0x20, 0x00, 0xfc, 0x00, 0x0b,
+    ];
+
+    // (module (import "env" "g" (global (mut i32))))
+    static MUTABLE_GLOBAL_IMPORT: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x02, 0x0a, 0x01, 0x03, 0x65, 0x6e, 0x76,
+        0x01, 0x67, 0x03, 0x7f, 0x01,
+    ];
+
+    // (module (global (mut i32) (i32.const 0)) (export "g" (global 0)))
+    static MUTABLE_GLOBAL_EXPORT: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x06, 0x06, 0x01, 0x7f, 0x01, 0x41, 0x00,
+        0x0b, 0x07, 0x05, 0x01, 0x01, 0x67, 0x03, 0x00,
+    ];
+
+    // (module (memory 1 1 shared))
+    static SHARED_MEMORY: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x05, 0x04, 0x01, 0x03, 0x01, 0x01,
+    ];
+
+    // (module (memory 1 2 shared) (func (result i32) i32.const 1 memory.grow))
+    static SHARED_MEMORY_GROW: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f,
+        0x03, 0x02, 0x01, 0x00, 0x05, 0x04, 0x01, 0x03, 0x01, 0x02, 0x0a, 0x08, 0x01, 0x06, 0x00,
+        0x41, 0x01, 0x40, 0x00, 0x0b,
+    ];
+
+    #[test]
+    fn test_sign_extension() {
+        let features = Features {
+            sign_extension: true,
+            ..Features::default()
+        };
+        assert!(validate(SIGN_EXTENSION).is_err());
+        assert!(validate_with_features(SIGN_EXTENSION, &features).is_ok());
+    }
+
+    #[test]
+    fn test_sat_float_to_int() {
+        let features = Features {
+            sat_float_to_int: true,
+            ..Features::default()
+        };
+        assert!(validate(SAT_FLOAT_TO_INT).is_err());
+        assert!(validate_with_features(SAT_FLOAT_TO_INT, &features).is_ok());
+    }
+
+    #[test]
+    fn test_sign_extension_and_sat_float_to_int_run() {
+        let features = Features {
+            sign_extension: true,
+            sat_float_to_int: true,
+            ..Features::default()
+        };
+
+        #[allow(unused_mut)]
+        let mut compilers: Vec<Box<dyn Compiler>> = vec![
+            Box::new(CraneliftCompiler::new()),
+            Box::new(InterpCompiler::new()),
+        ];
+        #[cfg(all(target_arch = "x86_64", unix))]
+        compilers.push(Box::new(SinglePassCompiler::new()));
+        for compiler in compilers {
+            let call = |wasm: &[u8], arg: Value| {
+                let module = compile_with_features(wasm, &*compiler, &features)
+                    .expect("WASM can't be compiled");
+                let instance = module
+                    .instantiate(ImportObject::new())
+                    .expect("WASM can't be instantiated");
+                instance.call("f", &[arg]).unwrap()[0].clone()
+            };
+
+            // Only the low 8 bits are sign-extended.
+            assert_eq!(call(EXTEND8_S, Value::I32(0x7f)), Value::I32(127));
+            assert_eq!(call(EXTEND8_S, Value::I32(0x80)), Value::I32(-128));
+            assert_eq!(call(EXTEND8_S, Value::I32(0xff)), Value::I32(-1));
+            assert_eq!(call(EXTEND8_S, Value::I32(0x1234_5601)), Value::I32(1));
+            assert_eq!(call(EXTEND8_S, Value::I32(-0x100)), Value::I32(0));
+
+            // Out-of-range values saturate and NaN gives 0, instead of trapping.
+            let trunc = |x: f32| call(TRUNC_SAT_F32_S, Value::F32(x));
+            assert_eq!(trunc(-1.9), Value::I32(-1));
+            assert_eq!(trunc(2_147_483_520.0), Value::I32(2_147_483_520));
+            assert_eq!(trunc(2_147_483_648.0), Value::I32(i32::max_value()));
+            assert_eq!(trunc(-2_147_483_648.0), Value::I32(i32::min_value()));
+            assert_eq!(trunc(-3e9), Value::I32(i32::min_value()));
+            assert_eq!(trunc(std::f32::INFINITY), Value::I32(i32::max_value()));
+            assert_eq!(trunc(std::f32::NEG_INFINITY), Value::I32(i32::min_value()));
+            assert_eq!(trunc(std::f32::NAN), Value::I32(0));
+        }
+    }
+
+    #[test]
+    fn test_mutable_global() {
+        let features = Features {
+            mutable_global: true,
+            ..Features::default()
+        };
+        assert!(validate(MUTABLE_GLOBAL_IMPORT).is_err());
+        assert!(validate(MUTABLE_GLOBAL_EXPORT).is_err());
+        assert!(validate_with_features(MUTABLE_GLOBAL_IMPORT, &features).is_ok());
+        assert!(validate_with_features(MUTABLE_GLOBAL_EXPORT, &features).is_ok());
+    }
+
+    #[test]
+    fn test_threads() {
+        let features = Features {
+            threads: true,
+            ..Features::default()
+        };
+        assert!(validate(SHARED_MEMORY).is_err());
+        assert!(validate_with_features(SHARED_MEMORY, &features).is_ok());
+
+        // Shared memories give different results on different hosts.
+        let deterministic = Features {
+            deterministic: true,
+            ..features
+        };
+        assert!(validate_with_features(SHARED_MEMORY, &deterministic).is_err());
+    }
+
+    #[test]
+    fn test_shared_memory_grow_with_cranelift() {
+        let features = Features {
+            threads: true,
+            ..Features::default()
+        };
+        assert!(validate_with_features(SHARED_MEMORY_GROW, &features).is_ok());
+
+        // Cranelift can't grow a shared memory, which must be
+        // a compile error rather than a panic.
+        match compile_with_features(SHARED_MEMORY_GROW, &CraneliftCompiler::new(), &features)
+            .map_err(|err| *err)
+        {
+            Err(CompileError::InternalError { .. }) => {}
+            _ => panic!("expected an internal error"),
+        }
+    }
+
+    #[test]
+    fn test_reference_types_with_cranelift() {
+        let features = Features {
+            sign_extension: true,
+            reference_types: true,
+            ..Features::default()
+        };
+        assert!(validate_with_features(EXTEND8_S, &features).is_ok());
+
+        // Cranelift can't translate references, so it rejects the flag
+        // instead of panicking on the first module that uses them.
+        match compile_with_features(EXTEND8_S, &CraneliftCompiler::new(), &features)
+            .map_err(|err| *err)
+        {
+            Err(CompileError::InternalError { .. }) => {}
+            _ => panic!("expected an internal error"),
+        }
+        assert!(
+            compile_with_features(EXTEND8_S, &CraneliftCompiler::new(), &Features::all()).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_validation_error_offset() {
+        // The `i32.extend8_s` is at offset 27.
+        match validate(SIGN_EXTENSION).map_err(|err| *err) {
+            Err(CompileError::ValidationError { offset, .. }) => assert_eq!(offset, 27),
+            _ => panic!("expected a validation error"),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+#[cfg(test)]
+mod tests {
+    use wabt::wat2wasm;
+    use wasmer_interp_backend::InterpCompiler;
+    use wasmer_runtime_core::{
+        backend::Compiler,
+        error::{CallError, CompileError, RuntimeError},
+        export::Export,
+        import::ImportObject,
+        imports,
+        types::Value,
+        Instance,
+    };
+
+    fn instantiate(module_str: &str) -> Instance {
+        instantiate_with(module_str, &InterpCompiler::new(), ImportObject::new())
+    }
+
+    fn instantiate_with(
+        module_str: &str,
+        compiler: &dyn Compiler,
+        imports: ImportObject,
+    ) -> Instance {
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+        let module = wasmer_runtime_core::compile_with(&wasm_binary[..], compiler)
+            .expect("WASM can't be compiled");
+        module
+            .instantiate(imports)
+            .expect("WASM can't be instantiated")
+    }
+
+    fn export(instance: &mut Instance, name: &str) -> Export {
+        instance
+            .exports()
+            .find(|(export_name, _)| export_name == name)
+            .map(|(_, export)| export)
+            .expect("missing export")
+    }
+
+    // Takes more integers and floats than there are registers for
+    // them, so the last ones are passed on the stack.
+    static MANY_PARAMS: &str = r#"(func (export "many")
+        (param i32 i32 i32 i32 i32 i32 i32 f64 f64 f64 f64 f64 f64 f64 f64 f64 f32)
+        (result f64)
+        (f64.add
+          (f64.convert_s/i32
+            (i32.add (i32.add (i32.add (get_local 0) (get_local 1)) (i32.add (get_local 2) (get_local 3)))
+                     (i32.add (i32.add (get_local 4) (get_local 5)) (i32.mul (get_local 6) (i32.const 100)))))
+          (f64.add
+            (f64.add (f64.add (get_local 7) (get_local 8)) (f64.add (get_local 9) (get_local 10)))
+            (f64.add
+              (f64.add (f64.add (get_local 11) (get_local 12)) (f64.add (get_local 13) (get_local 14)))
+              (f64.add (get_local 15) (f64.promote/f32 (get_local 16)))))))"#;
+
+    static CALLS_MANY: &str = r#"(module
+      (import "env" "many" (func $many
+        (param i32 i32 i32 i32 i32 i32 i32 f64 f64 f64 f64 f64 f64 f64 f64 f64 f32)
+        (result f64)))
+      (func (export "call_many") (result f64)
+        (call $many
+          (i32.const 1) (i32.const 2) (i32.const 3) (i32.const 4) (i32.const 5) (i32.const 6)
+          (i32.const 7)
+          (f64.const 0.5) (f64.const 0.25) (f64.const 1) (f64.const 2) (f64.const 3)
+          (f64.const 4) (f64.const 5) (f64.const 6) (f64.const 7000) (f32.const 0.125))))
+    "#;
+
+    // 1 + 2 + ... + 6 + 7 * 100, and then every float.
+    const MANY_RESULT: f64 =
+        721.0 + 0.5 + 0.25 + 1.0 + 2.0 + 3.0 + 4.0 + 5.0 + 6.0 + 7000.0 + 0.125;
+
+    #[test]
+    fn test_interp_calls_and_loops() {
+        let instance = instantiate(
+            r#"(module
+      (func $fac-rec (param i64) (result i64)
+        (if (result i64) (i64.eqz (get_local 0))
+          (then (i64.const 1))
+          (else (i64.mul (get_local 0) (call $fac-rec (i64.sub (get_local 0) (i64.const 1)))))))
+      (func $fac-iter (param i64) (result i64)
+        (local i64)
+        (set_local 1 (i64.const 1))
+        (block
+          (loop
+            (br_if 1 (i64.eqz (get_local 0)))
+            (set_local 1 (i64.mul (get_local 0) (get_local 1)))
+            (set_local 0 (i64.sub (get_local 0) (i64.const 1)))
+            (br 0)))
+        (get_local 1))
+      (export "fac-rec" (func $fac-rec))
+      (export "fac-iter" (func $fac-iter)))
+    "#,
+        );
+
+        for name in &["fac-rec", "fac-iter"] {
+            let result = instance.call(name, &[Value::I64(20)]).unwrap();
+            assert_eq!(result, vec![Value::I64(2_432_902_008_176_640_000)]);
+        }
+    }
+
+    #[test]
+    fn test_interp_memory_and_call_indirect() {
+        let instance = instantiate(
+            r#"(module
+      (type $binop (func (param i32 i32) (result i32)))
+      (memory 1)
+      (table anyfunc (elem $add $sub))
+      (func $add (type $binop) (i32.add (get_local 0) (get_local 1)))
+      (func $sub (type $binop) (i32.sub (get_local 0) (get_local 1)))
+      (func (export "apply") (param i32 i32 i32) (result i32)
+        (i32.store offset=4 (i32.const 0) (call_indirect (type $binop) (get_local 1) (get_local 2) (get_local 0)))
+        (i32.load8_s (i32.const 4)))
+      (func (export "load") (param i32) (result i32)
+        (i32.load (get_local 0))))
+    "#,
+        );
+
+        let result = instance
+            .call("apply", &[Value::I32(1), Value::I32(2), Value::I32(3)])
+            .unwrap();
+        assert_eq!(result, vec![Value::I32(-1)]);
+
+        match *instance.call("load", &[Value::I32(65_533)]).unwrap_err() {
+            CallError::Runtime(RuntimeError::OutOfBoundsAccess { .. }) => {}
+            _ => panic!("expected an out-of-bounds access"),
+        }
+        match *instance
+            .call("apply", &[Value::I32(2), Value::I32(0), Value::I32(0)])
+            .unwrap_err()
+        {
+            CallError::Runtime(RuntimeError::TableOutOfBounds { .. }) => {}
+            _ => panic!("expected an out-of-bounds table access"),
+        }
+    }
+
+    #[test]
+    fn test_interp_traps() {
+        let instance = instantiate(
+            r#"(module
+      (func (export "div") (param i32 i32) (result i32)
+        (i32.div_s (get_local 0) (get_local 1)))
+      (func $recurse (export "recurse")
+        (call $recurse)))
+    "#,
+        );
+
+        match *instance
+            .call("div", &[Value::I32(1), Value::I32(0)])
+            .unwrap_err()
+        {
+            CallError::Runtime(RuntimeError::IllegalArithmeticOperation) => {}
+            _ => panic!("expected a division by zero"),
+        }
+        match *instance.call("recurse", &[]).unwrap_err() {
+            CallError::Runtime(RuntimeError::Unknown { msg }) => {
+                assert_eq!(msg, "call stack exhausted")
+            }
+            _ => panic!("expected a stack overflow"),
+        }
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", unix))]
+    fn test_interp_calls_native_with_many_params() {
+        let mut native = instantiate_with(
+            &format!("(module {})", MANY_PARAMS),
+            &wasmer_clif_backend::CraneliftCompiler::new(),
+            ImportObject::new(),
+        );
+        let instance = instantiate_with(
+            CALLS_MANY,
+            &InterpCompiler::new(),
+            imports! {
+                "env" => {
+                    "many" => export(&mut native, "many"),
+                },
+            },
+        );
+
+        let result = instance.call("call_many", &[]).unwrap();
+        assert_eq!(result, vec![Value::F64(MANY_RESULT)]);
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", unix))]
+    fn test_interp_rejects_too_many_stack_params() {
+        // Sixteen integers and the vmctx leave eleven for the stack,
+        // which native code couldn't call this function with.
+        let wasm_binary = wat2wasm(
+            r#"(module
+              (func (export "wide")
+                (param i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64 i64)))
+            "#
+            .as_bytes(),
+        )
+        .expect("WAST not valid or malformed");
+        match wasmer_runtime_core::compile_with(&wasm_binary[..], &InterpCompiler::new())
+            .map_err(|err| *err)
+        {
+            Err(CompileError::InternalError { .. }) => {}
+            _ => panic!("expected an internal error"),
+        }
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", unix))]
+    fn test_native_calls_interp() {
+        let mut interpreted = instantiate(&format!(
+            r#"(module {}
+      (func (export "div") (param i32 i32) (result i32)
+        (i32.div_s (get_local 0) (get_local 1))))
+    "#,
+            MANY_PARAMS
+        ));
+        let instance = instantiate_with(
+            &CALLS_MANY.replace(
+                "(func (export \"call_many\")",
+                r#"(import "env" "div" (func $div (param i32 i32) (result i32)))
+      (func (export "div") (param i32 i32) (result i32)
+        (call $div (get_local 0) (get_local 1)))
+      (func (export "call_many")"#,
+            ),
+            &wasmer_clif_backend::CraneliftCompiler::new(),
+            imports! {
+                "env" => {
+                    "many" => export(&mut interpreted, "many"),
+                    "div" => export(&mut interpreted, "div"),
+                },
+            },
+        );
+
+        let result = instance.call("call_many", &[]).unwrap();
+        assert_eq!(result, vec![Value::F64(MANY_RESULT)]);
+        let result = instance
+            .call("div", &[Value::I32(-7), Value::I32(2)])
+            .unwrap();
+        assert_eq!(result, vec![Value::I32(-3)]);
+
+        // Errors in the interpreter reach the native caller as a trap.
+        assert!(instance
+            .call("div", &[Value::I32(1), Value::I32(0)])
+            .is_err());
+    }
+
+    #[test]
+    fn test_interp_calls_other_interp_module() {
+        let mut first = instantiate(
+            r#"(module
+      (table (export "table") 2 anyfunc)
+      (global $calls (mut i32) (i32.const 0))
+      (elem (i32.const 0) $count)
+      (func $count (export "count") (result i32)
+        (set_global $calls (i32.add (get_global $calls) (i32.const 1)))
+        (get_global $calls)))
+    "#,
+        );
+        let second = instantiate_with(
+            r#"(module
+      (type $ret_i32 (func (result i32)))
+      (import "env" "table" (table 2 anyfunc))
+      (import "env" "count" (func $count (result i32)))
+      (func (export "call") (result i32)
+        (call $count))
+      (func (export "call_indirect") (param i32) (result i32)
+        (call_indirect (type $ret_i32) (get_local 0))))
+    "#,
+            &InterpCompiler::new(),
+            imports! {
+                "env" => {
+                    "table" => export(&mut first, "table"),
+                    "count" => export(&mut first, "count"),
+                },
+            },
+        );
+
+        // Both calls run the first module's function, with its globals.
+        assert_eq!(second.call("call", &[]).unwrap(), vec![Value::I32(1)]);
+        assert_eq!(
+            second.call("call_indirect", &[Value::I32(0)]).unwrap(),
+            vec![Value::I32(2)]
+        );
+        assert_eq!(first.call("count", &[]).unwrap(), vec![Value::I32(3)]);
+
+        match *second.call("call_indirect", &[Value::I32(1)]).unwrap_err() {
+            CallError::Runtime(RuntimeError::IndirectCallToNull { .. }) => {}
+            _ => panic!("expected a call to a null element"),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_runtime_core::{
+        compile_with,
+        import::ImportObject,
+        jit_debug::{DebugInfo, FuncCode},
+        structures::TypedIndex,
+        types::{LocalFuncIndex, Value},
+    };
+
+    /// A DWARF 2 line table that puts the code section
+    /// from `start` up to `end` on line 42 of `src/answer.c`.
+    fn debug_line(start: u8, end: u8) -> Vec<u8> {
+        let mut header = vec![1, 1, -5i8 as u8, 14, 13];
+        header.extend_from_slice(&[0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+        header.extend_from_slice(b"src\0\0answer.c\0\x01\0\0\0");
+
+        let program = vec![
+            // DW_LNE_set_address
+            0,
+            5,
+            2,
+            start,
+            0,
+            0,
+            0,
+            // DW_LNS_advance_line
+            3,
+            41,
+            // DW_LNS_copy
+            1,
+            // DW_LNS_advance_pc
+            2,
+            end - start,
+            // DW_LNE_end_sequence
+            0,
+            1,
+            1,
+        ];
+
+        let mut unit = vec![2, 0];
+        unit.extend_from_slice(&le32(header.len()));
+        unit.extend_from_slice(&header);
+        unit.extend_from_slice(&program);
+
+        let mut debug_line = le32(unit.len()).to_vec();
+        debug_line.extend_from_slice(&unit);
+        debug_line
+    }
+
+    fn le32(value: usize) -> [u8; 4] {
+        [
+            value as u8,
+            (value >> 8) as u8,
+            (value >> 16) as u8,
+            (value >> 24) as u8,
+        ]
+    }
+
+    #[test]
+    fn test_jit_debug() {
+        let module_str = r#"(module
+      (func (export "answer") (result i32)
+        (i32.const 42)))
+    "#;
+        let mut wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+        assert!(DebugInfo::new(&wasm_binary).is_none());
+
+        // The code section holds the number of bodies, then the size
+        // of the body, its locals at 2, `i32.const` at 3 and `end` at 5.
+        let debug_line = debug_line(3, 6);
+        wasm_binary.push(0);
+        wasm_binary.push((1 + ".debug_line".len() + debug_line.len()) as u8);
+        wasm_binary.push(".debug_line".len() as u8);
+        wasm_binary.extend_from_slice(b".debug_line");
+        wasm_binary.extend_from_slice(&debug_line);
+
+        let debug_info = DebugInfo::new(&wasm_binary).expect("no debug info");
+        let registration = debug_info.register(
+            "test",
+            &[FuncCode {
+                local_func_index: LocalFuncIndex::new(0),
+                name: "answer",
+                address: 0x10000,
+                size: 16,
+                srclocs: &[(0, 1), (5, 3)],
+            }],
+        );
+
+        let object = registration.object();
+        let contains = |needle: &[u8]| object.windows(needle.len()).any(|bytes| bytes == needle);
+        assert!(object.starts_with(b"\x7fELF"));
+        assert!(contains(b"src/answer.c\0"));
+        assert!(contains(b".debug_line\0"));
+        assert!(contains(b"answer\0"));
+
+        // The backend registers the code of the module itself.
+        let module = compile_with(&wasm_binary[..], &CraneliftCompiler::new())
+            .expect("WASM can't be compiled");
+        let instance = module
+            .instantiate(ImportObject::new())
+            .expect("WASM can't be instantiated");
+        assert_eq!(instance.call("answer", &[]).unwrap(), vec![Value::I32(42)]);
+    }
+}
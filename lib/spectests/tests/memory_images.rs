@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod tests {
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_runtime_core::{
+        compile_with, compile_with_features, global::Global, imports, types::Value, Features,
+        Instance,
+    };
+
+    // (module
+    //   (import "env" "offset" (global (mut i32)))
+    //   (memory 1)
+    //   (data (get_global 0) "\2a")
+    //   (func (export "load") (param i32) (result i32)
+    //     (i32.load8_u (get_local 0))))
+    static IMPORTED_OFFSET: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x02, 0x0f, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x06, 0x6f, 0x66, 0x66, 0x73, 0x65, 0x74,
+        0x03, 0x7f, 0x01, 0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x08, 0x01,
+        0x04, 0x6c, 0x6f, 0x61, 0x64, 0x00, 0x00, 0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x2d,
+        0x00, 0x00, 0x0b, 0x0b, 0x07, 0x01, 0x00, 0x23, 0x00, 0x0b, 0x01, 0x2a,
+    ];
+
+    fn load(instance: &Instance, address: i32) -> Value {
+        instance.call("load", &[Value::I32(address)]).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn test_memory_images() {
+        let module_str = r#"(module
+      (memory 2)
+      (data (i32.const 0) "\01\02")
+      (data (i32.const 65537) "\03")
+      (func (export "load") (param i32) (result i32)
+        (i32.load8_u (get_local 0)))
+      (func (export "store") (param i32 i32)
+        (i32.store8 (get_local 0) (get_local 1))))
+    "#;
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+        let module = compile_with(&wasm_binary[..], &CraneliftCompiler::new())
+            .expect("WASM can't be compiled");
+        let mut pre_instance = module
+            .prelink(&imports! {})
+            .expect("imports can't be resolved");
+        pre_instance
+            .enable_memory_images()
+            .expect("memory images can't be created");
+
+        let first = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        assert_eq!(load(&first, 0), Value::I32(1));
+        assert_eq!(load(&first, 1), Value::I32(2));
+        assert_eq!(load(&first, 2), Value::I32(0));
+        assert_eq!(load(&first, 65537), Value::I32(3));
+
+        // Writes to one instance stay private to it.
+        first
+            .call("store", &[Value::I32(0), Value::I32(7)])
+            .unwrap();
+        first
+            .call("store", &[Value::I32(65537), Value::I32(8)])
+            .unwrap();
+        let second = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        assert_eq!(load(&first, 0), Value::I32(7));
+        assert_eq!(load(&first, 65537), Value::I32(8));
+        assert_eq!(load(&second, 0), Value::I32(1));
+        assert_eq!(load(&second, 65537), Value::I32(3));
+    }
+
+    #[test]
+    fn test_memory_images_imported_offset() {
+        let features = Features {
+            mutable_global: true,
+            ..Features::default()
+        };
+        let module = compile_with_features(IMPORTED_OFFSET, &CraneliftCompiler::new(), &features)
+            .expect("WASM can't be compiled");
+
+        let offset = Global::new_mutable(Value::I32(16));
+        let import_object = imports! {
+            "env" => {
+                "offset" => offset.clone(),
+            },
+        };
+        let mut pre_instance = module
+            .prelink(&import_object)
+            .expect("imports can't be resolved");
+        pre_instance
+            .enable_memory_images()
+            .expect("memory images can't be created");
+
+        let first = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        assert_eq!(load(&first, 16), Value::I32(42));
+
+        // The data segment follows the current value of the global.
+        offset.set(Value::I32(32));
+        let second = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        assert_eq!(load(&second, 16), Value::I32(0));
+        assert_eq!(load(&second, 32), Value::I32(42));
+    }
+}
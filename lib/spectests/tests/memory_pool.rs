@@ -0,0 +1,112 @@
+#[cfg(test)]
+mod tests {
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_runtime_core::{compile_with, imports, memory::MemoryPool, types::Value, Instance};
+
+    fn load(instance: &Instance, address: i32) -> Value {
+        instance.call("load", &[Value::I32(address)]).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn test_memory_pool() {
+        // A memory with a maximum is a static memory.
+        let module_str = r#"(module
+      (memory 1 1)
+      (data (i32.const 0) "\2a")
+      (func (export "load") (param i32) (result i32)
+        (i32.load8_u (get_local 0)))
+      (func (export "store") (param i32 i32)
+        (i32.store8 (get_local 0) (get_local 1))))
+    "#;
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+        let module = compile_with(&wasm_binary[..], &CraneliftCompiler::new())
+            .expect("WASM can't be compiled");
+
+        let pool = MemoryPool::new(1).expect("pool can't be created");
+        let mut pre_instance = module
+            .prelink(&imports! {})
+            .expect("imports can't be resolved");
+        pre_instance.set_memory_pool(pool.clone());
+        assert_eq!(pool.available(), 1);
+
+        let first = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        assert_eq!(pool.available(), 0);
+
+        // Once the pool is empty, memories are allocated as usual.
+        let second = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        assert_eq!(pool.available(), 0);
+        drop(second);
+        assert_eq!(pool.available(), 0);
+
+        first
+            .call("store", &[Value::I32(100), Value::I32(7)])
+            .unwrap();
+        assert_eq!(load(&first, 100), Value::I32(7));
+        drop(first);
+        assert_eq!(pool.available(), 1);
+
+        // The slot that comes back from the pool has been emptied.
+        let third = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        assert_eq!(pool.available(), 0);
+        assert_eq!(load(&third, 0), Value::I32(42));
+        assert_eq!(load(&third, 100), Value::I32(0));
+    }
+
+    #[test]
+    fn test_table_pool() {
+        let module_str = r#"(module
+      (type $t (func (result i32)))
+      (table 2 anyfunc)
+      (elem (i32.const 1) $f)
+      (func $f (result i32) (i32.const 7))
+      (func (export "call") (param i32) (result i32)
+        (call_indirect (type $t) (get_local 0))))
+    "#;
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+        let module = compile_with(&wasm_binary[..], &CraneliftCompiler::new())
+            .expect("WASM can't be compiled");
+
+        let pool = MemoryPool::new(1).expect("pool can't be created");
+        let mut pre_instance = module
+            .prelink(&imports! {})
+            .expect("imports can't be resolved");
+        pre_instance.set_memory_pool(pool.clone());
+        assert_eq!(pool.available_tables(), 0);
+
+        let first = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        assert_eq!(
+            first.call("call", &[Value::I32(1)]).unwrap(),
+            vec![Value::I32(7)]
+        );
+        drop(first);
+        assert_eq!(pool.available_tables(), 1);
+
+        // The buffer is reused, emptied, with the elements of the new instance.
+        let second = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        assert_eq!(pool.available_tables(), 0);
+        assert_eq!(
+            second.call("call", &[Value::I32(1)]).unwrap(),
+            vec![Value::I32(7)]
+        );
+        assert!(second.call("call", &[Value::I32(0)]).is_err());
+
+        // The pool keeps no more buffers than it has slots.
+        let third = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        drop(second);
+        drop(third);
+        assert_eq!(pool.available_tables(), 1);
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_runtime_core::{
+        compile_with,
+        error::{CallError, RuntimeError},
+        export::Export,
+        imports,
+        memory::Memory,
+        structures::TypedIndex,
+        types::{MemoryDescriptor, MemoryIndex, Value},
+        units::Pages,
+        Instance, Module,
+    };
+
+    static IMPORTS_MEMORY: &str = r#"(module
+      (import "env" "memory" (memory 1 1))
+      (func (export "load") (param i32) (result i32)
+        (i32.load (get_local 0))))
+    "#;
+
+    fn compile(wat: &str) -> Module {
+        let wasm_binary = wat2wasm(wat.as_bytes()).expect("WAST not valid or malformed");
+        compile_with(&wasm_binary[..], &CraneliftCompiler::new()).expect("WASM can't be compiled")
+    }
+
+    fn check_out_of_bounds(instance: &Instance, address: i32) {
+        match *instance.call("load", &[Value::I32(address)]).unwrap_err() {
+            CallError::Runtime(RuntimeError::OutOfBoundsAccess { memory, addr }) => {
+                assert_eq!(memory, MemoryIndex::new(0));
+                assert_eq!(addr, address as u32);
+            }
+            _ => panic!("expected an out-of-bounds access"),
+        }
+    }
+
+    #[test]
+    fn test_imported_host_memory() {
+        let memory = Memory::new(MemoryDescriptor::new(Pages(1), Some(Pages(1)), false))
+            .expect("memory can't be created");
+        let instance = compile(IMPORTS_MEMORY)
+            .instantiate(imports! {
+                "env" => {
+                    "memory" => memory,
+                },
+            })
+            .expect("WASM can't be instantiated");
+
+        assert!(instance.call("load", &[Value::I32(65_532)]).is_ok());
+        check_out_of_bounds(&instance, 65_536);
+    }
+
+    #[test]
+    fn test_memory_imported_from_another_instance() {
+        let mut exporter = compile(
+            r#"(module
+          (memory (export "memory") 1 1))
+        "#,
+        )
+        .instantiate(imports! {})
+        .expect("WASM can't be instantiated");
+        let memory = exporter
+            .exports()
+            .find_map(|(name, export)| match export {
+                Export::Memory(memory) if name == "memory" => Some(memory),
+                _ => None,
+            })
+            .expect("memory isn't exported");
+
+        let instance = compile(IMPORTS_MEMORY)
+            .instantiate(imports! {
+                "env" => {
+                    "memory" => memory,
+                },
+            })
+            .expect("WASM can't be instantiated");
+
+        // The memory is still found once the instance that created it is gone.
+        drop(exporter);
+        check_out_of_bounds(&instance, 65_536);
+    }
+}
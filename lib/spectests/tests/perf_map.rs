@@ -0,0 +1,82 @@
+#[cfg(all(test, target_os = "linux", target_arch = "x86_64"))]
+mod tests {
+    use std::{fs, process};
+    use wabt::{wat2wasm, Wat2Wasm};
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_runtime_core::{backend::Compiler, compile_with, perf_map};
+    use wasmer_singlepass_backend::SinglePassCompiler;
+
+    #[test]
+    fn test_perf_map() {
+        let module_str = r#"(module
+      (func $helper (result i32)
+        (i32.const 42))
+      (func (export "answer") (result i32)
+        (call $helper)))
+    "#;
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+
+        perf_map::enable();
+
+        let compilers: Vec<Box<dyn Compiler>> = vec![
+            Box::new(CraneliftCompiler::new()),
+            Box::new(SinglePassCompiler::new()),
+        ];
+        for compiler in compilers {
+            compile_with(&wasm_binary[..], &*compiler).expect("WASM can't be compiled");
+        }
+
+        let map = fs::read_to_string(format!("/tmp/perf-{}.map", process::id()))
+            .expect("perf map can't be read");
+        let names: Vec<&str> = map
+            .lines()
+            .map(|line| line.splitn(3, ' ').nth(2).unwrap())
+            .collect();
+
+        assert_eq!(
+            names
+                .iter()
+                .filter(|&&name| name == "wasm-function[0]")
+                .count(),
+            2
+        );
+        assert_eq!(names.iter().filter(|&&name| name == "answer").count(), 2);
+        assert!(names.contains(&"trampoline for answer"));
+        assert!(names.contains(&"trampoline for signature 0"));
+    }
+
+    #[test]
+    fn test_perf_map_name_section() {
+        let module_str = r#"(module
+      (func $square (param i32) (result i32)
+        (i32.mul (get_local 0) (get_local 0)))
+      (func $run (export "entry") (result i32)
+        (call $square (i32.const 7)))
+      (func (export "other") (result i32)
+        (i32.const 0)))
+    "#;
+        let wasm_binary = Wat2Wasm::new()
+            .write_debug_names(true)
+            .convert(module_str)
+            .expect("WAST not valid or malformed");
+
+        perf_map::enable();
+        compile_with(wasm_binary.as_ref(), &CraneliftCompiler::new())
+            .expect("WASM can't be compiled");
+
+        let map = fs::read_to_string(format!("/tmp/perf-{}.map", process::id()))
+            .expect("perf map can't be read");
+        let names: Vec<&str> = map
+            .lines()
+            .map(|line| line.splitn(3, ' ').nth(2).unwrap())
+            .collect();
+
+        assert!(names.contains(&"square"));
+        // The name section takes precedence over exports.
+        assert!(names.contains(&"run"));
+        assert!(names.contains(&"trampoline for run"));
+        assert!(!names.contains(&"entry"));
+        // Exports still name the functions that the name section doesn't.
+        assert!(names.contains(&"other"));
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_runtime_core::{
+        compile_with,
+        export::{Context, Export, FuncPointer},
+        func,
+        import::Namespace,
+        imports,
+        types::{FuncSig, Type, Value},
+        vm::Ctx,
+    };
+
+    static MODULE: &str = r#"(module
+      (import "env" "read_first_byte" (func $read_first_byte (result i32)))
+      (import "env" "is_null_ctx" (func $is_null_ctx (result i32)))
+      (memory 1)
+      (func (export "store_and_read") (param i32) (result i32)
+        (i32.store8 (i32.const 0) (get_local 0))
+        (call $read_first_byte))
+      (func (export "is_null_ctx") (result i32)
+        (call $is_null_ctx)))
+    "#;
+
+    extern "C" fn read_first_byte(ctx: &mut Ctx) -> i32 {
+        ctx.memory(0).read::<u8>(0).unwrap() as i32
+    }
+
+    extern "C" fn is_null_ctx(ctx: *mut Ctx) -> i32 {
+        ctx.is_null() as i32
+    }
+
+    #[test]
+    fn test_prelink() {
+        let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+        let module = compile_with(&wasm_binary[..], &CraneliftCompiler::new())
+            .expect("WASM can't be compiled");
+
+        // A function exported with a null context of its own must keep it.
+        let mut env = Namespace::new();
+        env.insert("read_first_byte", func!(read_first_byte, [] -> [i32]));
+        env.insert(
+            "is_null_ctx",
+            Export::Function {
+                func: unsafe { FuncPointer::new(is_null_ctx as _) },
+                ctx: Context::External(ptr::null_mut()),
+                signature: FuncSig::new(&[] as &[Type], &[Type::I32] as &[Type]).into(),
+            },
+        );
+        let import_object = imports! {
+            "env" => env,
+        };
+
+        let pre_instance = module
+            .prelink(&import_object)
+            .expect("imports can't be resolved");
+        drop(import_object);
+
+        let first = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        let second = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+
+        // Each instance passes its own context to the imports that want it.
+        assert_eq!(
+            first.call("store_and_read", &[Value::I32(1)]).unwrap(),
+            vec![Value::I32(1)]
+        );
+        assert_eq!(
+            second.call("store_and_read", &[Value::I32(2)]).unwrap(),
+            vec![Value::I32(2)]
+        );
+        assert_eq!(
+            first.call("store_and_read", &[Value::I32(3)]).unwrap(),
+            vec![Value::I32(3)]
+        );
+
+        assert_eq!(first.call("is_null_ctx", &[]).unwrap(), vec![Value::I32(1)]);
+        assert_eq!(
+            second.call("is_null_ctx", &[]).unwrap(),
+            vec![Value::I32(1)]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(all(test, target_os = "linux", target_arch = "x86_64"))]
+mod tests {
+    use std::{
+        env, fs, process,
+        time::{Duration, Instant},
+    };
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::{CraneliftCompiler, ObjectLoader};
+    use wasmer_runtime_core::{
+        backend::Compiler,
+        compile_with,
+        import::ImportObject,
+        profiler::{Profile, Profiler},
+        types::Value,
+        Features,
+    };
+    use wasmer_singlepass_backend::SinglePassCompiler;
+
+    static SPIN: &str = r#"(module
+      (func $spin (param i32)
+        (loop
+          (br_if 0 (tee_local 0 (i32.sub (get_local 0) (i32.const 1))))))
+      (func (export "run") (param i32)
+        (call $spin (get_local 0))))
+    "#;
+
+    /// Runs the module compiled by `compiler` while profiling it.
+    fn profile_spin(compiler: &dyn Compiler, wasm_binary: &[u8]) -> Profile {
+        let profiler = Profiler::start(Duration::from_millis(1)).unwrap();
+        assert!(Profiler::start(Duration::from_millis(1)).is_err());
+
+        let module = compile_with(wasm_binary, compiler).expect("WASM can't be compiled");
+        let instance = module
+            .instantiate(ImportObject::new())
+            .expect("WASM can't be instantiated");
+
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(300) {
+            instance.call("run", &[Value::I32(1_000_000)]).unwrap();
+        }
+
+        profiler.stop()
+    }
+
+    fn check_spinning(profile: &Profile) {
+        assert!(profile.num_samples() > 0);
+
+        // Nearly all the time is spent in the loop, called from
+        // the export, which is called from its trampoline.
+        let spinning: usize = profile
+            .stacks()
+            .filter(|&(stack, _)| {
+                stack.len() == 3
+                    && stack[0].starts_with("trampoline for ")
+                    && stack[1..] == ["run", "wasm-function[0]"]
+            })
+            .map(|(_, count)| count)
+            .sum();
+        assert!(spinning * 2 > profile.num_samples());
+
+        let mut folded = Vec::new();
+        profile.write_folded(&mut folded).unwrap();
+        assert!(String::from_utf8(folded)
+            .unwrap()
+            .lines()
+            .any(|line| line.contains(";run;wasm-function[0] ")));
+    }
+
+    #[test]
+    fn test_profiler() {
+        let wasm_binary = wat2wasm(SPIN.as_bytes()).expect("WAST not valid or malformed");
+
+        let compilers: Vec<Box<dyn Compiler>> = vec![
+            Box::new(CraneliftCompiler::new()),
+            Box::new(SinglePassCompiler::new()),
+        ];
+        for compiler in compilers {
+            check_spinning(&profile_spin(&*compiler, &wasm_binary));
+        }
+
+        // Only one profiler can run at a time, so
+        // shared objects are profiled here too.
+        let object = CraneliftCompiler::new()
+            .compile_to_object(&wasm_binary, &Features::default())
+            .expect("WASM can't be compiled to a shared object");
+        let path = env::temp_dir().join(format!("wasmer-profiler-test-{}.so", process::id()));
+        fs::write(&path, object).unwrap();
+
+        // The functions of the object are named when it's loaded.
+        check_spinning(&profile_spin(&ObjectLoader::new(&path), &wasm_binary));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(test)]
+mod tests {
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_runtime_core::{compile_with, imports, types::Value, Instance, Module};
+
+    static MODULE: &str = r#"(module
+      (type $ret_i32 (func (result i32)))
+      (memory 1 4)
+      (table 2 anyfunc)
+      (global $counter (mut i32) (i32.const 10))
+      (global $starts (mut i32) (i32.const 0))
+      (data (i32.const 0) "\2a")
+      (elem (i32.const 0) $seven)
+      (func $seven (result i32) (i32.const 7))
+      (func $eight (result i32) (i32.const 8))
+      (func $start
+        (set_global $starts (i32.add (get_global $starts) (i32.const 1))))
+      (start $start)
+      (func (export "dirty")
+        (i32.store8 (i32.const 0) (i32.const 1))
+        (i32.store8 (i32.const 100) (i32.const 2))
+        (set_global $counter (i32.const 99))
+        (drop (memory.grow (i32.const 2))))
+      (func (export "load") (param i32) (result i32)
+        (i32.load8_u (get_local 0)))
+      (func (export "counter") (result i32)
+        (get_global $counter))
+      (func (export "starts") (result i32)
+        (get_global $starts))
+      (func (export "size") (result i32)
+        (memory.size))
+      (func (export "call_indirect") (param i32) (result i32)
+        (call_indirect (type $ret_i32) (get_local 0))))
+    "#;
+
+    fn compile() -> Module {
+        let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+        compile_with(&wasm_binary[..], &CraneliftCompiler::new()).expect("WASM can't be compiled")
+    }
+
+    fn call(instance: &Instance, name: &str, args: &[Value]) -> Value {
+        instance.call(name, args).unwrap()[0].clone()
+    }
+
+    fn check_fresh(instance: &Instance) {
+        assert_eq!(call(instance, "size", &[]), Value::I32(1));
+        assert_eq!(call(instance, "load", &[Value::I32(0)]), Value::I32(42));
+        assert_eq!(call(instance, "load", &[Value::I32(100)]), Value::I32(0));
+        assert_eq!(call(instance, "counter", &[]), Value::I32(10));
+        assert_eq!(
+            call(instance, "call_indirect", &[Value::I32(0)]),
+            Value::I32(7)
+        );
+        assert!(instance.call("call_indirect", &[Value::I32(1)]).is_err());
+    }
+
+    fn dirty(instance: &Instance) {
+        instance.call("dirty", &[]).unwrap();
+        assert_eq!(call(instance, "size", &[]), Value::I32(3));
+        assert_eq!(call(instance, "load", &[Value::I32(0)]), Value::I32(1));
+        assert_eq!(call(instance, "counter", &[]), Value::I32(99));
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut instance = compile()
+            .instantiate(imports! {})
+            .expect("WASM can't be instantiated");
+        check_fresh(&instance);
+        assert_eq!(call(&instance, "starts", &[]), Value::I32(1));
+
+        dirty(&instance);
+        instance.reset().expect("instance can't be reset");
+        check_fresh(&instance);
+
+        // The start function runs again after the globals are reset.
+        assert_eq!(call(&instance, "starts", &[]), Value::I32(1));
+
+        // An instance can be reset more than once.
+        dirty(&instance);
+        instance.reset().expect("instance can't be reset");
+        check_fresh(&instance);
+    }
+
+    #[test]
+    fn test_reset_with_memory_images() {
+        let module = compile();
+        let mut pre_instance = module
+            .prelink(&imports! {})
+            .expect("imports can't be resolved");
+        pre_instance
+            .enable_memory_images()
+            .expect("memory images can't be created");
+
+        let mut instance = pre_instance
+            .instantiate()
+            .expect("WASM can't be instantiated");
+        check_fresh(&instance);
+
+        dirty(&instance);
+        instance.reset().expect("instance can't be reset");
+        check_fresh(&instance);
+    }
+}
@@ -0,0 +1,153 @@
+#[cfg(all(test, target_arch = "x86_64", unix))]
+mod tests {
+    use wabt::wat2wasm;
+    use wasmer_runtime_core::{
+        error::{CallError, RuntimeError},
+        import::ImportObject,
+        types::Value,
+        Instance,
+    };
+    use wasmer_singlepass_backend::SinglePassCompiler;
+
+    fn instantiate(module_str: &str) -> Instance {
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+        let module =
+            wasmer_runtime_core::compile_with(&wasm_binary[..], &SinglePassCompiler::new())
+                .expect("WASM can't be compiled");
+        module
+            .instantiate(ImportObject::new())
+            .expect("WASM can't be instantiated")
+    }
+
+    #[test]
+    fn test_singlepass_calls_and_loops() {
+        let instance = instantiate(
+            r#"(module
+      (func $fac-rec (param i64) (result i64)
+        (if (result i64) (i64.eqz (get_local 0))
+          (then (i64.const 1))
+          (else (i64.mul (get_local 0) (call $fac-rec (i64.sub (get_local 0) (i64.const 1)))))))
+      (func $fac-iter (param i64) (result i64)
+        (local i64)
+        (set_local 1 (i64.const 1))
+        (block
+          (loop
+            (br_if 1 (i64.eqz (get_local 0)))
+            (set_local 1 (i64.mul (get_local 0) (get_local 1)))
+            (set_local 0 (i64.sub (get_local 0) (i64.const 1)))
+            (br 0)))
+        (get_local 1))
+      (func (export "many-params")
+        (param i32 f64 i64 f32 i32 i32 i32 i32 i32 f64) (result f64)
+        (f64.add
+          (f64.add (get_local 1) (get_local 9))
+          (f64.convert_s/i32 (get_local 8))))
+      (func (export "call-many-params") (result f64)
+        (call 2 (i32.const 0) (f64.const 1.5) (i64.const 0) (f32.const 0)
+          (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 7) (f64.const 0.25)))
+      (export "fac-rec" (func $fac-rec))
+      (export "fac-iter" (func $fac-iter)))
+    "#,
+        );
+
+        for name in &["fac-rec", "fac-iter"] {
+            let result = instance.call(name, &[Value::I64(20)]).unwrap();
+            assert_eq!(result, vec![Value::I64(2_432_902_008_176_640_000)]);
+        }
+
+        let result = instance.call("call-many-params", &[]).unwrap();
+        assert_eq!(result, vec![Value::F64(8.75)]);
+    }
+
+    #[test]
+    fn test_singlepass_memory_and_call_indirect() {
+        let instance = instantiate(
+            r#"(module
+      (type $binop (func (param i32 i32) (result i32)))
+      (memory 1)
+      (table anyfunc (elem $add $sub))
+      (func $add (type $binop) (i32.add (get_local 0) (get_local 1)))
+      (func $sub (type $binop) (i32.sub (get_local 0) (get_local 1)))
+      (func (export "apply") (param i32 i32 i32) (result i32)
+        (i32.store offset=4 (i32.const 0) (call_indirect (type $binop) (get_local 1) (get_local 2) (get_local 0)))
+        (i32.load8_s (i32.const 4)))
+      (func (export "load") (param i32) (result i32)
+        (i32.load (get_local 0))))
+    "#,
+        );
+
+        let result = instance
+            .call("apply", &[Value::I32(1), Value::I32(2), Value::I32(3)])
+            .unwrap();
+        assert_eq!(result, vec![Value::I32(-1)]);
+
+        match *instance.call("load", &[Value::I32(65_533)]).unwrap_err() {
+            CallError::Runtime(RuntimeError::OutOfBoundsAccess { .. }) => {}
+            _ => panic!("expected an out-of-bounds access"),
+        }
+        match *instance
+            .call("apply", &[Value::I32(2), Value::I32(0), Value::I32(0)])
+            .unwrap_err()
+        {
+            CallError::Runtime(RuntimeError::TableOutOfBounds { .. }) => {}
+            _ => panic!("expected an out-of-bounds table access"),
+        }
+    }
+
+    #[test]
+    fn test_singlepass_arithmetic_traps() {
+        let instance = instantiate(
+            r#"(module
+      (func (export "div") (param i32 i32) (result i32)
+        (i32.div_s (get_local 0) (get_local 1)))
+      (func (export "div64") (param i64 i64) (result i64)
+        (i64.div_s (get_local 0) (get_local 1)))
+      (func (export "rem") (param i32 i32) (result i32)
+        (i32.rem_s (get_local 0) (get_local 1)))
+      (func (export "trunc") (param f64) (result i32)
+        (i32.trunc_s/f64 (get_local 0))))
+    "#,
+        );
+
+        let result = instance
+            .call("rem", &[Value::I32(i32::min_value()), Value::I32(-1)])
+            .unwrap();
+        assert_eq!(result, vec![Value::I32(0)]);
+
+        match *instance
+            .call("div", &[Value::I32(1), Value::I32(0)])
+            .unwrap_err()
+        {
+            CallError::Runtime(RuntimeError::IllegalArithmeticOperation) => {}
+            _ => panic!("expected a division by zero"),
+        }
+
+        // Dividing by -1 only overflows for the smallest integer.
+        let result = instance
+            .call("div", &[Value::I32(-7), Value::I32(-1)])
+            .unwrap();
+        assert_eq!(result, vec![Value::I32(7)]);
+        let result = instance
+            .call("div64", &[Value::I64(i64::min_value() + 1), Value::I64(-1)])
+            .unwrap();
+        assert_eq!(result, vec![Value::I64(i64::max_value())]);
+        match *instance
+            .call("div", &[Value::I32(i32::min_value()), Value::I32(-1)])
+            .unwrap_err()
+        {
+            CallError::Runtime(RuntimeError::IllegalArithmeticOperation) => {}
+            _ => panic!("expected an overflow"),
+        }
+        match *instance
+            .call("div64", &[Value::I64(i64::min_value()), Value::I64(-1)])
+            .unwrap_err()
+        {
+            CallError::Runtime(RuntimeError::IllegalArithmeticOperation) => {}
+            _ => panic!("expected an overflow"),
+        }
+        match *instance.call("trunc", &[Value::F64(1e10)]).unwrap_err() {
+            CallError::Runtime(RuntimeError::IllegalArithmeticOperation) => {}
+            _ => panic!("expected an invalid conversion"),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests {
+    use wabt::wat2wasm;
+    use wasmer_clif_backend::CraneliftCompiler;
+    use wasmer_runtime_core::{
+        compile_with,
+        error::{Error, SnapshotError},
+        export::Export,
+        imports,
+        snapshot::InstanceSnapshot,
+        types::Value,
+        Instance, Module,
+    };
+
+    static MODULE: &str = r#"(module
+      (type $ret_i32 (func (result i32)))
+      (memory 1)
+      (table (export "table") 3 anyfunc)
+      (global $counter (mut i32) (i32.const 0))
+      (elem (i32.const 0) $one $two)
+      (func $one (result i32) (i32.const 1))
+      (func $two (result i32) (i32.const 2))
+      (func (export "bump") (result i32)
+        (set_global $counter (i32.add (get_global $counter) (i32.const 1)))
+        (i32.store8 (i32.const 10) (get_global $counter))
+        (get_global $counter))
+      (func (export "load") (param i32) (result i32)
+        (i32.load8_u (get_local 0)))
+      (func (export "call_indirect") (param i32) (result i32)
+        (call_indirect (type $ret_i32) (get_local 0))))
+    "#;
+
+    fn compile(wat: &str) -> Module {
+        let wasm_binary = wat2wasm(wat.as_bytes()).expect("WAST not valid or malformed");
+        compile_with(&wasm_binary[..], &CraneliftCompiler::new()).expect("WASM can't be compiled")
+    }
+
+    fn call(instance: &Instance, name: &str, args: &[Value]) -> Value {
+        instance.call(name, args).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let module = compile(MODULE);
+        let instance = module
+            .instantiate(imports! {})
+            .expect("WASM can't be instantiated");
+        call(&instance, "bump", &[]);
+        call(&instance, "bump", &[]);
+
+        let bytes = instance
+            .snapshot()
+            .expect("snapshot can't be taken")
+            .to_bytes();
+        let snapshot = InstanceSnapshot::from_bytes(&bytes).expect("snapshot can't be decoded");
+        assert_eq!(snapshot.to_bytes(), bytes);
+
+        let restored = module
+            .instantiate_from_snapshot(&snapshot, imports! {})
+            .expect("snapshot can't be restored");
+        assert_eq!(call(&restored, "load", &[Value::I32(10)]), Value::I32(2));
+        assert_eq!(call(&restored, "bump", &[]), Value::I32(3));
+
+        // Each table element comes back as the same function.
+        assert_eq!(
+            call(&restored, "call_indirect", &[Value::I32(0)]),
+            Value::I32(1)
+        );
+        assert_eq!(
+            call(&restored, "call_indirect", &[Value::I32(1)]),
+            Value::I32(2)
+        );
+        assert!(restored.call("call_indirect", &[Value::I32(2)]).is_err());
+
+        // Truncated snapshots are rejected.
+        assert!(InstanceSnapshot::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_other_instance_function() {
+        let module = compile(MODULE);
+        let mut first = module
+            .instantiate(imports! {})
+            .expect("WASM can't be instantiated");
+        let table = first
+            .exports()
+            .find_map(|(name, export)| match export {
+                Export::Table(table) if name == "table" => Some(table),
+                _ => None,
+            })
+            .expect("table isn't exported");
+
+        // Another instance puts its own function into the last element.
+        let other = compile(
+            r#"(module
+          (import "env" "table" (table 3 anyfunc))
+          (elem (i32.const 2) $one)
+          (func $one (result i32) (i32.const 1)))
+        "#,
+        );
+        let _second = other
+            .instantiate(imports! {
+                "env" => {
+                    "table" => table,
+                },
+            })
+            .expect("WASM can't be instantiated");
+
+        match first.snapshot().map_err(|err| *err) {
+            Err(Error::SnapshotError(SnapshotError::UnknownTableElement { index, .. })) => {
+                assert_eq!(index, 2)
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
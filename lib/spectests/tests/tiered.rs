@@ -0,0 +1,82 @@
+#[cfg(all(test, target_arch = "x86_64", unix))]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+    use wabt::wat2wasm;
+    use wasmer_runtime_core::{
+        error::{CallError, RuntimeError},
+        import::ImportObject,
+        types::Value,
+    };
+    use wasmer_tiered_backend::TieredCompiler;
+
+    #[test]
+    fn test_tiered_hot_swap() {
+        let module_str = r#"(module
+      (memory 1)
+      (func $fac (export "fac") (param i64) (result i64)
+        (if (result i64) (i64.eqz (get_local 0))
+          (then (i64.const 1))
+          (else (i64.mul (get_local 0) (call $fac (i64.sub (get_local 0) (i64.const 1)))))))
+      (func (export "div") (param i32 i32) (result i32)
+        (i32.div_s (get_local 0) (get_local 1)))
+      (func (export "load") (param i32) (result i32)
+        (i32.load (get_local 0))))
+    "#;
+        let wasm_binary = wat2wasm(module_str.as_bytes()).expect("WAST not valid or malformed");
+        let compiler = TieredCompiler::with_threshold(1);
+        let module = wasmer_runtime_core::compile_with(&wasm_binary[..], &compiler)
+            .expect("WASM can't be compiled");
+        let instance = module
+            .instantiate(ImportObject::new())
+            .expect("WASM can't be instantiated");
+        let tables = compiler.func_tables();
+        assert_eq!(tables.len(), 1);
+
+        // Results and traps stay the same whichever tier the functions run in.
+        let check = || {
+            let result = instance.call("fac", &[Value::I64(20)]).unwrap();
+            assert_eq!(result, vec![Value::I64(2_432_902_008_176_640_000)]);
+
+            let result = instance
+                .call("div", &[Value::I32(7), Value::I32(2)])
+                .unwrap();
+            assert_eq!(result, vec![Value::I32(3)]);
+            match *instance
+                .call("div", &[Value::I32(1), Value::I32(0)])
+                .unwrap_err()
+            {
+                CallError::Runtime(RuntimeError::IllegalArithmeticOperation) => {}
+                _ => panic!("expected a division by zero"),
+            }
+            match *instance.call("load", &[Value::I32(65_533)]).unwrap_err() {
+                CallError::Runtime(RuntimeError::OutOfBoundsAccess { .. }) => {}
+                _ => panic!("expected an out-of-bounds access"),
+            }
+        };
+
+        // Every function gets hot after its first call, so
+        // each one is redirected to Cranelift code eventually.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            check();
+            let redirected = tables[0].redirected().expect("module was dropped");
+            if redirected.iter().all(|&redirected| redirected) {
+                break;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "functions weren't redirected: {:?}",
+                redirected
+            );
+            thread::sleep(Duration::from_millis(2));
+        }
+
+        // And behave the same once they are.
+        for _ in 0..10 {
+            check();
+        }
+    }
+}
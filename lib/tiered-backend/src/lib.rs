@@ -0,0 +1,155 @@
+//! A tiered compiler backend for wasmer.
+//!
+//! Modules are compiled with the single-pass backend first, so they
+//! start running right away. A background thread then watches how
+//! many times each function is called, and once one gets hot,
+//! compiles the module with Cranelift and redirects the hot
+//! functions to the optimized code while the module keeps running.
+//!
+//! Functions are only redirected at their entry, so a call that's
+//! already running finishes in the code it started in. A module
+//! that isn't called for a while is no longer watched, and keeps
+//! running in the tiers it's in.
+
+use std::{
+    cmp,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use wasmer_clif_backend::{trap::HandlerData, CraneliftCompiler};
+use wasmer_runtime_core::{
+    backend::{Compiler, Token},
+    error::{CompileError, CompileResult},
+    module::ModuleInner,
+    structures::TypedIndex,
+    types::LocalFuncIndex,
+    Features,
+};
+use wasmer_singlepass_backend::{FuncTable, SinglePassCompiler};
+
+/// How many calls make a function hot, by default.
+const DEFAULT_THRESHOLD: usize = 1000;
+
+/// How often the call counts are looked at at first. The interval
+/// doubles, up to `MAX_POLL_INTERVAL`, whenever nothing got hot since
+/// the last time, and goes back to this once something does.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many times in a row the call counts can be found unchanged
+/// before the module is considered idle, and no longer watched. Its
+/// functions then stay in the tier they're in.
+const MAX_IDLE_POLLS: usize = 60;
+
+pub struct TieredCompiler {
+    threshold: usize,
+    /// The functions of every module this compiled.
+    tables: Mutex<Vec<FuncTable>>,
+}
+
+impl TieredCompiler {
+    pub fn new() -> Self {
+        Self::with_threshold(DEFAULT_THRESHOLD)
+    }
+
+    /// Optimizes the functions that are called
+    /// at least `threshold` times.
+    pub fn with_threshold(threshold: usize) -> Self {
+        Self {
+            threshold,
+            tables: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The functions of every module compiled by this
+    /// compiler that is still alive, in compilation order.
+    pub fn func_tables(&self) -> Vec<FuncTable> {
+        let mut tables = self.tables.lock().unwrap();
+        tables.retain(|table| table.call_counts().is_some());
+        tables.clone()
+    }
+}
+
+impl Compiler for TieredCompiler {
+    // Compiles a wasm binary to a wasmer module that starts in the single-pass tier.
+    fn compile(&self, wasm: &[u8], features: &Features, _: Token) -> CompileResult<ModuleInner> {
+        let (module, table) = SinglePassCompiler::new().compile_tiered(wasm, features)?;
+        self.tables.lock().unwrap().push(table.clone());
+
+        let wasm = wasm.to_vec();
+        let features = features.clone();
+        let threshold = self.threshold;
+        thread::Builder::new()
+            .name("wasmer-tiering".to_string())
+            .spawn(move || optimize(&wasm, &features, &table, threshold))
+            .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+
+        Ok(module)
+    }
+}
+
+/// Redirects the functions of `table` to Cranelift code as they get hot,
+/// until every function is redirected, the module goes idle or is dropped.
+fn optimize(wasm: &[u8], features: &Features, table: &FuncTable, threshold: usize) {
+    let mut optimized: Option<(Arc<ModuleInner>, Arc<HandlerData>)> = None;
+    let mut replaced: Vec<bool> = Vec::new();
+    let mut interval = POLL_INTERVAL;
+    let mut last_counts: Vec<usize> = Vec::new();
+    let mut idle_polls = 0;
+
+    loop {
+        thread::sleep(interval);
+
+        let counts = match table.call_counts() {
+            Some(counts) => counts,
+            None => return,
+        };
+        replaced.resize(counts.len(), false);
+        if replaced.iter().all(|&replaced| replaced) {
+            return;
+        }
+
+        if counts == last_counts {
+            idle_polls += 1;
+            if idle_polls == MAX_IDLE_POLLS {
+                return;
+            }
+        } else {
+            idle_polls = 0;
+        }
+        last_counts = counts.clone();
+
+        let hot: Vec<LocalFuncIndex> = counts
+            .iter()
+            .enumerate()
+            .filter(|&(index, &count)| !replaced[index] && count >= threshold)
+            .map(|(index, _)| LocalFuncIndex::new(index))
+            .collect();
+        if hot.is_empty() {
+            interval = cmp::min(interval * 2, MAX_POLL_INTERVAL);
+            continue;
+        }
+        interval = POLL_INTERVAL;
+
+        if optimized.is_none() {
+            match CraneliftCompiler::new().compile_with_handler_data(wasm, features) {
+                Ok((module, handler_data)) => optimized = Some((Arc::new(module), handler_data)),
+                // The baseline code keeps running.
+                Err(_) => return,
+            }
+        }
+        let (module, handler_data) = optimized.as_ref().unwrap();
+
+        if !table.replace(module, handler_data, &hot) {
+            return;
+        }
+        for local_func_index in hot {
+            replaced[local_func_index.index()] = true;
+        }
+    }
+}
+
+/// The current version of this crate
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,16 +1,19 @@
 extern crate structopt;
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
 use std::io::Read;
 use std::path::PathBuf;
 use std::process::exit;
+use std::time::Duration;
 
 use structopt::StructOpt;
 
 use wasmer::webassembly::InstanceABI;
 use wasmer::*;
 use wasmer_emscripten;
+use wasmer_runtime::Backend;
+use wasmer_runtime_core::{profiler::Profiler, Features};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "wasmer", about = "Wasm execution runtime.")]
@@ -20,6 +23,10 @@ enum CLIOptions {
     #[structopt(name = "run")]
     Run(Run),
 
+    /// Compile a WebAssembly file ahead of time to a native shared object
+    #[structopt(name = "compile")]
+    Compile(Compile),
+
     /// Update wasmer to the latest version
     #[structopt(name = "self-update")]
     SelfUpdate,
@@ -34,11 +41,107 @@ struct Run {
     #[structopt(parse(from_os_str))]
     path: PathBuf,
 
+    /// Compiler backend: cranelift (the default), singlepass, tiered or interpreter
+    #[structopt(long = "backend")]
+    backend: Option<Backend>,
+
+    /// Run the code compiled into this shared object by `wasmer compile`
+    #[structopt(long = "object", parse(from_os_str))]
+    object: Option<PathBuf>,
+
+    /// Write the compiled functions to /tmp/perf-<pid>.map for `perf`
+    #[structopt(long = "perf-map")]
+    perf_map: bool,
+
+    /// Sample the stacks of the guest code every millisecond, and
+    /// write them to this file in the folded format of flamegraph.pl
+    #[structopt(long = "profile", parse(from_os_str))]
+    profile: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    features: FeatureOptions,
+
     /// Application arguments
     #[structopt(name = "--", raw(multiple = "true"))]
     args: Vec<String>,
 }
 
+#[derive(Debug, StructOpt)]
+struct Compile {
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// Output shared object
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: PathBuf,
+
+    #[structopt(flatten)]
+    features: FeatureOptions,
+}
+
+/// The WebAssembly proposals a module can use, and how its code is
+/// generated. A shared object must be run with the same options
+/// that it was compiled with.
+#[derive(Debug, StructOpt)]
+struct FeatureOptions {
+    /// Enable the sign-extension operators
+    #[structopt(long = "enable-sign-extension")]
+    sign_extension: bool,
+
+    /// Enable the non-trapping float-to-int conversions
+    #[structopt(long = "enable-sat-float-to-int")]
+    sat_float_to_int: bool,
+
+    /// Enable importing and exporting mutable globals
+    #[structopt(long = "enable-mutable-global")]
+    mutable_global: bool,
+
+    /// Enable shared memories and atomic operators
+    #[structopt(long = "enable-threads")]
+    threads: bool,
+
+    /// Enable functions that return more than one value
+    #[structopt(long = "enable-multi-value")]
+    multi_value: bool,
+
+    /// Generate code that gives the same results on every host
+    #[structopt(long = "deterministic")]
+    deterministic: bool,
+
+    /// A function that can be imported in deterministic mode, as namespace:name
+    #[structopt(long = "allow-import", parse(try_from_str = "parse_import"))]
+    allowed_imports: Vec<(String, String)>,
+
+    /// Bounds-check every memory access instead of relying on guard pages
+    #[structopt(long = "bounds-checks")]
+    bounds_checks: bool,
+}
+
+impl FeatureOptions {
+    fn features(&self) -> Features {
+        Features {
+            sign_extension: self.sign_extension,
+            sat_float_to_int: self.sat_float_to_int,
+            mutable_global: self.mutable_global,
+            threads: self.threads,
+            reference_types: false,
+            multi_value: self.multi_value,
+            deterministic: self.deterministic,
+            allowed_imports: self.allowed_imports.clone(),
+            bounds_checks: self.bounds_checks,
+        }
+    }
+}
+
+/// Parse a namespace:name pair
+fn parse_import(import: &str) -> Result<(String, String), String> {
+    match import.find(':') {
+        Some(colon) => Ok((import[..colon].to_string(), import[colon + 1..].to_string())),
+        None => Err(format!("expected namespace:name, found {}", import)),
+    }
+}
+
 /// Read the contents of a file
 fn read_file_contents(path: &PathBuf) -> Result<Vec<u8>, io::Error> {
     let mut buffer: Vec<u8> = Vec::new();
@@ -49,10 +152,8 @@ fn read_file_contents(path: &PathBuf) -> Result<Vec<u8>, io::Error> {
     Ok(buffer)
 }
 
-/// Execute a wasm/wat file
-fn execute_wasm(options: &Run) -> Result<(), String> {
-    let wasm_path = &options.path;
-
+/// Read a wasm/wat file as a wasm binary
+fn read_wasm(wasm_path: &PathBuf) -> Result<Vec<u8>, String> {
     let mut wasm_binary: Vec<u8> = read_file_contents(wasm_path).map_err(|err| {
         format!(
             "Can't read the file {}: {}",
@@ -66,8 +167,51 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
             .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
     }
 
-    let module = webassembly::compile(&wasm_binary[..])
-        .map_err(|e| format!("Can't compile module: {:?}", e))?;
+    Ok(wasm_binary)
+}
+
+/// Execute a wasm/wat file
+fn execute_wasm(options: &Run) -> Result<(), String> {
+    let wasm_binary = read_wasm(&options.path)?;
+    let features = options.features.features();
+
+    if options.perf_map {
+        wasmer_runtime_core::perf_map::enable();
+    }
+
+    // The profiler has to run before compiling, so that
+    // it knows where the code of each function is.
+    let profiler = match options.profile {
+        Some(_) => Some(Profiler::start(Duration::from_millis(1))?),
+        None => None,
+    };
+
+    if options.object.is_some() && options.backend.is_some() {
+        return Err(
+            "--backend can't be used with --object, which runs code that's already compiled"
+                .to_string(),
+        );
+    }
+
+    let module = match options.object {
+        Some(ref object_path) => wasmer_runtime_core::compile_with_features(
+            &wasm_binary[..],
+            &wasmer_clif_backend::ObjectLoader::new(object_path.clone()),
+            &features,
+        )
+        .map_err(|e| format!("Can't load module: {:?}", e))?,
+        None => {
+            let backend = options.backend.unwrap_or_default();
+            let compiler = backend.compiler().ok_or_else(|| {
+                format!(
+                    "Can't compile module: the {} backend isn't enabled",
+                    backend
+                )
+            })?;
+            wasmer_runtime_core::compile_with_features(&wasm_binary[..], &*compiler, &features)
+                .map_err(|e| format!("Can't compile module: {:?}", e))?
+        }
+    };
 
     let (_abi, import_object) = if wasmer_emscripten::is_emscripten_module(&module) {
         let emscripten_globals = wasmer_emscripten::EmscriptenGlobals::new();
@@ -86,14 +230,40 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         .instantiate(import_object)
         .map_err(|e| format!("Can't instantiate module: {:?}", e))?;
 
-    webassembly::run_instance(
+    let result = webassembly::run_instance(
         &module,
         &mut instance,
         options.path.to_str().unwrap(),
         options.args.iter().map(|arg| arg.as_str()).collect(),
     )
-    .map_err(|e| format!("{:?}", e))?;
-    Ok(())
+    .map_err(|e| format!("{:?}", e));
+
+    if let (Some(profiler), Some(profile_path)) = (profiler, &options.profile) {
+        write_profile(profiler, profile_path)?;
+    }
+
+    result
+}
+
+/// Stops the profiler and writes its samples to `path`
+fn write_profile(profiler: Profiler, path: &PathBuf) -> Result<(), String> {
+    let profile = profiler.stop();
+    if profile.num_dropped() > 0 {
+        eprintln!(
+            "The profiler was full, {} samples were dropped",
+            profile.num_dropped()
+        );
+    }
+
+    File::create(path)
+        .and_then(|mut file| profile.write_folded(&mut file))
+        .map_err(|err| {
+            format!(
+                "Can't write the file {}: {}",
+                path.as_os_str().to_string_lossy(),
+                err
+            )
+        })
 }
 
 fn run(options: Run) {
@@ -106,10 +276,38 @@ fn run(options: Run) {
     }
 }
 
+/// Compile a wasm/wat file to a shared object
+fn compile_wasm(options: &Compile) -> Result<(), String> {
+    let wasm_binary = read_wasm(&options.path)?;
+
+    let object = wasmer_clif_backend::CraneliftCompiler::new()
+        .compile_to_object(&wasm_binary[..], &options.features.features())
+        .map_err(|e| format!("Can't compile module: {:?}", e))?;
+
+    fs::write(&options.output, object).map_err(|err| {
+        format!(
+            "Can't write the file {}: {}",
+            options.output.as_os_str().to_string_lossy(),
+            err
+        )
+    })
+}
+
+fn compile(options: Compile) {
+    match compile_wasm(&options) {
+        Ok(()) => {}
+        Err(message) => {
+            eprintln!("{:?}", message);
+            exit(1);
+        }
+    }
+}
+
 fn main() {
     let options = CLIOptions::from_args();
     match options {
         CLIOptions::Run(options) => run(options),
+        CLIOptions::Compile(options) => compile(options),
         CLIOptions::SelfUpdate => update::self_update(),
     }
 }
@@ -3,7 +3,7 @@ pub mod utils;
 use wasmer_runtime::{
     self as runtime,
     error::{CallResult, Result},
-    ImportObject, Instance, Module,
+    Backend, ImportObject, Instance, Module,
 };
 
 use std::panic;
@@ -77,6 +77,13 @@ pub fn compile(buffer_source: &[u8]) -> Result<Module> {
     Ok(module)
 }
 
+/// Like webassembly::compile(), but compiles the module
+/// with the given compiler backend.
+pub fn compile_with_backend(buffer_source: &[u8], backend: Backend) -> Result<Module> {
+    let module = runtime::compile_with_backend(buffer_source, backend)?;
+    Ok(module)
+}
+
 /// Performs common instance operations needed when an instance is first run
 /// including data setup, handling arguments and calling a main function
 pub fn run_instance(